@@ -0,0 +1,84 @@
+//! Benchmarks of formatting code which stresses the list and chain formatting, e.g. to
+//! check a change for performance regressions with `cargo bench`.
+//!
+//! The inputs are generated, so that their size can be tuned here rather than checked in.
+
+#![feature(test)]
+
+extern crate test;
+
+use rustfmt_nightly::{Config, EmitMode, Input, Session, Verbosity};
+use test::Bencher;
+
+fn bench_format(b: &mut Bencher, source: String) {
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    b.bytes = source.len() as u64;
+    b.iter(|| {
+        let mut out = Vec::with_capacity(source.len() * 2);
+        let mut session = Session::new(config.clone(), Some(&mut out));
+        session.format(Input::Text(source.clone())).unwrap();
+    });
+}
+
+/// A function with long method chains whose arguments are closures with chains of their own.
+fn deep_chains() -> String {
+    let mut source = String::from("fn chains() {\n");
+    for i in 0..50 {
+        source.push_str(&format!("let x{} = foo", i));
+        for j in 0..20 {
+            source.push_str(&format!(
+                ".method_{}(|x| x.inner_{}().map(|y| y + {}).unwrap_or_default())",
+                j, j, i
+            ));
+        }
+        source.push_str(";\n");
+    }
+    source.push_str("}\n");
+    source
+}
+
+/// A function with a match with many arms, with patterns and bodies of varying width.
+fn big_match() -> String {
+    let mut source = String::from("fn matches(x: Foo) -> u32 {\nmatch x {\n");
+    for i in 0..1000 {
+        match i % 3 {
+            0 => source.push_str(&format!("Foo::Variant{}(a, b) => a + b + {},\n", i, i)),
+            1 => source.push_str(&format!(
+                "Foo::Variant{} {{ first, second, .. }} if first > second => {{ \
+                 let sum = first + second; sum * {} }}\n",
+                i, i
+            )),
+            _ => source.push_str(&format!("Foo::Variant{} | Foo::Other{} => {},\n", i, i, i)),
+        }
+    }
+    source.push_str("_ => 0,\n}\n}\n");
+    source
+}
+
+/// Constants holding long arrays of literals and of short expressions.
+fn huge_arrays() -> String {
+    let literals: Vec<_> = (0..5000).map(|i| (i * 7919 % 65536).to_string()).collect();
+    let exprs: Vec<_> = (0..2000).map(|i| format!("(a{} + {}) * b", i, i)).collect();
+    format!(
+        "const LITERALS: [u32; 5000] = [{}];\nfn exprs() {{ let v = vec![{}]; }}\n",
+        literals.join(", "),
+        exprs.join(", ")
+    )
+}
+
+#[bench]
+fn format_deep_chains(b: &mut Bencher) {
+    bench_format(b, deep_chains());
+}
+
+#[bench]
+fn format_big_match(b: &mut Bencher) {
+    bench_format(b, big_match());
+}
+
+#[bench]
+fn format_huge_arrays(b: &mut Bencher) {
+    bench_format(b, huge_arrays());
+}
@@ -116,7 +116,7 @@ fn format_derive(
                 |span| Some(context.snippet(*span).to_owned()),
                 // We update derive attribute spans to start after the opening '('
                 // This helps us focus parsing to just what's inside #[derive(...)]
-                context.snippet_provider.span_after(attr.span, "("),
+                context.snippet_provider.span_after(attr.span, "(")?,
                 attr.span.hi(),
                 false,
             );
@@ -149,6 +149,7 @@ fn format_derive(
         ListTactic::HorizontalVertical,
         Separator::Comma,
         argument_shape.width,
+        context.config,
     );
     let trailing_separator = match context.config.indent_style() {
         // We always add the trailing comma and remove it if it is not needed.
@@ -308,6 +309,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                     } else {
                         SeparatorTactic::Never
                     }),
+                    ListLayout::Auto,
                 )?
             }
             ast::MetaItemKind::NameValue(ref literal) => {
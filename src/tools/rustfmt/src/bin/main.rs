@@ -7,16 +7,19 @@
 
 use std::collections::HashMap;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, stdout, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use getopts::{Matches, Options};
 
 use crate::rustfmt::{
-    load_config, CliOptions, Color, Config, Edition, EmitMode, FileLines, FileName,
-    FormatReportFormatterBuilder, Input, Session, Verbosity,
+    canonicalize_path, explain_config, innermost_item, load_config, migrate_config, source_items,
+    CliOptions, Color, Config, Edition, EmitMode, FileLines, FileName,
+    FormatReportFormatterBuilder, Input, ModifiedLines, Range, Session, Verbosity,
 };
 
 fn main() {
@@ -27,7 +30,7 @@ fn main() {
         Ok(code) => code,
         Err(e) => {
             eprintln!("{:#}", e);
-            1
+            ExitCode::OperationalError
         }
     };
     // Make sure standard output is flushed before we exit.
@@ -37,7 +40,57 @@ fn main() {
     //
     // NOTE: this immediately terminates the process without doing any cleanup,
     // so make sure to finish all necessary cleanup before this is called.
-    std::process::exit(exit_code);
+    std::process::exit(exit_code as i32);
+}
+
+/// The exit codes of rustfmt, so that scripts can tell failure modes apart
+/// without scraping stderr. When several failures occur, the most severe one
+/// (in declaration order, from `OperationalError` down) is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExitCode {
+    /// Formatting completed, and in `--check` mode no changes are needed.
+    Success = 0,
+    /// In `--check` mode, some input would be reformatted.
+    WouldReformat = 1,
+    /// An IO or configuration error occurred.
+    OperationalError = 2,
+    /// Some input could not be parsed.
+    ParseError = 3,
+    /// Rustfmt was unable to format some input correctly, e.g., a line exceeded
+    /// `max_width` with `error_on_line_overflow` enabled.
+    FormattingError = 4,
+}
+
+impl ExitCode {
+    fn from_session<T: Write>(session: &Session<'_, T>, check: bool) -> ExitCode {
+        if session.has_operational_errors() {
+            ExitCode::OperationalError
+        } else if session.has_parsing_errors() {
+            ExitCode::ParseError
+        } else if session.has_internal_errors() {
+            ExitCode::FormattingError
+        } else if check && (session.has_diff() || session.has_check_errors()) {
+            ExitCode::WouldReformat
+        } else {
+            ExitCode::Success
+        }
+    }
+
+    /// Returns the more severe of the two exit codes.
+    fn most_severe(self, other: ExitCode) -> ExitCode {
+        let severity = |code| match code {
+            ExitCode::Success => 0,
+            ExitCode::WouldReformat => 1,
+            ExitCode::FormattingError => 2,
+            ExitCode::ParseError => 3,
+            ExitCode::OperationalError => 4,
+        };
+        if severity(other) > severity(self) {
+            other
+        } else {
+            self
+        }
+    }
 }
 
 /// Rustfmt operations.
@@ -57,6 +110,16 @@ enum Operation {
     ConfigOutputCurrent { path: Option<String> },
     /// No file specified, read from stdin
     Stdin { input: String },
+    /// Check the files staged in git, as they are in the index.
+    CheckStaged,
+    /// Rewrite the deprecated options of a config file.
+    MigrateConfig { path: Option<String> },
+    /// Print where the value of a config option for a file or directory comes from.
+    ExplainConfig { key: String, path: Option<String> },
+    /// Format a file repeatedly and print how long it took.
+    BenchFile { file: PathBuf, runs: usize },
+    /// Check that the formatted files of a corpus are reproduced.
+    SelfCheckCorpus { dir: PathBuf },
 }
 
 /// Rustfmt operations errors.
@@ -78,6 +141,24 @@ pub enum OperationError {
     /// supported with stdandard input.
     #[error("Emit mode {0} not supported with standard output.")]
     StdinBadEmit(EmitMode),
+    /// Attempt to use `--lines` when formatting files.
+    #[error("The `--lines` option only works with standard input.")]
+    LinesWithFiles,
+    /// Attempt to use `--staged` without `--check`, or with files to format.
+    #[error("The `--staged` option only works with `--check` and without files.")]
+    StagedWithoutCheck,
+    /// A git command run for `--staged` failed.
+    #[error("`git {0}` failed: {1}")]
+    GitError(String, String),
+    /// Attempt to use `--compare-with` without files to format.
+    #[error("The `--compare-with` option only works with files.")]
+    CompareWithoutFiles,
+    /// The binary or config given to `--compare-with` failed to format a file.
+    #[error("`{0}` failed to format `{1}`: {2}")]
+    CompareWithFailed(String, String, String),
+    /// An invalid number of runs was given to `--bench-runs`.
+    #[error("Invalid value for `--bench-runs`: `{0}`.")]
+    InvalidBenchRuns(String),
 }
 
 impl From<IoError> for OperationError {
@@ -90,6 +171,7 @@ fn from(e: IoError) -> OperationError {
 enum HelpOp {
     None,
     Config,
+    ConfigReference,
     FileLines,
 }
 
@@ -100,16 +182,31 @@ fn make_opts() -> Options {
         "",
         "check",
         "Run in 'check' mode. Exits with 0 if input is formatted correctly. Exits \
-         with 1 and prints a diff if formatting is required.",
+         with 1 and prints a diff if formatting is required. Exits with 2 on IO or \
+         configuration errors, 3 on parse errors and 4 if formatting failed.",
     );
     let is_nightly = is_nightly();
     let emit_opts = if is_nightly {
-        "[files|stdout|coverage|checkstyle|json]"
+        "[files|stdout|coverage|checkstyle|json|srcmap|item-diff|rustc-json]"
     } else {
         "[files|stdout]"
     };
     opts.optopt("", "emit", "What data to emit and how", emit_opts);
     opts.optflag("", "backup", "Backup any modified files.");
+    opts.optopt(
+        "",
+        "keep-backups",
+        "Back up modified files like `--backup`, keeping the COUNT most recent backups of \
+         each file: `<name>.bk`, then `<name>.bk.1` and so on. Older backups are removed.",
+        "COUNT",
+    );
+    opts.optopt(
+        "",
+        "out-dir",
+        "Write the formatted files into DIR, mirroring the tree of the input files \
+         relative to the current directory, and leave the input files untouched.",
+        "DIR",
+    );
     opts.optopt(
         "",
         "config-path",
@@ -117,6 +214,13 @@ fn make_opts() -> Options {
          found reverts to the input file path",
         "[Path for the configuration file]",
     );
+    opts.optopt(
+        "",
+        "profile",
+        "Applies the options of the given profile of the config file, e.g. `ci` for the \
+         `[profile.ci]` table, on top of its other options",
+        "[name]",
+    );
     opts.optopt("", "edition", "Rust edition to use", "[2015|2018|2021]");
     opts.optopt(
         "",
@@ -132,12 +236,51 @@ fn make_opts() -> Options {
          `current` writes to stdout current config as if formatting the file at PATH.",
         "[default|minimal|current] PATH",
     );
+    opts.optopt(
+        "",
+        "explain-config",
+        "Prints the config files, environment variables and command line options which \
+         set KEY for the file or directory at PATH (the current directory by default), \
+         and the value each of them sets. The last value is the effective one.",
+        "KEY [PATH]",
+    );
+    opts.optflag(
+        "",
+        "migrate-config",
+        "Rewrites the deprecated options of the config file at PATH, or of the config \
+         file found for the current directory, to the options which replace them.",
+    );
+    opts.optflag(
+        "",
+        "self-check-corpus",
+        "Checks that this rustfmt reproduces the formatted files of the corpus in DIR \
+         (`tests/corpus` by default), with the config of each crate of the corpus, and \
+         prints the items whose formatting changed.",
+    );
     opts.optflag(
         "l",
         "files-with-diff",
         "Prints the names of mismatched files that were formatted. Prints the names of \
          files that would be formated when used with `--check` mode. ",
     );
+    opts.optopt(
+        "",
+        "files-from",
+        "Format the files listed in the given file, one path per line, in addition to \
+         the files given as arguments. Use `-` to read the list from stdin.",
+        "[Path|-]",
+    );
+    opts.optflag(
+        "",
+        "staged",
+        "With `--check`, check the Rust files staged in git, as they are in the index \
+         rather than in the working tree, e.g. in a pre-commit hook.",
+    );
+    opts.optflag(
+        "",
+        "ignore-max-file-size",
+        "Format files regardless of their size, ignoring the `max_file_size` option.",
+    );
     opts.optmulti(
         "",
         "config",
@@ -158,6 +301,25 @@ fn make_opts() -> Options {
              more detail (unstable).",
             "JSON",
         );
+        opts.optopt(
+            "",
+            "lines",
+            "Format only the given 1-based, inclusive line range of the input read \
+             from stdin, emitting the rest unchanged (unstable).",
+            "START:END",
+        );
+        opts.optflag(
+            "",
+            "interactive",
+            "Show each change to a file and ask whether to apply it, writing only the \
+             applied changes back to the file (unstable).",
+        );
+        opts.optflag(
+            "",
+            "verify",
+            "Check that formatting keeps every comment, and leave the files which would \
+             lose a comment unformatted (unstable).",
+        );
         opts.optflag(
             "",
             "error-on-unformatted",
@@ -169,15 +331,37 @@ fn make_opts() -> Options {
             "skip-children",
             "Don't reformat child modules (unstable).",
         );
+        opts.optopt(
+            "",
+            "compare-with",
+            "Format the given files with this rustfmt and with OTHER, either a binary (e.g. \
+             another version of rustfmt) or a `.toml` config file to use instead of the \
+             config of the files, and report the items whose formatting differs, without \
+             changing the files (unstable).",
+            "OTHER",
+        );
+        opts.optopt(
+            "",
+            "bench-file",
+            "Format FILE repeatedly without writing it and print how long formatting took, \
+             to measure the performance of rustfmt (unstable).",
+            "FILE",
+        );
+        opts.optopt(
+            "",
+            "bench-runs",
+            "How many times `--bench-file` formats the file, 10 by default (unstable).",
+            "N",
+        );
     }
 
     opts.optflag("v", "verbose", "Print verbose output");
     opts.optflag("q", "quiet", "Print less output");
     opts.optflag("V", "version", "Show version information");
     let help_topics = if is_nightly {
-        "`config` or `file-lines`"
+        "`config`, `config-reference` or `file-lines`"
     } else {
-        "`config`"
+        "`config` or `config-reference`"
     };
     let mut help_topic_msg = "Show this message or help about a specific topic: ".to_owned();
     help_topic_msg.push_str(help_topics);
@@ -191,27 +375,38 @@ fn is_nightly() -> bool {
     option_env!("CFG_RELEASE_CHANNEL").map_or(true, |c| c == "nightly" || c == "dev")
 }
 
-// Returned i32 is an exit code
-fn execute(opts: &Options) -> Result<i32> {
+fn execute(opts: &Options) -> Result<ExitCode> {
     let matches = opts.parse(env::args().skip(1))?;
     let options = GetOptsOptions::from_matches(&matches)?;
+    let operation = determine_operation(&matches)?;
+
+    if matches!(operation, Operation::Format { .. }) && matches.opt_present("lines") {
+        return Err(OperationError::LinesWithFiles.into());
+    }
+    if options.compare_with.is_some() && !matches!(operation, Operation::Format { .. }) {
+        return Err(OperationError::CompareWithoutFiles.into());
+    }
 
-    match determine_operation(&matches)? {
+    match operation {
         Operation::Help(HelpOp::None) => {
             print_usage_to_stdout(opts, "");
-            Ok(0)
+            Ok(ExitCode::Success)
         }
         Operation::Help(HelpOp::Config) => {
             Config::print_docs(&mut stdout(), options.unstable_features);
-            Ok(0)
+            Ok(ExitCode::Success)
+        }
+        Operation::Help(HelpOp::ConfigReference) => {
+            Config::print_reference(&mut stdout(), options.unstable_features)?;
+            Ok(ExitCode::Success)
         }
         Operation::Help(HelpOp::FileLines) => {
             print_help_file_lines();
-            Ok(0)
+            Ok(ExitCode::Success)
         }
         Operation::Version => {
             print_version();
-            Ok(0)
+            Ok(ExitCode::Success)
         }
         Operation::ConfigOutputDefault { path } => {
             let toml = Config::default().all_options().to_toml()?;
@@ -221,7 +416,7 @@ fn execute(opts: &Options) -> Result<i32> {
             } else {
                 io::stdout().write_all(toml.as_bytes())?;
             }
-            Ok(0)
+            Ok(ExitCode::Success)
         }
         Operation::ConfigOutputCurrent { path } => {
             let path = match path {
@@ -230,33 +425,470 @@ fn execute(opts: &Options) -> Result<i32> {
             };
 
             let file = PathBuf::from(path);
-            let file = file.canonicalize().unwrap_or(file);
+            let file = canonicalize_path(&file).unwrap_or(file);
 
             let (config, _) = load_config(Some(file.parent().unwrap()), Some(options))?;
             let toml = config.all_options().to_toml()?;
             io::stdout().write_all(toml.as_bytes())?;
 
-            Ok(0)
+            Ok(ExitCode::Success)
         }
+        Operation::ExplainConfig { key, path } => {
+            let path = PathBuf::from(path.unwrap_or_else(|| ".".to_owned()));
+            let path = canonicalize_path(&path).unwrap_or(path);
+            let dir = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or_else(|| Path::new("."))
+            };
+
+            let sources = explain_config(&key, Some(dir), Some(options))?;
+            if let Some((_, value)) = sources.last() {
+                println!("{} = {}", key, value);
+            }
+            for (source, value) in &sources {
+                println!("    {}: {}", source, value);
+            }
+            Ok(ExitCode::Success)
+        }
+        Operation::BenchFile { file, runs } => bench_file(&file, runs, &options),
+        Operation::SelfCheckCorpus { dir } => self_check_corpus(&dir, &options),
         Operation::Stdin { input } => format_string(input, options),
+        Operation::Format { files, .. } if options.compare_with.is_some() => {
+            compare_with(files, &options)
+        }
         Operation::Format {
             files,
             minimal_config_path,
         } => format(files, minimal_config_path, &options),
+        Operation::CheckStaged => check_staged(&options),
+        Operation::MigrateConfig { path } => {
+            let path = match path {
+                Some(path) => PathBuf::from(path),
+                None => match load_config(Some(Path::new(".")), Some(options))?.1 {
+                    Some(path) => path,
+                    None => return Err(format_err!("no config file found to migrate")),
+                },
+            };
+            let toml = fs::read_to_string(&path)?;
+            let migrated = migrate_config(&toml).map_err(|err| format_err!("{}", err))?;
+            if migrated == toml {
+                println!("{} has no deprecated options", path.display());
+            } else {
+                fs::write(&path, migrated)?;
+                println!("Migrated {}", path.display());
+            }
+            Ok(ExitCode::Success)
+        }
+    }
+}
+
+/// Checks the formatting of the Rust files staged in git. The files are read from the
+/// index, so that partially staged files are checked as they will be committed.
+fn check_staged(options: &GetOptsOptions) -> Result<ExitCode> {
+    let staged = git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "--relative",
+        "--diff-filter=ACMR",
+        "-z",
+    ])?;
+    let mut exit_code = ExitCode::Success;
+    for path in staged.split('\0').filter(|path| path.ends_with(".rs")) {
+        let text = git(&["show", &format!(":./{}", path)])?;
+        let formatted = match format_file_text(
+            Path::new(path),
+            &text,
+            EmitMode::ModifiedLines,
+            true,
+            options,
+        )? {
+            Some(formatted) => formatted,
+            None => continue,
+        };
+        exit_code = exit_code.most_severe(formatted.exit_code);
+
+        let chunks = formatted
+            .output
+            .parse::<ModifiedLines>()
+            .map_or_else(|_| Vec::new(), |modified_lines| modified_lines.chunks);
+        let original_lines: Vec<_> = text.lines().collect();
+        for chunk in chunks {
+            println!(
+                "Diff in {} (staged) at line {}:",
+                path, chunk.line_number_orig
+            );
+            let start = (chunk.line_number_orig as usize).saturating_sub(1);
+            let removed = original_lines
+                .iter()
+                .skip(start)
+                .take(chunk.lines_removed as usize);
+            for line in removed {
+                println!("-{}", line);
+            }
+            for line in &chunk.lines {
+                println!("+{}", line);
+            }
+        }
+    }
+    Ok(exit_code)
+}
+
+/// Runs git with the given arguments and returns its standard output.
+fn git(args: &[&str]) -> Result<String, OperationError> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(OperationError::GitError(
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Formats `file` `runs` times, including parsing but without writing the result, and prints
+/// the fastest, median and slowest run, e.g. to compare the performance of two builds.
+fn bench_file(file: &Path, runs: usize, options: &GetOptsOptions) -> Result<ExitCode> {
+    let (mut config, _) = load_config(Some(config_dir(file)), Some(options.clone()))?;
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+
+    let mut times = Vec::with_capacity(runs);
+    let mut exit_code = ExitCode::Success;
+    for _ in 0..runs {
+        let mut out = io::sink();
+        let start = Instant::now();
+        let mut session = Session::new(config.clone(), Some(&mut out));
+        format_and_emit_report(&mut session, Input::File(file.to_owned()));
+        exit_code = ExitCode::from_session(&session, false);
+        drop(session);
+        times.push(start.elapsed());
+        if exit_code != ExitCode::Success {
+            break;
+        }
+    }
+    times.sort();
+
+    let millis = |time: &Duration| time.as_secs_f64() * 1000.0;
+    println!(
+        "{}: {} runs, min {:.2}ms, median {:.2}ms, max {:.2}ms",
+        file.display(),
+        times.len(),
+        millis(&times[0]),
+        millis(&times[times.len() / 2]),
+        millis(&times[times.len() - 1]),
+    );
+    Ok(exit_code)
+}
+
+/// Formats the files of the corpus in `dir`, which are formatted already, and reports the
+/// items whose formatting changed, i.e. which a release of rustfmt would reformat. Each
+/// directory of the corpus is formatted with its config, and the files are not changed.
+fn self_check_corpus(dir: &Path, options: &GetOptsOptions) -> Result<ExitCode> {
+    let mut files = vec![];
+    find_corpus_files(dir, &mut files)?;
+    files.sort();
+    if files.is_empty() {
+        return Err(format_err!("no corpus files found in {}", dir.display()));
+    }
+
+    let mut exit_code = ExitCode::Success;
+    let mut reproduced = 0;
+    for file in &files {
+        let text = fs::read_to_string(file)?;
+        let formatted = match format_file_text(file, &text, EmitMode::Stdout, false, options)? {
+            Some(formatted) => formatted,
+            None => continue,
+        };
+        if formatted.exit_code != ExitCode::Success {
+            eprintln!("Failed to format corpus file {}", file.display());
+            exit_code = exit_code.most_severe(formatted.exit_code);
+            continue;
+        }
+
+        let differences = differing_items(&text, &formatted.output, formatted.edition);
+        if differences.is_empty() {
+            reproduced += 1;
+            continue;
+        }
+        exit_code = exit_code.most_severe(ExitCode::WouldReformat);
+        print_item_differences(file, &differences, "changed");
+    }
+    println!("{} of {} corpus files reproduced", reproduced, files.len());
+    Ok(exit_code)
+}
+
+/// Adds the Rust files in `dir` and its subdirectories to `files`.
+fn find_corpus_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_corpus_files(&path, files)?;
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Formats `files` with this rustfmt and with the binary or config file given to
+/// `--compare-with`, and reports the items whose formatting differs, e.g. to assess the
+/// changes an upgrade of rustfmt or a change of config would make. The files are not changed.
+fn compare_with(files: Vec<PathBuf>, options: &GetOptsOptions) -> Result<ExitCode> {
+    let other = options.compare_with.as_deref().unwrap_or("rustfmt");
+    let other_config =
+        Some(Path::new(other)).filter(|path| path.extension().map_or(false, |ext| ext == "toml"));
+    let mut exit_code = ExitCode::Success;
+    let mut same = 0;
+    let total = files.len();
+    for file in files {
+        let text = fs::read_to_string(&file)?;
+        let formatted = match format_file_text(&file, &text, EmitMode::Stdout, false, options)? {
+            Some(formatted) => formatted,
+            None => continue,
+        };
+        if formatted.exit_code != ExitCode::Success {
+            exit_code = exit_code.most_severe(formatted.exit_code);
+            continue;
+        }
+        let theirs = match other_config {
+            Some(config_path) => format_with_config(config_path, &file, &text, options)?,
+            None => format_with_binary(other, &file, &text, &formatted, options)?,
+        };
+
+        let differences = differing_items(&formatted.output, &theirs, formatted.edition);
+        if differences.is_empty() {
+            same += 1;
+        } else {
+            exit_code = exit_code.most_severe(ExitCode::WouldReformat);
+        }
+        print_item_differences(&file, &differences, "differs");
+    }
+    println!("{same} of {total} files formatted the same by {other}");
+    Ok(exit_code)
+}
+
+/// Returns the directory whose config applies to `file`.
+fn config_dir(file: &Path) -> &Path {
+    file.parent()
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// The output of formatting the text of a file with `format_file_text`.
+struct FormattedText {
+    /// The output of the emit mode.
+    output: String,
+    exit_code: ExitCode,
+    edition: Edition,
+    config_path: Option<PathBuf>,
+}
+
+/// Formats `text`, the contents of `file` (e.g. the staged version of it), with the config
+/// which applies to `file`, emitting the result with `emit_mode`. Returns `None` if rustfmt
+/// skips `file`, e.g. because of the `ignore` option. The exit code is that of `--check` if
+/// `check` is `true`.
+fn format_file_text(
+    file: &Path,
+    text: &str,
+    emit_mode: EmitMode,
+    check: bool,
+    options: &GetOptsOptions,
+) -> Result<Option<FormattedText>> {
+    let (mut config, config_path) = load_config(Some(config_dir(file)), Some(options.clone()))?;
+    config.set().emit_mode(emit_mode);
+    config.set().verbose(Verbosity::Quiet);
+    let edition = config.edition();
+
+    let mut out = Vec::new();
+    let exit_code = {
+        let mut session = Session::new(config, Some(&mut out));
+        if session.skips_file(file, text) {
+            return Ok(None);
+        }
+        format_and_emit_report(&mut session, Input::Text(text.to_owned()));
+        ExitCode::from_session(&session, check)
+    };
+    Ok(Some(FormattedText {
+        output: String::from_utf8_lossy(&out).into_owned(),
+        exit_code,
+        edition,
+        config_path,
+    }))
+}
+
+/// Prints the `differences` of the formatting of `file`, whose formatting `what`, e.g.
+/// "changed".
+fn print_item_differences(file: &Path, differences: &[ItemDifference], what: &str) {
+    for difference in differences {
+        println!(
+            "{}:{}: formatting of `{}` {}",
+            file.display(),
+            difference.line,
+            difference.item,
+            what
+        );
+        for line in &difference.ours {
+            println!("< {}", line);
+        }
+        println!("---");
+        for line in &difference.theirs {
+            println!("> {}", line);
+        }
+    }
+}
+
+/// Formats `text`, the contents of `file`, with this rustfmt and the config file at
+/// `config_path` instead of the config which applies to `file`.
+fn format_with_config(
+    config_path: &Path,
+    file: &Path,
+    text: &str,
+    options: &GetOptsOptions,
+) -> Result<String> {
+    let mut config_options = options.clone();
+    config_options.config_path = Some(config_path.to_owned());
+    match format_file_text(file, text, EmitMode::Stdout, false, &config_options)? {
+        Some(formatted) if formatted.exit_code == ExitCode::Success => Ok(formatted.output),
+        // The config of `config_path` doesn't decide whether `file` is skipped, so an
+        // ignored file is formatted the same by both.
+        None => Ok(text.to_owned()),
+        Some(_) => Err(OperationError::CompareWithFailed(
+            config_path.display().to_string(),
+            file.display().to_string(),
+            "formatting failed".to_owned(),
+        )
+        .into()),
+    }
+}
+
+/// Formats `text`, the contents of `file`, by running `binary` on it with the same edition,
+/// config file and config overrides as this rustfmt used for `ours`.
+fn format_with_binary(
+    binary: &str,
+    file: &Path,
+    text: &str,
+    ours: &FormattedText,
+    options: &GetOptsOptions,
+) -> Result<String, OperationError> {
+    let mut command = Command::new(binary);
+    command
+        .current_dir(config_dir(file))
+        .arg("--edition")
+        .arg(ours.edition.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(ref config_path) = ours.config_path {
+        command
+            .arg("--config-path")
+            .arg(canonicalize_path(config_path).unwrap_or_else(|_| config_path.clone()));
+    }
+    if !options.inline_config.is_empty() {
+        let inline_config: Vec<_> = options
+            .inline_config
+            .iter()
+            .map(|(key, val)| format!("{}={}", key, val))
+            .collect();
+        command.arg("--config").arg(inline_config.join(","));
+    }
+
+    let mut child = command.spawn()?;
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(OperationError::CompareWithFailed(
+            binary.to_owned(),
+            file.display().to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// An item whose formatting differs between two formattings of the same source.
+#[derive(Debug, PartialEq)]
+struct ItemDifference {
+    /// The 1-based line of `ours` at which the difference starts.
+    line: usize,
+    /// The description of the innermost item of `ours` containing the difference, e.g.
+    /// `fn foo::bar`, or `(outside items)`.
+    item: String,
+    ours: Vec<String>,
+    theirs: Vec<String>,
+}
+
+/// Returns the differences between `ours` and `theirs`, two formattings of the same source,
+/// along with the items of `ours` they are in.
+fn differing_items(ours: &str, theirs: &str, edition: Edition) -> Vec<ItemDifference> {
+    let mut differences: Vec<ItemDifference> = vec![];
+    let mut line = 0;
+    let mut in_difference = false;
+    for result in diff::lines(ours, theirs) {
+        let (ours_line, theirs_line) = match result {
+            diff::Result::Both(..) => {
+                line += 1;
+                in_difference = false;
+                continue;
+            }
+            diff::Result::Left(ours_line) => (Some(ours_line), None),
+            diff::Result::Right(theirs_line) => (None, Some(theirs_line)),
+        };
+        if ours_line.is_some() {
+            line += 1;
+        }
+        if !in_difference {
+            // A difference without lines of `ours` starts before the next line of `ours`.
+            let start_line = if ours_line.is_some() { line } else { line + 1 };
+            differences.push(ItemDifference {
+                line: start_line,
+                item: String::new(),
+                ours: vec![],
+                theirs: vec![],
+            });
+            in_difference = true;
+        }
+        let difference = differences.last_mut().unwrap();
+        difference.ours.extend(ours_line.map(str::to_owned));
+        difference.theirs.extend(theirs_line.map(str::to_owned));
     }
+
+    let items = source_items(ours, edition).unwrap_or_default();
+    for difference in &mut differences {
+        // Lines which are only inserted belong to the line before them.
+        let line = if difference.ours.is_empty() {
+            difference.line.saturating_sub(1).max(1)
+        } else {
+            difference.line
+        };
+        difference.item = innermost_item(&items, line).map_or_else(
+            || "(outside items)".to_owned(),
+            |item| item.description.clone(),
+        );
+    }
+    differences
 }
 
-fn format_string(input: String, options: GetOptsOptions) -> Result<i32> {
+fn format_string(input: String, options: GetOptsOptions) -> Result<ExitCode> {
     // try to read config from local directory
     let (mut config, _) = load_config(Some(Path::new(".")), Some(options.clone()))?;
 
     if options.check {
-        config.set().emit_mode(EmitMode::Diff);
+        config
+            .set()
+            .emit_mode(options.emit_mode.unwrap_or(EmitMode::Diff));
     } else {
         match options.emit_mode {
             // Emit modes which work with standard input
             // None means default, which is Stdout.
-            None | Some(EmitMode::Stdout) | Some(EmitMode::Checkstyle) | Some(EmitMode::Json) => {}
+            None
+            | Some(EmitMode::Stdout)
+            | Some(EmitMode::Checkstyle)
+            | Some(EmitMode::Json)
+            | Some(EmitMode::Srcmap)
+            | Some(EmitMode::ItemDiff)
+            | Some(EmitMode::RustcJson) => {}
             Some(emit_mode) => {
                 return Err(OperationError::StdinBadEmit(emit_mode).into());
             }
@@ -280,19 +912,14 @@ fn format_string(input: String, options: GetOptsOptions) -> Result<i32> {
     let mut session = Session::new(config, Some(out));
     format_and_emit_report(&mut session, Input::Text(input));
 
-    let exit_code = if session.has_operational_errors() || session.has_parsing_errors() {
-        1
-    } else {
-        0
-    };
-    Ok(exit_code)
+    Ok(ExitCode::from_session(&session, options.check))
 }
 
 fn format(
     files: Vec<PathBuf>,
     minimal_config_path: Option<String>,
     options: &GetOptsOptions,
-) -> Result<i32> {
+) -> Result<ExitCode> {
     options.verify_file_lines(&files);
     let (config, config_path) = load_config(None, Some(options.clone()))?;
 
@@ -344,15 +971,7 @@ fn format(
         file.write_all(toml.as_bytes())?;
     }
 
-    let exit_code = if session.has_operational_errors()
-        || session.has_parsing_errors()
-        || ((session.has_diff() || session.has_check_errors()) && options.check)
-    {
-        1
-    } else {
-        0
-    };
-    Ok(exit_code)
+    Ok(ExitCode::from_session(&session, options.check))
 }
 
 fn format_and_emit_report<T: Write>(session: &mut Session<'_, T>, input: Input) {
@@ -419,7 +1038,14 @@ fn print_help_file_lines() {
 
 would format lines `7-13` and `21-29` of `src/lib.rs`, and lines `10-11`,
 and `15` of `src/foo.rs`. No other files would be formatted, even if they
-are included as out of line modules from `src/lib.rs`."
+are included as out of line modules from `src/lib.rs`.
+
+When reading from stdin, a single range can be given more simply with the
+`--lines` option. For example, `rustfmt --lines 7:13` is equivalent to
+
+```
+rustfmt --file-lines '[{{\"file\":\"stdin\",\"range\":[7,13]}}]'
+```"
     );
 }
 
@@ -440,6 +1066,8 @@ fn determine_operation(matches: &Matches) -> Result<Operation, OperationError> {
             return Ok(Operation::Help(HelpOp::None));
         } else if topic == Some("config".to_owned()) {
             return Ok(Operation::Help(HelpOp::Config));
+        } else if topic == Some("config-reference".to_owned()) {
+            return Ok(Operation::Help(HelpOp::ConfigReference));
         } else if topic == Some("file-lines".to_owned()) && is_nightly() {
             return Ok(Operation::Help(HelpOp::FileLines));
         } else {
@@ -470,17 +1098,70 @@ fn determine_operation(matches: &Matches) -> Result<Operation, OperationError> {
         return Ok(Operation::Version);
     }
 
-    let files: Vec<_> = free_matches
+    if let Some(file) = matches.opt_str("bench-file") {
+        let runs = match matches.opt_str("bench-runs") {
+            Some(runs) => match runs.parse() {
+                Ok(runs) if runs > 0 => runs,
+                _ => return Err(OperationError::InvalidBenchRuns(runs)),
+            },
+            None => 10,
+        };
+        return Ok(Operation::BenchFile {
+            file: PathBuf::from(file),
+            runs,
+        });
+    }
+
+    if let Some(key) = matches.opt_str("explain-config") {
+        let path = free_matches.next().cloned();
+        return Ok(Operation::ExplainConfig { key, path });
+    }
+
+    if matches.opt_present("migrate-config") {
+        let path = free_matches.next().cloned();
+        return Ok(Operation::MigrateConfig { path });
+    }
+
+    if matches.opt_present("self-check-corpus") {
+        let dir = free_matches.next().map_or("tests/corpus", String::as_str);
+        return Ok(Operation::SelfCheckCorpus {
+            dir: PathBuf::from(dir),
+        });
+    }
+
+    if matches.opt_present("staged") {
+        if !matches.opt_present("check")
+            || !matches.free.is_empty()
+            || matches.opt_present("files-from")
+        {
+            return Err(OperationError::StagedWithoutCheck);
+        }
+        return Ok(Operation::CheckStaged);
+    }
+
+    let files_from = matches.opt_str("files-from");
+    let listed_files = match files_from.as_deref() {
+        Some(path) => read_file_list(path)?,
+        None => vec![],
+    };
+
+    let mut files: Vec<_> = free_matches
+        .cloned()
+        .chain(listed_files)
         .map(|s| {
             let p = PathBuf::from(s);
             // we will do comparison later, so here tries to canonicalize first
             // to get the expected behavior.
-            p.canonicalize().unwrap_or(p)
+            canonicalize_path(&p).unwrap_or(p)
         })
         .collect();
+    // Format each file once, in the same order whatever the order of the arguments, so
+    // that the output does not depend on how the shell expanded a glob.
+    files.sort();
+    files.dedup();
 
     // if no file argument is supplied, read from stdin
-    if files.is_empty() {
+    if files.is_empty() && files_from.is_none() {
         if minimal_config_path.is_some() {
             return Err(OperationError::MinimalPathWithStdin);
         }
@@ -496,6 +1177,27 @@ fn determine_operation(matches: &Matches) -> Result<Operation, OperationError> {
     })
 }
 
+/// Reads the list of files given to `--files-from`, one path per line, from the file at
+/// `path` or from stdin if `path` is `-`.
+fn read_file_list(path: &str) -> Result<Vec<String>, OperationError> {
+    let mut buffer = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut buffer)?;
+    } else {
+        buffer = fs::read_to_string(path)?;
+    }
+    Ok(file_list_from_str(&buffer))
+}
+
+/// Returns the paths of a list of files given to `--files-from`, skipping empty lines.
+fn file_list_from_str(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
 const STABLE_EMIT_MODES: [EmitMode; 3] = [EmitMode::Files, EmitMode::Stdout, EmitMode::Diff];
 
 /// Parsed command line options.
@@ -505,16 +1207,22 @@ struct GetOptsOptions {
     quiet: bool,
     verbose: bool,
     config_path: Option<PathBuf>,
+    profile: Option<String>,
     inline_config: HashMap<String, String>,
     emit_mode: Option<EmitMode>,
     backup: bool,
+    backup_count: Option<usize>,
+    out_dir: Option<PathBuf>,
     check: bool,
     edition: Option<Edition>,
     color: Option<Color>,
     file_lines: FileLines, // Default is all lines in all files.
     unstable_features: bool,
     error_on_unformatted: Option<bool>,
+    verify: bool,
     print_misformatted_file_names: bool,
+    ignore_max_file_size: bool,
+    compare_with: Option<String>,
 }
 
 impl GetOptsOptions {
@@ -527,6 +1235,7 @@ pub fn from_matches(matches: &Matches) -> Result<GetOptsOptions> {
         }
 
         let rust_nightly = is_nightly();
+        let mut interactive = false;
 
         if rust_nightly {
             options.unstable_features = matches.opt_present("unstable-features");
@@ -538,9 +1247,18 @@ pub fn from_matches(matches: &Matches) -> Result<GetOptsOptions> {
                 if matches.opt_present("error-on-unformatted") {
                     options.error_on_unformatted = Some(true);
                 }
+                options.verify = matches.opt_present("verify");
                 if let Some(ref file_lines) = matches.opt_str("file-lines") {
                     options.file_lines = file_lines.parse()?;
                 }
+                if let Some(ref lines) = matches.opt_str("lines") {
+                    if matches.opt_present("file-lines") {
+                        return Err(format_err!("Can't use both `--lines` and `--file-lines`"));
+                    }
+                    options.file_lines = stdin_file_lines_from_str(lines)?;
+                }
+                options.compare_with = matches.opt_str("compare-with");
+                interactive = matches.opt_present("interactive");
             } else {
                 let mut unstable_options = vec![];
                 if matches.opt_present("skip-children") {
@@ -549,9 +1267,24 @@ pub fn from_matches(matches: &Matches) -> Result<GetOptsOptions> {
                 if matches.opt_present("error-on-unformatted") {
                     unstable_options.push("`--error-on-unformatted`");
                 }
+                if matches.opt_present("verify") {
+                    unstable_options.push("`--verify`");
+                }
                 if matches.opt_present("file-lines") {
                     unstable_options.push("`--file-lines`");
                 }
+                if matches.opt_present("lines") {
+                    unstable_options.push("`--lines`");
+                }
+                if matches.opt_present("compare-with") {
+                    unstable_options.push("`--compare-with`");
+                }
+                if matches.opt_present("bench-file") {
+                    unstable_options.push("`--bench-file`");
+                }
+                if matches.opt_present("interactive") {
+                    unstable_options.push("`--interactive`");
+                }
                 if !unstable_options.is_empty() {
                     let s = if unstable_options.len() == 1 { "" } else { "s" };
                     return Err(format_err!(
@@ -564,6 +1297,7 @@ pub fn from_matches(matches: &Matches) -> Result<GetOptsOptions> {
         }
 
         options.config_path = matches.opt_str("config-path").map(PathBuf::from);
+        options.profile = matches.opt_str("profile");
 
         options.inline_config = matches
             .opt_strs("config")
@@ -590,11 +1324,23 @@ pub fn from_matches(matches: &Matches) -> Result<GetOptsOptions> {
 
         options.check = matches.opt_present("check");
         if let Some(ref emit_str) = matches.opt_str("emit") {
-            if options.check {
+            let emit_mode = emit_mode_from_emit_str(emit_str)?;
+            // `--check` prints a line diff, or the changed items with `--emit item-diff`, or
+            // diagnostics with `--emit rustc-json`.
+            if options.check && !matches!(emit_mode, EmitMode::ItemDiff | EmitMode::RustcJson) {
                 return Err(format_err!("Invalid to use `--emit` and `--check`"));
             }
 
-            options.emit_mode = Some(emit_mode_from_emit_str(emit_str)?);
+            options.emit_mode = Some(emit_mode);
+        }
+
+        if interactive {
+            if options.check || options.emit_mode.is_some() {
+                return Err(format_err!(
+                    "Invalid to use `--interactive` with `--check` or `--emit`"
+                ));
+            }
+            options.emit_mode = Some(EmitMode::Interactive);
         }
 
         if let Some(ref edition_str) = matches.opt_str("edition") {
@@ -605,10 +1351,52 @@ pub fn from_matches(matches: &Matches) -> Result<GetOptsOptions> {
             options.backup = true;
         }
 
+        if let Some(count) = matches.opt_str("keep-backups") {
+            match count.parse() {
+                Ok(count) if count > 0 => {
+                    options.backup = true;
+                    options.backup_count = Some(count);
+                }
+                _ => {
+                    return Err(format_err!(
+                        "Invalid value for `--keep-backups`: `{}`",
+                        count
+                    ));
+                }
+            }
+        }
+
+        if let Some(out_dir) = matches.opt_str("out-dir") {
+            if options.check
+                || options
+                    .emit_mode
+                    .map_or(false, |mode| mode != EmitMode::Files)
+            {
+                return Err(format_err!("`--out-dir` only works with `--emit files`"));
+            }
+            let out_dir = PathBuf::from(out_dir);
+            // The input files are mirrored relative to the current directory, so they would
+            // be overwritten by their formatted copies.
+            let current_dir = env::current_dir().and_then(|dir| dir.canonicalize());
+            if let (Ok(out_dir), Ok(current_dir)) = (out_dir.canonicalize(), current_dir) {
+                if out_dir == current_dir {
+                    return Err(format_err!(
+                        "`--out-dir` must not be the current directory, the formatted files \
+                         would overwrite the original ones"
+                    ));
+                }
+            }
+            options.out_dir = Some(out_dir);
+        }
+
         if matches.opt_present("files-with-diff") {
             options.print_misformatted_file_names = true;
         }
 
+        if matches.opt_present("ignore-max-file-size") {
+            options.ignore_max_file_size = true;
+        }
+
         if !rust_nightly {
             if let Some(ref emit_mode) = options.emit_mode {
                 if !STABLE_EMIT_MODES.contains(emit_mode) {
@@ -660,23 +1448,37 @@ fn apply_to(self, config: &mut Config) {
         if let Some(error_on_unformatted) = self.error_on_unformatted {
             config.set().error_on_unformatted(error_on_unformatted);
         }
+        if self.verify {
+            config.set().verify(true);
+        }
         if let Some(edition) = self.edition {
             config.set().edition(edition);
         }
         if self.check {
-            config.set().emit_mode(EmitMode::Diff);
+            config
+                .set()
+                .emit_mode(self.emit_mode.unwrap_or(EmitMode::Diff));
         } else if let Some(emit_mode) = self.emit_mode {
             config.set().emit_mode(emit_mode);
         }
         if self.backup {
             config.set().make_backup(true);
         }
+        if let Some(backup_count) = self.backup_count {
+            config.set().backup_count(backup_count);
+        }
+        if let Some(out_dir) = self.out_dir {
+            config.set().out_dir(out_dir.to_string_lossy().into_owned());
+        }
         if let Some(color) = self.color {
             config.set().color(color);
         }
         if self.print_misformatted_file_names {
             config.set().print_misformatted_file_names(true);
         }
+        if self.ignore_max_file_size {
+            config.set().max_file_size(0);
+        }
 
         for (key, val) in self.inline_config {
             config.override_value(&key, &val);
@@ -686,6 +1488,10 @@ fn apply_to(self, config: &mut Config) {
     fn config_path(&self) -> Option<&Path> {
         self.config_path.as_deref()
     }
+
+    fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
 }
 
 fn edition_from_edition_str(edition_str: &str) -> Result<Edition> {
@@ -698,6 +1504,20 @@ fn edition_from_edition_str(edition_str: &str) -> Result<Edition> {
     }
 }
 
+/// Parses the `START:END` argument of `--lines` into the `FileLines` of stdin.
+fn stdin_file_lines_from_str(lines_str: &str) -> Result<FileLines> {
+    let invalid = || format_err!("Invalid value for `--lines`, expected `START:END`");
+    let (lo, hi) = lines_str.split_once(':').ok_or_else(invalid)?;
+    let (lo, hi): (usize, usize) = match (lo.trim().parse(), hi.trim().parse()) {
+        (Ok(lo), Ok(hi)) if 0 < lo && lo <= hi => (lo, hi),
+        _ => return Err(invalid()),
+    };
+
+    let mut ranges = HashMap::new();
+    ranges.insert(FileName::Stdin, vec![Range::new(lo, hi)]);
+    Ok(FileLines::from_ranges(ranges))
+}
+
 fn emit_mode_from_emit_str(emit_str: &str) -> Result<EmitMode> {
     match emit_str {
         "files" => Ok(EmitMode::Files),
@@ -705,6 +1525,77 @@ fn emit_mode_from_emit_str(emit_str: &str) -> Result<EmitMode> {
         "coverage" => Ok(EmitMode::Coverage),
         "checkstyle" => Ok(EmitMode::Checkstyle),
         "json" => Ok(EmitMode::Json),
+        "srcmap" => Ok(EmitMode::Srcmap),
+        "item-diff" => Ok(EmitMode::ItemDiff),
+        "rustc-json" => Ok(EmitMode::RustcJson),
         _ => Err(format_err!("Invalid value for `--emit`")),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn differing_items_are_reported_with_their_items() {
+        let ours = "mod a {\n    fn b() {\n        c(1, 2);\n    }\n}\n\nfn d() {}\n";
+        let theirs = "mod a {\n    fn b() {\n        c(1,\n          2);\n    }\n}\n\nfn d() {}\n";
+        assert_eq!(
+            differing_items(ours, theirs, Edition::Edition2018),
+            vec![ItemDifference {
+                line: 3,
+                item: "fn a::b".to_owned(),
+                ours: vec!["        c(1, 2);".to_owned()],
+                theirs: vec!["        c(1,".to_owned(), "          2);".to_owned()],
+            }]
+        );
+    }
+
+    #[test]
+    fn differing_items_outside_items() {
+        let ours = "fn a() {}\n\nfn b() {}\n";
+        let theirs = "fn a() {}\n\n// c\nfn b() {}\n";
+        assert_eq!(
+            differing_items(ours, theirs, Edition::Edition2018),
+            vec![ItemDifference {
+                line: 3,
+                item: "(outside items)".to_owned(),
+                ours: vec![],
+                theirs: vec!["// c".to_owned()],
+            }]
+        );
+        assert_eq!(differing_items(ours, ours, Edition::Edition2018), vec![]);
+    }
+
+    #[test]
+    fn stdin_file_lines_ranges() {
+        let file_lines = stdin_file_lines_from_str("3:7").unwrap();
+        assert_eq!(file_lines.to_string(), "<stdin>: 3..7\n");
+        let file_lines = stdin_file_lines_from_str(" 5 : 5 ").unwrap();
+        assert_eq!(file_lines.to_string(), "<stdin>: 5..5\n");
+    }
+
+    #[test]
+    fn stdin_file_lines_bad_input() {
+        for lines in &[
+            "", ":", "3", "3:", ":7", "0:7", "7:3", "a:b", "-1:7", "3:7:9",
+        ] {
+            assert!(stdin_file_lines_from_str(lines).is_err(), "{:?}", lines);
+        }
+    }
+
+    #[test]
+    fn file_list_skips_empty_lines() {
+        assert_eq!(
+            file_list_from_str("src/a.rs\n\n  src/b.rs  \r\n\t\nsrc/c d.rs\n"),
+            vec!["src/a.rs", "src/b.rs", "src/c d.rs"]
+        );
+        assert!(file_list_from_str("").is_empty());
+        assert!(file_list_from_str("\n \n\r\n").is_empty());
+    }
+
+    #[test]
+    fn file_list_from_missing_file() {
+        assert!(read_file_list("tests/files-from/does-not-exist").is_err());
+    }
+}
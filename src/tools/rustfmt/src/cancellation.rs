@@ -0,0 +1,37 @@
+//! Cooperative cancellation of a running [`Session`](crate::Session), e.g. for an editor
+//! which no longer needs the result of a format request because the buffer changed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag which cancels the sessions it is given to once it is set.
+///
+/// The token is checked between the files of a session and between the items of a file,
+/// so formatting stops shortly after [`CancellationToken::cancel`] is called from another
+/// thread, or from a [`FormatProgress`](crate::FormatProgress) callback. A cancelled file
+/// is left untouched, and [`Session::format`](crate::Session::format) returns
+/// [`ErrorKind::Cancelled`](crate::ErrorKind::Cancelled).
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels every session using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns `true` if `token` is set and has been cancelled.
+pub(crate) fn is_cancelled(token: &Option<CancellationToken>) -> bool {
+    token
+        .as_ref()
+        .map_or(false, CancellationToken::is_cancelled)
+}
@@ -24,7 +24,7 @@
 #[clap(
     global_setting(AppSettings::NoAutoVersion),
     bin_name = "cargo fmt",
-    about = "This utility formats all bin and lib files of \
+    about = "This utility formats all lib, bin, test, bench and example files of \
              the current crate using rustfmt."
 )]
 pub struct Opts {
@@ -69,6 +69,31 @@ pub struct Opts {
     /// Run rustfmt in check mode
     #[clap(long = "check")]
     check: bool,
+
+    /// Format only the library target
+    #[clap(long = "lib")]
+    lib: bool,
+
+    /// Format only the specified binary target
+    #[clap(long = "bin", value_name = "name", multiple_occurrences = true)]
+    bins: Vec<String>,
+
+    /// Format only the specified test target
+    #[clap(long = "test", value_name = "name", multiple_occurrences = true)]
+    tests: Vec<String>,
+
+    /// Format only the specified bench target
+    #[clap(long = "bench", value_name = "name", multiple_occurrences = true)]
+    benches: Vec<String>,
+
+    /// Format only the specified example target
+    #[clap(long = "example", value_name = "name", multiple_occurrences = true)]
+    examples: Vec<String>,
+
+    /// Also format the test, bench and example entrypoints which cargo does not discover
+    /// because the manifest disables it, e.g. with `autotests = false`
+    #[clap(long = "undiscovered-targets")]
+    undiscovered_targets: bool,
 }
 
 fn main() {
@@ -78,7 +103,9 @@ fn main() {
 }
 
 const SUCCESS: i32 = 0;
-const FAILURE: i32 = 1;
+// Matches rustfmt's exit code for IO and configuration errors, while the exit codes of
+// the rustfmt invocations themselves are passed through unchanged.
+const FAILURE: i32 = 2;
 
 fn execute() -> i32 {
     // Drop extra `fmt` argument provided by `cargo`.
@@ -116,6 +143,8 @@ fn execute() -> i32 {
     }
 
     let strategy = CargoFmtStrategy::from_opts(&opts);
+    let selection = TargetSelection::from_opts(&opts);
+    let undiscovered_targets = opts.undiscovered_targets;
     let mut rustfmt_args = opts.rustfmt_options;
     if opts.check {
         let check_flag = "--check";
@@ -131,6 +160,8 @@ fn execute() -> i32 {
         }
     }
 
+    add_verbosity_to_rustfmt_args(verbosity, &mut rustfmt_args);
+
     if let Some(specified_manifest_path) = opts.manifest_path {
         if !specified_manifest_path.ends_with("Cargo.toml") {
             print_usage_to_stderr("the manifest-path must be a path to a Cargo.toml file");
@@ -140,11 +171,20 @@ fn execute() -> i32 {
         handle_command_status(format_crate(
             verbosity,
             &strategy,
+            &selection,
+            undiscovered_targets,
             rustfmt_args,
             Some(&manifest_path),
         ))
     } else {
-        handle_command_status(format_crate(verbosity, &strategy, rustfmt_args, None))
+        handle_command_status(format_crate(
+            verbosity,
+            &strategy,
+            &selection,
+            undiscovered_targets,
+            rustfmt_args,
+            None,
+        ))
     }
 }
 
@@ -207,6 +247,22 @@ fn convert_message_format_to_rustfmt_args(
     }
 }
 
+/// Forwards cargo-fmt's verbosity to rustfmt, unless the user already passed a
+/// verbosity flag to rustfmt explicitly.
+fn add_verbosity_to_rustfmt_args(verbosity: Verbosity, rustfmt_args: &mut Vec<String>) {
+    let has_verbosity_flag = rustfmt_args
+        .iter()
+        .any(|arg| ["-v", "--verbose", "-q", "--quiet"].contains(&arg.as_str()));
+    if has_verbosity_flag {
+        return;
+    }
+    match verbosity {
+        Verbosity::Verbose => rustfmt_args.push(String::from("--verbose")),
+        Verbosity::Quiet => rustfmt_args.push(String::from("--quiet")),
+        Verbosity::Normal => {}
+    }
+}
+
 fn print_usage_to_stderr(reason: &str) {
     eprintln!("{}", reason);
     let app = Opts::command();
@@ -255,12 +311,14 @@ fn get_rustfmt_info(args: &[String]) -> Result<i32, io::Error> {
 fn format_crate(
     verbosity: Verbosity,
     strategy: &CargoFmtStrategy,
+    selection: &TargetSelection,
+    undiscovered_targets: bool,
     rustfmt_args: Vec<String>,
     manifest_path: Option<&Path>,
 ) -> Result<i32, io::Error> {
-    let targets = get_targets(strategy, manifest_path)?;
+    let targets = get_targets(strategy, manifest_path, undiscovered_targets)?;
+    let targets = selection.select(targets)?;
 
-    // Currently only bin and lib files get formatted.
     run_rustfmt(&targets, &rustfmt_args, verbosity)
 }
 
@@ -269,6 +327,8 @@ fn format_crate(
 pub struct Target {
     /// A path to the main source file of the target.
     path: PathBuf,
+    /// The name of the target.
+    name: String,
     /// A kind of target (e.g., lib, bin, example, ...).
     kind: String,
     /// Rust edition for this target.
@@ -277,15 +337,28 @@ pub struct Target {
 
 impl Target {
     pub fn from_target(target: &cargo_metadata::Target) -> Self {
-        let path = PathBuf::from(&target.src_path);
+        Target::new(
+            PathBuf::from(&target.src_path),
+            &target.name,
+            &target.kind[0],
+            &target.edition,
+        )
+    }
+
+    fn new(path: PathBuf, name: &str, kind: &str, edition: &str) -> Self {
         let canonicalized = fs::canonicalize(&path).unwrap_or(path);
 
         Target {
             path: canonicalized,
-            kind: target.kind[0].clone(),
-            edition: target.edition.clone(),
+            name: name.to_owned(),
+            kind: kind.to_owned(),
+            edition: edition.to_owned(),
         }
     }
+
+    fn is_lib(&self) -> bool {
+        ["lib", "rlib", "dylib", "cdylib", "staticlib", "proc-macro"].contains(&self.kind.as_str())
+    }
 }
 
 impl PartialEq for Target {
@@ -334,20 +407,93 @@ pub fn from_opts(opts: &Opts) -> CargoFmtStrategy {
     }
 }
 
-/// Based on the specified `CargoFmtStrategy`, returns a set of main source files.
+/// The targets selected by `--lib`, `--bin`, `--test`, `--bench` and `--example`. All targets
+/// are formatted if none is selected.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TargetSelection {
+    lib: bool,
+    /// The names of the selected targets, by kind.
+    named: Vec<(&'static str, Vec<String>)>,
+}
+
+impl TargetSelection {
+    pub fn from_opts(opts: &Opts) -> TargetSelection {
+        let named = [
+            ("bin", &opts.bins),
+            ("test", &opts.tests),
+            ("bench", &opts.benches),
+            ("example", &opts.examples),
+        ]
+        .iter()
+        .filter(|(_, names)| !names.is_empty())
+        .map(|&(kind, names)| (kind, names.clone()))
+        .collect();
+        TargetSelection {
+            lib: opts.lib,
+            named,
+        }
+    }
+
+    /// Returns the selected targets among `targets`, or an error if a selected target does
+    /// not exist.
+    fn select(&self, targets: BTreeSet<Target>) -> Result<BTreeSet<Target>, io::Error> {
+        if !self.lib && self.named.is_empty() {
+            return Ok(targets);
+        }
+        if self.lib && !targets.iter().any(Target::is_lib) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no library targets found",
+            ));
+        }
+        for (kind, names) in &self.named {
+            if let Some(name) = names
+                .iter()
+                .find(|name| !targets.iter().any(|t| t.kind == *kind && t.name == **name))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("no {} target named `{}`", kind, name),
+                ));
+            }
+        }
+        Ok(targets
+            .into_iter()
+            .filter(|target| {
+                (self.lib && target.is_lib())
+                    || self
+                        .named
+                        .iter()
+                        .any(|(kind, names)| target.kind == *kind && names.contains(&target.name))
+            })
+            .collect())
+    }
+}
+
+/// Based on the specified `CargoFmtStrategy`, returns a set of main source files. With
+/// `undiscovered_targets`, the set includes the entrypoints which cargo would discover if
+/// the manifests did not disable it.
 fn get_targets(
     strategy: &CargoFmtStrategy,
     manifest_path: Option<&Path>,
+    undiscovered_targets: bool,
 ) -> Result<BTreeSet<Target>, io::Error> {
     let mut targets = BTreeSet::new();
+    let package_targets =
+        |package: &cargo_metadata::Package| package_targets(package, undiscovered_targets);
 
     match *strategy {
-        CargoFmtStrategy::Root => get_targets_root_only(manifest_path, &mut targets)?,
-        CargoFmtStrategy::All => {
-            get_targets_recursive(manifest_path, &mut targets, &mut BTreeSet::new())?
+        CargoFmtStrategy::Root => {
+            get_targets_root_only(manifest_path, &mut targets, &package_targets)?
         }
+        CargoFmtStrategy::All => get_targets_recursive(
+            manifest_path,
+            &mut targets,
+            &mut BTreeSet::new(),
+            &package_targets,
+        )?,
         CargoFmtStrategy::Some(ref hitlist) => {
-            get_targets_with_hitlist(manifest_path, hitlist, &mut targets)?
+            get_targets_with_hitlist(manifest_path, hitlist, &mut targets, &package_targets)?
         }
     }
 
@@ -364,6 +510,7 @@ fn get_targets(
 fn get_targets_root_only(
     manifest_path: Option<&Path>,
     targets: &mut BTreeSet<Target>,
+    package_targets: &dyn Fn(&cargo_metadata::Package) -> Vec<Target>,
 ) -> Result<(), io::Error> {
     let metadata = get_cargo_metadata(manifest_path)?;
     let workspace_root_path = PathBuf::from(&metadata.workspace_root).canonicalize()?;
@@ -380,11 +527,11 @@ fn get_targets_root_only(
         )
     };
 
-    let package_targets = match metadata.packages.len() {
-        1 => metadata.packages.into_iter().next().unwrap().targets,
+    let root_targets = match metadata.packages.len() {
+        1 => package_targets(&metadata.packages[0]),
         _ => metadata
             .packages
-            .into_iter()
+            .iter()
             .filter(|p| {
                 in_workspace_root
                     || PathBuf::from(&p.manifest_path)
@@ -392,13 +539,10 @@ fn get_targets_root_only(
                         .unwrap_or_default()
                         == current_dir_manifest
             })
-            .flat_map(|p| p.targets)
+            .flat_map(package_targets)
             .collect(),
     };
-
-    for target in package_targets {
-        targets.insert(Target::from_target(&target));
-    }
+    targets.extend(root_targets);
 
     Ok(())
 }
@@ -407,10 +551,11 @@ fn get_targets_recursive(
     manifest_path: Option<&Path>,
     targets: &mut BTreeSet<Target>,
     visited: &mut BTreeSet<String>,
+    package_targets: &dyn Fn(&cargo_metadata::Package) -> Vec<Target>,
 ) -> Result<(), io::Error> {
     let metadata = get_cargo_metadata(manifest_path)?;
     for package in &metadata.packages {
-        add_targets(&package.targets, targets);
+        targets.extend(package_targets(package));
 
         // Look for local dependencies using information available since cargo v1.51
         // It's theoretically possible someone could use a newer version of rustfmt with
@@ -431,7 +576,7 @@ fn get_targets_recursive(
                     .any(|p| p.manifest_path.eq(&manifest_path))
             {
                 visited.insert(dependency.name.to_owned());
-                get_targets_recursive(Some(&manifest_path), targets, visited)?;
+                get_targets_recursive(Some(&manifest_path), targets, visited, package_targets)?;
             }
         }
     }
@@ -443,15 +588,14 @@ fn get_targets_with_hitlist(
     manifest_path: Option<&Path>,
     hitlist: &[String],
     targets: &mut BTreeSet<Target>,
+    package_targets: &dyn Fn(&cargo_metadata::Package) -> Vec<Target>,
 ) -> Result<(), io::Error> {
     let metadata = get_cargo_metadata(manifest_path)?;
     let mut workspace_hitlist: BTreeSet<&String> = BTreeSet::from_iter(hitlist);
 
     for package in metadata.packages {
         if workspace_hitlist.remove(&package.name) {
-            for target in package.targets {
-                targets.insert(Target::from_target(&target));
-            }
+            targets.extend(package_targets(&package));
         }
     }
 
@@ -466,10 +610,51 @@ fn get_targets_with_hitlist(
     }
 }
 
-fn add_targets(target_paths: &[cargo_metadata::Target], targets: &mut BTreeSet<Target>) {
-    for target in target_paths {
-        targets.insert(Target::from_target(target));
+/// The directories in which cargo discovers targets that are not declared in the manifest,
+/// along with the kind of those targets.
+const AUTODISCOVERED_TARGET_DIRS: &[(&str, &str)] = &[
+    ("tests", "test"),
+    ("benches", "bench"),
+    ("examples", "example"),
+];
+
+/// Returns the targets of `package`. The targets include the test, bench and example
+/// entrypoints which cargo discovers by convention, e.g. `tests/*.rs` and `tests/*/main.rs`,
+/// unless the manifest disables their discovery (e.g. `autotests = false`). With
+/// `undiscovered_targets`, they are included in that case too.
+fn package_targets(package: &cargo_metadata::Package, undiscovered_targets: bool) -> Vec<Target> {
+    let mut targets: Vec<Target> = package.targets.iter().map(Target::from_target).collect();
+    if !undiscovered_targets {
+        return targets;
+    }
+    let manifest_path = PathBuf::from(&package.manifest_path);
+    let package_dir = match manifest_path.parent() {
+        Some(package_dir) => package_dir,
+        None => return targets,
+    };
+
+    for (dir, kind) in AUTODISCOVERED_TARGET_DIRS {
+        let mut entries: Vec<PathBuf> = match fs::read_dir(package_dir.join(dir)) {
+            Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).collect(),
+            Err(_) => continue,
+        };
+        entries.sort();
+        for path in entries {
+            let (name, src_path) = if path.is_file() && path.extension() == Some(OsStr::new("rs")) {
+                (path.file_stem(), path.clone())
+            } else if path.join("main.rs").is_file() {
+                (path.file_name(), path.join("main.rs"))
+            } else {
+                continue;
+            };
+            let name = name.map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            let target = Target::new(src_path, &name, kind, &package.edition);
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
     }
+    targets
 }
 
 fn run_rustfmt(
@@ -44,6 +44,28 @@ fn good_options() {
     assert_eq!(Some(String::from("short")), o.message_format);
 }
 
+#[test]
+fn verbosity_is_forwarded_to_rustfmt() {
+    let mut args = vec![];
+    add_verbosity_to_rustfmt_args(Verbosity::Verbose, &mut args);
+    assert_eq!(args, vec!["--verbose"]);
+
+    let mut args = vec![];
+    add_verbosity_to_rustfmt_args(Verbosity::Quiet, &mut args);
+    assert_eq!(args, vec!["--quiet"]);
+
+    let mut args = vec![];
+    add_verbosity_to_rustfmt_args(Verbosity::Normal, &mut args);
+    assert!(args.is_empty());
+}
+
+#[test]
+fn explicit_rustfmt_verbosity_is_kept() {
+    let mut args = vec![String::from("-q")];
+    add_verbosity_to_rustfmt_args(Verbosity::Verbose, &mut args);
+    assert_eq!(args, vec!["-q"]);
+}
+
 #[test]
 fn unexpected_option() {
     assert!(
@@ -18,7 +18,7 @@ fn assert_correct_targets_loaded(
         let root_path = Path::new("tests/cargo-fmt/source").join(source_root);
         let get_path = |exp: &str| PathBuf::from(&root_path).join(exp).canonicalize().unwrap();
         let manifest_path = Path::new(&root_path).join(manifest_suffix);
-        let targets = get_targets(&CargoFmtStrategy::All, Some(manifest_path.as_path()))
+        let targets = get_targets(&CargoFmtStrategy::All, Some(manifest_path.as_path()), true)
             .expect("Targets should have been loaded");
 
         assert_eq!(targets.len(), exp_num_targets);
@@ -26,6 +26,7 @@ fn assert_correct_targets_loaded(
         for target in exp_targets {
             assert!(targets.contains(&Target {
                 path: get_path(target.path),
+                name: String::new(),
                 edition: target.edition.to_owned(),
                 kind: target.kind.to_owned(),
             }));
@@ -131,4 +132,99 @@ fn includes_all_packages_from_workspace_subdir() {
             assert_correct_targets_loaded("ws/a/d/f/Cargo.toml");
         }
     }
+
+    mod autodiscovery {
+        use super::*;
+
+        #[test]
+        fn includes_undeclared_tests_benches_and_examples() {
+            let exp_targets = vec![
+                ExpTarget {
+                    path: "src/lib.rs",
+                    edition: "2018",
+                    kind: "lib",
+                },
+                ExpTarget {
+                    path: "src/main.rs",
+                    edition: "2018",
+                    kind: "bin",
+                },
+                ExpTarget {
+                    path: "tests/it.rs",
+                    edition: "2018",
+                    kind: "test",
+                },
+                ExpTarget {
+                    path: "tests/multi/main.rs",
+                    edition: "2018",
+                    kind: "test",
+                },
+                ExpTarget {
+                    path: "benches/b.rs",
+                    edition: "2018",
+                    kind: "bench",
+                },
+                ExpTarget {
+                    path: "examples/ex.rs",
+                    edition: "2018",
+                    kind: "example",
+                },
+            ];
+            super::assert_correct_targets_loaded("Cargo.toml", "autodiscovery", &exp_targets, 6);
+        }
+
+        #[test]
+        fn respects_disabled_discovery() {
+            let manifest_path = Path::new("tests/cargo-fmt/source/autodiscovery/Cargo.toml");
+            let targets = get_targets(&CargoFmtStrategy::Root, Some(manifest_path), false)
+                .expect("Targets should have been loaded");
+            let mut kinds: Vec<_> = targets.iter().map(|t| t.kind.as_str()).collect();
+            kinds.sort_unstable();
+            assert_eq!(kinds, vec!["bin", "lib"]);
+        }
+    }
+}
+
+mod target_selection {
+    use super::*;
+
+    fn selected(args: &[&str]) -> Result<Vec<(String, String)>, io::Error> {
+        let opts = Opts::parse_from(std::iter::once(&"test").chain(args));
+        let manifest_path = Path::new("tests/cargo-fmt/source/autodiscovery/Cargo.toml");
+        let targets = get_targets(&CargoFmtStrategy::Root, Some(manifest_path), true)?;
+        let targets = TargetSelection::from_opts(&opts).select(targets)?;
+        let mut selected: Vec<_> = targets.into_iter().map(|t| (t.kind, t.name)).collect();
+        selected.sort();
+        Ok(selected)
+    }
+
+    fn names(targets: &[(&str, &str)]) -> Vec<(String, String)> {
+        targets
+            .iter()
+            .map(|&(kind, name)| (kind.to_owned(), name.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn no_selection_keeps_all_targets() {
+        assert_eq!(selected(&[]).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn selects_lib_and_named_targets() {
+        assert_eq!(
+            selected(&["--lib"]).unwrap(),
+            names(&[("lib", "autodiscovery")])
+        );
+        assert_eq!(
+            selected(&["--bin", "autodiscovery", "--test", "multi", "--test", "it"]).unwrap(),
+            names(&[("bin", "autodiscovery"), ("test", "it"), ("test", "multi")])
+        );
+    }
+
+    #[test]
+    fn unknown_target_is_an_error() {
+        let err = selected(&["--example", "missing"]).unwrap_err();
+        assert_eq!(err.to_string(), "no example target named `missing`");
+    }
 }
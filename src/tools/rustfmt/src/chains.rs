@@ -69,6 +69,7 @@
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
+use crate::types::closing_angle_bracket;
 use crate::utils::{
     self, first_line_width, last_line_extendable, last_line_width, mk_sp, rewrite_ident,
     trimmed_last_line_width, wrap_str,
@@ -184,7 +185,10 @@ fn from_ast(context: &RewriteContext<'_>, expr: &ast::Expr) -> (ChainItemKind, S
         };
 
         // Remove comments from the span.
-        let lo = context.snippet_provider.span_before(span, ".");
+        let lo = context
+            .snippet_provider
+            .span_before(span, ".")
+            .unwrap_or(span.lo());
         (kind, mk_sp(lo, span.hi()))
     }
 }
@@ -250,10 +254,15 @@ fn rewrite_method_call(
                 .map(|ty| ty.rewrite(context, shape))
                 .collect::<Option<Vec<_>>>()?;
 
-            format!("::<{}>", type_list.join(", "))
+            let mut type_str = format!("::<{}", type_list.join(", "));
+            type_str.push_str(closing_angle_bracket(&type_str, context.config));
+            type_str
         };
         let callee_str = format!(".{}{}", rewrite_ident(context, method_name), type_str);
-        rewrite_call(context, &callee_str, &args, span, shape)
+        let is_chain_call_arg = context.is_chain_call_arg.replace(true);
+        let result = rewrite_call(context, &callee_str, &args, span, shape);
+        context.is_chain_call_arg.replace(is_chain_call_arg);
+        result
     }
 }
 
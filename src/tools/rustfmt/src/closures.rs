@@ -1,7 +1,10 @@
+use std::borrow::Cow;
+
 use rustc_ast::{ast, ptr};
 use rustc_span::Span;
 
 use crate::attr::get_attrs_from_stmt;
+use crate::comment::{contains_comment, rewrite_missing_comment};
 use crate::config::lists::*;
 use crate::config::Version;
 use crate::expr::{block_contains_comment, is_simple_block, is_unsafe_block, rewrite_cond};
@@ -12,7 +15,7 @@
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
 use crate::types::rewrite_lifetime_param;
-use crate::utils::{last_line_width, left_most_sub_expr, stmt_expr, NodeIdExt};
+use crate::utils::{last_line_width, left_most_sub_expr, mk_sp, stmt_expr, NodeIdExt};
 
 // This module is pretty messy because of the rules around closures and blocks:
 // FIXME - the below is probably no longer true in full.
@@ -37,33 +40,81 @@ pub(crate) fn rewrite_closure(
 ) -> Option<String> {
     debug!("rewrite_closure {:?}", body);
 
-    let (prefix, extra_offset) = rewrite_closure_fn_decl(
+    // Closures within the body are not arguments of the chain, even if this one is.
+    let is_chain_call_arg = context.is_chain_call_arg.replace(false);
+    let force_block = is_chain_call_arg && context.config.force_brace_closure_bodies_in_chains();
+    let result = rewrite_closure_inner(
+        binder,
+        capture,
+        is_async,
+        movability,
+        fn_decl,
+        body,
+        span,
+        context,
+        shape,
+        force_block,
+    );
+    context.is_chain_call_arg.replace(is_chain_call_arg);
+    result
+}
+
+fn rewrite_closure_inner(
+    binder: &ast::ClosureBinder,
+    capture: ast::CaptureBy,
+    is_async: &ast::Async,
+    movability: ast::Movability,
+    fn_decl: &ast::FnDecl,
+    body: &ast::Expr,
+    span: Span,
+    context: &RewriteContext<'_>,
+    shape: Shape,
+    force_block: bool,
+) -> Option<String> {
+    let (mut prefix, mut extra_offset, is_ret_broken) = rewrite_closure_fn_decl(
         binder, capture, is_async, movability, fn_decl, body, span, context, shape,
     )?;
+    if let Some(comment_span) = comment_span_before_body(fn_decl, body, span, context) {
+        let comment = rewrite_missing_comment(comment_span, shape, context)?;
+        if comment.contains('\n') || comment.starts_with("//") {
+            return rewrite_closure_with_comment_above(
+                body,
+                &prefix,
+                is_ret_broken,
+                comment_span,
+                context,
+                shape,
+            );
+        }
+        prefix = format!("{} {}", prefix, comment);
+        // 1 = space between the comment and body.
+        extra_offset = last_line_width(&prefix) + 1;
+    }
     // 1 = space between `|...|` and body.
     let body_shape = shape.offset_left(extra_offset)?;
 
     if let ast::ExprKind::Block(ref block, _) = body.kind {
         // The body of the closure is an empty block.
         if block.stmts.is_empty() && !block_contains_comment(context, block) {
-            return body
-                .rewrite(context, shape)
-                .map(|s| format!("{} {}", prefix, s));
+            return body.rewrite(context, shape).map(|s| {
+                let separator = body_separator(is_ret_broken, shape, context);
+                format!("{}{}{}", prefix, separator, s)
+            });
         }
 
         let result = match fn_decl.output {
             ast::FnRetTy::Default(_) if !context.inside_macro() => {
-                try_rewrite_without_block(body, &prefix, context, shape, body_shape)
+                try_rewrite_without_block(body, &prefix, context, shape, body_shape, force_block)
             }
             _ => None,
         };
 
         result.or_else(|| {
             // Either we require a block, or tried without and failed.
-            rewrite_closure_block(block, &prefix, context, body_shape)
+            rewrite_closure_block(block, &prefix, is_ret_broken, context, body_shape)
         })
     } else {
-        rewrite_closure_expr(body, &prefix, context, body_shape).or_else(|| {
+        rewrite_closure_expr(body, &prefix, context, body_shape, force_block).or_else(|| {
             // The closure originally had a non-block expression, but we can't fit on
             // one line, so we'll insert a block.
             rewrite_closure_with_block(body, &prefix, context, body_shape)
@@ -71,19 +122,92 @@ pub(crate) fn rewrite_closure(
     }
 }
 
+/// Returns the span between the parameters (or the return type) of a closure and its body,
+/// e.g. ` /* note */ ` in `|x| /* note */ x + 1`, if it contains a comment.
+fn comment_span_before_body(
+    fn_decl: &ast::FnDecl,
+    body: &ast::Expr,
+    span: Span,
+    context: &RewriteContext<'_>,
+) -> Option<Span> {
+    let lo = match fn_decl.output {
+        ast::FnRetTy::Ty(ref ty) => ty.span.hi(),
+        ast::FnRetTy::Default(_) => context
+            .snippet_provider
+            .span_after_last(mk_sp(span.lo(), body.span.lo()), "|"),
+    };
+    let comment_span = mk_sp(lo, body.span.lo());
+    if contains_comment(context.snippet(comment_span)) {
+        Some(comment_span)
+    } else {
+        None
+    }
+}
+
+// Rewrite closure with a comment before its body which does not fit on the line of the
+// parameters, putting the comment above the body in a block.
+// |x| // note
+//     x + 1
+// ->
+// |x| {
+//     // note
+//     x + 1
+// }
+fn rewrite_closure_with_comment_above(
+    body: &ast::Expr,
+    prefix: &str,
+    is_ret_broken: bool,
+    comment_span: Span,
+    context: &RewriteContext<'_>,
+    shape: Shape,
+) -> Option<String> {
+    let inner_shape = shape
+        .block_indent(context.config.tab_spaces())
+        .with_max_width(context.config);
+    let comment = rewrite_missing_comment(comment_span, inner_shape, context)?;
+    let inner_indent = inner_shape.indent.to_string_with_newline(context.config);
+    let outer_indent = shape.indent.to_string_with_newline(context.config);
+    let separator = body_separator(is_ret_broken, shape, context);
+
+    if let ast::ExprKind::Block(ref block, None) = body.kind {
+        if matches!(block.rules, ast::BlockCheckMode::Default) && body.attrs.is_empty() {
+            let block_str = block.rewrite(context, shape)?;
+            let rest = block_str.strip_prefix('{')?;
+            if rest.starts_with('\n') {
+                return Some(format!(
+                    "{}{}{{{}{}{}",
+                    prefix, separator, inner_indent, comment, rest
+                ));
+            } else if rest.trim() == "}" {
+                return Some(format!(
+                    "{}{}{{{}{}{}}}",
+                    prefix, separator, inner_indent, comment, outer_indent
+                ));
+            }
+        }
+    }
+
+    let body_str = body.rewrite(context, inner_shape)?;
+    Some(format!(
+        "{}{}{{{}{}{}{}{}}}",
+        prefix, separator, inner_indent, comment, inner_indent, body_str, outer_indent
+    ))
+}
+
 fn try_rewrite_without_block(
     expr: &ast::Expr,
     prefix: &str,
     context: &RewriteContext<'_>,
     shape: Shape,
     body_shape: Shape,
+    force_block: bool,
 ) -> Option<String> {
     let expr = get_inner_expr(expr, prefix, context);
 
     if is_block_closure_forced(context, expr) {
         rewrite_closure_with_block(expr, prefix, context, shape)
     } else {
-        rewrite_closure_expr(expr, prefix, context, body_shape)
+        rewrite_closure_expr(expr, prefix, context, body_shape, force_block)
     }
 }
 
@@ -177,11 +301,13 @@ fn rewrite_closure_with_block(
 }
 
 // Rewrite closure with a single expression without wrapping its body with block.
+// If `force_block` is true, the body must fit in a single line.
 fn rewrite_closure_expr(
     expr: &ast::Expr,
     prefix: &str,
     context: &RewriteContext<'_>,
     shape: Shape,
+    force_block: bool,
 ) -> Option<String> {
     fn allow_multi_line(expr: &ast::Expr) -> bool {
         match expr.kind {
@@ -205,7 +331,8 @@ fn allow_multi_line(expr: &ast::Expr) -> bool {
     // When rewriting closure's body without block, we require it to fit in a single line
     // unless it is a block-like expression or we are inside macro call.
     let veto_multiline = (!allow_multi_line(expr) && !context.inside_macro())
-        || context.config.force_multiline_blocks();
+        || context.config.force_multiline_blocks()
+        || force_block;
     expr.rewrite(context, shape)
         .and_then(|rw| {
             if veto_multiline && rw.contains('\n') {
@@ -221,13 +348,36 @@ fn allow_multi_line(expr: &ast::Expr) -> bool {
 fn rewrite_closure_block(
     block: &ast::Block,
     prefix: &str,
+    is_ret_broken: bool,
     context: &RewriteContext<'_>,
     shape: Shape,
 ) -> Option<String> {
-    Some(format!("{} {}", prefix, block.rewrite(context, shape)?))
+    let separator = body_separator(is_ret_broken, shape, context);
+    Some(format!(
+        "{}{}{}",
+        prefix,
+        separator,
+        block.rewrite(context, shape)?
+    ))
+}
+
+/// Returns what goes between the prefix of a closure and its block body: a space, or a line
+/// break if `rewrite_closure_fn_decl` put the return type on a line of its own, so that the
+/// brace does not trail a long return type.
+fn body_separator(
+    is_ret_broken: bool,
+    shape: Shape,
+    context: &RewriteContext<'_>,
+) -> Cow<'static, str> {
+    if is_ret_broken {
+        shape.indent.to_string_with_newline(context.config)
+    } else {
+        Cow::from(" ")
+    }
 }
 
-// Return type is (prefix, extra_offset)
+// Return type is (prefix, extra_offset, is_ret_broken), where `is_ret_broken` tells whether
+// the return type is on a line of its own (only with `version = Two`).
 fn rewrite_closure_fn_decl(
     binder: &ast::ClosureBinder,
     capture: ast::CaptureBy,
@@ -238,7 +388,7 @@ fn rewrite_closure_fn_decl(
     span: Span,
     context: &RewriteContext<'_>,
     shape: Shape,
-) -> Option<(String, usize)> {
+) -> Option<(String, usize, bool)> {
     let binder = match binder {
         ast::ClosureBinder::For { generic_params, .. } if generic_params.is_empty() => {
             "for<> ".to_owned()
@@ -270,7 +420,17 @@ fn rewrite_closure_fn_decl(
     // 1 = |
     let param_offset = nested_shape.indent + 1;
     let param_shape = nested_shape.offset_left(1)?.visual_indent(0);
-    let ret_str = fn_decl.output.rewrite(context, param_shape)?;
+    // The shape of a return type on a line of its own, which is block indented. The body
+    // then starts a line of its own as well.
+    let ret_indent = shape.block_indent(context.config.tab_spaces()).indent;
+    let ret_shape = Shape::indented(ret_indent, context.config);
+    let version_two = context.config.version() == Version::Two;
+    let ret_str = match fn_decl.output.rewrite(context, param_shape) {
+        Some(ret_str) => ret_str,
+        // The return type goes on a line of its own below.
+        None if version_two => fn_decl.output.rewrite(context, ret_shape)?,
+        None => return None,
+    };
 
     let param_items = itemize_list(
         context.snippet_provider,
@@ -280,7 +440,7 @@ fn rewrite_closure_fn_decl(
         |param| span_lo_for_param(param),
         |param| span_hi_for_param(context, param),
         |param| param.rewrite(context, param_shape),
-        context.snippet_provider.span_after(span, "|"),
+        context.snippet_provider.span_after(span, "|")?,
         body.span.lo(),
         false,
     );
@@ -292,6 +452,7 @@ fn rewrite_closure_fn_decl(
         ListTactic::HorizontalVertical,
         Separator::Comma,
         horizontal_budget,
+        context.config,
     );
     let param_shape = match tactic {
         DefinitiveListTactic::Horizontal => param_shape.sub_width(ret_str.len() + 1)?,
@@ -304,19 +465,37 @@ fn rewrite_closure_fn_decl(
     let list_str = write_list(&item_vec, &fmt)?;
     let mut prefix = format!("{}{}{}{}|{}|", binder, immovable, is_async, mover, list_str);
 
+    let mut is_ret_broken = false;
     if !ret_str.is_empty() {
-        if prefix.contains('\n') {
+        // Break before the return type if `|...| -> Type {` does not fit on one line.
+        // 3 = spaces around the return type and `{`.
+        let break_ret = prefix.contains('\n')
+            || (version_two
+                && (ret_str.contains('\n') || prefix.len() + ret_str.len() + 3 > shape.width));
+        if break_ret {
             prefix.push('\n');
-            prefix.push_str(&param_offset.to_string(context.config));
+            if version_two {
+                prefix.push_str(&ret_indent.to_string(context.config));
+                prefix.push_str(&fn_decl.output.rewrite(context, ret_shape)?);
+                is_ret_broken = true;
+            } else {
+                prefix.push_str(&param_offset.to_string(context.config));
+                prefix.push_str(&ret_str);
+            }
         } else {
             prefix.push(' ');
+            prefix.push_str(&ret_str);
         }
-        prefix.push_str(&ret_str);
     }
-    // 1 = space between `|...|` and body.
-    let extra_offset = last_line_width(&prefix) + 1;
+    let extra_offset = if is_ret_broken {
+        // The body starts a line of its own, see `body_separator`.
+        0
+    } else {
+        // 1 = space between `|...|` and body.
+        last_line_width(&prefix) + 1
+    };
 
-    Some((prefix, extra_offset))
+    Some((prefix, extra_offset, is_ret_broken))
 }
 
 // Rewriting closure which is placed at the end of the function call's arg.
@@ -346,7 +525,7 @@ pub(crate) fn rewrite_last_closure(
             }
             _ => body,
         };
-        let (prefix, extra_offset) = rewrite_closure_fn_decl(
+        let (prefix, extra_offset, _) = rewrite_closure_fn_decl(
             binder,
             capture_clause,
             asyncness,
@@ -361,6 +540,10 @@ pub(crate) fn rewrite_last_closure(
         if prefix.contains('\n') {
             return None;
         }
+        // The comment is placed along with the body in the usual manner.
+        if comment_span_before_body(fn_decl, &closure.body, expr.span, context).is_some() {
+            return expr.rewrite(context, shape);
+        }
 
         let body_shape = shape.offset_left(extra_offset)?;
 
@@ -373,7 +556,7 @@ pub(crate) fn rewrite_last_closure(
                             // If the expression can fit in a single line, we need not force block
                             // closure.  However, if the closure has a return type, then we must
                             // keep the blocks.
-                            match rewrite_closure_expr(body, &prefix, context, shape) {
+                            match rewrite_closure_expr(body, &prefix, context, shape, false) {
                                 Some(single_line_body_str)
                                     if !single_line_body_str.contains('\n') =>
                                 {
@@ -1,5 +1,6 @@
 // Formatting and tools for comments.
 
+use std::collections::HashMap;
 use std::{self, borrow::Cow, iter};
 
 use itertools::{multipeek, MultiPeek};
@@ -7,13 +8,13 @@
 use regex::Regex;
 use rustc_span::Span;
 
-use crate::config::Config;
+use crate::config::{Config, Version};
 use crate::rewrite::RewriteContext;
 use crate::shape::{Indent, Shape};
 use crate::string::{rewrite_string, StringFormat};
 use crate::utils::{
-    count_newlines, first_line_width, last_line_width, trim_left_preserve_layout,
-    trimmed_last_line_width, unicode_str_width,
+    count_newlines, first_line_width, last_line_width, shift_lines_preserve_layout,
+    trim_left_preserve_layout, trimmed_last_line_width, unicode_str_width,
 };
 use crate::{ErrorKind, FormattingError};
 
@@ -254,7 +255,7 @@ pub(crate) fn combine_strs_with_missing_comments(
 }
 
 pub(crate) fn rewrite_doc_comment(orig: &str, shape: Shape, config: &Config) -> Option<String> {
-    identify_comment(orig, false, shape, config, true)
+    identify_comment(orig, false, shape, config, true, None)
 }
 
 pub(crate) fn rewrite_comment(
@@ -263,7 +264,20 @@ pub(crate) fn rewrite_comment(
     shape: Shape,
     config: &Config,
 ) -> Option<String> {
-    identify_comment(orig, block_style, shape, config, false)
+    identify_comment(orig, block_style, shape, config, false, None)
+}
+
+/// Rewrites a comment, the first line of which was indented by `orig_indent` in the original
+/// source. With `Version::Two`, the lines of a multi-line block comment are shifted as a whole
+/// to the indent of `shape`, so that they keep their position relative to the first line.
+pub(crate) fn rewrite_comment_from_indent(
+    orig: &str,
+    block_style: bool,
+    shape: Shape,
+    config: &Config,
+    orig_indent: &str,
+) -> Option<String> {
+    identify_comment(orig, block_style, shape, config, false, Some(orig_indent))
 }
 
 fn identify_comment(
@@ -272,6 +286,7 @@ fn identify_comment(
     shape: Shape,
     config: &Config,
     is_doc_comment: bool,
+    orig_indent: Option<&str>,
 ) -> Option<String> {
     let style = comment_style(orig, false);
 
@@ -364,7 +379,16 @@ fn consume_same_line_comments(
     let (first_group, rest) = orig.split_at(first_group_ending);
     let rewritten_first_group =
         if !config.normalize_comments() && has_bare_lines && style.is_block_comment() {
-            trim_left_preserve_layout(first_group, shape.indent, config)?
+            match orig_indent {
+                Some(orig_indent) if config.version() == Version::Two => {
+                    shift_lines_preserve_layout(first_group, orig_indent, shape.indent, config)
+                        .lines()
+                        .map(str::trim_end)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }
+                _ => trim_left_preserve_layout(first_group, shape.indent, config)?,
+            }
         } else if !config.normalize_comments()
             && !config.wrap_comments()
             && !config.format_code_in_doc_comments()
@@ -383,12 +407,18 @@ fn consume_same_line_comments(
     if rest.is_empty() {
         Some(rewritten_first_group)
     } else {
+        // The original indent of the rest, which starts a line of its own if any.
+        let rest_indent = orig_indent.and_then(|_| {
+            let whitespace = &rest[..rest.len() - rest.trim_start().len()];
+            whitespace.rfind('\n').map(|i| &whitespace[i + 1..])
+        });
         identify_comment(
             rest.trim_start(),
             block_style,
             shape,
             config,
             is_doc_comment,
+            rest_indent,
         )
         .map(|rest_str| {
             format!(
@@ -684,6 +714,7 @@ fn handle_line(
         line: &'a str,
         has_leading_whitespace: bool,
         is_doc_comment: bool,
+        keeps_layout: bool,
     ) -> bool {
         let num_newlines = count_newlines(orig);
         let is_last = i == num_newlines;
@@ -765,7 +796,10 @@ fn handle_line(
         self.item_block = None;
         if let Some(stripped) = line.strip_prefix("```") {
             self.code_block_attr = Some(CodeBlockAttribute::new(stripped))
-        } else if self.fmt.config.wrap_comments() && ItemizedBlock::is_itemized_line(line) {
+        } else if self.fmt.config.wrap_comments()
+            && !keeps_layout
+            && ItemizedBlock::is_itemized_line(line)
+        {
             let ib = ItemizedBlock::new(line);
             self.item_block = Some(ib);
             return false;
@@ -779,7 +813,7 @@ fn handle_line(
             if line.is_empty() {
                 return false;
             }
-        } else if self.is_prev_line_multi_line && !line.is_empty() {
+        } else if self.is_prev_line_multi_line && !line.is_empty() && !keeps_layout {
             self.result.push(' ')
         } else if is_last && line.is_empty() {
             // trailing blank lines are unwanted
@@ -801,8 +835,10 @@ fn handle_line(
         // 2) The comment is not the start of a markdown header doc comment
         // 3) The comment width exceeds the shape's width
         // 4) No URLS were found in the comment
+        // 5) The line is not part of a table or an ASCII diagram
         let should_wrap_comment = self.fmt.config.wrap_comments()
             && !is_markdown_header_doc_comment
+            && !keeps_layout
             && unicode_str_width(line) > self.fmt.shape.width
             && !has_url(line);
 
@@ -893,10 +929,23 @@ fn rewrite_comment_inner(
             } else {
                 (line, has_leading_whitespace || config.normalize_comments())
             }
-        });
+        })
+        .collect::<Vec<_>>();
+    let keeps_layout = if config.wrap_comments() {
+        lines_keeping_layout(lines.iter().map(|&(line, _)| line))
+    } else {
+        vec![false; lines.len()]
+    };
 
-    for (i, (line, has_leading_whitespace)) in lines.enumerate() {
-        if rewriter.handle_line(orig, i, line, has_leading_whitespace, is_doc_comment) {
+    for (i, (line, has_leading_whitespace)) in lines.into_iter().enumerate() {
+        if rewriter.handle_line(
+            orig,
+            i,
+            line,
+            has_leading_whitespace,
+            is_doc_comment,
+            keeps_layout[i],
+        ) {
             break;
         }
     }
@@ -904,6 +953,54 @@ fn rewrite_comment_inner(
     Some(rewriter.finish())
 }
 
+/// A line of a comment which stops `wrap_comments` from reflowing any line of the comment.
+const NO_WRAP_MARKER: &str = "rustfmt: nowrap";
+
+/// Returns, for each of the `lines` of a comment, whether its layout must be kept when
+/// wrapping comments, because it is part of a table or an ASCII diagram.
+///
+/// A paragraph, i.e. a run of non-empty lines, is a table or diagram if one of its lines
+/// has 3 or more consecutive spaces after its indentation, or if two consecutive lines
+/// have a `|` or `+` in the same column. All lines keep their layout if one of them is
+/// `rustfmt: nowrap`.
+fn lines_keeping_layout<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<bool> {
+    let lines: Vec<&str> = lines.collect();
+    if lines.iter().any(|line| line.trim() == NO_WRAP_MARKER) {
+        return vec![true; lines.len()];
+    }
+
+    let mut keeps_layout = vec![false; lines.len()];
+    let mut start = 0;
+    while start < lines.len() {
+        let end = lines[start..]
+            .iter()
+            .position(|line| line.trim().is_empty())
+            .map_or(lines.len(), |len| start + len);
+        let paragraph = &lines[start..end];
+        let has_aligned_spaces = paragraph.iter().any(|line| line.trim().contains("   "));
+        let has_aligned_borders = paragraph.windows(2).any(|pair| {
+            let borders = |line: &str| -> Vec<usize> {
+                line.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c == '|' || c == '+')
+                    .map(|(column, _)| column)
+                    .collect()
+            };
+            let next_borders = borders(pair[1]);
+            borders(pair[0])
+                .iter()
+                .any(|column| next_borders.contains(column))
+        });
+        if has_aligned_spaces || has_aligned_borders {
+            keeps_layout[start..end]
+                .iter_mut()
+                .for_each(|keep| *keep = true);
+        }
+        start = end + 1;
+    }
+    keeps_layout
+}
+
 const RUSTFMT_CUSTOM_COMMENT_PREFIX: &str = "//#### ";
 
 fn hide_sharp_behind_comment(s: &str) -> Cow<'_, str> {
@@ -930,7 +1027,7 @@ fn trim_custom_comment_prefix(s: &str) -> String {
 }
 
 /// Returns `true` if the given string MAY include URLs or alike.
-fn has_url(s: &str) -> bool {
+pub(crate) fn has_url(s: &str) -> bool {
     // This function may return false positive, but should get its job done in most cases.
     s.contains("https://")
         || s.contains("http://")
@@ -1065,30 +1162,53 @@ fn left_trim_comment_line<'a>(line: &'a str, style: &CommentStyle<'_>) -> (&'a s
 pub(crate) trait FindUncommented {
     fn find_uncommented(&self, pat: &str) -> Option<usize>;
     fn find_last_uncommented(&self, pat: &str) -> Option<usize>;
+    /// Like `find_uncommented`, but also skips over string and character literals.
+    fn find_in_code(&self, pat: &str) -> Option<usize>;
+}
+
+// Returns the position of the first occurrence of `pat` in `haystack` outside of comments,
+// and outside of string and character literals if `skip_literals` is set.
+fn find_outside_comments(haystack: &str, pat: &str, skip_literals: bool) -> Option<usize> {
+    if pat.is_empty() {
+        return Some(0);
+    }
+    let mut iter = CharClasses::new(haystack.char_indices());
+    // The start of the current run of characters in which `pat` is searched.
+    let mut run_start = None;
+    loop {
+        let status_before = iter.status;
+        let (kind, (i, c)) = iter.next()?;
+        // A raw string prefix which isn't followed by a string is a raw identifier,
+        // e.g. `r#type`.
+        let in_literal = iter.status.is_literal()
+            || status_before.is_literal()
+                && !matches!(status_before, CharClassesStatus::RawStringPrefix(_));
+        let searched = match kind {
+            FullCodeCharKind::Normal | FullCodeCharKind::InString => !skip_literals || !in_literal,
+            _ => false,
+        };
+        if !searched {
+            run_start = None;
+            continue;
+        }
+        let start = *run_start.get_or_insert(i);
+        let end = i + c.len_utf8();
+        if end - start >= pat.len()
+            && haystack.is_char_boundary(end - pat.len())
+            && haystack[end - pat.len()..end] == *pat
+        {
+            return Some(end - pat.len());
+        }
+    }
 }
 
 impl FindUncommented for str {
     fn find_uncommented(&self, pat: &str) -> Option<usize> {
-        let mut needle_iter = pat.chars();
-        for (kind, (i, b)) in CharClasses::new(self.char_indices()) {
-            match needle_iter.next() {
-                None => {
-                    return Some(i - pat.len());
-                }
-                Some(c) => match kind {
-                    FullCodeCharKind::Normal | FullCodeCharKind::InString if b == c => {}
-                    _ => {
-                        needle_iter = pat.chars();
-                    }
-                },
-            }
-        }
+        find_outside_comments(self, pat, false)
+    }
 
-        // Handle case where the pattern is a suffix of the search string
-        match needle_iter.next() {
-            Some(_) => None,
-            None => Some(self.len() - pat.len()),
-        }
+    fn find_in_code(&self, pat: &str) -> Option<usize> {
+        find_outside_comments(self, pat, true)
     }
 
     fn find_last_uncommented(&self, pat: &str) -> Option<usize> {
@@ -1125,6 +1245,66 @@ pub(crate) fn find_comment_end(s: &str) -> Option<usize> {
     }
 }
 
+/// Returns the byte positions at which the comments of `text` start. Comment markers
+/// within string literals or other comments (e.g. nested block comments) are skipped.
+pub(crate) fn comment_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    CharClasses::new(text.char_indices())
+        .filter(|(kind, _)| *kind == FullCodeCharKind::StartComment)
+        .map(|(_, (i, _))| i)
+}
+
+/// Returns `off` or `on` if `text` starts with a `// rustfmt: off` or `// rustfmt: on` line
+/// comment.
+pub(crate) fn rustfmt_toggle(text: &str) -> Option<&str> {
+    let toggle = text
+        .strip_prefix("//")?
+        .lines()
+        .next()?
+        .trim()
+        .strip_prefix("rustfmt:")?
+        .trim();
+    matches!(toggle, "off" | "on").then(|| toggle)
+}
+
+/// Returns the byte position of the first `// rustfmt: <toggle>` line comment of `text`,
+/// where `toggle` is `off` or `on`.
+pub(crate) fn find_rustfmt_toggle(text: &str, toggle: &str) -> Option<usize> {
+    comment_starts(text).find(|&i| rustfmt_toggle(&text[i..]) == Some(toggle))
+}
+
+/// Returns `true` if the parentheses, brackets and braces of `text` are balanced, ignoring
+/// those in comments and literals.
+pub(crate) fn has_balanced_delimiters(text: &str) -> bool {
+    let mut iter = CharClasses::new(text.chars());
+    let mut open = vec![];
+    loop {
+        let status_before = iter.status;
+        let (kind, c) = match iter.next() {
+            Some(next) => next,
+            None => return open.is_empty(),
+        };
+        if kind != FullCodeCharKind::Normal
+            || iter.status.is_literal()
+            || status_before.is_literal()
+        {
+            continue;
+        }
+        let expected = match c {
+            '(' | '[' | '{' => {
+                open.push(c);
+                continue;
+            }
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            _ => continue,
+        };
+        if open.pop() != Some(expected) {
+            return false;
+        }
+    }
+}
+
 /// Returns `true` if text contains any comment.
 pub(crate) fn contains_comment(text: &str) -> bool {
     CharClasses::new(text.chars()).any(|(kind, _)| kind.is_comment())
@@ -1183,6 +1363,22 @@ enum CharClassesStatus {
     LineComment,
 }
 
+impl CharClassesStatus {
+    /// Returns `true` if the status is within a string or character literal.
+    fn is_literal(self) -> bool {
+        matches!(
+            self,
+            CharClassesStatus::LitString
+                | CharClassesStatus::LitStringEscape
+                | CharClassesStatus::LitRawString(_)
+                | CharClassesStatus::RawStringPrefix(_)
+                | CharClassesStatus::RawStringSuffix(_)
+                | CharClassesStatus::LitChar
+                | CharClassesStatus::LitCharEscape
+        )
+    }
+}
+
 /// Distinguish between functional part of code and comments
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub(crate) enum CodeCharKind {
@@ -1367,17 +1563,13 @@ fn next(&mut self) -> Option<(FullCodeCharKind, T::Item)> {
                     CharClassesStatus::LitString
                 }
                 '\'' => {
-                    // HACK: Work around mut borrow.
-                    match self.base.peek() {
-                        Some(next) if next.get_char() == '\\' => {
-                            self.status = CharClassesStatus::LitChar;
-                            return Some((char_kind, item));
-                        }
-                        _ => (),
-                    }
-
-                    match self.base.peek() {
-                        Some(next) if next.get_char() == '\'' => CharClassesStatus::LitChar,
+                    // A quote starts a character literal, e.g. `'x'` or `'\n'`, if it is
+                    // followed by an escape or by a character and a quote. Otherwise it starts
+                    // a lifetime or a label, e.g. `'a`.
+                    let next = self.base.peek().map(RichChar::get_char);
+                    let after_next = self.base.peek().map(RichChar::get_char);
+                    match (next, after_next) {
+                        (Some('\\'), _) | (Some(_), Some('\'')) => CharClassesStatus::LitChar,
                         _ => CharClassesStatus::Normal,
                     }
                 }
@@ -1719,6 +1911,82 @@ fn changed_comment_content(orig: &str, new: &str) -> bool {
     res
 }
 
+/// Returns the position in `orig` of the first comment whose payload, as compared by
+/// `changed_comment_content`, is not found in `new`, i.e. a comment which formatting `orig`
+/// into `new` lost. Each comment of `new` accounts for one comment of `orig`. Wrapping comments
+/// (`wrap_comments`) moves text across the boundaries of comments, so the payloads of the
+/// comments of `orig` which are not kept as they are must make up the payloads of the
+/// remaining comments of `new`, in order. Doc comments are not checked if
+/// `skip_doc_comments`, e.g. when the code in doc comments is formatted, which changes their
+/// payload.
+pub(crate) fn find_lost_comment(orig: &str, new: &str, skip_doc_comments: bool) -> Option<usize> {
+    let comments = |code| {
+        UngroupedCommentCodeSlices::new(code)
+            .filter(move |&(ref kind, _, s)| {
+                *kind == CodeCharKind::Comment && (!skip_doc_comments || !is_doc_comment_str(s))
+            })
+            .map(|(_, pos, s)| (pos, CommentReducer::new(s).collect::<String>()))
+    };
+    let new_payloads: Vec<String> = comments(new).map(|(_, payload)| payload).collect();
+    let mut unused: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, payload) in new_payloads.iter().enumerate().rev() {
+        unused.entry(payload.as_str()).or_default().push(i);
+    }
+    let mut used = vec![false; new_payloads.len()];
+
+    // Comments whose payload is unchanged are matched first, so that they may move, e.g. with
+    // the items they belong to.
+    let mut changed = vec![];
+    for (pos, payload) in comments(orig) {
+        match unused.get_mut(payload.as_str()).and_then(Vec::pop) {
+            Some(i) => used[i] = true,
+            None => changed.push((pos, payload)),
+        }
+    }
+
+    let rest: String = new_payloads
+        .iter()
+        .zip(&used)
+        .filter(|&(_, &used)| !used)
+        .map(|(payload, _)| payload.as_str())
+        .collect();
+    let mut rest = rest.as_str();
+    changed
+        .into_iter()
+        .find(|(_, payload)| match strip_payload(rest, payload) {
+            Some(after) => {
+                rest = after;
+                false
+            }
+            None => true,
+        })
+        .map(|(pos, _)| pos)
+}
+
+/// Returns the rest of `payloads` after `payload`, skipping the punctuation which wrapping a
+/// comment repeats at the start of each line, e.g. the `>` of quotes or the `@` of `//@`
+/// comments. Returns `None` if `payloads` does not start with `payload`.
+fn strip_payload<'a>(payloads: &'a str, payload: &str) -> Option<&'a str> {
+    let mut chars = payloads.chars();
+    for c in payload.chars() {
+        loop {
+            let next = chars.next()?;
+            if next == c {
+                break;
+            }
+            if next.is_alphanumeric() {
+                return None;
+            }
+        }
+    }
+    Some(chars.as_str())
+}
+
+fn is_doc_comment_str(s: &str) -> bool {
+    ["///", "//!", "/*!"].iter().any(|doc| s.starts_with(doc))
+        || s.starts_with("/**") && !s.starts_with("/**/")
+}
+
 /// Iterator over the 'payload' characters of a comment.
 /// It skips whitespace, comment start/end marks, and '*' at the beginning of lines.
 /// The comment must be one comment, ie not more than one start mark (no multiple line comments,
@@ -1986,6 +2254,65 @@ fn check(haystack: &str, needle: &str, expected: Option<usize>) {
         check("/**/abc/* */", "abc", Some(4));
         check("\"/* abc */\"", "abc", Some(4));
         check("\"/* abc", "abc", Some(4));
+        check("aab", "ab", Some(1));
+        check("a/**/ab", "ab", Some(5));
+        check("/* /* ( */ ( */ (", "(", Some(16));
+    }
+
+    #[test]
+    fn test_find_in_code() {
+        fn check(haystack: &str, needle: &str, expected: Option<usize>) {
+            assert_eq!(expected, haystack.find_in_code(needle));
+        }
+
+        check("foo(a)", "(", Some(3));
+        check("\"(\" /* ( */ (", "(", Some(12));
+        check("'(' (", "(", Some(4));
+        check("'\\'' (", "(", Some(5));
+        check("r#\"(\"# (", "(", Some(7));
+        check("trait r#type", "type", Some(8));
+        check("'a (", "(", Some(3));
+        check("&'a T, '(' (", "(", Some(11));
+        check("b'(' (", "(", Some(5));
+        check("\"abc\"", "abc", None);
+        check("\"a\" abc", "abc", Some(4));
+    }
+
+    #[test]
+    fn test_comment_starts() {
+        fn check(text: &str, expected: &[usize]) {
+            assert_eq!(comment_starts(text).collect::<Vec<_>>(), expected);
+        }
+
+        check("a, b", &[]);
+        check("a /* b */ c // d", &[2, 12]);
+        check("/* /* a */ // b */ c", &[0]);
+        check("\"/* a\" /* b */", &[7]);
+        check("'\"' // a", &[4]);
+        check("'\\'' // a", &[5]);
+        check("// a /* b\n/* c */", &[0, 10]);
+    }
+
+    #[test]
+    fn test_find_rustfmt_toggle() {
+        assert_eq!(find_rustfmt_toggle(" // rustfmt: off\n", "off"), Some(1));
+        assert_eq!(find_rustfmt_toggle(" // rustfmt: off\n", "on"), None);
+        assert_eq!(
+            find_rustfmt_toggle("/* // rustfmt: off */\n// rustfmt:off", "off"),
+            Some(22)
+        );
+        assert_eq!(find_rustfmt_toggle("// rustfmt: offset", "off"), None);
+        assert_eq!(find_rustfmt_toggle("\"// rustfmt: off\"", "off"), None);
+    }
+
+    #[test]
+    fn test_has_balanced_delimiters() {
+        assert!(has_balanced_delimiters("let a = [(1, 2), (3, 4)];"));
+        assert!(has_balanced_delimiters("let a = ('(', \"[\"); // {"));
+        assert!(has_balanced_delimiters("fn f<'a>(x: &'a str) { /* ) */ }"));
+        assert!(has_balanced_delimiters("f('{', '\\'', b')')"));
+        assert!(!has_balanced_delimiters("let a = [(1, 2), (3, 4);"));
+        assert!(!has_balanced_delimiters("}{"));
     }
 
     #[test]
@@ -2004,4 +2331,73 @@ fn main() {
 "#;
         assert_eq!(s, filter_normal_code(s_with_comment));
     }
+
+    #[test]
+    fn test_lines_keeping_layout() {
+        let lines = [
+            "Some text which may be wrapped.",
+            "",
+            "| Option | Value |",
+            "|--------|-------|",
+            "",
+            "+---+   +---+",
+            "| a |-->| b |",
+            "+---+   +---+",
+            "",
+            "Name     Width",
+            "",
+            "Not a table: a | b",
+            "and c + d.",
+        ];
+        assert_eq!(
+            lines_keeping_layout(lines.iter().copied()),
+            vec![
+                false, false, true, true, false, true, true, true, false, true, false, false,
+                false,
+            ]
+        );
+        assert_eq!(
+            lines_keeping_layout(["rustfmt: nowrap", "text"].iter().copied()),
+            vec![true, true]
+        );
+    }
+
+    #[test]
+    fn test_find_lost_comment() {
+        let orig = "use a; // a\nfn f(/* x */ x: u32) {}\n/// Code: `f(1,)`\nfn g() {}\n";
+        let kept =
+            "use a; // a\nfn f(\n    // x\n    x: u32,\n) {\n}\n/// Code: `f(1)`\nfn g() {}\n";
+        let lost = "use a;\nfn f(/* x */ x: u32) {}\n/// Code: `f(1,)`\nfn g() {}\n";
+        assert_eq!(find_lost_comment(orig, kept, true), None);
+        assert_eq!(find_lost_comment(orig, kept, false), Some(36));
+        assert_eq!(find_lost_comment(orig, lost, false), Some(7));
+        assert_eq!(
+            find_lost_comment("let s = \"// a\";\n", "let s = \"\";\n", false),
+            None
+        );
+
+        // Each comment of `new` accounts for one comment of `orig`.
+        assert_eq!(find_lost_comment("// b\n// b\n", "// b\n", false), Some(5));
+        assert_eq!(
+            find_lost_comment("// a\n// c\n", "// abc\n", false),
+            Some(5)
+        );
+        // Comments which are wrapped, or joined with the tail of the previous line, are kept.
+        assert_eq!(
+            find_lost_comment("// a long comment\n", "// a long\n// comment\n", false),
+            None
+        );
+        assert_eq!(
+            find_lost_comment(
+                "fn f() {}\n// aaa bbb\n// ccc\n",
+                "// aaa\n// bbb ccc\nfn f() {}\n",
+                false
+            ),
+            None
+        );
+        assert_eq!(
+            find_lost_comment("/// > a long quote\n", "/// > a long\n/// > quote\n", false),
+            None
+        );
+    }
 }
@@ -1,5 +1,5 @@
 use crate::config::file_lines::FileLines;
-use crate::config::options::{IgnoreList, WidthHeuristics};
+use crate::config::options::{GeneratedMarkers, IgnoreList, WidthHeuristics};
 
 /// Trait for types that can be used in `Config`.
 pub(crate) trait ConfigType: Sized {
@@ -50,6 +50,24 @@ fn doc_hint() -> String {
     }
 }
 
+impl ConfigType for GeneratedMarkers {
+    fn doc_hint() -> String {
+        String::from("[<string>,..]")
+    }
+}
+
+/// The documentation of a config option, from its declaration in `create_config!`.
+pub(crate) struct OptionDoc {
+    pub(crate) name: &'static str,
+    /// The values of the option, see `ConfigType::doc_hint`.
+    pub(crate) hint: String,
+    pub(crate) default: String,
+    pub(crate) stable: bool,
+    pub(crate) hidden: bool,
+    /// The lines of the description of the option.
+    pub(crate) description: Vec<&'static str>,
+}
+
 macro_rules! create_config {
     ($($i:ident: $ty:ty, $def:expr, $stb:expr, $( $dstring:expr ),+ );+ $(;)*) => (
         #[cfg(test)]
@@ -142,6 +160,8 @@ pub fn was_set(&self) -> ConfigWasSet<'_> {
             }
 
             fn fill_from_parsed_config(mut self, parsed: PartialConfig, dir: &Path) -> Config {
+                // An inherited ignore list stays relative to the file which set it.
+                let sets_ignore = parsed.ignore.is_some();
             $(
                 if let Some(val) = parsed.$i {
                     if self.$i.3 {
@@ -159,7 +179,9 @@ fn fill_from_parsed_config(mut self, parsed: PartialConfig, dir: &Path) -> Confi
                 }
             )+
                 self.set_heuristics();
-                self.set_ignore(dir);
+                if sets_ignore {
+                    self.set_ignore(dir);
+                }
                 self.set_merge_imports();
                 self
             }
@@ -183,6 +205,16 @@ pub(crate) fn is_valid_name(name: &str) -> bool {
                 }
             }
 
+            /// Returns `true` if the option named `key` is stable.
+            pub(crate) fn is_stable_option(key: &str) -> bool {
+                match key {
+                    $(
+                        stringify!($i) => $stb,
+                    )+
+                        _ => false,
+                }
+            }
+
             #[allow(unreachable_pub)]
             pub fn is_valid_key_val(key: &str, val: &str) -> bool {
                 match key {
@@ -254,41 +286,48 @@ pub fn is_hidden_option(name: &str) -> bool {
                 HIDE_OPTIONS.contains(&name)
             }
 
+            /// Returns the documentation of all options, in the order of their declaration,
+            /// for `print_docs` and the generated reference of the options.
+            pub(crate) fn option_docs() -> Vec<crate::config::config_type::OptionDoc> {
+                let mut docs = vec![];
+                $(
+                    let mut default = format!("{}", $def);
+                    if default.is_empty() {
+                        default = String::from("\"\"");
+                    }
+                    docs.push(crate::config::config_type::OptionDoc {
+                        name: stringify!($i),
+                        hint: <$ty>::doc_hint(),
+                        default,
+                        stable: $stb,
+                        hidden: Config::is_hidden_option(stringify!($i)),
+                        description: vec![$($dstring),+],
+                    });
+                )+
+                docs
+            }
+
             #[allow(unreachable_pub)]
             pub fn print_docs(out: &mut dyn Write, include_unstable: bool) {
-                use std::cmp;
-                let max = 0;
-                $( let max = cmp::max(max, stringify!($i).len()+1); )+
+                let docs = Config::option_docs();
+                let max = docs.iter().map(|doc| doc.name.len() + 1).max().unwrap_or(0);
                 let space_str = " ".repeat(max);
                 writeln!(out, "Configuration Options:").unwrap();
-                $(
-                    if $stb || include_unstable {
-                        let name_raw = stringify!($i);
-
-                        if !Config::is_hidden_option(name_raw) {
-                            let mut name_out = String::with_capacity(max);
-                            for _ in name_raw.len()..max-1 {
-                                name_out.push(' ')
-                            }
-                            name_out.push_str(name_raw);
-                            name_out.push(' ');
-                            let mut default_str = format!("{}", $def);
-                            if default_str.is_empty() {
-                                default_str = String::from("\"\"");
-                            }
-                            writeln!(out,
-                                    "{}{} Default: {}{}",
-                                    name_out,
-                                    <$ty>::doc_hint(),
-                                    default_str,
-                                    if !$stb { " (unstable)" } else { "" }).unwrap();
-                            $(
-                                writeln!(out, "{}{}", space_str, $dstring).unwrap();
-                            )+
-                            writeln!(out).unwrap();
+                for doc in docs {
+                    if (doc.stable || include_unstable) && !doc.hidden {
+                        writeln!(out,
+                                "{:>width$} {} Default: {}{}",
+                                doc.name,
+                                doc.hint,
+                                doc.default,
+                                if !doc.stable { " (unstable)" } else { "" },
+                                width = max - 1).unwrap();
+                        for line in doc.description {
+                            writeln!(out, "{}{}", space_str, line).unwrap();
                         }
+                        writeln!(out).unwrap();
                     }
-                )+
+                }
             }
 
             fn set_width_heuristics(&mut self, heuristics: WidthHeuristics) {
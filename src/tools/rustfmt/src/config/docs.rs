@@ -0,0 +1,355 @@
+//! Generates the documentation of the config options from their declarations in
+//! `create_config!`, so that it is complete as soon as an option is added: the Markdown reference
+//! printed by `rustfmt --help=config-reference`, and the sections of the options in
+//! `Configurations.md`, which `cargo make generate-configurations` regenerates.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::config::config_type::OptionDoc;
+use crate::config::Config;
+
+const DEFAULT_VALUE: &str = "Default value";
+const POSSIBLE_VALUES: &str = "Possible values";
+const STABLE: &str = "Stable";
+const INTERNAL_OPTIONS: &str = "# Internal Options";
+
+impl Config {
+    /// Writes the Markdown reference of the config options, e.g. for
+    /// `rustfmt --help=config-reference`.
+    #[allow(unreachable_pub)]
+    pub fn print_reference(out: &mut dyn Write, include_unstable: bool) -> io::Result<()> {
+        write_reference(out, &Config::option_docs(), include_unstable)
+    }
+}
+
+fn write_reference(
+    out: &mut dyn Write,
+    docs: &[OptionDoc],
+    include_unstable: bool,
+) -> io::Result<()> {
+    let mut docs: Vec<_> = docs
+        .iter()
+        .filter(|doc| !doc.hidden && (doc.stable || include_unstable))
+        .collect();
+    docs.sort_by_key(|doc| doc.name);
+
+    writeln!(out, "# Configuration Options")?;
+    for doc in docs {
+        writeln!(out)?;
+        writeln!(out, "## `{}`", doc.name)?;
+        writeln!(out)?;
+        writeln!(out, "{}", doc.description.join(" "))?;
+        writeln!(out)?;
+        for line in metadata_lines(doc, &[]) {
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Regenerates `Configurations.md`, whose current content is `existing`, from the declarations
+/// of the options `docs`.
+///
+/// The sections of the options are sorted by name, those of options which don't exist anymore
+/// are removed, and options without a section get one with their description. The metadata
+/// lines of every section, i.e. its default value, possible values and stability, are
+/// generated. Only the tracking issue of an unstable option, and the default and possible
+/// values of an option whose type doesn't tell them, e.g. a string, are taken from `existing`,
+/// as are the introduction, the descriptions, the examples and the internal options.
+pub(crate) fn generate_configurations_md(existing: &str, docs: &[OptionDoc]) -> String {
+    let mut intro = vec![];
+    // The name of each option of `existing`, whether it is internal, and the lines of its
+    // section after the heading.
+    let mut sections: Vec<(&str, bool, Vec<&str>)> = vec![];
+    let mut internal = false;
+    let mut in_code_block = false;
+    for line in existing.lines() {
+        if line.starts_with("```") {
+            in_code_block = !in_code_block;
+        } else if !in_code_block {
+            if line == INTERNAL_OPTIONS {
+                internal = true;
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix("## `")
+                .and_then(|name| name.strip_suffix('`'))
+            {
+                sections.push((name, internal, vec![]));
+                continue;
+            }
+        }
+        match sections.last_mut() {
+            Some((_, _, lines)) => lines.push(line),
+            None => intro.push(line),
+        }
+    }
+
+    // Hidden options are documented only if they already are, e.g. deprecated ones.
+    let mut docs: Vec<_> = docs
+        .iter()
+        .filter(|doc| !doc.hidden || sections.iter().any(|(name, ..)| *name == doc.name))
+        .collect();
+    docs.sort_by_key(|doc| doc.name);
+    let internal_options: HashSet<_> = sections
+        .iter()
+        .filter(|(_, internal, _)| *internal)
+        .map(|(name, ..)| *name)
+        .collect();
+    let section_lines = |name: &str, internal: bool| {
+        sections
+            .iter()
+            .find(|section| section.0 == name && section.1 == internal)
+            .map_or(&[][..], |section| &section.2[..])
+    };
+
+    let mut out = String::new();
+    push_lines(&mut out, trim_blank_lines(&intro));
+    for doc in docs
+        .iter()
+        .filter(|doc| !internal_options.contains(doc.name))
+    {
+        out.push_str(&format!("\n## `{}`\n\n", doc.name));
+        push_lines(
+            &mut out,
+            &option_section(doc, section_lines(doc.name, false)),
+        );
+    }
+    out.push('\n');
+    out.push_str(INTERNAL_OPTIONS);
+    out.push('\n');
+    for doc in docs
+        .iter()
+        .filter(|doc| internal_options.contains(doc.name))
+    {
+        out.push_str(&format!("\n## `{}`\n\n", doc.name));
+        push_lines(&mut out, trim_blank_lines(section_lines(doc.name, true)));
+    }
+    out
+}
+
+/// Returns the lines of the section of the option `doc` after its heading, given the lines
+/// `lines` of its current section.
+fn option_section(doc: &OptionDoc, lines: &[&str]) -> Vec<String> {
+    let lines = trim_blank_lines(lines);
+    let (start, end) = match lines.iter().position(|line| metadata_line(line).is_some()) {
+        Some(start) => {
+            let len = lines[start..]
+                .iter()
+                .position(|line| metadata_line(line).is_none())
+                .unwrap_or(lines.len() - start);
+            (start, start + len)
+        }
+        None => (lines.len(), lines.len()),
+    };
+
+    let mut section: Vec<_> = trim_blank_lines(&lines[..start])
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    if section.is_empty() {
+        section.push(doc.description.join(" "));
+    }
+    section.push(String::new());
+    section.extend(metadata_lines(doc, &lines[start..end]));
+    section.extend(lines[end..].iter().map(|line| line.to_string()));
+    section
+}
+
+/// Returns the metadata lines of the option `doc`, given the metadata lines `existing` of its
+/// current section.
+fn metadata_lines(doc: &OptionDoc, existing: &[&str]) -> Vec<String> {
+    let hand_written = |name| {
+        existing
+            .iter()
+            .filter_map(|line| metadata_line(line))
+            .find(|(metadata, _)| *metadata == name)
+            .map(|(_, value)| value)
+    };
+    let is_enum = doc.hint.starts_with('[') && doc.hint != "[<string>,..]";
+
+    // The values of booleans, integers and enums are told by their type.
+    let default = match doc.hint.as_str() {
+        "<boolean>" | "<unsigned integer>" | "<signed integer>" => format!("`{}`", doc.default),
+        _ if is_enum => format!("`\"{}\"`", doc.default),
+        _ => {
+            hand_written(DEFAULT_VALUE).map_or_else(|| format!("`{}`", doc.default), str::to_owned)
+        }
+    };
+    let possible_values = match doc.hint.as_str() {
+        "<boolean>" => possible_values(&doc.hint),
+        _ if is_enum => possible_values(&doc.hint),
+        _ => hand_written(POSSIBLE_VALUES)
+            .map(str::to_owned)
+            .or_else(|| possible_values(&doc.hint)),
+    };
+    let stable = if doc.stable { "Yes" } else { "No" };
+    // E.g. ` (tracking issue: ...)` after `No`.
+    let stable_note = hand_written(STABLE)
+        .and_then(|value| value.strip_prefix(stable))
+        .unwrap_or("");
+
+    let mut lines = vec![format!("- **{}**: {}", DEFAULT_VALUE, default)];
+    if let Some(possible_values) = possible_values {
+        lines.push(format!("- **{}**: {}", POSSIBLE_VALUES, possible_values));
+    }
+    lines.push(format!("- **{}**: {}{}", STABLE, stable, stable_note));
+    lines
+}
+
+/// Returns the name and the value of a metadata line of `Configurations.md`, e.g.
+/// `- **Stable**: Yes`.
+fn metadata_line(line: &str) -> Option<(&'static str, &str)> {
+    let (name, value) = line.strip_prefix("- **")?.split_once("**")?;
+    let value = value.trim_start().strip_prefix(':')?.trim();
+    [DEFAULT_VALUE, POSSIBLE_VALUES, STABLE]
+        .iter()
+        .find(|metadata| metadata.eq_ignore_ascii_case(name))
+        .map(|metadata| (*metadata, value))
+}
+
+fn trim_blank_lines<'a, 'b>(lines: &'a [&'b str]) -> &'a [&'b str] {
+    let start = lines
+        .iter()
+        .position(|line| !line.trim().is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(start, |end| end + 1);
+    &lines[start..end]
+}
+
+fn push_lines<S: AsRef<str>>(out: &mut String, lines: &[S]) {
+    for line in lines {
+        out.push_str(line.as_ref());
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    const CONFIGURATIONS_MD: &str = "Configurations.md";
+
+    fn reference(include_unstable: bool) -> String {
+        let mut out = vec![];
+        Config::print_reference(&mut out, include_unstable).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn reference_lists_all_options() {
+        let reference = reference(true);
+        for doc in Config::option_docs() {
+            assert_eq!(
+                reference.contains(&format!("## `{}`\n", doc.name)),
+                !doc.hidden,
+                "{}",
+                doc.name
+            );
+        }
+        assert!(reference.contains(
+            "## `hard_tabs`\n\n\
+             Use tab characters for indentation, spaces for alignment\n\n\
+             - **Default value**: `false`\n\
+             - **Possible values**: `true`, `false`\n\
+             - **Stable**: Yes\n"
+        ));
+        assert!(reference.contains(
+            "- **Possible values**: `\"Auto\"`, `\"Windows\"`, `\"Unix\"`, `\"Native\"`\n"
+        ));
+    }
+
+    #[test]
+    fn configurations_md_is_generated() {
+        let configurations = fs::read_to_string(CONFIGURATIONS_MD).unwrap();
+        let generated = generate_configurations_md(&configurations, &Config::option_docs());
+        if env::var_os("RUSTFMT_GENERATE_CONFIGURATIONS").is_some() {
+            fs::write(CONFIGURATIONS_MD, generated).unwrap();
+        } else {
+            assert!(
+                configurations == generated,
+                "{} is out of date, run `cargo make generate-configurations`",
+                CONFIGURATIONS_MD
+            );
+        }
+    }
+
+    fn option_doc(name: &'static str, hint: &str, default: &str, stable: bool) -> OptionDoc {
+        OptionDoc {
+            name,
+            hint: hint.to_owned(),
+            default: default.to_owned(),
+            stable,
+            hidden: false,
+            description: vec!["Description of", name],
+        }
+    }
+
+    #[test]
+    fn generate_configurations_md_sections() {
+        let existing = "# Configuration Options\n\n\
+                        ## `removed`\n\n\
+                        Removed option\n\n\
+                        ## `mode`\n\n\
+                        Hand-written description\n\n\
+                        - **Default Value** : Foo\n\
+                        - **Possible values**: Foo, Bar\n\
+                        - **Stable**: Yes (tracking issue: [#1](#1))\n\
+                        ```rust\n\
+                        ## `not_a_section`\n\
+                        ```\n\n\
+                        ## `path`\n\n\
+                        Path\n\n\
+                        - **Default value**: the current directory\n\
+                        - **Stable**: No (tracking issue: [#2](#2))\n\n\n\
+                        # Internal Options\n\n\
+                        ## `internal`\n\n\
+                        Internal option\n";
+        let docs = [
+            option_doc("path", "<string>", "", false),
+            option_doc("mode", "[Foo|Bar|Baz]", "Foo", false),
+            option_doc("internal", "<boolean>", "false", false),
+            option_doc("added", "<unsigned integer>", "4", true),
+        ];
+        assert_eq!(
+            generate_configurations_md(existing, &docs),
+            "# Configuration Options\n\n\
+             ## `added`\n\n\
+             Description of added\n\n\
+             - **Default value**: `4`\n\
+             - **Possible values**: any non-negative integer\n\
+             - **Stable**: Yes\n\n\
+             ## `mode`\n\n\
+             Hand-written description\n\n\
+             - **Default value**: `\"Foo\"`\n\
+             - **Possible values**: `\"Foo\"`, `\"Bar\"`, `\"Baz\"`\n\
+             - **Stable**: No\n\
+             ```rust\n\
+             ## `not_a_section`\n\
+             ```\n\n\
+             ## `path`\n\n\
+             Path\n\n\
+             - **Default value**: the current directory\n\
+             - **Possible values**: any string\n\
+             - **Stable**: No (tracking issue: [#2](#2))\n\n\
+             # Internal Options\n\n\
+             ## `internal`\n\n\
+             Internal option\n"
+        );
+    }
+
+    #[test]
+    fn reference_without_unstable_options() {
+        let reference = reference(false);
+        assert!(reference.contains("## `max_width`\n"));
+        assert!(!reference.contains("## `indent_style`\n"));
+        assert!(!reference.contains("- **Stable**: No"));
+    }
+}
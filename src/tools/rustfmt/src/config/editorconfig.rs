@@ -0,0 +1,250 @@
+//! Reads the basic indentation, width and line ending settings from `.editorconfig` files,
+//! for the options which no config file, environment variable or command line option sets.
+//!
+//! The settings of a directory apply to all the files rustfmt formats with the config of
+//! that directory, so only the sections which apply to all Rust files are read (e.g. `[*]`,
+//! `[*.rs]` or `[*.{rs,toml}]`), and sections for particular files or directories are ignored.
+
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use crate::config::{canonicalize_path, Config};
+
+/// The name of the files read.
+const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+
+/// Sets the options of `config` which are not set otherwise from the `.editorconfig` files
+/// for `dir`.
+pub(crate) fn apply_editorconfig(config: &mut Config, dir: &Path) -> Result<(), Error> {
+    let unset: Vec<_> = editorconfig_overrides(dir)?
+        .into_iter()
+        .filter(|(_, key, _)| !is_set(config, key))
+        .collect();
+    for (_, key, value) in unset {
+        config.override_value(key, &value);
+    }
+    Ok(())
+}
+
+/// Returns the options set by the `.editorconfig` files for `dir`, as `(file, key, value)`,
+/// where `file` is the file setting the option.
+pub(crate) fn editorconfig_overrides(
+    dir: &Path,
+) -> Result<Vec<(PathBuf, &'static str, String)>, Error> {
+    let mut properties: Vec<(String, (PathBuf, String))> = vec![];
+    for path in editorconfig_files(dir)?.into_iter().rev() {
+        for (name, value) in read_properties(&fs::read_to_string(&path)?) {
+            properties.retain(|(other, _)| *other != name);
+            if value != "unset" {
+                properties.push((name, (path.clone(), value)));
+            }
+        }
+    }
+    let property = |name: &str| {
+        properties
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, property)| property.clone())
+    };
+    let number =
+        |(path, value): (PathBuf, String)| value.parse::<usize>().ok().map(|_| (path, value));
+
+    let mut overrides = vec![];
+    let indent_style = property("indent_style");
+    match indent_style {
+        Some((ref path, ref value)) if value == "tab" => {
+            overrides.push((path.clone(), "hard_tabs", String::from("true")))
+        }
+        Some((ref path, ref value)) if value == "space" => {
+            overrides.push((path.clone(), "hard_tabs", String::from("false")))
+        }
+        _ => (),
+    }
+    let uses_tab_width = match property("indent_size") {
+        Some((_, ref value)) if value == "tab" => true,
+        Some(indent_size) => {
+            if let Some((path, value)) = number(indent_size) {
+                overrides.push((path, "tab_spaces", value));
+            }
+            false
+        }
+        None => indent_style.map_or(false, |(_, value)| value == "tab"),
+    };
+    if uses_tab_width {
+        if let Some((path, value)) = property("tab_width").and_then(number) {
+            overrides.push((path, "tab_spaces", value));
+        }
+    }
+    if let Some((path, value)) = property("max_line_length").and_then(number) {
+        overrides.push((path, "max_width", value));
+    }
+    match property("end_of_line") {
+        Some((path, ref value)) if value == "lf" => {
+            overrides.push((path, "newline_style", String::from("Unix")))
+        }
+        Some((path, ref value)) if value == "crlf" => {
+            overrides.push((path, "newline_style", String::from("Windows")))
+        }
+        _ => (),
+    }
+    Ok(overrides)
+}
+
+/// Returns whether the option `key`, one of those set by `editorconfig_overrides`, is set
+/// otherwise.
+fn is_set(config: &Config, key: &str) -> bool {
+    match key {
+        "hard_tabs" => config.was_set().hard_tabs(),
+        "tab_spaces" => config.was_set().tab_spaces(),
+        "max_width" => config.was_set().max_width(),
+        "newline_style" => config.was_set().newline_style(),
+        _ => true,
+    }
+}
+
+/// Returns the `.editorconfig` files in `dir` and its parent directories, from the nearest
+/// one to the one with `root = true`.
+fn editorconfig_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    let mut current = canonicalize_path(dir)?;
+    loop {
+        let path = current.join(EDITORCONFIG_FILE_NAME);
+        if path.is_file() {
+            let is_root = is_root(&fs::read_to_string(&path)?);
+            files.push(path);
+            if is_root {
+                break;
+            }
+        }
+        if !current.pop() {
+            break;
+        }
+    }
+    Ok(files)
+}
+
+/// Returns whether the preamble of the `.editorconfig` file `text` sets `root = true`.
+fn is_root(text: &str) -> bool {
+    for line in lines(text) {
+        if line.starts_with('[') {
+            return false;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("root") {
+                return value.trim().eq_ignore_ascii_case("true");
+            }
+        }
+    }
+    false
+}
+
+/// Returns the properties set by the sections of the `.editorconfig` file `text` which
+/// apply to all Rust files, in order, with lowercase names and values.
+fn read_properties(text: &str) -> Vec<(String, String)> {
+    let mut properties = vec![];
+    let mut in_rust_section = false;
+    for line in lines(text) {
+        if let Some(glob) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            in_rust_section = applies_to_rust_files(glob);
+        } else if let Some((name, value)) = line.split_once('=') {
+            if in_rust_section {
+                properties.push((
+                    name.trim().to_ascii_lowercase(),
+                    value.trim().to_ascii_lowercase(),
+                ));
+            }
+        }
+    }
+    properties
+}
+
+/// Returns the lines of `text` which are neither blank nor comments, trimmed.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+}
+
+/// Returns whether the section `glob` applies to all Rust files in all directories.
+fn applies_to_rust_files(glob: &str) -> bool {
+    let glob = glob.strip_prefix("**/").unwrap_or(glob);
+    let alternatives: Vec<String> = match (glob.find('{'), glob.rfind('}')) {
+        (Some(open), Some(close)) if open < close => glob[open + 1..close]
+            .split(',')
+            .map(|alternative| {
+                format!(
+                    "{}{}{}",
+                    &glob[..open],
+                    alternative.trim(),
+                    &glob[close + 1..]
+                )
+            })
+            .collect(),
+        _ => vec![glob.to_owned()],
+    };
+    alternatives
+        .iter()
+        .any(|glob| ["*", "**", "*.rs", "**.rs"].contains(&glob.as_str()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rust_sections() {
+        assert!(applies_to_rust_files("*"));
+        assert!(applies_to_rust_files("*.rs"));
+        assert!(applies_to_rust_files("**/*.rs"));
+        assert!(applies_to_rust_files("*.{rs,toml}"));
+        assert!(applies_to_rust_files("{*.toml, *.rs}"));
+        assert!(!applies_to_rust_files("*.py"));
+        assert!(!applies_to_rust_files("src/*.rs"));
+        assert!(!applies_to_rust_files("Makefile"));
+    }
+
+    #[test]
+    fn reads_rust_properties() {
+        let text = "root = true\n\n[*]\nindent_style = space\nindent_size = 2\n\n\
+                    # Python is indented by 4 spaces.\n[*.py]\nindent_size = 4\n\n\
+                    [*.rs]\nMax_Line_Length = 80\n";
+        assert!(is_root(text));
+        assert_eq!(
+            read_properties(text),
+            vec![
+                (String::from("indent_style"), String::from("space")),
+                (String::from("indent_size"), String::from("2")),
+                (String::from("max_line_length"), String::from("80")),
+            ]
+        );
+    }
+
+    #[test]
+    fn config_options_take_precedence() {
+        let dir = Path::new("tests/config/editorconfig/child");
+        let overrides: Vec<_> = editorconfig_overrides(dir)
+            .unwrap()
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect();
+        assert_eq!(
+            overrides,
+            vec![
+                ("hard_tabs", String::from("true")),
+                ("tab_spaces", String::from("8")),
+                ("max_width", String::from("80")),
+                ("newline_style", String::from("Unix")),
+            ]
+        );
+
+        let mut config = Config::from_toml("max_width = 90", dir).unwrap();
+        apply_editorconfig(&mut config, dir).unwrap();
+        assert!(config.hard_tabs());
+        assert_eq!(config.tab_spaces(), 8);
+        assert_eq!(config.max_width(), 90);
+    }
+}
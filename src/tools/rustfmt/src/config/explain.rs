@@ -0,0 +1,196 @@
+//! Explains where the effective value of a configuration option comes from, when several
+//! config files, the environment and the command line set it.
+
+use std::env;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::config::editorconfig::editorconfig_overrides;
+use crate::config::{
+    config_file_chain, config_path, editorconfig_dir, env_overrides, resolve_project_file,
+    CliOptions, Config, PROFILES_KEY, RENAMED_OPTIONS,
+};
+
+/// A source of the value of a configuration option.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The default value of the option.
+    Default,
+    /// A config file, including the config files it inherits from.
+    File(PathBuf),
+    /// An environment variable, either `RUSTFMT_CONFIG` or one named after the option.
+    Environment(String),
+    /// The command line options.
+    CommandLine,
+    /// An `.editorconfig` file, for options which are not set otherwise.
+    EditorConfig(PathBuf),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Environment(name) => write!(f, "environment variable `{}`", name),
+            ConfigSource::CommandLine => write!(f, "command line"),
+            ConfigSource::EditorConfig(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Returns the sources which set the option `key` for a file in `dir`, along with the values
+/// they set, from the lowest to the highest precedence. The last value is the effective one.
+///
+/// The sources are looked up like `load_config` does. The command line only shows up if it
+/// changes the value.
+pub fn explain_config<O: CliOptions>(
+    key: &str,
+    dir: Option<&Path>,
+    options: Option<O>,
+) -> Result<Vec<(ConfigSource, String)>, Error> {
+    let default = option_value(&Config::default(), key).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown configuration option: `{}`", key),
+        )
+    })?;
+    let mut sources = vec![(ConfigSource::Default, default)];
+
+    let config_file = match options {
+        Some(ref options) => config_path(options)?,
+        None => None,
+    };
+    let profile = options
+        .as_ref()
+        .and_then(|options| options.profile())
+        .map(ToOwned::to_owned);
+    let config_file = match (config_file, dir) {
+        (Some(path), _) => Some(path),
+        (None, Some(dir)) => resolve_project_file(dir)?,
+        (None, None) => None,
+    };
+    let mut config = match config_file {
+        Some(ref path) => {
+            for (path, toml) in config_file_chain(path, profile.as_deref())? {
+                if let Some(value) = file_value(&toml, key, profile.as_deref())? {
+                    sources.push((ConfigSource::File(path), value));
+                }
+            }
+            Config::from_toml_path(path, profile.as_deref())?
+        }
+        None => Config::default(),
+    };
+
+    for (name, override_key, value) in env_overrides(env::vars()) {
+        config.override_value(&override_key, &value);
+        if override_key == key {
+            let value = option_value(&config, key).unwrap_or(value);
+            sources.push((ConfigSource::Environment(name), value));
+        }
+    }
+
+    if let Some(options) = options {
+        let before = option_value(&config, key);
+        options.apply_to(&mut config);
+        let after = option_value(&config, key);
+        if after != before {
+            if let Some(value) = after {
+                sources.push((ConfigSource::CommandLine, value));
+            }
+        }
+    }
+
+    if config.editorconfig() && sources.len() == 1 {
+        if let Some(dir) = editorconfig_dir(dir, config_file.as_deref()) {
+            for (path, override_key, value) in editorconfig_overrides(dir)? {
+                if override_key == key {
+                    config.override_value(override_key, &value);
+                    let value = option_value(&config, key).unwrap_or(value);
+                    sources.push((ConfigSource::EditorConfig(path), value));
+                }
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// Returns the value of the option `key` of `config`, as it is written in a config file.
+fn option_value(config: &Config, key: &str) -> Option<String> {
+    let toml = config.all_options().to_toml().ok()?;
+    let table: toml::Value = toml.parse().ok()?;
+    table.get(key).map(ToString::to_string)
+}
+
+/// Returns the value of the option `key` set in the config file `toml`, or in its profile
+/// `profile`, if any.
+fn file_value(toml: &str, key: &str, profile: Option<&str>) -> Result<Option<String>, Error> {
+    let table: toml::Value = toml
+        .parse()
+        .map_err(|err| Error::new(ErrorKind::InvalidData, format!("{}", err)))?;
+    let profile_table = profile.and_then(|profile| table.get(PROFILES_KEY)?.get(profile));
+    let old_keys = RENAMED_OPTIONS
+        .iter()
+        .filter(|&&(_, new)| new == key)
+        .map(|&(old, _)| old);
+    let keys: Vec<_> = std::iter::once(key).chain(old_keys).collect();
+    Ok(profile_table
+        .into_iter()
+        .chain(std::iter::once(&table))
+        .find_map(|table| keys.iter().find_map(|key| table.get(key)))
+        .map(ToString::to_string))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::canonicalize_path;
+
+    struct Options {
+        max_width: Option<usize>,
+    }
+
+    impl CliOptions for Options {
+        fn apply_to(self, config: &mut Config) {
+            if let Some(max_width) = self.max_width {
+                config.set().max_width(max_width);
+            }
+        }
+
+        fn config_path(&self) -> Option<&Path> {
+            None
+        }
+    }
+
+    #[test]
+    fn explains_inherited_files_and_command_line() {
+        let dir = canonicalize_path(Path::new("tests/config/inherit")).unwrap();
+        let child_dir = dir.join("child");
+
+        let options = Options {
+            max_width: Some(110),
+        };
+        let sources = explain_config("max_width", Some(&child_dir), Some(options)).unwrap();
+        assert_eq!(
+            sources,
+            vec![
+                (ConfigSource::Default, "100".to_owned()),
+                (
+                    ConfigSource::File(dir.join("rustfmt.toml")),
+                    "80".to_owned()
+                ),
+                (
+                    ConfigSource::File(dir.join("child").join("rustfmt.toml")),
+                    "60".to_owned()
+                ),
+                (ConfigSource::CommandLine, "110".to_owned()),
+            ]
+        );
+
+        assert_eq!(
+            explain_config::<Options>("hard_tabs", Some(&child_dir), None).unwrap(),
+            vec![(ConfigSource::Default, "false".to_owned())]
+        );
+        assert!(explain_config::<Options>("max_wdith", None, None).is_err());
+    }
+}
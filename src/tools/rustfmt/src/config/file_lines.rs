@@ -2,8 +2,9 @@
 
 use itertools::Itertools;
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::{cmp, fmt, iter, str};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::{cmp, fmt, io, iter, str};
 
 use rustc_data_structures::sync::Lrc;
 use rustc_span::{self, SourceFile};
@@ -38,7 +39,7 @@ fn from(name: rustc_span::FileName) -> FileName {
 impl fmt::Display for FileName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            FileName::Real(p) => write!(f, "{}", p.to_str().unwrap()),
+            FileName::Real(p) => write!(f, "{}", p.display()),
             FileName::Stdin => write!(f, "<stdin>"),
         }
     }
@@ -283,11 +284,44 @@ fn next(&mut self) -> Option<&'a FileName> {
 
 fn canonicalize_path_string(file: &FileName) -> Option<FileName> {
     match *file {
-        FileName::Real(ref path) => path.canonicalize().ok().map(FileName::Real),
+        FileName::Real(ref path) => canonicalize_path(path).ok().map(FileName::Real),
         _ => Some(file.clone()),
     }
 }
 
+/// Canonicalizes `path` like `Path::canonicalize`, but without the verbatim (`\\?\`)
+/// prefix that Windows adds. Verbatim paths never compare equal to the paths users
+/// write, which breaks `ignore` and `file_lines` matching and clutters diff headers.
+pub fn canonicalize_path(path: &Path) -> io::Result<PathBuf> {
+    path.canonicalize().map(|path| strip_verbatim_prefix(&path))
+}
+
+/// Replaces a verbatim prefix (`\\?\C:\` or `\\?\UNC\server\share`) with its
+/// regular form (`C:\` or `\\server\share`). Other paths are returned unchanged.
+pub(crate) fn strip_verbatim_prefix(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) => prefix,
+        _ => return path.to_path_buf(),
+    };
+    let mut normalized = match prefix.kind() {
+        Prefix::VerbatimDisk(disk) => OsString::from(format!("{}:", disk as char)),
+        Prefix::VerbatimUNC(server, share) => {
+            let mut unc = OsString::from(r"\\");
+            unc.push(server);
+            unc.push(r"\");
+            unc.push(share);
+            unc
+        }
+        _ => return path.to_path_buf(),
+    };
+    // The root directory component is re-added here, as part of the prefix.
+    normalized.push(r"\");
+    let mut result = PathBuf::from(normalized);
+    result.extend(components.filter(|c| *c != Component::RootDir));
+    result
+}
+
 #[derive(Error, Debug)]
 pub enum FileLinesError {
     #[error("{0}")]
@@ -405,8 +439,32 @@ fn test_range_merge() {
     }
 
     use super::json::{self, json};
-    use super::{FileLines, FileName};
-    use std::{collections::HashMap, path::PathBuf};
+    use super::{strip_verbatim_prefix, FileLines, FileName};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn strip_verbatim_prefix_keeps_regular_paths() {
+        let path = Path::new("src/lib.rs");
+        assert_eq!(strip_verbatim_prefix(path), path);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn strip_verbatim_prefix_from_windows_paths() {
+        assert_eq!(
+            strip_verbatim_prefix(Path::new(r"\\?\C:\foo\bar.rs")),
+            PathBuf::from(r"C:\foo\bar.rs")
+        );
+        assert_eq!(
+            strip_verbatim_prefix(Path::new(r"\\?\UNC\server\share\foo\bar.rs")),
+            PathBuf::from(r"\\server\share\foo\bar.rs")
+        );
+        assert_eq!(
+            strip_verbatim_prefix(Path::new(r"C:\foo\bar.rs")),
+            PathBuf::from(r"C:\foo\bar.rs")
+        );
+    }
 
     #[test]
     fn file_lines_to_json() {
@@ -40,6 +40,35 @@ pub enum ListTactic {
     Mixed,
 }
 
+/// The layout of a kind of lists, which overrides the usual choice of a layout depending on
+/// the width of the items.
+#[config_type]
+pub enum ListLayout {
+    /// Choose the layout depending on the width of the items.
+    Auto,
+    /// All items on one row.
+    Horizontal,
+    /// One item per row.
+    Vertical,
+    /// All items on one row if they fit, one item per row otherwise.
+    HorizontalVertical,
+    /// Pack as many items as possible per row over (possibly) many rows.
+    Mixed,
+}
+
+impl ListLayout {
+    /// Returns the tactic to format lists with, or `None` if it is chosen as usual.
+    pub(crate) fn to_list_tactic(self) -> Option<ListTactic> {
+        match self {
+            ListLayout::Auto => None,
+            ListLayout::Horizontal => Some(ListTactic::Horizontal),
+            ListLayout::Vertical => Some(ListTactic::Vertical),
+            ListLayout::HorizontalVertical => Some(ListTactic::HorizontalVertical),
+            ListLayout::Mixed => Some(ListTactic::Mixed),
+        }
+    }
+}
+
 #[config_type]
 pub enum SeparatorTactic {
     Always,
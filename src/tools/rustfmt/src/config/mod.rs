@@ -8,8 +8,11 @@
 use thiserror::Error;
 
 use crate::config::config_type::ConfigType;
+use crate::config::editorconfig::apply_editorconfig;
 #[allow(unreachable_pub)]
-pub use crate::config::file_lines::{FileLines, FileName, Range};
+pub use crate::config::explain::{explain_config, ConfigSource};
+#[allow(unreachable_pub)]
+pub use crate::config::file_lines::{canonicalize_path, FileLines, FileName, Range};
 #[allow(unreachable_pub)]
 pub use crate::config::lists::*;
 #[allow(unreachable_pub)]
@@ -20,9 +23,18 @@
 #[macro_use]
 pub(crate) mod options;
 
+pub(crate) mod docs;
+pub(crate) mod editorconfig;
+pub(crate) mod explain;
 pub(crate) mod file_lines;
 pub(crate) mod lists;
 
+/// A `max_width` below this is warned about, as most code does not fit within it.
+const MIN_RECOMMENDED_MAX_WIDTH: usize = 40;
+/// Widths with which fewer levels of indentation than this fit within `max_width` are warned
+/// about.
+const MIN_RECOMMENDED_INDENT_LEVELS: usize = 8;
+
 // This macro defines configuration options used in rustfmt. Each option
 // is defined as follows:
 //
@@ -67,20 +79,30 @@
     format_macro_matchers: bool, false, false,
         "Format the metavariable matching patterns in macros";
     format_macro_bodies: bool, true, false, "Format the bodies of macros";
+    format_brace_macros: bool, false, false,
+        "Format the bodies of brace-delimited macro calls which parse as statements";
+    brace_macro_if_bodies: bool, false, false,
+        "Add braces around the block metavariables of macro bodies which don't parse without \
+         them, such as `if $cond $body`";
     hex_literal_case: HexLiteralCase, HexLiteralCase::Preserve, false,
         "Format hexadecimal integer literals";
+    format_imports: bool, true, false, "Format use declarations";
+    format_patterns: bool, true, false, "Format patterns";
+    format_signatures: bool, true, false, "Format function signatures";
 
     // Single line expressions and items
     empty_item_single_line: bool, true, false,
         "Put empty-body functions and impls on a single line";
     struct_lit_single_line: bool, true, false,
         "Put small struct literals on a single line";
+    struct_lit_width_ignores_base: bool, false, false,
+        "Don't count a `..base` expression towards `struct_lit_width`";
     fn_single_line: bool, false, false, "Put single-expression functions on a single line";
     where_single_line: bool, false, false, "Force where-clauses to be on a single line";
 
     // Imports
     imports_indent: IndentStyle, IndentStyle::Block, false, "Indent of imports";
-    imports_layout: ListTactic, ListTactic::Mixed, false, "Item layout inside a import block";
+    imports_layout: ListLayout, ListLayout::Mixed, false, "Item layout inside a import block";
     imports_granularity: ImportGranularity, ImportGranularity::Preserve, false,
         "Merge or split imports to the provided granularity";
     group_imports: GroupImportsTactic, GroupImportsTactic::Preserve, false,
@@ -90,34 +112,67 @@
     // Ordering
     reorder_imports: bool, true, true, "Reorder import and extern crate statements alphabetically";
     reorder_modules: bool, true, true, "Reorder module statements alphabetically in group";
+    test_modules_last: bool, false, false,
+        "Keep `#[cfg(test)]` module declarations after the other modules of their group";
     reorder_impl_items: bool, false, false, "Reorder impl items";
+    reorder_by_alias: bool, false, false,
+        "Sort renamed imports and extern crates by the name they are imported as";
 
     // Spaces around punctuation
     type_punctuation_density: TypeDensity, TypeDensity::Wide, false,
         "Determines if '+' or '=' are wrapped in spaces in the punctuation of types";
+    keyword_prefix_spacing: TypeDensity, TypeDensity::Wide, false,
+        "Determines if a keyword prefix, e.g. `box` or `&mut`, is followed by a space before a \
+         delimited operand";
     space_before_colon: bool, false, false, "Leave a space before the colon";
     space_after_colon: bool, true, false, "Leave a space after the colon";
     spaces_around_ranges: bool, false, false, "Put spaces around the  .. and ..= range operators";
+    spaces_around_struct_braces: bool, true, false,
+        "Put spaces around the braces of single-line struct literals and patterns";
+    space_between_closing_angle_brackets: bool, false, false,
+        "Separate consecutive closing angle brackets of generics with a space";
     binop_separator: SeparatorPlace, SeparatorPlace::Front, false,
         "Where to put a binary operator when a binary expression goes multiline";
+    binop_args_indent: IndentStyle, IndentStyle::Visual, false,
+        "Indent of the continuation lines of a binary expression going multiline which is an \
+         argument of a call";
 
     // Misc.
     remove_nested_parens: bool, true, true, "Remove nested parens";
+    remove_redundant_parens: bool, false, false,
+        "Remove parens around the condition of `if` and `while`, and the operand of `return`";
     combine_control_expr: bool, true, false, "Combine control expressions with function calls";
     short_array_element_width_threshold: usize, 10, true,
         "Width threshold for an array element to be considered short";
     overflow_delimited_expr: bool, false, false,
         "Allow trailing bracket/brace delimited expressions to overflow";
+    prefer_stable_diffs: bool, false, false,
+        "Put each item of a list which does not fit on one line on its own line";
+    optimize_layout: bool, false, false,
+        "Choose between alternative layouts of small expressions with a cost model";
+    call_args_layout: ListLayout, ListLayout::Auto, false,
+        "Layout of the arguments of function and method calls";
+    struct_lit_layout: ListLayout, ListLayout::Auto, false,
+        "Layout of the fields of struct literals";
+    pattern_layout: ListLayout, ListLayout::Auto, false,
+        "Layout of the fields of struct, tuple struct and tuple patterns";
     struct_field_align_threshold: usize, 0, false,
         "Align struct fields if their diffs fits within threshold";
+    const_align_threshold: usize, 0, false,
+        "Align the `=` of consecutive const items whose declarations are at most this long";
     enum_discrim_align_threshold: usize, 0, false,
         "Align enum variants discrims, if their diffs fit within threshold";
     match_arm_blocks: bool, true, false, "Wrap the body of arms in blocks when it does not fit on \
         the same line with the pattern of arms";
+    single_line_match_max_arms: usize, 0, false,
+        "Maximum number of arms of a match to be put on a single line if it fits, 0 for none";
     match_arm_leading_pipes: MatchArmLeadingPipe, MatchArmLeadingPipe::Never, true,
         "Determines whether leading pipes are emitted on match arms";
     force_multiline_blocks: bool, false, false,
         "Force multiline closure bodies and match arms to be wrapped in a block";
+    force_brace_closure_bodies_in_chains: bool, false, false,
+        "Force multiline closure bodies in the arguments of chained method calls to be wrapped \
+         in a block";
     fn_args_layout: Density, Density::Tall, true,
         "Control the layout of arguments in a function";
     brace_style: BraceStyle, BraceStyle::SameLineWhere, false, "Brace style for items";
@@ -139,6 +194,15 @@
         "Write an item and its attribute on the same line \
         if their combined width is below a threshold";
     format_generated_files: bool, true, false, "Format generated files";
+    generated_markers: GeneratedMarkers, GeneratedMarkers::default(), false,
+        "Comment markers and attributes which identify generated files and items";
+    max_file_size: usize, 10485760, false,
+        "Skip files larger than this many bytes, with a warning. 0 means no limit";
+    file_timeout: usize, 0, false,
+        "Leave files unformatted which take longer than this many seconds. 0 means no limit";
+    memory_budget: usize, 0, false,
+        "Resident memory in MiB above which the parser interners and buffers reused across \
+        files are dropped. 0 means no budget";
 
     // Options that can change the source code beyond whitespace/blocks (somewhat linty things)
     merge_derives: bool, true, true, "Merge multiple `#[derive(...)]` into a single one";
@@ -162,8 +226,15 @@
     error_on_unformatted: bool, false, false,
         "Error if unable to get comments or string literals within max_width, \
          or they are left with trailing whitespaces";
+    comment_overflow: CommentOverflow, CommentOverflow::Error, false,
+        "Which comment-only lines may exceed max_width without an error";
     ignore: IgnoreList, IgnoreList::default(), false,
         "Skip formatting the specified files and directories";
+    inherit: bool, false, false,
+        "Apply this config file on top of the nearest config file in a parent directory";
+    editorconfig: bool, false, false,
+        "Read indent_style, indent_size, max_line_length and end_of_line from .editorconfig \
+         files for the options which are not set otherwise";
 
     // Not user-facing
     verbose: Verbosity, Verbosity::Normal, false, "How much to information to emit to the user";
@@ -173,6 +244,14 @@
     emit_mode: EmitMode, EmitMode::Files, false,
         "What emit Mode to use when none is supplied";
     make_backup: bool, false, false, "Backup changed files";
+    backup_count: usize, 1, false,
+        "The number of backups kept of each changed file, the older ones are removed";
+    verify: bool, false, false,
+        "Check that the formatted output keeps every comment of the input, and leave the \
+         files which would lose a comment unformatted";
+    out_dir: String, String::new(), false,
+        "Write the formatted files into this directory, mirroring the tree of the input \
+         files, instead of replacing them";
     print_misformatted_file_names: bool, false, true,
         "Prints the names of mismatched files that were formatted. Prints the names of \
          files that would be formated when used with `--check` mode. ";
@@ -213,6 +292,30 @@ pub(crate) fn version_meets_requirement(&self) -> bool {
         true
     }
 
+    /// Returns a warning if `max_width` and `tab_spaces` leave too little room to format most
+    /// code.
+    pub(crate) fn width_warning(&self) -> Option<String> {
+        let max_width = self.max_width();
+        if max_width < MIN_RECOMMENDED_MAX_WIDTH {
+            return Some(format!(
+                "`max_width = {}` is below {}, code which does not fit within it is left \
+                 unformatted or overflows `max_width`",
+                max_width, MIN_RECOMMENDED_MAX_WIDTH,
+            ));
+        }
+        let tab_spaces = self.tab_spaces();
+        if tab_spaces > 0 && max_width / tab_spaces < MIN_RECOMMENDED_INDENT_LEVELS {
+            return Some(format!(
+                "only {} levels of indentation fit within `max_width = {}` with \
+                 `tab_spaces = {}`",
+                max_width / tab_spaces,
+                max_width,
+                tab_spaces,
+            ));
+        }
+        None
+    }
+
     /// Constructs a `Config` from the toml file specified at `file_path`.
     ///
     /// This method only looks at the provided path, for a method that
@@ -220,12 +323,35 @@ pub(crate) fn version_meets_requirement(&self) -> bool {
     ///
     /// Returns a `Config` if the config could be read and parsed from
     /// the file, otherwise errors.
-    pub(super) fn from_toml_path(file_path: &Path) -> Result<Config, Error> {
-        let mut file = File::open(&file_path)?;
-        let mut toml = String::new();
-        file.read_to_string(&mut toml)?;
-        Config::from_toml(&toml, file_path.parent().unwrap())
-            .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    ///
+    /// If the file sets `inherit = true`, it is applied on top of the config file that
+    /// would be found for its parent directory, which may itself inherit.
+    ///
+    /// If `profile` is given, the options of the `[profile.<name>]` table of each file are
+    /// applied on top of the other options of the file. It is an error if none of the files
+    /// defines the profile.
+    pub(super) fn from_toml_path(file_path: &Path, profile: Option<&str>) -> Result<Config, Error> {
+        let chain = config_file_chain(file_path, profile)?;
+        let config = chain
+            .iter()
+            .try_fold(Config::default(), |config, (path, toml)| {
+                config
+                    .fill_from_toml(toml, path.parent().unwrap(), profile)
+                    .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+            })?;
+        match profile {
+            Some(profile) if !chain.iter().any(|(_, toml)| defines_profile(toml, profile)) => {
+                Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Error: the config profile `{}` is not defined in {}",
+                        profile,
+                        file_path.display()
+                    ),
+                ))
+            }
+            _ => Ok(config),
+        }
     }
 
     /// Resolves the config for input in `dir`.
@@ -237,64 +363,45 @@ pub(super) fn from_toml_path(file_path: &Path) -> Result<Config, Error> {
     ///
     /// Returns the `Config` to use, and the path of the project file if there was
     /// one.
-    pub(super) fn from_resolved_toml_path(dir: &Path) -> Result<(Config, Option<PathBuf>), Error> {
-        /// Try to find a project file in the given directory and its parents.
-        /// Returns the path of a the nearest project file if one exists,
-        /// or `None` if no project file was found.
-        fn resolve_project_file(dir: &Path) -> Result<Option<PathBuf>, Error> {
-            let mut current = if dir.is_relative() {
-                env::current_dir()?.join(dir)
-            } else {
-                dir.to_path_buf()
-            };
-
-            current = fs::canonicalize(current)?;
-
-            loop {
-                match get_toml_path(&current) {
-                    Ok(Some(path)) => return Ok(Some(path)),
-                    Err(e) => return Err(e),
-                    _ => (),
-                }
-
-                // If the current directory has no parent, we're done searching.
-                if !current.pop() {
-                    break;
-                }
-            }
-
-            // If nothing was found, check in the home directory.
-            if let Some(home_dir) = dirs::home_dir() {
-                if let Some(path) = get_toml_path(&home_dir)? {
-                    return Ok(Some(path));
-                }
-            }
-
-            // If none was found ther either, check in the user's configuration directory.
-            if let Some(mut config_dir) = dirs::config_dir() {
-                config_dir.push("rustfmt");
-                if let Some(path) = get_toml_path(&config_dir)? {
-                    return Ok(Some(path));
-                }
-            }
-
-            Ok(None)
-        }
-
+    pub(super) fn from_resolved_toml_path(
+        dir: &Path,
+        profile: Option<&str>,
+    ) -> Result<(Config, Option<PathBuf>), Error> {
         match resolve_project_file(dir)? {
             None => Ok((Config::default(), None)),
-            Some(path) => Config::from_toml_path(&path).map(|config| (config, Some(path))),
+            Some(path) => Config::from_toml_path(&path, profile).map(|config| (config, Some(path))),
         }
     }
 
     pub(crate) fn from_toml(toml: &str, dir: &Path) -> Result<Config, String> {
-        let parsed: ::toml::Value = toml
+        Config::default().fill_from_toml(toml, dir, None)
+    }
+
+    /// Overrides the options of `self` which are set in `toml`, or in its profile `profile`.
+    fn fill_from_toml(
+        self,
+        toml: &str,
+        dir: &Path,
+        profile: Option<&str>,
+    ) -> Result<Config, String> {
+        let mut parsed: ::toml::Value = toml
             .parse()
             .map_err(|e| format!("Could not parse TOML: {}", e))?;
         let mut err = String::new();
         let table = parsed
-            .as_table()
+            .as_table_mut()
             .ok_or_else(|| String::from("Parsed config was not table"))?;
+        apply_profile(table, profile)?;
+        for &(old, new) in RENAMED_OPTIONS {
+            if let Some(val) = table.remove(old) {
+                err.push_str(&format!(
+                    "Warning: the `{}` option is deprecated. Use `{}` instead, or run \
+                     `rustfmt --migrate-config` to update your config file\n",
+                    old, new
+                ));
+                table.entry(new).or_insert(val);
+            }
+        }
         for key in table.keys() {
             if !Config::is_valid_name(key) {
                 let msg = &format!("Warning: Unknown configuration option `{}`\n", key);
@@ -306,7 +413,7 @@ pub(crate) fn from_toml(toml: &str, dir: &Path) -> Result<Config, String> {
                 if !err.is_empty() {
                     eprint!("{}", err);
                 }
-                Ok(Config::default().fill_from_parsed_config(parsed_config, dir))
+                Ok(self.fill_from_parsed_config(parsed_config, dir))
             }
             Err(e) => {
                 err.push_str("Error: Decoding config file failed:\n");
@@ -318,6 +425,103 @@ pub(crate) fn from_toml(toml: &str, dir: &Path) -> Result<Config, String> {
     }
 }
 
+/// The table of a config file holding its profiles, e.g. `[profile.ci]`.
+const PROFILES_KEY: &str = "profile";
+
+/// Overrides the options of the config file `table` with those of its profile `profile`, if
+/// it defines it, and removes the profiles from `table`.
+fn apply_profile(table: &mut ::toml::value::Table, profile: Option<&str>) -> Result<(), String> {
+    let mut profiles = match table.remove(PROFILES_KEY) {
+        Some(::toml::Value::Table(profiles)) => profiles,
+        Some(_) => {
+            return Err(format!(
+                "Error: `{}` must be a table of profiles",
+                PROFILES_KEY
+            ));
+        }
+        None => return Ok(()),
+    };
+    match profile.and_then(|profile| profiles.remove(profile)) {
+        Some(::toml::Value::Table(options)) => table.extend(options),
+        Some(_) => {
+            return Err(format!(
+                "Error: the profile `{}` must be a table of options",
+                profile.unwrap_or_default()
+            ));
+        }
+        None => (),
+    }
+    Ok(())
+}
+
+/// Returns whether the config file `toml` defines the profile `profile`.
+fn defines_profile(toml: &str, profile: &str) -> bool {
+    toml.parse::<::toml::Value>()
+        .ok()
+        .and_then(|parsed| parsed.get(PROFILES_KEY)?.get(profile).cloned())
+        .is_some()
+}
+
+/// Options which were renamed, as `(old name, new name)`. The old names are still accepted
+/// in config files, with a warning.
+const RENAMED_OPTIONS: &[(&str, &str)] = &[
+    ("fn_args_density", "fn_args_layout"),
+    ("fn_brace_style", "brace_style"),
+    ("item_brace_style", "brace_style"),
+];
+
+/// Rewrites the deprecated options of the config file `toml` to the options which replace
+/// them, keeping comments and the layout of the file. An option is dropped if its
+/// replacement is already set.
+pub fn migrate_config(toml: &str) -> Result<String, String> {
+    let parsed: ::toml::Value = toml
+        .parse()
+        .map_err(|e| format!("Could not parse TOML: {}", e))?;
+    let mut set_keys: Vec<String> = match parsed.as_table() {
+        Some(table) => table.keys().cloned().collect(),
+        None => return Err(String::from("Parsed config was not table")),
+    };
+
+    let mut migrated = String::with_capacity(toml.len());
+    for line in toml.split_inclusive('\n') {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let key: String = line
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let rest = &line[indent.len() + key.len()..];
+        if !rest.trim_start().starts_with('=') {
+            migrated.push_str(line);
+            continue;
+        }
+
+        let replacement = if key == "merge_imports" {
+            let rest = match parsed.get("merge_imports").and_then(::toml::Value::as_bool) {
+                Some(true) => rest.replacen("true", "\"Crate\"", 1),
+                _ => rest.replacen("false", "\"Preserve\"", 1),
+            };
+            Some(("imports_granularity", rest))
+        } else {
+            RENAMED_OPTIONS
+                .iter()
+                .find(|(old, _)| *old == key)
+                .map(|&(_, new)| (new, rest.to_owned()))
+        };
+        match replacement {
+            Some((new, _)) if set_keys.iter().any(|key| key == new) => {}
+            Some((new, rest)) => {
+                set_keys.push(new.to_owned());
+                migrated.push_str(indent);
+                migrated.push_str(new);
+                migrated.push_str(&rest);
+            }
+            None => migrated.push_str(line),
+        }
+    }
+    Ok(migrated)
+}
+
 /// Loads a config by checking the client-supplied options and if appropriate, the
 /// file system (including searching the file system for overrides).
 pub fn load_config<O: CliOptions>(
@@ -328,21 +532,179 @@ pub fn load_config<O: CliOptions>(
         Some(ref opts) => config_path(opts)?,
         None => None,
     };
+    let profile = options
+        .as_ref()
+        .and_then(|opts| opts.profile())
+        .map(ToOwned::to_owned);
+    let profile = profile.as_deref();
 
     let result = if let Some(over_ride) = over_ride {
-        Config::from_toml_path(over_ride.as_ref()).map(|p| (p, Some(over_ride.to_owned())))
+        Config::from_toml_path(over_ride.as_ref(), profile).map(|p| (p, Some(over_ride.to_owned())))
     } else if let Some(file_path) = file_path {
-        Config::from_resolved_toml_path(file_path)
+        Config::from_resolved_toml_path(file_path, profile)
     } else {
         Ok((Config::default(), None))
     };
 
-    result.map(|(mut c, p)| {
-        if let Some(options) = options {
-            options.apply_to(&mut c);
+    let (mut c, p) = result?;
+    if let (Some(profile), None) = (profile, &p) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "Error: the config profile `{}` is selected, but there is no config file",
+                profile
+            ),
+        ));
+    }
+    apply_env_overrides(&mut c, env::vars());
+    if let Some(options) = options {
+        options.apply_to(&mut c);
+    }
+    if c.editorconfig() {
+        if let Some(dir) = editorconfig_dir(file_path, p.as_deref()) {
+            apply_editorconfig(&mut c, dir)?;
         }
-        (c, p)
-    })
+    }
+    Ok((c, p))
+}
+
+/// Returns the directory whose `.editorconfig` files apply, which is the directory of the
+/// formatted files if known, or else the directory of the config file.
+fn editorconfig_dir<'a>(
+    file_path: Option<&'a Path>,
+    config_path: Option<&'a Path>,
+) -> Option<&'a Path> {
+    file_path.or_else(|| config_path.and_then(Path::parent))
+}
+
+/// The environment variable holding a comma-separated list of `key=val` overrides.
+const CONFIG_ENV_VAR: &str = "RUSTFMT_CONFIG";
+
+/// Overrides the options set in the environment, either in `RUSTFMT_CONFIG` or in
+/// variables named after the option, e.g. `RUSTFMT_MAX_WIDTH=120`. The latter take
+/// precedence. Other `RUSTFMT_` variables, like `RUSTFMT_LOG`, are ignored.
+fn apply_env_overrides(config: &mut Config, vars: impl Iterator<Item = (String, String)>) {
+    for (_, key, val) in env_overrides(vars) {
+        config.override_value(&key, &val);
+    }
+}
+
+/// Returns the valid overrides among the environment variables `vars`, as
+/// `(variable, key, value)` in the order in which they apply, see `apply_env_overrides`.
+fn env_overrides(vars: impl Iterator<Item = (String, String)>) -> Vec<(String, String, String)> {
+    let vars: Vec<_> = vars
+        .filter(|(name, _)| name.starts_with("RUSTFMT_"))
+        .collect();
+    let mut overrides = vec![];
+    for (name, list) in vars.iter().filter(|(name, _)| name == CONFIG_ENV_VAR) {
+        for key_val in list.split(',').filter(|key_val| !key_val.is_empty()) {
+            match key_val.split_once('=') {
+                Some((key, val)) => overrides.push((name.clone(), key.to_owned(), val.to_owned())),
+                None => eprintln!(
+                    "Warning: `{}` expects a comma-separated list of key=val pairs, found `{}`",
+                    CONFIG_ENV_VAR, key_val
+                ),
+            }
+        }
+    }
+    for (name, val) in &vars {
+        let key = name["RUSTFMT_".len()..].to_ascii_lowercase();
+        if name != CONFIG_ENV_VAR && Config::is_valid_name(&key) {
+            overrides.push((name.clone(), key, val.clone()));
+        }
+    }
+
+    overrides.retain(|(_, key, val)| {
+        let is_valid = Config::is_valid_key_val(key, val);
+        if !is_valid {
+            eprintln!(
+                "Warning: ignoring invalid configuration override from the environment: \
+                 `{}={}`",
+                key, val
+            );
+        }
+        is_valid
+    });
+    overrides
+}
+
+/// Returns the config file at `path` and the config files it inherits from, along with their
+/// contents, from the farthest ancestor to `path`.
+///
+/// A file which sets `inherit = true`, itself or in its profile `profile`, inherits from the
+/// config file that would be found for the parent of its directory.
+fn config_file_chain(path: &Path, profile: Option<&str>) -> Result<Vec<(PathBuf, String)>, Error> {
+    let mut chain = vec![];
+    let mut visited = vec![];
+    let mut next = Some(path.to_owned());
+    while let Some(path) = next.take() {
+        let mut toml = String::new();
+        File::open(&path)?.read_to_string(&mut toml)?;
+        let dir = path.parent().unwrap();
+        let inherit = Config::default()
+            .fill_from_toml(&toml, dir, profile)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?
+            .inherit();
+        if inherit {
+            // Guard against a file inheriting from itself, e.g. through the home directory
+            // fallback of the search.
+            visited.push(canonicalize_path(&path)?);
+            if let Some(parent_dir) = dir.parent() {
+                match resolve_project_file(parent_dir)? {
+                    Some(parent) if !visited.contains(&canonicalize_path(&parent)?) => {
+                        next = Some(parent);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        chain.push((path, toml));
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+/// Try to find a project file in the given directory and its parents.
+/// Returns the path of a the nearest project file if one exists,
+/// or `None` if no project file was found.
+fn resolve_project_file(dir: &Path) -> Result<Option<PathBuf>, Error> {
+    let mut current = if dir.is_relative() {
+        env::current_dir()?.join(dir)
+    } else {
+        dir.to_path_buf()
+    };
+
+    current = canonicalize_path(&current)?;
+
+    loop {
+        match get_toml_path(&current) {
+            Ok(Some(path)) => return Ok(Some(path)),
+            Err(e) => return Err(e),
+            _ => (),
+        }
+
+        // If the current directory has no parent, we're done searching.
+        if !current.pop() {
+            break;
+        }
+    }
+
+    // If nothing was found, check in the home directory.
+    if let Some(home_dir) = dirs::home_dir() {
+        if let Some(path) = get_toml_path(&home_dir)? {
+            return Ok(Some(path));
+        }
+    }
+
+    // If none was found ther either, check in the user's configuration directory.
+    if let Some(mut config_dir) = dirs::config_dir() {
+        config_dir.push("rustfmt");
+        if let Some(path) = get_toml_path(&config_dir)? {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
 }
 
 // Check for the presence of known config file names (`rustfmt.toml, `.rustfmt.toml`) in `dir`
@@ -541,9 +903,15 @@ fn test_dump_default_config() {
 format_strings = false
 format_macro_matchers = false
 format_macro_bodies = true
+format_brace_macros = false
+brace_macro_if_bodies = false
 hex_literal_case = "Preserve"
+format_imports = true
+format_patterns = true
+format_signatures = true
 empty_item_single_line = true
 struct_lit_single_line = true
+struct_lit_width_ignores_base = false
 fn_single_line = false
 where_single_line = false
 imports_indent = "Block"
@@ -552,21 +920,36 @@ fn test_dump_default_config() {
 group_imports = "Preserve"
 reorder_imports = true
 reorder_modules = true
+test_modules_last = false
 reorder_impl_items = false
+reorder_by_alias = false
 type_punctuation_density = "Wide"
+keyword_prefix_spacing = "Wide"
 space_before_colon = false
 space_after_colon = true
 spaces_around_ranges = false
+spaces_around_struct_braces = true
+space_between_closing_angle_brackets = false
 binop_separator = "Front"
+binop_args_indent = "Visual"
 remove_nested_parens = true
+remove_redundant_parens = false
 combine_control_expr = true
 short_array_element_width_threshold = 10
 overflow_delimited_expr = false
+prefer_stable_diffs = false
+optimize_layout = false
+call_args_layout = "Auto"
+struct_lit_layout = "Auto"
+pattern_layout = "Auto"
 struct_field_align_threshold = 0
+const_align_threshold = 0
 enum_discrim_align_threshold = 0
 match_arm_blocks = true
+single_line_match_max_arms = 0
 match_arm_leading_pipes = "Never"
 force_multiline_blocks = false
+force_brace_closure_bodies_in_chains = false
 fn_args_layout = "Tall"
 brace_style = "SameLineWhere"
 control_brace_style = "AlwaysSameLine"
@@ -579,6 +962,10 @@ fn test_dump_default_config() {
 version = "One"
 inline_attribute_width = 0
 format_generated_files = true
+generated_markers = ["@generated", "#[automatically_derived]"]
+max_file_size = 10485760
+file_timeout = 0
+memory_budget = 0
 merge_derives = true
 use_try_shorthand = false
 use_field_init_shorthand = false
@@ -592,9 +979,15 @@ fn test_dump_default_config() {
 hide_parse_errors = false
 error_on_line_overflow = false
 error_on_unformatted = false
+comment_overflow = "Error"
 ignore = []
+inherit = false
+editorconfig = false
 emit_mode = "Files"
 make_backup = false
+backup_count = 1
+verify = false
+out_dir = ""
 "#,
             env!("CARGO_PKG_VERSION")
         );
@@ -631,6 +1024,93 @@ fn test_unstable_from_toml() {
         assert_eq!(config.unstable_features(), true);
     }
 
+    #[nightly_only_test]
+    #[test]
+    fn test_inherit_from_parent_config() {
+        let path = Path::new("tests/config/inherit/child/rustfmt.toml");
+        let config = Config::from_toml_path(path, None).unwrap();
+        assert_eq!(config.max_width(), 60);
+        assert_eq!(config.tab_spaces(), 2);
+
+        let parent = Config::from_toml_path(Path::new("tests/config/inherit/rustfmt.toml"), None);
+        assert_eq!(parent.unwrap().max_width(), 80);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let path = Path::new("tests/config/profile/rustfmt.toml");
+        let config = Config::from_toml_path(path, None).unwrap();
+        assert_eq!(config.max_width(), 90);
+        assert!(!config.error_on_line_overflow());
+
+        let ci = Config::from_toml_path(path, Some("ci")).unwrap();
+        assert_eq!(ci.max_width(), 80);
+        assert!(ci.error_on_line_overflow());
+
+        let dev = Config::from_toml_path(path, Some("dev")).unwrap();
+        assert_eq!(dev.max_width(), 90);
+        assert!(Config::from_toml_path(path, Some("release")).is_err());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = vec![
+            ("RUSTFMT_MAX_WIDTH", "120"),
+            ("RUSTFMT_CONFIG", "max_width=80,hard_tabs=true"),
+            ("RUSTFMT_LOG", "debug"),
+            ("RUSTFMT_TAB_SPACES", "four"),
+            ("PATH", "/bin"),
+        ];
+        let mut config = Config::default();
+        apply_env_overrides(
+            &mut config,
+            vars.into_iter()
+                .map(|(name, val)| (name.to_owned(), val.to_owned())),
+        );
+        assert_eq!(config.max_width(), 120);
+        assert_eq!(config.hard_tabs(), true);
+        assert_eq!(config.tab_spaces(), 4);
+        assert_eq!(config.was_set().tab_spaces(), false);
+    }
+
+    #[test]
+    fn test_renamed_option() {
+        let config = Config::from_toml("fn_args_density = \"Compressed\"", Path::new("")).unwrap();
+        assert_eq!(config.fn_args_layout(), Density::Compressed);
+
+        let toml = r#"
+            fn_args_density = "Compressed"
+            fn_args_layout = "Vertical"
+        "#;
+        let config = Config::from_toml(toml, Path::new("")).unwrap();
+        assert_eq!(config.fn_args_layout(), Density::Vertical);
+    }
+
+    #[test]
+    fn test_migrate_config() {
+        let toml = "# Settings\n\
+                    fn_args_density = \"Compressed\" # keep it short\n\
+                    merge_imports = true\n\
+                    fn_brace_style = \"AlwaysNextLine\"\n\
+                    item_brace_style = \"AlwaysNextLine\"\n\
+                    max_width = 80\n";
+        assert_eq!(
+            migrate_config(toml).unwrap(),
+            "# Settings\n\
+             fn_args_layout = \"Compressed\" # keep it short\n\
+             imports_granularity = \"Crate\"\n\
+             brace_style = \"AlwaysNextLine\"\n\
+             max_width = 80\n"
+        );
+
+        let toml = "merge_imports = false\nimports_granularity = \"Module\"\n";
+        assert_eq!(
+            migrate_config(toml).unwrap(),
+            "imports_granularity = \"Module\"\n"
+        );
+        assert_eq!(migrate_config("max_width = 80").unwrap(), "max_width = 80");
+    }
+
     #[cfg(test)]
     mod deprecated_option_merge_imports {
         use super::*;
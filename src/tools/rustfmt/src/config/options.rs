@@ -171,6 +171,17 @@ pub enum EmitMode {
     /// This option is designed to be run in CI where a non-zero exit signifies
     /// non-standard code formatting. Used for `--check`.
     Diff,
+    /// Writes a mapping from the lines of the formatted output to the spans of the
+    /// original source they were formatted from.
+    Srcmap,
+    /// Writes the items which formatting would change, rather than the changed lines.
+    ItemDiff,
+    /// Writes a diagnostic suggesting the formatted lines for each change, in the JSON format
+    /// of rustc diagnostics.
+    RustcJson,
+    /// Asks which hunks of the diff of each file to apply, and writes the files with the
+    /// applied hunks only. Used for `--interactive`.
+    Interactive,
 }
 
 /// Client-preference for coloured output.
@@ -401,11 +412,72 @@ fn from_str(_: &str) -> Result<Self, Self::Err> {
     }
 }
 
+/// Markers which identify generated code. Entries of the form `#[path]` are attributes,
+/// other entries are comment markers looked for in the first lines of a file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GeneratedMarkers(Vec<String>);
+
+impl Default for GeneratedMarkers {
+    fn default() -> GeneratedMarkers {
+        GeneratedMarkers(vec![
+            String::from("@generated"),
+            String::from("#[automatically_derived]"),
+        ])
+    }
+}
+
+impl fmt::Display for GeneratedMarkers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.0.iter().format(", "))
+    }
+}
+
+impl FromStr for GeneratedMarkers {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let markers: Vec<_> = s
+            .split(',')
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(String::from)
+            .collect();
+        Ok(GeneratedMarkers(markers))
+    }
+}
+
+impl GeneratedMarkers {
+    /// The comment markers, e.g. `@generated`.
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .iter()
+            .filter(|m| Self::attribute_path(m).is_none())
+            .map(String::as_str)
+    }
+
+    /// The paths of the attribute markers, e.g. `automatically_derived`.
+    pub fn attributes(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|m| Self::attribute_path(m))
+    }
+
+    fn attribute_path(marker: &str) -> Option<&str> {
+        marker
+            .strip_prefix("#[")
+            .and_then(|m| m.strip_suffix(']'))
+            .map(str::trim)
+    }
+}
+
 /// Maps client-supplied options to Rustfmt's internals, mostly overriding
 /// values in a config with values from the command line.
 pub trait CliOptions {
     fn apply_to(self, config: &mut Config);
     fn config_path(&self) -> Option<&Path>;
+    /// The name of the profile of the config file to apply, e.g. `ci` for `[profile.ci]`.
+    fn profile(&self) -> Option<&str> {
+        None
+    }
 }
 
 /// The edition of the syntax and semntics of code (RFC 2052).
@@ -462,3 +534,15 @@ pub enum MatchArmLeadingPipe {
     /// Preserve any existing leading pipes
     Preserve,
 }
+
+/// Which comment lines may exceed `max_width` without being reported by
+/// `error_on_line_overflow`.
+#[config_type]
+pub enum CommentOverflow {
+    /// Report comment lines which are too long, like any other line.
+    Error,
+    /// Do not report comment lines which contain a URL.
+    AllowUrls,
+    /// Do not report comment lines.
+    Allow,
+}
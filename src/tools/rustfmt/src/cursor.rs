@@ -0,0 +1,212 @@
+//! Maps positions in the original source to positions in the formatted output, so that
+//! editors can restore the cursor and selections after formatting.
+//!
+//! Formatting mostly changes whitespace, so the non-whitespace characters of both texts
+//! are aligned with each other. Characters that rustfmt inserted or removed (trailing
+//! commas, braces, ...) are skipped by re-synchronising on the next common run of
+//! characters.
+
+/// How far ahead (in non-whitespace characters) to look for the next common run of
+/// characters when the two texts diverge.
+const RESYNC_WINDOW: usize = 32;
+
+/// How many consecutive characters must match for the texts to be considered in sync
+/// again after they diverged.
+const RESYNC_RUN: usize = 3;
+
+/// Returns the byte offset in `formatted` which corresponds to the byte offset `offset`
+/// in `original`, where `formatted` is the result of formatting `original`.
+///
+/// A cursor on a character is mapped to the same character in the formatted text. A
+/// cursor within whitespace sticks to the end of the preceding character, unless it is
+/// in the indentation of a line, in which case it sticks to the start of the following
+/// character. Selections can be mapped by mapping both of their ends.
+pub fn map_cursor_offset(original: &str, formatted: &str, offset: usize) -> usize {
+    OffsetMap::new(original, formatted).map(offset)
+}
+
+/// The alignment of the characters of two texts, to map many offsets from one text to
+/// the other without aligning the texts again for each of them.
+pub(crate) struct OffsetMap<'a> {
+    original: &'a str,
+    formatted: &'a str,
+    original_chars: Vec<(usize, char)>,
+    formatted_chars: Vec<(usize, char)>,
+    alignment: Vec<Option<usize>>,
+}
+
+impl<'a> OffsetMap<'a> {
+    pub(crate) fn new(original: &'a str, formatted: &'a str) -> Self {
+        let original_chars = significant_chars(original);
+        let formatted_chars = significant_chars(formatted);
+        let alignment = align(&original_chars, &formatted_chars);
+        OffsetMap {
+            original,
+            formatted,
+            original_chars,
+            formatted_chars,
+            alignment,
+        }
+    }
+
+    /// Maps `offset` in the original text to the formatted text, like
+    /// [`map_cursor_offset`].
+    pub(crate) fn map(&self, offset: usize) -> usize {
+        let (original, formatted) = (self.original, self.formatted);
+        let (original_chars, formatted_chars) = (&self.original_chars, &self.formatted_chars);
+        let alignment = &self.alignment;
+
+        let mut offset = offset.min(original.len());
+        while !original.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        // The end of the formatted character aligned with the closest original character
+        // at or before `index`.
+        let end_of_previous = |index: usize| {
+            (0..=index).rev().find_map(|i| alignment[i]).map_or(0, |j| {
+                let (pos, c) = formatted_chars[j];
+                pos + c.len_utf8()
+            })
+        };
+        // The start of the formatted character aligned with the closest original
+        // character at or after `index`.
+        let start_of_next = |index: usize| {
+            (index..original_chars.len())
+                .find_map(|i| alignment[i])
+                .map_or(formatted.len(), |j| formatted_chars[j].0)
+        };
+
+        let next = original_chars.partition_point(|&(pos, _)| pos < offset);
+        if next < original_chars.len() && original_chars[next].0 == offset {
+            if let Some(j) = alignment[next] {
+                return formatted_chars[j].0;
+            }
+            return start_of_next(next);
+        }
+
+        // The cursor is within whitespace.
+        let previous = match next.checked_sub(1) {
+            Some(previous) => previous,
+            None => return start_of_next(0),
+        };
+        let (previous_pos, previous_char) = original_chars[previous];
+        let whitespace = &original[previous_pos + previous_char.len_utf8()..offset];
+        if whitespace.contains('\n') && next < original_chars.len() {
+            start_of_next(next)
+        } else {
+            end_of_previous(previous)
+        }
+    }
+}
+
+/// Returns the non-whitespace characters of `s` along with their byte offsets.
+fn significant_chars(s: &str) -> Vec<(usize, char)> {
+    s.char_indices()
+        .filter(|(_, c)| !c.is_whitespace())
+        .collect()
+}
+
+/// For each character in `original`, returns the index of the aligned character in
+/// `formatted`, or `None` if the character has no counterpart.
+fn align(original: &[(usize, char)], formatted: &[(usize, char)]) -> Vec<Option<usize>> {
+    let runs_match = |i: usize, j: usize| {
+        let len = RESYNC_RUN.min(original.len() - i).min(formatted.len() - j);
+        len > 0
+            && original[i..i + len]
+                .iter()
+                .zip(&formatted[j..j + len])
+                .all(|((_, a), (_, b))| a == b)
+    };
+
+    let mut alignment = vec![None; original.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < original.len() && j < formatted.len() {
+        if original[i].1 == formatted[j].1 {
+            alignment[i] = Some(j);
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        // Find the closest point at which both texts are in sync again, preferring
+        // to skip as few characters as possible.
+        let resync = (1..=RESYNC_WINDOW).find_map(|distance| {
+            (0..=distance)
+                .map(|skipped| (i + skipped, j + distance - skipped))
+                .find(|&(i, j)| i < original.len() && j < formatted.len() && runs_match(i, j))
+        });
+        match resync {
+            Some((next_i, next_j)) => {
+                i = next_i;
+                j = next_j;
+            }
+            // The texts diverge too much, treat the characters as substituted.
+            None => {
+                alignment[i] = Some(j);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    alignment
+}
+
+#[cfg(test)]
+mod test {
+    use super::map_cursor_offset;
+
+    const ORIGINAL: &str = "fn main(){let x=foo(a,\n  b);}\n";
+    const FORMATTED: &str = "fn main() {\n    let x = foo(a, b);\n}\n";
+
+    fn map(original_needle: &str, formatted_needle: &str) {
+        let offset = ORIGINAL.find(original_needle).unwrap();
+        let expected = FORMATTED.find(formatted_needle).unwrap();
+        assert_eq!(
+            map_cursor_offset(ORIGINAL, FORMATTED, offset),
+            expected,
+            "cursor at `{}`",
+            original_needle
+        );
+    }
+
+    #[test]
+    fn cursor_on_character() {
+        map("main", "main");
+        map("x=", "x =");
+        map("=foo", "= foo");
+        map("b)", "b)");
+    }
+
+    #[test]
+    fn cursor_in_whitespace() {
+        // In the indentation, the cursor sticks to the next character.
+        map("  b", "b)");
+        // Otherwise, the cursor sticks to the previous character.
+        assert_eq!(
+            map_cursor_offset(ORIGINAL, FORMATTED, ORIGINAL.find("\n  b").unwrap()),
+            FORMATTED.find(" b)").unwrap()
+        );
+    }
+
+    #[test]
+    fn cursor_after_inserted_characters() {
+        let original = "struct Foo { a: u8, b: u8 }";
+        let formatted = "struct Foo {\n    a: u8,\n    b: u8,\n}\n";
+        let offset = original.find('}').unwrap();
+        assert_eq!(
+            map_cursor_offset(original, formatted, offset),
+            formatted.find('}').unwrap()
+        );
+    }
+
+    #[test]
+    fn cursor_out_of_bounds() {
+        assert_eq!(
+            map_cursor_offset(ORIGINAL, FORMATTED, ORIGINAL.len() + 10),
+            FORMATTED.rfind('}').unwrap() + 1
+        );
+        assert_eq!(map_cursor_offset(ORIGINAL, FORMATTED, 0), 0);
+        assert_eq!(map_cursor_offset("", "", 3), 0);
+    }
+}
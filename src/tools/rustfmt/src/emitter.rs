@@ -2,10 +2,16 @@
 pub(crate) use self::diff::*;
 pub(crate) use self::files::*;
 pub(crate) use self::files_with_backup::*;
+pub(crate) use self::interactive::*;
+pub(crate) use self::item_diff::*;
 pub(crate) use self::json::*;
 pub(crate) use self::modified_lines::*;
+pub(crate) use self::out_dir::*;
+pub(crate) use self::rustc_json::*;
+pub(crate) use self::srcmap::*;
 pub(crate) use self::stdout::*;
 use crate::FileName;
+use std::env;
 use std::io::{self, Write};
 use std::path::Path;
 
@@ -13,8 +19,14 @@
 mod diff;
 mod files;
 mod files_with_backup;
+mod interactive;
+mod item_diff;
 mod json;
 mod modified_lines;
+mod out_dir;
+mod replace;
+mod rustc_json;
+mod srcmap;
 mod stdout;
 
 pub(crate) struct FormattedFile<'a> {
@@ -1,5 +1,5 @@
+use super::replace::replace_file;
 use super::*;
-use std::fs;
 
 #[derive(Debug, Default)]
 pub(crate) struct FilesEmitter {
@@ -24,13 +24,13 @@ fn emit_formatted_file(
             formatted_text,
         }: FormattedFile<'_>,
     ) -> Result<EmitterResult, io::Error> {
-        // Write text directly over original file if there is a diff.
+        // Replace the original file if there is a diff.
         let filename = ensure_real_path(filename);
-        if original_text != formatted_text {
-            fs::write(filename, formatted_text)?;
-            if self.print_misformatted_file_names {
-                writeln!(output, "{}", filename.display())?;
-            }
+        if original_text != formatted_text
+            && replace_file(filename, formatted_text, 0)?
+            && self.print_misformatted_file_names
+        {
+            writeln!(output, "{}", filename.display())?;
         }
         Ok(EmitterResult::default())
     }
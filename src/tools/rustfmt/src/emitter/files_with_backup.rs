@@ -1,8 +1,17 @@
+use super::replace::replace_file;
 use super::*;
-use std::fs;
 
-#[derive(Debug, Default)]
-pub(crate) struct FilesWithBackupEmitter;
+#[derive(Debug)]
+pub(crate) struct FilesWithBackupEmitter {
+    /// The number of backups kept of each file.
+    backup_count: usize,
+}
+
+impl FilesWithBackupEmitter {
+    pub(crate) fn new(backup_count: usize) -> Self {
+        Self { backup_count }
+    }
+}
 
 impl Emitter for FilesWithBackupEmitter {
     fn emit_formatted_file(
@@ -16,15 +25,7 @@ fn emit_formatted_file(
     ) -> Result<EmitterResult, io::Error> {
         let filename = ensure_real_path(filename);
         if original_text != formatted_text {
-            // Do a little dance to make writing safer - write to a temp file
-            // rename the original to a .bk, then rename the temp file to the
-            // original.
-            let tmp_name = filename.with_extension("tmp");
-            let bk_name = filename.with_extension("bk");
-
-            fs::write(&tmp_name, formatted_text)?;
-            fs::rename(filename, bk_name)?;
-            fs::rename(tmp_name, filename)?;
+            replace_file(filename, formatted_text, self.backup_count)?;
         }
         Ok(EmitterResult::default())
     }
@@ -0,0 +1,284 @@
+use super::replace::replace_file;
+use super::*;
+use crate::rustfmt_diff::{diff_hunks, DiffHunk, HunkLine};
+use std::fs;
+use std::io::BufRead;
+use std::process::Command;
+
+const HELP: &str = "y - apply this hunk
+n - do not apply this hunk
+a - apply this hunk and all later hunks in the file
+d - do not apply this hunk or any of the later hunks in the file
+e - edit the formatted lines of this hunk, and apply them
+q - quit; do not apply this hunk or any of the later hunks
+? - print help";
+
+/// Presents each hunk of the diff of a file and asks whether to apply it, and writes the
+/// file with the applied hunks only. The answers are read from `input`, standard input
+/// unless testing.
+pub(crate) struct InteractiveEmitter {
+    input: Box<dyn BufRead>,
+    /// Whether the user chose to quit, after which no more hunks are applied.
+    quit: bool,
+}
+
+impl InteractiveEmitter {
+    pub(crate) fn new() -> Self {
+        Self::with_input(Box::new(io::BufReader::new(io::stdin())))
+    }
+
+    fn with_input(input: Box<dyn BufRead>) -> Self {
+        Self { input, quit: false }
+    }
+
+    /// Returns the answer of the user, or `None` when the input ends.
+    fn ask(&mut self, output: &mut dyn Write, question: &str) -> io::Result<Option<String>> {
+        write!(output, "{} ", question)?;
+        output.flush()?;
+        let mut answer = String::new();
+        if self.input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(None);
+        }
+        Ok(Some(answer.trim().to_owned()))
+    }
+}
+
+impl Emitter for InteractiveEmitter {
+    fn emit_formatted_file(
+        &mut self,
+        output: &mut dyn Write,
+        FormattedFile {
+            filename,
+            original_text,
+            formatted_text,
+        }: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        const CONTEXT_SIZE: usize = 3;
+        let hunks = diff_hunks(original_text, formatted_text, CONTEXT_SIZE);
+        if self.quit || hunks.is_empty() {
+            return Ok(EmitterResult::default());
+        }
+
+        let mut applied: Vec<(&DiffHunk, Vec<String>)> = vec![];
+        let mut apply_rest = None;
+        for (i, hunk) in hunks.iter().enumerate() {
+            if let Some(apply) = apply_rest {
+                if apply {
+                    applied.push((hunk, formatted_lines(hunk)));
+                }
+                continue;
+            }
+
+            writeln!(
+                output,
+                "Diff in {} at line {}:",
+                filename, hunk.original_start
+            )?;
+            print_hunk(output, hunk)?;
+            let question = format!(
+                "({}/{}) Apply this hunk [y,n,a,d,e,q,?]?",
+                i + 1,
+                hunks.len()
+            );
+            loop {
+                match self.ask(output, &question)?.as_deref() {
+                    Some("y") => applied.push((hunk, formatted_lines(hunk))),
+                    Some("n") => (),
+                    Some("a") => {
+                        applied.push((hunk, formatted_lines(hunk)));
+                        apply_rest = Some(true);
+                    }
+                    Some("d") => apply_rest = Some(false),
+                    Some("e") => match edit_lines(&formatted_lines(hunk)) {
+                        Ok(lines) => applied.push((hunk, lines)),
+                        Err(e) => {
+                            writeln!(output, "Could not edit the hunk: {}", e)?;
+                            continue;
+                        }
+                    },
+                    Some("q") | None => {
+                        self.quit = true;
+                        apply_rest = Some(false);
+                    }
+                    Some(_) => {
+                        writeln!(output, "{}", HELP)?;
+                        continue;
+                    }
+                }
+                break;
+            }
+        }
+
+        if !applied.is_empty() {
+            let newline = if formatted_text.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            };
+            let text = apply_hunks(original_text, &applied, newline);
+            replace_file(ensure_real_path(filename), &text, 0)?;
+        }
+        Ok(EmitterResult::default())
+    }
+}
+
+fn print_hunk(output: &mut dyn Write, hunk: &DiffHunk) -> io::Result<()> {
+    writeln!(
+        output,
+        "@@ -{},{} +{},{} @@",
+        hunk.original_start, hunk.original_len, hunk.formatted_start, hunk.formatted_len
+    )?;
+    for line in &hunk.lines {
+        match line {
+            HunkLine::Context(line) => writeln!(output, " {}", line)?,
+            HunkLine::Removed(line) => writeln!(output, "-{}", line)?,
+            HunkLine::Added(line) => writeln!(output, "+{}", line)?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns the lines of `hunk` in the formatted text.
+fn formatted_lines(hunk: &DiffHunk) -> Vec<String> {
+    hunk.lines
+        .iter()
+        .filter_map(|line| match line {
+            HunkLine::Context(line) | HunkLine::Added(line) => Some(line.clone()),
+            HunkLine::Removed(_) => None,
+        })
+        .collect()
+}
+
+/// Lets the user edit `lines` with the editor of `$VISUAL` or `$EDITOR`, and returns the
+/// edited lines.
+fn edit_lines(lines: &[String]) -> io::Result<Vec<String>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "neither $VISUAL nor $EDITOR is set",
+            )
+        })?;
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the editor is empty"))?;
+
+    let path = env::temp_dir().join(format!("rustfmt-hunk-{}.rs", std::process::id()));
+    fs::write(&path, lines.join("\n") + "\n")?;
+    let status = Command::new(program).args(words).arg(&path).status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    if !status?.success() {
+        return Err(io::Error::new(io::ErrorKind::Other, "the editor failed"));
+    }
+    let edited = edited?;
+    Ok(edited
+        .strip_suffix('\n')
+        .unwrap_or(&edited)
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r').to_owned())
+        .collect())
+}
+
+/// Returns `original` with the lines of each of `hunks` replaced with the given lines, and
+/// with its lines ended by `newline`. `hunks` are in order and do not overlap.
+fn apply_hunks(original: &str, hunks: &[(&DiffHunk, Vec<String>)], newline: &str) -> String {
+    let original_lines: Vec<&str> = original.lines().collect();
+    let mut lines: Vec<&str> = vec![];
+    let mut next = 0;
+    for (hunk, replacement) in hunks {
+        let start = (hunk.original_start as usize)
+            .saturating_sub(1)
+            .max(next)
+            .min(original_lines.len());
+        let end = (start + hunk.original_len as usize).min(original_lines.len());
+        lines.extend(&original_lines[next..start]);
+        lines.extend(replacement.iter().map(String::as_str));
+        next = end;
+    }
+    lines.extend(&original_lines[next..]);
+    let mut text = lines.join(newline);
+    if original.ends_with('\n') {
+        text.push_str(newline);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileName;
+    use std::path::PathBuf;
+
+    const ORIGINAL: &str = "fn a() {}\nfn b( ) {}\n\n\n\n\n\n\n\nfn c( ) {}\n";
+    const FORMATTED: &str = "fn a() {}\nfn b() {}\n\n\n\n\n\n\n\nfn c() {}\n";
+
+    fn emit(name: &str, answers: &'static str) -> (String, String) {
+        emit_text(name, answers, ORIGINAL, FORMATTED)
+    }
+
+    fn emit_text(
+        name: &str,
+        answers: &'static str,
+        original_text: &str,
+        formatted_text: &str,
+    ) -> (String, String) {
+        let path = env::temp_dir().join(format!(
+            "rustfmt-interactive-{}-{}.rs",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, original_text).unwrap();
+        let mut emitter = InteractiveEmitter::with_input(Box::new(answers.as_bytes()));
+        let mut output = Vec::new();
+        emitter
+            .emit_formatted_file(
+                &mut output,
+                FormattedFile {
+                    filename: &FileName::Real(PathBuf::from(&path)),
+                    original_text,
+                    formatted_text,
+                },
+            )
+            .unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        (String::from_utf8(output).unwrap(), text)
+    }
+
+    #[test]
+    fn applies_accepted_hunks_only() {
+        let (output, text) = emit("accepted", "n\ny\n");
+        assert!(output.contains("@@ -1,5 +1,5 @@\n fn a() {}\n-fn b( ) {}\n+fn b() {}\n"));
+        assert!(output.contains("(2/2) Apply this hunk"));
+        assert_eq!(text, "fn a() {}\nfn b( ) {}\n\n\n\n\n\n\n\nfn c() {}\n");
+    }
+
+    #[test]
+    fn help_and_quit() {
+        let (output, text) = emit("quit", "x\nq\n");
+        assert!(output.contains(HELP));
+        assert!(!output.contains("(2/2)"));
+        assert_eq!(text, ORIGINAL);
+    }
+
+    #[test]
+    fn apply_rest_of_file() {
+        let (_, text) = emit("rest", "a\n");
+        assert_eq!(text, FORMATTED);
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let original = ORIGINAL.replace('\n', "\r\n");
+        let formatted = FORMATTED.replace('\n', "\r\n");
+        let (_, text) = emit_text("crlf", "y\nn\n", &original, &formatted);
+        assert_eq!(
+            text,
+            "fn a() {}\r\nfn b() {}\r\n\r\n\r\n\r\n\r\n\r\n\r\n\r\nfn c( ) {}\r\n"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use super::*;
+use crate::config::Edition;
+use crate::rustfmt_diff::{make_diff, DiffLine};
+use crate::source_items::{innermost_item, source_items, SourceItem};
+use std::io::Write;
+
+/// Emits the items which formatting would change rather than the changed lines, as
+/// `<file>:<line>: <item>`, where `<line>` is the line the item starts on in the original
+/// source and `<item>` is e.g. `fn foo::bar` or `impl Display for Foo`.
+///
+/// Items are found by parsing the original source, so items generated by macros are not
+/// told apart. A change is reported for the innermost item containing it, and changes
+/// outside of any item are reported as `(outside items)`.
+#[derive(Debug)]
+pub(crate) struct ItemDiffEmitter {
+    edition: Edition,
+}
+
+impl ItemDiffEmitter {
+    pub(crate) fn new(edition: Edition) -> Self {
+        ItemDiffEmitter { edition }
+    }
+}
+
+impl Emitter for ItemDiffEmitter {
+    fn emit_formatted_file(
+        &mut self,
+        output: &mut dyn Write,
+        FormattedFile {
+            filename,
+            original_text,
+            formatted_text,
+        }: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        const CONTEXT_SIZE: usize = 0;
+        let diff = make_diff(original_text, formatted_text, CONTEXT_SIZE);
+        if diff.is_empty() {
+            return Ok(EmitterResult::default());
+        }
+
+        let items = source_items(original_text, self.edition).unwrap_or_default();
+        let mut reported: Vec<Option<&SourceItem>> = vec![];
+        for mismatch in diff {
+            let first_line = mismatch.line_number_orig as usize;
+            let removed = mismatch
+                .lines
+                .iter()
+                .filter(|line| matches!(line, DiffLine::Resulting(_)))
+                .count();
+            // Lines which are only inserted belong to the line before them.
+            let changed_lines = if removed == 0 {
+                let line = first_line.saturating_sub(1).max(1);
+                line..line + 1
+            } else {
+                first_line..first_line + removed
+            };
+            for line in changed_lines {
+                let item = innermost_item(&items, line);
+                if !reported.contains(&item) {
+                    reported.push(item);
+                }
+            }
+        }
+
+        for item in reported {
+            match item {
+                Some(item) => writeln!(
+                    output,
+                    "{}:{}: {}",
+                    filename, item.first_line, item.description
+                )?,
+                None => writeln!(output, "{}: (outside items)", filename)?,
+            }
+        }
+        Ok(EmitterResult { has_diff: true })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn emit(original_text: &str, formatted_text: &str) -> String {
+        let mut writer = Vec::new();
+        let _ = ItemDiffEmitter::new(Edition::Edition2018)
+            .emit_formatted_file(
+                &mut writer,
+                FormattedFile {
+                    filename: &FileName::Real(PathBuf::from("src/lib.rs")),
+                    original_text,
+                    formatted_text,
+                },
+            )
+            .unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn reports_innermost_changed_items() {
+        let original = "mod foo {\n    fn bar() {\n        baz( );\n    }\n\n    fn qux() {}\n}\n\n\
+                        impl Display for Foo {\n    fn fmt(&self) {\n        x( );\n    }\n}\n";
+        let formatted = original.replace("( )", "()");
+        assert_eq!(
+            emit(original, &formatted),
+            "src/lib.rs:2: fn foo::bar\nsrc/lib.rs:10: fn <Foo as Display>::fmt\n"
+        );
+    }
+
+    #[test]
+    fn attributes_belong_to_the_item() {
+        let original = "/// Docs.\n#[derive( Debug )]\npub(crate) struct Foo {\n    a: u8,\n}\n";
+        let formatted = original.replace("( Debug )", "(Debug)");
+        assert_eq!(emit(original, &formatted), "src/lib.rs:1: struct Foo\n");
+    }
+
+    #[test]
+    fn reports_changes_outside_items() {
+        let original = "use  foo;\n\n\n\n// comment\n";
+        let formatted = "use foo;\n\n// comment\n";
+        assert_eq!(
+            emit(original, formatted),
+            "src/lib.rs:1: use foo\nsrc/lib.rs: (outside items)\n"
+        );
+    }
+
+    #[test]
+    fn no_output_without_changes() {
+        let text = "fn foo() {}\n";
+        assert_eq!(emit(text, text), "");
+    }
+
+    #[test]
+    fn raw_strings_end_items() {
+        let original = "fn a() {\n    let s = r\"C:\\\";\n}\n\nfn b() {\n    c( );\n}\n";
+        let formatted = original.replace("( )", "()");
+        assert_eq!(emit(original, &formatted), "src/lib.rs:5: fn b\n");
+    }
+}
@@ -0,0 +1,181 @@
+use super::replace::has_contents;
+use super::*;
+use std::fs;
+use std::path::{Component, PathBuf};
+
+/// Writes the formatted files into a separate directory instead of replacing them. The
+/// directory mirrors the tree of the input files relative to `base`, which is the current
+/// directory, so `src/lib.rs` is written to `<out_dir>/src/lib.rs`.
+#[derive(Debug)]
+pub(crate) struct OutDirEmitter {
+    out_dir: PathBuf,
+    base: PathBuf,
+    print_misformatted_file_names: bool,
+}
+
+impl OutDirEmitter {
+    pub(crate) fn new(out_dir: PathBuf, print_misformatted_file_names: bool) -> Self {
+        let base = env::current_dir()
+            .and_then(|dir| dir.canonicalize())
+            .unwrap_or_default();
+        Self {
+            out_dir,
+            base,
+            print_misformatted_file_names,
+        }
+    }
+
+    /// Returns the path in the output directory of the input file at `path`. Files outside
+    /// of `base` keep their whole path below the output directory.
+    fn out_path(&self, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix(&self.base).unwrap_or(path);
+        let mut out_path = self.out_dir.clone();
+        out_path.extend(
+            relative
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                }),
+        );
+        out_path
+    }
+}
+
+impl Emitter for OutDirEmitter {
+    fn emit_formatted_file(
+        &mut self,
+        output: &mut dyn Write,
+        FormattedFile {
+            filename,
+            original_text,
+            formatted_text,
+        }: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        let path = ensure_real_path(filename);
+        let out_path = self.out_path(path);
+        if is_same_file(&out_path, path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "`out_dir` would overwrite {} with its formatted copy",
+                    path.display()
+                ),
+            ));
+        }
+
+        // Write every file, so that the output directory holds a complete tree, but leave
+        // the copies which are up to date untouched.
+        if !has_contents(&out_path, formatted_text) {
+            if let Some(dir) = out_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&out_path, formatted_text)?;
+        }
+        if original_text != formatted_text && self.print_misformatted_file_names {
+            writeln!(output, "{}", out_path.display())?;
+        }
+        Ok(EmitterResult::default())
+    }
+}
+
+/// Returns `true` if the paths `a` and `b` are the same existing file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_the_input_tree() {
+        let base = env::temp_dir().join("rustfmt-out-dir-test");
+        let emitter = OutDirEmitter {
+            out_dir: PathBuf::from("formatted"),
+            base: base.clone(),
+            print_misformatted_file_names: false,
+        };
+        assert_eq!(
+            emitter.out_path(&base.join("src").join("lib.rs")),
+            Path::new("formatted").join("src").join("lib.rs")
+        );
+        assert_eq!(
+            emitter.out_path(Path::new("/elsewhere/main.rs")),
+            Path::new("formatted").join("elsewhere").join("main.rs")
+        );
+    }
+
+    #[test]
+    fn writes_formatted_copies() {
+        let dir = env::temp_dir().join("rustfmt-out-dir-copies");
+        let _ = fs::remove_dir_all(&dir);
+        let base = dir.join("input");
+        let mut emitter = OutDirEmitter {
+            out_dir: dir.join("output"),
+            base: base.clone(),
+            print_misformatted_file_names: true,
+        };
+        let mut writer = Vec::new();
+        for (name, original_text, formatted_text) in &[
+            ("a.rs", "fn a(){}\n", "fn a() {}\n"),
+            ("b.rs", "fn b() {}\n", "fn b() {}\n"),
+        ] {
+            let filename = FileName::Real(base.join("sub").join(name));
+            let _ = emitter
+                .emit_formatted_file(
+                    &mut writer,
+                    FormattedFile {
+                        filename: &filename,
+                        original_text,
+                        formatted_text,
+                    },
+                )
+                .unwrap();
+        }
+
+        let out_dir = dir.join("output").join("sub");
+        assert_eq!(
+            fs::read_to_string(out_dir.join("a.rs")).unwrap(),
+            "fn a() {}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(out_dir.join("b.rs")).unwrap(),
+            "fn b() {}\n"
+        );
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            format!("{}\n", out_dir.join("a.rs").display())
+        );
+        assert!(!base.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn never_overwrites_the_input() {
+        let dir = env::temp_dir().join("rustfmt-out-dir-input");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.rs");
+        fs::write(&path, "fn a(){}\n").unwrap();
+        let mut emitter = OutDirEmitter {
+            out_dir: dir.clone(),
+            base: dir.clone(),
+            print_misformatted_file_names: false,
+        };
+        let result = emitter.emit_formatted_file(
+            &mut Vec::new(),
+            FormattedFile {
+                filename: &FileName::Real(path.clone()),
+                original_text: "fn a(){}\n",
+                formatted_text: "fn a() {}\n",
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a(){}\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Replaces the contents of the file at `path` with `text`, such that the file keeps either
+/// its old or its new contents if rustfmt panics or is killed meanwhile.
+///
+/// The new contents are written to `<path>.rustfmt.tmp`, which is renamed to `path` once it
+/// is complete. The old contents are copied to a backup first unless `backups` is 0, see
+/// `rotate_backups`. As the names are derived from `path`, a file left behind by a killed run
+/// is replaced by the next run rather than piling up.
+///
+/// A file which already holds `text` is not touched at all, so that incremental builds do
+/// not consider it modified. Returns whether the file was replaced.
+pub(super) fn replace_file(path: &Path, text: &str, backups: usize) -> io::Result<bool> {
+    // Replace the file a symlink points to, rather than the symlink.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    if has_contents(&path, text) {
+        return Ok(false);
+    }
+    let permissions = fs::metadata(&path)?.permissions();
+
+    let tmp = TempFile::new(with_suffix(&path, ".rustfmt.tmp"));
+    fs::write(tmp.path(), text)?;
+    fs::set_permissions(tmp.path(), permissions)?;
+    if backups > 0 {
+        rotate_backups(&path, backups)?;
+        fs::copy(&path, path.with_extension("bk"))?;
+    }
+    tmp.persist(&path)?;
+    Ok(true)
+}
+
+/// Makes room for a new backup of the file at `path`, keeping at most `backups` of them.
+///
+/// The most recent backup is `<name>.bk`, the older ones are `<name>.bk.1`, `<name>.bk.2` and
+/// so on, up to `<name>.bk.<backups - 1>`. Each of them is moved one place up, and the oldest
+/// one is removed.
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    let backup = |n: usize| match n {
+        0 => path.with_extension("bk"),
+        _ => path.with_extension(format!("bk.{}", n)),
+    };
+    for n in (0..backups - 1).rev() {
+        let from = backup(n);
+        if from.exists() {
+            fs::rename(from, backup(n + 1))?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns `true` if the file at `path` holds exactly `text`.
+///
+/// The file is compared as it is on disk, as the original text given to emitters has its
+/// line endings normalized.
+pub(super) fn has_contents(path: &Path, text: &str) -> bool {
+    fs::read(path).map_or(false, |contents| contents == text.as_bytes())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// A temporary file which is removed when the guard is dropped, including while unwinding
+/// from a panic, unless it has been persisted.
+struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl TempFile {
+    fn new(path: PathBuf) -> TempFile {
+        TempFile {
+            path,
+            persisted: false,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves the temporary file to `dest`, replacing the file at `dest`.
+    fn persist(mut self, dest: &Path) -> io::Result<()> {
+        fs::rename(&self.path, dest)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustfmt-replace-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn replaces_file_without_leftovers() {
+        let dir = temp_dir("plain");
+        let path = dir.join("lib.rs");
+        fs::write(&path, "fn main(){}").unwrap();
+        replace_file(&path, "fn main() {}\n", 0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn main() {}\n");
+        assert_eq!(file_names(&dir), vec!["lib.rs"]);
+    }
+
+    #[test]
+    fn leaves_unchanged_file_untouched() {
+        let dir = temp_dir("unchanged");
+        let path = dir.join("lib.rs");
+        fs::write(&path, "fn main() {}\r\n").unwrap();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        assert!(!replace_file(&path, "fn main() {}\r\n", 1).unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        assert_eq!(file_names(&dir), vec!["lib.rs"]);
+    }
+
+    #[test]
+    fn keeps_backup() {
+        let dir = temp_dir("backup");
+        let path = dir.join("lib.rs");
+        fs::write(&path, "fn main(){}").unwrap();
+        replace_file(&path, "fn main() {}\n", 1).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn main() {}\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("lib.bk")).unwrap(),
+            "fn main(){}"
+        );
+        assert_eq!(file_names(&dir), vec!["lib.bk", "lib.rs"]);
+    }
+
+    #[test]
+    fn keeps_most_recent_backups() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("lib.rs");
+        fs::write(&path, "0").unwrap();
+        for text in &["1", "2", "3", "4"] {
+            replace_file(&path, text, 3).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "4");
+        assert_eq!(fs::read_to_string(dir.join("lib.bk")).unwrap(), "3");
+        assert_eq!(fs::read_to_string(dir.join("lib.bk.1")).unwrap(), "2");
+        assert_eq!(fs::read_to_string(dir.join("lib.bk.2")).unwrap(), "1");
+        assert_eq!(
+            file_names(&dir),
+            vec!["lib.bk", "lib.bk.1", "lib.bk.2", "lib.rs"]
+        );
+    }
+
+    #[test]
+    fn removes_temp_file_on_panic() {
+        let dir = temp_dir("panic");
+        let path = dir.join("lib.rs.rustfmt.tmp");
+        let result = std::panic::catch_unwind(|| {
+            let tmp = TempFile::new(path.clone());
+            fs::write(tmp.path(), "fn main() {}\n").unwrap();
+            panic!("formatting failed");
+        });
+        assert!(result.is_err());
+        assert!(file_names(&dir).is_empty());
+    }
+}
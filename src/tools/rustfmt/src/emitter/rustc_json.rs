@@ -0,0 +1,220 @@
+use super::*;
+use crate::rustfmt_diff::{diff_hunks, DiffHunk, HunkLine};
+use serde::Serialize;
+use serde_json::to_string as to_json_string;
+use std::io::{self, Write};
+
+const MESSAGE: &str = "code is not formatted";
+const HELP: &str = "format the code with rustfmt";
+
+/// Emits a diagnostic for each changed hunk of each file, as one JSON object per line in the
+/// shape of the JSON diagnostics of rustc (`--error-format=json`), so that the tools which
+/// display rustc diagnostics display formatting changes too. The formatted lines of a hunk are
+/// a machine applicable suggestion replacing its original lines.
+#[derive(Debug, Default)]
+pub(crate) struct RustcJsonEmitter;
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Diagnostic {
+    message: &'static str,
+    /// Always `null`, rustfmt has no diagnostic codes.
+    code: Option<()>,
+    level: &'static str,
+    spans: Vec<DiagnosticSpan>,
+    children: Vec<Diagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DiagnosticSpan {
+    file_name: String,
+    byte_start: usize,
+    byte_end: usize,
+    /// The lines and columns start at 1.
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    is_primary: bool,
+    text: Vec<DiagnosticSpanLine>,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<&'static str>,
+    /// Always `null`, the spans of the original text are not in macro expansions.
+    expansion: Option<()>,
+}
+
+/// A line of the source covered by a span.
+#[derive(Debug, PartialEq, Serialize)]
+struct DiagnosticSpanLine {
+    text: String,
+    highlight_start: usize,
+    highlight_end: usize,
+}
+
+impl Emitter for RustcJsonEmitter {
+    fn emit_formatted_file(
+        &mut self,
+        output: &mut dyn Write,
+        FormattedFile {
+            filename,
+            original_text,
+            formatted_text,
+        }: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        let diagnostics = file_diagnostics(filename, original_text, formatted_text);
+        for diagnostic in &diagnostics {
+            writeln!(output, "{}", to_json_string(diagnostic)?)?;
+        }
+        Ok(EmitterResult {
+            has_diff: !diagnostics.is_empty(),
+        })
+    }
+}
+
+fn file_diagnostics(
+    filename: &FileName,
+    original_text: &str,
+    formatted_text: &str,
+) -> Vec<Diagnostic> {
+    const CONTEXT_SIZE: usize = 0;
+    let newline = if formatted_text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    diff_hunks(original_text, formatted_text, CONTEXT_SIZE)
+        .iter()
+        .map(|hunk| hunk_diagnostic(&filename.to_string(), original_text, hunk, newline))
+        .collect()
+}
+
+/// Returns the diagnostic for `hunk`, whose span covers its original lines along with their
+/// line terminators, i.e. it ends at the start of the line after them. The span of a hunk
+/// which only adds lines is empty, at the start of the line they are added before.
+fn hunk_diagnostic(file_name: &str, original: &str, hunk: &DiffHunk, newline: &str) -> Diagnostic {
+    let line_start = hunk.original_start as usize;
+    let line_end = line_start + hunk.original_len as usize;
+    let span = |suggested_replacement: Option<String>| DiagnosticSpan {
+        file_name: file_name.to_owned(),
+        byte_start: line_offset(original, line_start),
+        byte_end: line_offset(original, line_end),
+        line_start,
+        line_end,
+        column_start: 1,
+        column_end: 1,
+        is_primary: true,
+        text: original
+            .lines()
+            .skip(line_start - 1)
+            .take(line_end - line_start)
+            .map(|line| DiagnosticSpanLine {
+                text: line.to_owned(),
+                highlight_start: 1,
+                highlight_end: line.chars().count() + 1,
+            })
+            .collect(),
+        label: None,
+        suggestion_applicability: suggested_replacement.as_ref().map(|_| "MachineApplicable"),
+        suggested_replacement,
+        expansion: None,
+    };
+
+    let mut replacement = String::new();
+    let mut rendered = format!(
+        "warning: {}\n --> {}:{}:1\n  |\n  = help: {}\n",
+        MESSAGE, file_name, line_start, HELP
+    );
+    for line in &hunk.lines {
+        match line {
+            HunkLine::Context(line) => rendered.push_str(&format!(" {}\n", line)),
+            HunkLine::Removed(line) => rendered.push_str(&format!("-{}\n", line)),
+            HunkLine::Added(line) => {
+                rendered.push_str(&format!("+{}\n", line));
+                replacement.push_str(line);
+                replacement.push_str(newline);
+            }
+        }
+    }
+
+    Diagnostic {
+        message: MESSAGE,
+        code: None,
+        level: "warning",
+        spans: vec![span(None)],
+        children: vec![Diagnostic {
+            message: HELP,
+            code: None,
+            level: "help",
+            spans: vec![span(Some(replacement))],
+            children: vec![],
+            rendered: None,
+        }],
+        rendered: Some(rendered),
+    }
+}
+
+/// Returns the offset of the start of the 1-based line `line` of `text`, or the length of
+/// `text` for the line after its last line.
+fn line_offset(text: &str, line: usize) -> usize {
+    if line <= 1 {
+        return 0;
+    }
+    text.match_indices('\n')
+        .nth(line - 2)
+        .map_or(text.len(), |(i, _)| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn suggests_formatted_lines() {
+        let filename = FileName::Real(PathBuf::from("src/lib.rs"));
+        let original = "fn main( ) {}\n\nfn f( ) {\n}\n";
+        let formatted = "fn main() {}\n\nfn f() {}\n";
+        let diagnostics = file_diagnostics(&filename, original, formatted);
+        assert_eq!(diagnostics.len(), 2);
+
+        let span = &diagnostics[1].spans[0];
+        assert_eq!((span.byte_start, span.byte_end), (15, original.len()));
+        assert_eq!((span.line_start, span.line_end), (3, 5));
+        assert_eq!(span.text[0].text, "fn f( ) {");
+        assert_eq!(span.text[1].highlight_end, 2);
+        assert_eq!(span.suggested_replacement, None);
+        let suggestion = &diagnostics[1].children[0].spans[0];
+        assert_eq!(
+            suggestion.suggested_replacement.as_deref(),
+            Some("fn f() {}\n")
+        );
+
+        let json = to_json_string(&diagnostics[0]).unwrap();
+        assert!(json.starts_with(
+            "{\"message\":\"code is not formatted\",\"code\":null,\"level\":\"warning\",\
+             \"spans\":[{\"file_name\":\"src/lib.rs\",\"byte_start\":0,\"byte_end\":14,"
+        ));
+        assert!(json.contains(
+            "\"suggested_replacement\":\"fn main() {}\\n\",\
+             \"suggestion_applicability\":\"MachineApplicable\""
+        ));
+        assert!(json.ends_with(
+            "\"rendered\":\"warning: code is not formatted\\n --> src/lib.rs:1:1\\n  |\\n  \
+             = help: format the code with rustfmt\\n-fn main( ) {}\\n+fn main() {}\\n\"}"
+        ));
+    }
+
+    #[test]
+    fn added_lines_have_empty_spans() {
+        let filename = FileName::Stdin;
+        let diagnostics =
+            file_diagnostics(&filename, "use a;\nfn f() {}\n", "use a;\n\nfn f() {}\n");
+        assert_eq!(diagnostics.len(), 1);
+        let suggestion = &diagnostics[0].children[0].spans[0];
+        assert_eq!((suggestion.byte_start, suggestion.byte_end), (7, 7));
+        assert_eq!((suggestion.line_start, suggestion.line_end), (2, 2));
+        assert!(suggestion.text.is_empty());
+        assert_eq!(suggestion.suggested_replacement.as_deref(), Some("\n"));
+    }
+}
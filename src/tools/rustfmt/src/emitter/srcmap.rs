@@ -0,0 +1,111 @@
+use super::*;
+use crate::cursor::OffsetMap;
+use std::io::Write;
+
+/// Emits a mapping from the lines of the formatted output to the spans of the original
+/// source they were formatted from. Each non-blank output line is written as
+/// `<output line> <start line>:<start column>-<end line>:<end column>`, where lines and
+/// columns are 1-based and the end column is inclusive.
+#[derive(Debug, Default)]
+pub(crate) struct SrcmapEmitter;
+
+impl Emitter for SrcmapEmitter {
+    fn emit_formatted_file(
+        &mut self,
+        output: &mut dyn Write,
+        FormattedFile {
+            filename,
+            original_text,
+            formatted_text,
+        }: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        writeln!(output, "==== {} ====", filename)?;
+        let offset_map = OffsetMap::new(formatted_text, original_text);
+        let original_lines = LineStarts::new(original_text);
+        let mut line_start = 0;
+        for (i, line) in formatted_text.split_terminator('\n').enumerate() {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() {
+                let lo = line_start + (line.len() - line.trim_start().len());
+                let last_char = trimmed.chars().next_back().map_or(0, char::len_utf8);
+                let hi = lo + trimmed.len() - last_char;
+                let (lo_line, lo_col) = original_lines.line_col(offset_map.map(lo));
+                let (hi_line, hi_col) = original_lines.line_col(offset_map.map(hi));
+                writeln!(
+                    output,
+                    "{} {}:{}-{}:{}",
+                    i + 1,
+                    lo_line,
+                    lo_col,
+                    hi_line,
+                    hi_col
+                )?;
+            }
+            line_start += line.len() + 1;
+        }
+        Ok(EmitterResult::default())
+    }
+}
+
+/// The byte offsets at which the lines of a text start.
+struct LineStarts<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineStarts<'a> {
+    fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineStarts { text, starts }
+    }
+
+    /// Returns the 1-based line and character column of the byte offset `pos`.
+    fn line_col(&self, pos: usize) -> (usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= pos) - 1;
+        let start = self.starts[line];
+        let column = self.text[start..pos.min(self.text.len())].chars().count();
+        (line + 1, column + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn emit(original_text: &str, formatted_text: &str) -> String {
+        let mut writer = Vec::new();
+        let _ = SrcmapEmitter
+            .emit_formatted_file(
+                &mut writer,
+                FormattedFile {
+                    filename: &FileName::Real(PathBuf::from("src/lib.rs")),
+                    original_text,
+                    formatted_text,
+                },
+            )
+            .unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn maps_output_lines_to_original_spans() {
+        let original = "fn main(){let x=foo(a,\n  b);}\n";
+        let formatted = "fn main() {\n    let x = foo(a, b);\n}\n";
+        assert_eq!(
+            emit(original, formatted),
+            "==== src/lib.rs ====\n1 1:1-1:10\n2 1:11-2:5\n3 2:6-2:6\n"
+        );
+    }
+
+    #[test]
+    fn skips_blank_lines() {
+        let text = "fn a() {}\n\nfn b() {}\n";
+        assert_eq!(
+            emit(text, text),
+            "==== src/lib.rs ====\n1 1:1-1:9\n3 3:1-3:9\n"
+        );
+    }
+}
@@ -23,10 +23,58 @@ fn emit_formatted_file(
             ..
         }: FormattedFile<'_>,
     ) -> Result<EmitterResult, io::Error> {
+        // Mark the start of each file so that tools can split the output when several
+        // files are written to stdout.
         if self.verbosity != Verbosity::Quiet {
-            writeln!(output, "{}:\n", filename)?;
+            writeln!(output, "==== {} ====", filename)?;
         }
         write!(output, "{}", formatted_text)?;
         Ok(EmitterResult::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileName;
+    use std::path::PathBuf;
+
+    fn emit(verbosity: Verbosity, files: &[(&str, &str)]) -> String {
+        let mut writer = Vec::new();
+        let mut emitter = StdoutEmitter::new(verbosity);
+        for (filename, text) in files {
+            let _ = emitter
+                .emit_formatted_file(
+                    &mut writer,
+                    FormattedFile {
+                        filename: &FileName::Real(PathBuf::from(filename)),
+                        original_text: text,
+                        formatted_text: text,
+                    },
+                )
+                .unwrap();
+        }
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn prints_file_markers() {
+        let output = emit(
+            Verbosity::Normal,
+            &[
+                ("src/lib.rs", "mod foo;\n"),
+                ("src/foo.rs", "fn foo() {}\n"),
+            ],
+        );
+        assert_eq!(
+            output,
+            "==== src/lib.rs ====\nmod foo;\n==== src/foo.rs ====\nfn foo() {}\n"
+        );
+    }
+
+    #[test]
+    fn does_not_print_file_markers_when_quiet() {
+        let output = emit(Verbosity::Quiet, &[("src/lib.rs", "mod foo;\n")]);
+        assert_eq!(output, "mod foo;\n");
+    }
+}
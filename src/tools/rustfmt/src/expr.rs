@@ -14,24 +14,26 @@
 };
 use crate::config::lists::*;
 use crate::config::{Config, ControlBraceStyle, HexLiteralCase, IndentStyle, Version};
+use crate::layout_cost::layout_cost;
 use crate::lists::{
     definitive_tactic, itemize_list, shape_for_tactic, struct_lit_formatting, struct_lit_shape,
-    struct_lit_tactic, write_list, ListFormatting, Separator,
+    struct_lit_tactic, write_list, ItemWrap, ListFormatting, Separator,
 };
 use crate::macros::{rewrite_macro, MacroPosition};
 use crate::matches::rewrite_match;
 use crate::overflow::{self, IntoOverflowableItem, OverflowableItem};
 use crate::pairs::{rewrite_all_pairs, rewrite_pair, PairParts};
+use crate::patterns::rewrite_pattern_after_keyword;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::{Indent, Shape};
 use crate::source_map::{LineRangeUtils, SpanUtils};
 use crate::spanned::Spanned;
 use crate::string::{rewrite_string, StringFormat};
-use crate::types::{rewrite_path, PathContext};
+use crate::types::{rewrite_overlong_path, rewrite_path, PathContext};
 use crate::utils::{
     colon_spaces, contains_skip, count_newlines, first_line_ends_with, inner_attributes,
-    last_line_extendable, last_line_width, mk_sp, outer_attributes, semicolon_for_expr,
-    unicode_str_width, wrap_str,
+    keyword_prefix, last_line_extendable, last_line_width, mk_sp, outer_attributes,
+    semicolon_for_expr, spaced_prefix, spaced_range_delim, unicode_str_width, wrap_str, wrap_token,
 };
 use crate::vertical::rewrite_with_alignment;
 use crate::visitor::FmtVisitor;
@@ -56,6 +58,11 @@ pub(crate) fn format_expr(
 ) -> Option<String> {
     skip_out_of_file_lines_range!(context, expr.span);
 
+    // Give up quickly once the time budget is spent, the file is left unformatted anyway.
+    if context.deadline_exceeded() {
+        return None;
+    }
+
     if contains_skip(&*expr.attrs) {
         return Some(context.snippet(expr.span()).to_owned());
     }
@@ -64,6 +71,7 @@ pub(crate) fn format_expr(
     } else {
         shape
     };
+    let failure_mark = context.rewrite_failures.mark();
 
     let expr_rw = match expr.kind {
         ast::ExprKind::Array(ref expr_vec) => rewrite_array(
@@ -105,7 +113,7 @@ pub(crate) fn format_expr(
                 )
             })
         }
-        ast::ExprKind::Unary(op, ref subexpr) => rewrite_unary_op(context, op, subexpr, shape),
+        ast::ExprKind::Unary(..) => rewrite_unary_chain(context, expr, shape),
         ast::ExprKind::Struct(ref struct_expr) => {
             let ast::StructExpr {
                 qself,
@@ -170,6 +178,7 @@ pub(crate) fn format_expr(
         }
         ast::ExprKind::Path(ref qself, ref path) => {
             rewrite_path(context, PathContext::Expr, qself, path, shape)
+                .or_else(|| rewrite_overlong_path(context, PathContext::Expr, qself, path, shape))
         }
         ast::ExprKind::Assign(ref lhs, ref rhs, _) => {
             rewrite_assignment(context, lhs, rhs, None, shape)
@@ -229,16 +238,15 @@ pub(crate) fn format_expr(
         }
         ast::ExprKind::Ret(None) => Some("return".to_owned()),
         ast::ExprKind::Ret(Some(ref expr)) => {
-            rewrite_unary_prefix(context, "return ", &**expr, shape)
+            let expr = strip_redundant_parens(context, expr, false);
+            rewrite_unary_prefix(context, "return ", expr, shape)
         }
         ast::ExprKind::Yeet(None) => Some("do yeet".to_owned()),
         ast::ExprKind::Yeet(Some(ref expr)) => {
             rewrite_unary_prefix(context, "do yeet ", &**expr, shape)
         }
-        ast::ExprKind::Box(ref expr) => rewrite_unary_prefix(context, "box ", &**expr, shape),
-        ast::ExprKind::AddrOf(borrow_kind, mutability, ref expr) => {
-            rewrite_expr_addrof(context, borrow_kind, mutability, expr, shape)
-        }
+        ast::ExprKind::Box(ref expr) => rewrite_keyword_prefix(context, &["box"], &**expr, shape),
+        ast::ExprKind::AddrOf(..) => rewrite_unary_chain(context, expr, shape),
         ast::ExprKind::Cast(ref expr, ref ty) => rewrite_pair(
             &**expr,
             &**ty,
@@ -307,11 +315,8 @@ fn needs_space_after_range(rhs: &ast::Expr) -> bool {
 
             match (lhs.as_ref().map(|x| &**x), rhs.as_ref().map(|x| &**x)) {
                 (Some(lhs), Some(rhs)) => {
-                    let sp_delim = if context.config.spaces_around_ranges() {
-                        format!(" {} ", delim)
-                    } else {
-                        default_sp_delim(Some(lhs), Some(rhs))
-                    };
+                    let sp_delim = spaced_range_delim(context.config, delim, true, true)
+                        .unwrap_or_else(|| default_sp_delim(Some(lhs), Some(rhs)));
                     rewrite_pair(
                         &*lhs,
                         &*rhs,
@@ -322,19 +327,13 @@ fn needs_space_after_range(rhs: &ast::Expr) -> bool {
                     )
                 }
                 (None, Some(rhs)) => {
-                    let sp_delim = if context.config.spaces_around_ranges() {
-                        format!("{} ", delim)
-                    } else {
-                        default_sp_delim(None, Some(rhs))
-                    };
+                    let sp_delim = spaced_range_delim(context.config, delim, false, true)
+                        .unwrap_or_else(|| default_sp_delim(None, Some(rhs)));
                     rewrite_unary_prefix(context, &sp_delim, &*rhs, shape)
                 }
                 (Some(lhs), None) => {
-                    let sp_delim = if context.config.spaces_around_ranges() {
-                        format!(" {}", delim)
-                    } else {
-                        default_sp_delim(Some(lhs), None)
-                    };
+                    let sp_delim = spaced_range_delim(context.config, delim, true, false)
+                        .unwrap_or_else(|| default_sp_delim(Some(lhs), None));
                     rewrite_unary_suffix(context, &sp_delim, &*lhs, shape)
                 }
                 (None, None) => Some(delim.to_owned()),
@@ -403,7 +402,7 @@ fn needs_space_after_range(rhs: &ast::Expr) -> bool {
         ast::ExprKind::Err => None,
     };
 
-    expr_rw
+    let result = expr_rw
         .and_then(|expr_str| recover_comment_removed(expr_str, expr.span, context))
         .and_then(|expr_str| {
             let attrs = outer_attributes(&expr.attrs);
@@ -413,7 +412,38 @@ fn needs_space_after_range(rhs: &ast::Expr) -> bool {
                 expr.span.lo(),
             );
             combine_strs_with_missing_comments(context, &attrs_str, &expr_str, span, shape, false)
-        })
+        });
+    if result.is_none() {
+        if let Some(construct) = expr_construct(expr) {
+            context
+                .rewrite_failures
+                .record(failure_mark, expr.span, construct);
+        }
+    }
+    result
+}
+
+/// Describes `expr` for reporting that it could not be formatted. Macro calls are not
+/// reported, as their arguments often cannot be formatted by design.
+fn expr_construct(expr: &ast::Expr) -> Option<&'static str> {
+    let construct = match expr.kind {
+        ast::ExprKind::MacCall(..) | ast::ExprKind::Err => return None,
+        ast::ExprKind::Lit(token_lit) => match token_lit.kind {
+            LitKind::Str | LitKind::StrRaw(_) | LitKind::ByteStr | LitKind::ByteStrRaw(_) => {
+                "string literal"
+            }
+            _ => "literal",
+        },
+        ast::ExprKind::Array(..) => "array",
+        ast::ExprKind::Call(..) => "function call",
+        ast::ExprKind::MethodCall(..) => "method call",
+        ast::ExprKind::Closure(..) => "closure",
+        ast::ExprKind::Match(..) => "match expression",
+        ast::ExprKind::Struct(..) => "struct literal",
+        ast::ExprKind::Binary(..) => "binary expression",
+        _ => "expression",
+    };
+    Some(construct)
 }
 
 pub(crate) fn rewrite_array<'a, T: 'a + IntoOverflowableItem<'a>>(
@@ -779,6 +809,17 @@ fn rewrite_single_line(
             {
                 return None;
             }
+            // Comments around the blocks would be lost on a single line.
+            let cond_hi = self
+                .cond
+                .map_or(self.block.span.lo(), |cond| cond.span.hi());
+            if contains_comment(context.snippet(mk_sp(cond_hi, self.block.span.lo())))
+                || contains_comment(
+                    context.snippet(mk_sp(self.block.span.hi(), else_node.span.lo())),
+                )
+            {
+                return None;
+            }
 
             let new_width = width.checked_sub(pat_expr_str.len() + fixed_cost)?;
             let expr = &self.block.stmts[0];
@@ -840,10 +881,10 @@ fn rewrite_pat_expr(
             let pat_shape = cond_shape
                 .offset_left(matcher.len())?
                 .sub_width(self.connector.len())?;
-            let pat_string = pat.rewrite(context, pat_shape)?;
+            let pat_string = rewrite_pattern_after_keyword(context, pat, pat_shape)?;
             let comments_lo = context
                 .snippet_provider
-                .span_after(self.span.with_lo(pat.span.hi()), self.connector.trim());
+                .span_after(self.span.with_lo(pat.span.hi()), self.connector.trim())?;
             let comments_span = mk_sp(comments_lo, expr.span.lo());
             return rewrite_assign_rhs_with_comments(
                 context,
@@ -857,6 +898,7 @@ fn rewrite_pat_expr(
             );
         }
 
+        let expr = strip_redundant_parens(context, expr, true);
         let expr_rw = expr.rewrite(context, cond_shape);
         // The expression may (partially) fit on the current line.
         // We do not allow splitting between `if` and condition.
@@ -944,7 +986,7 @@ fn rewrite_cond(
         let between_kwd_cond = mk_sp(
             context
                 .snippet_provider
-                .span_after(mk_sp(lo, self.span.hi()), self.keyword.trim()),
+                .span_after(mk_sp(lo, self.span.hi()), self.keyword.trim())?,
             if self.pat.is_none() {
                 cond_span.lo()
             } else if self.matcher.is_empty() {
@@ -952,7 +994,7 @@ fn rewrite_cond(
             } else {
                 context
                     .snippet_provider
-                    .span_before(self.span, self.matcher.trim())
+                    .span_before(self.span, self.matcher.trim())?
             },
         );
 
@@ -1069,15 +1111,32 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 self.block.span.hi(),
                 context
                     .snippet_provider
-                    .span_before(mk_sp(self.block.span.hi(), else_block.span.lo()), "else"),
+                    .span_before(mk_sp(self.block.span.hi(), else_block.span.lo()), "else")?,
             );
-            let between_kwd_else_block_comment =
-                extract_comment(between_kwd_else_block, context, shape);
+            let else_on_next_line =
+                context.config.control_brace_style() != ControlBraceStyle::AlwaysSameLine;
+            let between_kwd_else_block_comment = if else_on_next_line
+                && starts_with_trailing_comment(context.snippet(between_kwd_else_block))
+            {
+                // `else` goes on the next line anyway, so a comment after the closing brace
+                // of the block can be kept there.
+                rewrite_missing_comment(between_kwd_else_block, shape, context)
+                    .filter(|comment| !comment.is_empty())
+                    .map(|comment| {
+                        format!(
+                            " {}{}",
+                            comment,
+                            shape.indent.to_string_with_newline(context.config)
+                        )
+                    })
+            } else {
+                extract_comment(between_kwd_else_block, context, shape)
+            };
 
             let after_else = mk_sp(
                 context
                     .snippet_provider
-                    .span_after(mk_sp(self.block.span.hi(), else_block.span.lo()), "else"),
+                    .span_after(mk_sp(self.block.span.hi(), else_block.span.lo()), "else")?,
                 else_block.span.lo(),
             );
             let after_else_comment = extract_comment(after_else, context, shape);
@@ -1114,6 +1173,13 @@ fn rewrite_label(opt_label: Option<ast::Label>) -> Cow<'static, str> {
     }
 }
 
+/// Returns `true` if `snippet`, the text following some code, starts with a comment on the
+/// same line as the code.
+fn starts_with_trailing_comment(snippet: &str) -> bool {
+    let snippet = snippet.trim_start_matches(|c: char| c.is_whitespace() && c != '\n');
+    snippet.starts_with("//") || snippet.starts_with("/*")
+}
+
 fn extract_comment(span: Span, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
     match rewrite_missing_comment(span, shape, context) {
         Some(ref comment) if !comment.is_empty() => Some(format!(
@@ -1192,11 +1258,7 @@ pub(crate) fn rewrite_literal(
     match token_lit.kind {
         token::LitKind::Str => rewrite_string_lit(context, span, shape),
         token::LitKind::Integer => rewrite_int_lit(context, token_lit, span, shape),
-        _ => wrap_str(
-            context.snippet(span).to_owned(),
-            context.config.max_width(),
-            shape,
-        ),
+        _ => wrap_token(context.snippet(span).to_owned(), context.config, shape),
     }
 }
 
@@ -1212,7 +1274,7 @@ fn rewrite_string_lit(context: &RewriteContext<'_>, span: Span, shape: Shape) ->
         {
             return Some(string_lit.to_owned());
         } else {
-            return wrap_str(string_lit.to_owned(), context.config.max_width(), shape);
+            return wrap_token(string_lit.to_owned(), context.config, shape);
         }
     }
 
@@ -1241,23 +1303,19 @@ fn rewrite_int_lit(
             HexLiteralCase::Lower => Some(symbol_stripped.to_ascii_lowercase()),
         };
         if let Some(hex_lit) = hex_lit {
-            return wrap_str(
+            return wrap_token(
                 format!(
                     "0x{}{}",
                     hex_lit,
                     token_lit.suffix.map_or(String::new(), |s| s.to_string())
                 ),
-                context.config.max_width(),
+                context.config,
                 shape,
             );
         }
     }
 
-    wrap_str(
-        context.snippet(span).to_owned(),
-        context.config.max_width(),
-        shape,
-    )
+    wrap_token(context.snippet(span).to_owned(), context.config, shape)
 }
 
 fn choose_separator_tactic(context: &RewriteContext<'_>, span: Span) -> Option<SeparatorTactic> {
@@ -1287,6 +1345,7 @@ pub(crate) fn rewrite_call(
         span,
         context.config.fn_call_width(),
         choose_separator_tactic(context, span),
+        context.config.call_args_layout(),
     )
 }
 
@@ -1444,6 +1503,55 @@ fn rewrite_paren(
     }
 }
 
+/// Returns the expression within the parens of `expr` if `remove_redundant_parens` is
+/// enabled and the parens contain no comments. In the condition of an `if` or `while`,
+/// parens which are needed to parse the condition are kept.
+fn strip_redundant_parens<'a>(
+    context: &RewriteContext<'_>,
+    expr: &'a ast::Expr,
+    is_cond: bool,
+) -> &'a ast::Expr {
+    match expr.kind {
+        ast::ExprKind::Paren(ref subexpr)
+            if context.config.remove_redundant_parens()
+                && !contains_comment(context.snippet(expr.span))
+                && !(is_cond && cond_needs_parens(subexpr)) =>
+        {
+            strip_redundant_parens(context, subexpr, is_cond)
+        }
+        _ => expr,
+    }
+}
+
+/// Whether the condition of an `if` or `while` would be parsed differently without parens:
+/// a struct literal would be parsed as the block, as would be the end of an open range or
+/// the body of a closure.
+fn cond_needs_parens(expr: &ast::Expr) -> bool {
+    match expr.kind {
+        ast::ExprKind::Struct(..)
+        | ast::ExprKind::Closure(..)
+        | ast::ExprKind::Range(_, None, _) => true,
+        ast::ExprKind::Assign(ref lhs, ref rhs, _)
+        | ast::ExprKind::AssignOp(_, ref lhs, ref rhs)
+        | ast::ExprKind::Binary(_, ref lhs, ref rhs) => {
+            cond_needs_parens(lhs) || cond_needs_parens(rhs)
+        }
+        ast::ExprKind::Range(ref lhs, Some(ref rhs), _) => {
+            lhs.as_ref().map_or(false, |lhs| cond_needs_parens(lhs)) || cond_needs_parens(rhs)
+        }
+        ast::ExprKind::Unary(_, ref expr)
+        | ast::ExprKind::AddrOf(_, _, ref expr)
+        | ast::ExprKind::Cast(ref expr, _)
+        | ast::ExprKind::Type(ref expr, _)
+        | ast::ExprKind::Field(ref expr, _)
+        | ast::ExprKind::Index(ref expr, _)
+        | ast::ExprKind::Try(ref expr)
+        | ast::ExprKind::Await(ref expr) => cond_needs_parens(expr),
+        ast::ExprKind::MethodCall(ref call) => cond_needs_parens(&call.receiver),
+        _ => false,
+    }
+}
+
 fn rewrite_paren_in_multi_line(
     context: &RewriteContext<'_>,
     subexpr: &ast::Expr,
@@ -1557,9 +1665,11 @@ enum StructLitField<'a> {
     let path_str = rewrite_path(context, PathContext::Expr, qself, path, path_shape)?;
 
     let has_base_or_rest = match struct_rest {
-        ast::StructRest::None if fields.is_empty() => return Some(format!("{} {{}}", path_str)),
+        ast::StructRest::None if fields.is_empty() => {
+            return Some(struct_lit_str(context, &path_str, ""));
+        }
         ast::StructRest::Rest(_) if fields.is_empty() => {
-            return Some(format!("{} {{ .. }}", path_str));
+            return Some(struct_lit_str(context, &path_str, " .. "));
         }
         ast::StructRest::Rest(_) | ast::StructRest::Base(_) => true,
         _ => false,
@@ -1567,9 +1677,29 @@ enum StructLitField<'a> {
 
     // Foo { a: Foo } - indent is +3, width is -5.
     let (h_shape, v_shape) = struct_lit_shape(shape, context, path_str.len() + 3, 2)?;
+    let h_shape = match struct_rest {
+        ast::StructRest::Base(expr) if context.config.struct_lit_width_ignores_base() => {
+            h_shape.map(|h_shape| {
+                let base = context.snippet(expr.span);
+                // 2 = "..", 4 = ", .."
+                let base_width = match (base.contains('\n'), fields.is_empty()) {
+                    (true, _) => 0,
+                    (false, true) => base.len() + 2,
+                    (false, false) => base.len() + 4,
+                };
+                // Foo { a: Foo } - width is -5, as in `struct_lit_shape`.
+                let max_width = shape.width.saturating_sub(path_str.len() + 5);
+                Shape {
+                    width: min(max_width, h_shape.width + base_width),
+                    ..h_shape
+                }
+            })
+        }
+        _ => h_shape,
+    };
 
     let one_line_width = h_shape.map_or(0, |shape| shape.width);
-    let body_lo = context.snippet_provider.span_after(span, "{");
+    let body_lo = context.snippet_provider.span_after(span, "{")?;
     let fields_str = if struct_lit_can_be_aligned(fields, has_base_or_rest)
         && context.config.struct_field_align_threshold() > 0
     {
@@ -1579,6 +1709,7 @@ enum StructLitField<'a> {
             v_shape,
             mk_sp(body_lo, span.hi()),
             one_line_width,
+            false,
         )?
     } else {
         let field_iter = fields.iter().map(StructLitField::Regular).chain(
@@ -1630,9 +1761,22 @@ enum StructLitField<'a> {
             span.hi(),
             false,
         );
-        let item_vec = items.collect::<Vec<_>>();
+        let mut item_vec = items.collect::<Vec<_>>();
+        if let ast::StructRest::Rest(_) = struct_rest {
+            if context.config.version() == Version::Two {
+                // The `..` stays next to the last field.
+                if let Some(item) = item_vec.last_mut() {
+                    item.wrap = ItemWrap::NoBreak;
+                }
+            }
+        }
 
-        let tactic = struct_lit_tactic(h_shape, context, &item_vec);
+        let tactic = struct_lit_tactic(
+            h_shape,
+            context,
+            &item_vec,
+            context.config.struct_lit_layout(),
+        );
         let nested_shape = shape_for_tactic(tactic, h_shape, v_shape);
 
         let ends_with_comma = span_ends_with_comma(context, span);
@@ -1650,12 +1794,26 @@ enum StructLitField<'a> {
 
     let fields_str =
         wrap_struct_field(context, attrs, &fields_str, shape, v_shape, one_line_width)?;
-    Some(format!("{} {{{}}}", path_str, fields_str))
+    Some(struct_lit_str(context, &path_str, &fields_str))
 
     // FIXME if context.config.indent_style() == Visual, but we run out
     // of space, we should fall back to BlockIndent.
 }
 
+/// Puts the fields of a struct literal or pattern, as returned by `wrap_struct_field`,
+/// in braces after its path.
+pub(crate) fn struct_lit_str(
+    context: &RewriteContext<'_>,
+    path_str: &str,
+    fields_str: &str,
+) -> String {
+    if context.config.spaces_around_struct_braces() || fields_str.contains('\n') {
+        format!("{} {{{}}}", path_str, fields_str)
+    } else {
+        format!("{}{{{}}}", path_str, fields_str.trim())
+    }
+}
+
 pub(crate) fn wrap_struct_field(
     context: &RewriteContext<'_>,
     attrs: &[ast::Attribute],
@@ -1766,7 +1924,7 @@ fn rewrite_tuple_in_visual_indent_style<'a, T: 'a + IntoOverflowableItem<'a>>(
             .map(|s| format!("({},)", s));
     }
 
-    let list_lo = context.snippet_provider.span_after(span, "(");
+    let list_lo = context.snippet_provider.span_after(span, "(")?;
     let nested_shape = shape.sub_width(2)?.visual_indent(1);
     let items = itemize_list(
         context.snippet_provider,
@@ -1786,6 +1944,7 @@ fn rewrite_tuple_in_visual_indent_style<'a, T: 'a + IntoOverflowableItem<'a>>(
         ListTactic::HorizontalVertical,
         Separator::Comma,
         nested_shape.width,
+        context.config,
     );
     let fmt = ListFormatting::new(nested_shape, context.config)
         .tactic(tactic)
@@ -1824,6 +1983,7 @@ pub(crate) fn rewrite_tuple<'a, T: 'a + IntoOverflowableItem<'a>>(
             span,
             context.config.fn_call_width(),
             force_tactic,
+            ListLayout::Auto,
         )
     } else {
         rewrite_tuple_in_visual_indent_style(context, items, span, shape, is_singleton_tuple)
@@ -1838,7 +1998,18 @@ pub(crate) fn rewrite_unary_prefix<R: Rewrite>(
 ) -> Option<String> {
     rewrite
         .rewrite(context, shape.offset_left(prefix.len())?)
-        .map(|r| format!("{}{}", prefix, r))
+        .map(|r| format!("{}{}", spaced_prefix(context.config, prefix, &r), r))
+}
+
+/// Rewrites `rewrite` after the keywords `keywords`, e.g. `box` or `raw const`, each of
+/// which is followed by a single space unless `spaced_prefix` drops the last one.
+pub(crate) fn rewrite_keyword_prefix<R: Rewrite>(
+    context: &RewriteContext<'_>,
+    keywords: &[&str],
+    rewrite: &R,
+    shape: Shape,
+) -> Option<String> {
+    rewrite_unary_prefix(context, &keyword_prefix(keywords), rewrite, shape)
 }
 
 // FIXME: this is probably not correct for multi-line Rewrites. we should
@@ -1857,14 +2028,51 @@ pub(crate) fn rewrite_unary_suffix<R: Rewrite>(
         })
 }
 
-fn rewrite_unary_op(
+/// Rewrites a chain of unary operators and borrows, e.g. `!!flag` or `&&mut x`. The
+/// operators are written in front of the innermost operand at once, without spaces between
+/// them, so that the chain is never broken between two of them.
+fn rewrite_unary_chain(
     context: &RewriteContext<'_>,
-    op: ast::UnOp,
     expr: &ast::Expr,
     shape: Shape,
 ) -> Option<String> {
-    // For some reason, an UnOp is not spanned like BinOp!
-    rewrite_unary_prefix(context, ast::UnOp::to_string(op), expr, shape)
+    let mut prefix = String::new();
+    let mut operand = expr;
+    while let Some((op, inner)) = unary_operator(operand) {
+        // Keep the operators after an attribute or a comment to the rewrite of the operand
+        // they apply to. For some reason, an UnOp is not spanned like BinOp!
+        let op_span = mk_sp(operand.span.lo(), inner.span.lo());
+        if !prefix.is_empty()
+            && (!operand.attrs.is_empty() || contains_comment(context.snippet(op_span)))
+        {
+            break;
+        }
+        // `--x` reads like a decrement.
+        if context.config.version() == Version::Two && prefix.ends_with('-') && op.starts_with('-')
+        {
+            prefix.push(' ');
+        }
+        prefix.push_str(&op);
+        operand = inner;
+    }
+    rewrite_unary_prefix(context, &prefix, operand, shape)
+}
+
+/// Returns the operator of a unary operation or a borrow, along with its operand.
+fn unary_operator(expr: &ast::Expr) -> Option<(Cow<'static, str>, &ast::Expr)> {
+    match expr.kind {
+        ast::ExprKind::Unary(op, ref inner) => Some((Cow::from(ast::UnOp::to_string(op)), inner)),
+        ast::ExprKind::AddrOf(borrow_kind, mutability, ref inner) => {
+            let keywords: &[&str] = match (mutability, borrow_kind) {
+                (ast::Mutability::Not, ast::BorrowKind::Ref) => &[],
+                (ast::Mutability::Not, ast::BorrowKind::Raw) => &["raw", "const"],
+                (ast::Mutability::Mut, ast::BorrowKind::Ref) => &["mut"],
+                (ast::Mutability::Mut, ast::BorrowKind::Raw) => &["raw", "mut"],
+            };
+            Some((Cow::from(format!("&{}", keyword_prefix(keywords))), inner))
+        }
+        _ => None,
+    }
 }
 
 pub(crate) enum RhsAssignKind<'ast> {
@@ -1913,11 +2121,37 @@ fn rewrite_assignment(
 
     rewrite_assign_rhs(
         context,
-        lhs_str,
+        lhs_str.clone(),
         rhs,
         &RhsAssignKind::Expr(&rhs.kind, rhs.span),
         shape,
     )
+    .or_else(|| {
+        // The rhs fits neither on the same line nor on the next line, where it has to leave
+        // room for the `;`. Wrapped in a block, it gets the whole width of its lines.
+        let rhs_shape = Shape::indented(shape.indent.block_indent(context.config), context.config);
+        let rhs_str = rhs.rewrite(context, rhs_shape)?;
+        Some(format!(
+            "{} {{{}{}{}}}",
+            lhs_str,
+            rhs_shape.indent.to_string_with_newline(context.config),
+            rhs_str,
+            shape.indent.to_string_with_newline(context.config),
+        ))
+    })
+    .or_else(|| {
+        // Rather than leaving the whole statement unformatted, put the rhs on the next line
+        // and let it overflow.
+        let rhs_shape = Shape::indented(shape.indent.block_indent(context.config), context.config)
+            .infinite_width();
+        let rhs_str = rhs.rewrite(context, rhs_shape)?;
+        Some(format!(
+            "{}{}{}",
+            lhs_str,
+            rhs_shape.indent.to_string_with_newline(context.config),
+            rhs_str
+        ))
+    })
 }
 
 /// Controls where to put the rhs.
@@ -2054,6 +2288,20 @@ fn choose_rhs<R: Rewrite>(
                 {
                     Some(format!("{}{}", before_space_str, orig_rhs))
                 }
+                (Some(ref orig_rhs), Some(ref new_rhs))
+                    if context.config.optimize_layout()
+                        && rhs_tactics != RhsTactics::ForceNextLineWithoutIndent =>
+                {
+                    let next_line = format!("{}{}", new_indent_str, new_rhs);
+                    let config = context.config;
+                    if layout_cost(&next_line, 0, config)
+                        < layout_cost(orig_rhs, shape.used_width(), config)
+                    {
+                        Some(next_line)
+                    } else {
+                        Some(format!("{}{}", before_space_str, orig_rhs))
+                    }
+                }
                 (Some(ref orig_rhs), Some(ref new_rhs))
                     if prefer_next_line(orig_rhs, new_rhs, rhs_tactics) =>
                 {
@@ -2110,22 +2358,6 @@ pub(crate) fn prefer_next_line(
         || first_line_ends_with(orig_rhs, '[') && !first_line_ends_with(next_line_rhs, '[')
 }
 
-fn rewrite_expr_addrof(
-    context: &RewriteContext<'_>,
-    borrow_kind: ast::BorrowKind,
-    mutability: ast::Mutability,
-    expr: &ast::Expr,
-    shape: Shape,
-) -> Option<String> {
-    let operator_str = match (mutability, borrow_kind) {
-        (ast::Mutability::Not, ast::BorrowKind::Ref) => "&",
-        (ast::Mutability::Not, ast::BorrowKind::Raw) => "&raw const ",
-        (ast::Mutability::Mut, ast::BorrowKind::Ref) => "&mut ",
-        (ast::Mutability::Mut, ast::BorrowKind::Raw) => "&raw mut ",
-    };
-    rewrite_unary_prefix(context, operator_str, expr, shape)
-}
-
 pub(crate) fn is_method_call(expr: &ast::Expr) -> bool {
     match expr.kind {
         ast::ExprKind::MethodCall(..) => true,
@@ -0,0 +1,127 @@
+//! Formatting of code fragments which are not whole items, e.g. for bots suggesting
+//! replacements for a few lines of code in a review.
+//!
+//! A fragment is wrapped in just enough code to be parsed (a function, and a `match` for
+//! match arms), formatted along with the wrapper, and cut out of the formatted wrapper
+//! again. The fragment is formatted at the indentation it has in the wrapper, which is the
+//! usual indentation of match arms and statements in a function body, and returned
+//! without that indentation.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::config::{Config, EmitMode, FileLines, NewlineStyle, Verbosity};
+use crate::shape::Indent;
+use crate::{Input, Session};
+
+/// The line of the wrapper right before the fragment.
+const FRAGMENT_START: &str = "// rustfmt fragment start";
+/// The line of the wrapper right after the fragment.
+const FRAGMENT_END: &str = "// rustfmt fragment end";
+
+/// Formats `snippet`, one or more arms of a `match`, e.g. `Some(x) => foo(x),`.
+///
+/// Returns `None` if `snippet` is not a list of match arms or cannot be formatted.
+pub fn format_match_arm(snippet: &str, config: &Config) -> Option<String> {
+    format_fragment(
+        snippet,
+        config,
+        "fn rustfmt_fragment() {\nmatch rustfmt_fragment {\n",
+        "}\n}\n",
+        2,
+    )
+}
+
+/// Formats `snippet`, one or more statements or an expression of a function body, e.g.
+/// `let x = foo(a,b);`.
+///
+/// Returns `None` if `snippet` cannot be parsed as the body of a function or cannot be
+/// formatted.
+pub fn format_expr(snippet: &str, config: &Config) -> Option<String> {
+    format_fragment(snippet, config, "fn rustfmt_fragment() {\n", "}\n", 1)
+}
+
+/// Formats `snippet` between `before` and `after`, where the formatted fragment is
+/// indented by `depth` levels.
+fn format_fragment(
+    snippet: &str,
+    config: &Config,
+    before: &str,
+    after: &str,
+    depth: usize,
+) -> Option<String> {
+    let source = format!(
+        "{}{}\n{}\n{}\n{}",
+        before,
+        FRAGMENT_START,
+        snippet.trim(),
+        FRAGMENT_END,
+        after
+    );
+
+    let mut config = config.clone();
+    config.set().file_lines(FileLines::all());
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    config.set().hide_parse_errors(true);
+    config.set().newline_style(NewlineStyle::Unix);
+
+    let formatted = catch_unwind(AssertUnwindSafe(|| {
+        let mut out: Vec<u8> = Vec::with_capacity(source.len() * 2);
+        {
+            let mut session = Session::new(config.clone(), Some(&mut out));
+            session.format(Input::Text(source)).ok()?;
+            if !session.has_no_errors() {
+                return None;
+            }
+        }
+        String::from_utf8(out).ok()
+    }))
+    .ok()??;
+
+    let mut lines = formatted.lines();
+    lines.find(|line| line.trim() == FRAGMENT_START)?;
+    let indent = Indent::from_width(&config, depth * config.tab_spaces()).to_string(&config);
+    let mut result = Vec::new();
+    for line in lines {
+        if line.trim() == FRAGMENT_END {
+            return Some(result.join("\n"));
+        }
+        // Lines without the indentation are the continuation lines of string literals.
+        result.push(line.strip_prefix(&*indent).unwrap_or(line));
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_match_arms() {
+        let config = Config::default();
+        assert_eq!(
+            format_match_arm("Some( x )=>foo(x),None=>{}", &config).as_deref(),
+            Some("Some(x) => foo(x),\nNone => {}")
+        );
+        assert_eq!(
+            format_match_arm("Some(x) => {\nlet y = x;\nbar(y)\n}", &config).as_deref(),
+            Some("Some(x) => {\n    let y = x;\n    bar(y)\n}")
+        );
+    }
+
+    #[test]
+    fn formats_exprs() {
+        let config = Config::default();
+        assert_eq!(
+            format_expr("let x=foo(a,b);\nx+1", &config).as_deref(),
+            Some("let x = foo(a, b);\nx + 1")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fragments() {
+        let config = Config::default();
+        assert_eq!(format_match_arm("fn foo() {}", &config), None);
+        assert_eq!(format_expr("let x = ;", &config), None);
+    }
+}
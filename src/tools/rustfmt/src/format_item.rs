@@ -0,0 +1,181 @@
+//! Formatting of a single item, e.g. for an editor's "format the item under the cursor"
+//! command.
+//!
+//! The whole source is parsed so that the item is formatted with its surrounding
+//! context (indentation of the enclosing module, impl or function). Only the lines of
+//! the item are formatted; the rest of the source is left untouched.
+
+use std::collections::HashMap;
+use std::ops::Range as ByteRange;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use rustc_ast::ast;
+use rustc_ast::visit::{self, AssocCtxt, Visitor};
+use rustc_span::Span;
+
+use crate::config::{Config, EmitMode, FileLines, FileName, Range, Verbosity};
+use crate::parse::parser::Parser;
+use crate::parse::session::ParseSess;
+use crate::spanned::Spanned;
+use crate::{Input, Session};
+
+/// An item reformatted by [`format_item_at`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattedItem {
+    /// The byte range of the original source which `text` replaces. It always covers
+    /// whole lines, including the trailing newline of the last line.
+    pub span: ByteRange<usize>,
+    /// The formatted replacement for `span`.
+    pub text: String,
+}
+
+/// Formats the innermost item (including associated and foreign items) of `source`
+/// which contains the byte offset `byte_offset`.
+///
+/// Returns `None` if `source` cannot be parsed, if no item contains `byte_offset`, or
+/// if the item cannot be formatted.
+pub fn format_item_at(source: &str, byte_offset: usize, config: &Config) -> Option<FormattedItem> {
+    catch_unwind(AssertUnwindSafe(|| {
+        let (lo, hi) = innermost_item_at(source, byte_offset, config)?;
+        format_lines(source, lo, hi, config)
+    }))
+    .ok()?
+}
+
+/// Returns the byte range, including attributes, of the innermost item which contains
+/// `byte_offset`.
+fn innermost_item_at(source: &str, byte_offset: usize, config: &Config) -> Option<(usize, usize)> {
+    rustc_span::create_session_if_not_set_then(config.edition().into(), |_| {
+        let mut config = config.clone();
+        config.set().hide_parse_errors(true);
+        let sess = ParseSess::new(&config).ok()?;
+        let krate = Parser::parse_crate(Input::Text(source.to_owned()), &sess).ok()?;
+
+        let mut finder = ItemFinder {
+            sess: &sess,
+            byte_offset,
+            found: None,
+        };
+        visit::walk_crate(&mut finder, &krate);
+        finder.found
+    })
+}
+
+/// Formats the lines of `source` spanned by the byte range `lo..hi`, leaving the rest of
+/// `source` untouched.
+fn format_lines(source: &str, lo: usize, hi: usize, config: &Config) -> Option<FormattedItem> {
+    let start = source[..lo].rfind('\n').map_or(0, |i| i + 1);
+    let end = source[hi..].find('\n').map_or(source.len(), |i| hi + i + 1);
+    let first_line = source[..start].matches('\n').count() + 1;
+    let last_line = first_line
+        + source[start..end]
+            .trim_end_matches('\n')
+            .matches('\n')
+            .count();
+
+    let mut config = config.clone();
+    let mut ranges = HashMap::new();
+    ranges.insert(FileName::Stdin, vec![Range::new(first_line, last_line)]);
+    config.set().file_lines(FileLines::from_ranges(ranges));
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    config.set().hide_parse_errors(true);
+
+    let mut out: Vec<u8> = Vec::with_capacity(source.len() * 2);
+    {
+        let mut session = Session::new(config, Some(&mut out));
+        session.format(Input::Text(source.to_owned())).ok()?;
+        if !session.has_no_errors() {
+            return None;
+        }
+    }
+    let formatted = String::from_utf8(out).ok()?;
+
+    // Everything outside of the item's lines is left as is, so the replacement text is
+    // whatever lies between the unchanged prefix and suffix.
+    let (prefix, suffix) = (&source[..start], &source[end..]);
+    if formatted.len() < prefix.len() + suffix.len()
+        || !formatted.starts_with(prefix)
+        || !formatted.ends_with(suffix)
+    {
+        return None;
+    }
+    Some(FormattedItem {
+        span: start..end,
+        text: formatted[prefix.len()..formatted.len() - suffix.len()].to_owned(),
+    })
+}
+
+struct ItemFinder<'a> {
+    sess: &'a ParseSess,
+    byte_offset: usize,
+    found: Option<(usize, usize)>,
+}
+
+impl<'a> ItemFinder<'a> {
+    /// Records `span` if it contains the byte offset. Items are visited before their
+    /// children, so the innermost item is the last one recorded.
+    fn check(&mut self, span: Span) {
+        let source_map = self.sess.inner().source_map();
+        let lo = source_map.lookup_byte_offset(span.lo()).pos.to_usize();
+        let hi = source_map.lookup_byte_offset(span.hi()).pos.to_usize();
+        if lo <= self.byte_offset && self.byte_offset < hi {
+            self.found = Some((lo, hi));
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for ItemFinder<'a> {
+    fn visit_item(&mut self, item: &'ast ast::Item) {
+        self.check(item.span());
+        visit::walk_item(self, item);
+    }
+
+    fn visit_assoc_item(&mut self, item: &'ast ast::AssocItem, ctxt: AssocCtxt) {
+        self.check(item.span());
+        visit::walk_assoc_item(self, item, ctxt);
+    }
+
+    fn visit_foreign_item(&mut self, item: &'ast ast::ForeignItem) {
+        self.check(item.span());
+        visit::walk_foreign_item(self, item);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::NewlineStyle;
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.set().newline_style(NewlineStyle::Unix);
+        config
+    }
+
+    #[test]
+    fn formats_innermost_item() {
+        let source = "fn a(){}\nimpl Foo {\n    fn b( &self ){ 1+1 }\n}\nfn c(){}\n";
+        let offset = source.find("1+1").unwrap();
+        let item = format_item_at(source, offset, &config()).unwrap();
+        let start = source.find("    fn b").unwrap();
+        assert_eq!(item.span, start..source.find("}\nfn c").unwrap());
+        assert_eq!(item.text, "    fn b(&self) {\n        1 + 1\n    }\n");
+    }
+
+    #[test]
+    fn includes_attributes() {
+        let source = "#[inline]\nfn a( ){}\n";
+        let item = format_item_at(source, source.find("fn a").unwrap(), &config()).unwrap();
+        assert_eq!(item.span, 0..source.len());
+        assert_eq!(item.text, "#[inline]\nfn a() {}\n");
+    }
+
+    #[test]
+    fn no_item_at_offset() {
+        let source = "fn a() {}\n\nfn b() {}\n";
+        let offset = source.find("\n\n").unwrap() + 1;
+        assert_eq!(format_item_at(source, offset, &config()), None);
+        assert_eq!(format_item_at("fn a(", 0, &config()), None);
+    }
+}
@@ -144,7 +144,14 @@ fn error_kind_to_snippet_annotation_type(error_kind: &ErrorKind) -> AnnotationTy
         | ErrorKind::LostComment
         | ErrorKind::BadAttr
         | ErrorKind::InvalidGlobPattern(_)
-        | ErrorKind::VersionMismatch => AnnotationType::Error,
-        ErrorKind::DeprecatedAttr => AnnotationType::Warning,
+        | ErrorKind::VersionMismatch
+        | ErrorKind::FileTimeout(_)
+        | ErrorKind::Cancelled
+        | ErrorKind::UnusedToggleComment
+        | ErrorKind::UnbalancedUnformattedRegion
+        | ErrorKind::RewriteFailure(_)
+        | ErrorKind::ConflictMarker
+        | ErrorKind::InvalidSpan => AnnotationType::Error,
+        ErrorKind::DeprecatedAttr | ErrorKind::NarrowWidth(_) => AnnotationType::Warning,
     }
 }
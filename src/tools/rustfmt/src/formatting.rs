@@ -1,25 +1,34 @@
 // High level formatting functions.
 
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::fs;
 use std::io::{self, Write};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 use rustc_ast::ast;
-use rustc_span::Span;
+use rustc_data_structures::sync::Lrc;
+use rustc_span::{BytePos, SessionGlobals, Span};
 
 use self::newline_style::apply_newline_style;
-use crate::comment::{CharClasses, FullCodeCharKind};
-use crate::config::{Config, FileName, Verbosity};
-use crate::formatting::generated::is_generated_file;
+use crate::cancellation::{is_cancelled, CancellationToken};
+use crate::comment::{find_lost_comment, has_url, CharClasses, FullCodeCharKind};
+use crate::config::{CommentOverflow, Config, Edition, FileName, IgnoreList, Verbosity};
+use crate::formatting::generated::{contains_generated_attr, is_generated_file};
+use crate::ignore_path::IgnorePathSet;
+use crate::module_config::{inline_module_configs, module_config, ModuleConfigs};
 use crate::modules::Module;
 use crate::parse::parser::{DirectoryOwnership, Parser, ParserError};
 use crate::parse::session::ParseSess;
-use crate::utils::{contains_skip, count_newlines};
+use crate::rewrite::Deadline;
+use crate::utils::{contains_skip, count_newlines, mk_sp_lo_plus_one};
 use crate::visitor::FmtVisitor;
 use crate::{modules, source_file, ErrorKind, FormatReport, Input, Session};
 
-mod generated;
+pub(crate) mod generated;
 mod newline_style;
+pub(crate) mod toggles;
 
 // A map of the files of a crate, with their new content
 pub(crate) type SourceFile = Vec<FileRecord>;
@@ -35,8 +44,8 @@ pub(crate) fn format_input_inner(
             return Err(ErrorKind::VersionMismatch);
         }
 
-        rustc_span::create_session_if_not_set_then(self.config.edition().into(), |_| {
-            if self.config.disable_all_formatting() {
+        let format = |session: &mut Self| {
+            if session.config.disable_all_formatting() {
                 // When the input is from stdin, echo back the input.
                 return match input {
                     Input::Text(ref buf) => echo_back_stdin(buf),
@@ -44,17 +53,37 @@ pub(crate) fn format_input_inner(
                 };
             }
 
-            let config = &self.config.clone();
-            let format_result = format_project(input, config, self, is_macro_def);
+            let config = &session.config.clone();
+            let cancellation = session.cancellation.clone();
+            let format_result = format_project(input, config, session, cancellation, is_macro_def);
 
             format_result.map(|report| {
-                self.errors.add(&report.internal.borrow().1);
+                session.errors.add(&report.internal.borrow().1);
                 report
             })
-        })
+        };
+
+        // A session nested in another one, e.g. to format the code of a doc comment, uses the
+        // session globals of the outer session.
+        if IN_SESSION_GLOBALS.with(Cell::get) {
+            return rustc_span::create_session_if_not_set_then(
+                self.config.edition().into(),
+                |_| format(self),
+            );
+        }
+        let session_globals = self.reused.session_globals(self.config.edition());
+        IN_SESSION_GLOBALS.with(|in_session_globals| in_session_globals.set(true));
+        let result = rustc_span::set_session_globals_then(&session_globals, || format(self));
+        IN_SESSION_GLOBALS.with(|in_session_globals| in_session_globals.set(false));
+        result
     }
 }
 
+thread_local! {
+    /// Whether the session globals of a `Session` are set on this thread.
+    static IN_SESSION_GLOBALS: Cell<bool> = Cell::new(false);
+}
+
 /// Determine if a module should be skipped. True if the module should be skipped, false otherwise.
 fn should_skip_module<T: FormatHandler>(
     config: &Config,
@@ -65,31 +94,110 @@ fn should_skip_module<T: FormatHandler>(
     module: &Module<'_>,
 ) -> bool {
     if contains_skip(module.attrs()) {
+        should_emit_verbose(input_is_stdin, config, || {
+            println!(
+                "Skipping {}: module has a `#[rustfmt::skip]` attribute",
+                path
+            )
+        });
         return true;
     }
 
     if config.skip_children() && path != main_file {
+        should_emit_verbose(input_is_stdin, config, || {
+            println!("Skipping {}: child module and `skip_children` is set", path)
+        });
         return true;
     }
 
-    if !input_is_stdin && context.ignore_file(path) {
+    // FIXME(calebcartwright) - we need to determine how we'll handle the
+    // `format_generated_files` option with stdin based input.
+    if input_is_stdin {
+        return false;
+    }
+    let source_file = context.parse_session.span_to_file_contents(module.span);
+    let src = source_file.src.as_ref().expect("SourceFile without src");
+    should_skip_file(config, context.ignore_file(path), path, src, module.attrs())
+}
+
+/// Returns `true` if the file `path`, with the text `src` and the inner attributes `attrs`, is
+/// skipped because of the `ignore`, `max_file_size` or `format_generated_files` options.
+/// `ignored` is whether the `ignore` option matches `path`.
+fn should_skip_file(
+    config: &Config,
+    ignored: bool,
+    path: &FileName,
+    src: &str,
+    attrs: &[ast::Attribute],
+) -> bool {
+    if ignored {
+        should_emit_verbose(false, config, || {
+            println!("Skipping {}: matched by the `ignore` option", path)
+        });
         return true;
     }
 
-    // FIXME(calebcartwright) - we need to determine how we'll handle the
-    // `format_generated_files` option with stdin based input.
-    if !input_is_stdin && !config.format_generated_files() {
-        let source_file = context.parse_session.span_to_file_contents(module.span);
-        let src = source_file.src.as_ref().expect("SourceFile without src");
+    if exceeds_max_file_size(config, path, src.len()) {
+        return true;
+    }
 
-        if is_generated_file(src) {
-            return true;
-        }
+    if !config.format_generated_files()
+        && (is_generated_file(src, &config.generated_markers())
+            || contains_generated_attr(attrs, &config.generated_markers()))
+    {
+        should_emit_verbose(false, config, || {
+            println!("Skipping {}: file is marked as generated", path)
+        });
+        return true;
     }
 
     false
 }
 
+/// Returns `true` if formatting the file `path` whose text is `text` skips it, as
+/// `should_skip_module` would, for text which is formatted as `Input::Text` on behalf of
+/// the file.
+pub(crate) fn skips_file(config: &Config, path: &Path, text: &str) -> bool {
+    rustc_span::create_session_if_not_set_then(config.edition().into(), |_| {
+        let mut parse_session = match ParseSess::new(config) {
+            Ok(parse_session) => parse_session,
+            Err(_) => return false,
+        };
+        let path = FileName::Real(path.to_owned());
+        let ignored = parse_session.ignore_file(&path);
+        // Unless it is ignored, a file which does not parse is not skipped, so that the
+        // parse error is reported when formatting it.
+        parse_session.set_silent_emitter();
+        let krate = match Parser::parse_crate(Input::Text(text.to_owned()), &parse_session) {
+            Ok(krate) => krate,
+            Err(_) => return ignored,
+        };
+        let attrs: Vec<_> = krate
+            .attrs
+            .iter()
+            .filter(|attr| attr.style == ast::AttrStyle::Inner)
+            .cloned()
+            .collect();
+        should_skip_file(config, ignored, &path, text, &attrs)
+    })
+}
+
+/// Returns `true`, and warns about skipping the file, if a file of `size` bytes is larger
+/// than `max_file_size`.
+fn exceeds_max_file_size(config: &Config, path: &FileName, size: usize) -> bool {
+    let max_file_size = config.max_file_size();
+    if max_file_size == 0 || size <= max_file_size {
+        return false;
+    }
+    if config.verbose() != Verbosity::Quiet {
+        eprintln!(
+            "Warning: skipping {}: the file is {} bytes, more than `max_file_size` ({} bytes)",
+            path, size, max_file_size
+        );
+    }
+    true
+}
+
 fn echo_back_stdin(input: &str) -> Result<FormatReport, ErrorKind> {
     if let Err(e) = io::stdout().write_all(input.as_bytes()) {
         return Err(From::from(e));
@@ -102,6 +210,7 @@ fn format_project<T: FormatHandler>(
     input: Input,
     config: &Config,
     handler: &mut T,
+    cancellation: Option<CancellationToken>,
     is_macro_def: bool,
 ) -> Result<FormatReport, ErrorKind> {
     let mut timer = Timer::start();
@@ -109,16 +218,44 @@ fn format_project<T: FormatHandler>(
     let main_file = input.file_name();
     let input_is_stdin = main_file == FileName::Stdin;
 
-    let parse_session = ParseSess::new(config)?;
+    let parse_session = handler.reused().parse_session(config)?;
     if config.skip_children() && parse_session.ignore_file(&main_file) {
+        should_emit_verbose(input_is_stdin, config, || {
+            println!("Skipping {}: matched by the `ignore` option", main_file)
+        });
         return Ok(FormatReport::new());
     }
 
+    // Don't even parse a main file which is too large to be formatted.
+    if let Input::File(ref path) = input {
+        if let Ok(metadata) = path.metadata() {
+            if exceeds_max_file_size(config, &main_file, metadata.len() as usize) {
+                return Ok(FormatReport::new());
+            }
+        }
+    }
+
     // Parse the crate.
     let mut report = FormatReport::new();
     let directory_ownership = input.to_directory_ownership();
     let krate = match Parser::parse_crate(input, &parse_session) {
         Ok(krate) => krate,
+        // Don't format a main file with merge conflicts, which would lose one side of the
+        // conflict. Input from stdin is echoed back, as for `#[rustfmt::skip]`.
+        Err(ParserError::ConflictMarker(span)) => {
+            report.append(
+                main_file,
+                vec![FormattingError::from_span(
+                    span,
+                    &parse_session,
+                    ErrorKind::ConflictMarker,
+                )],
+            );
+            if input_is_stdin {
+                echo_back_stdin(parse_session.snippet_provider(span).entire_snippet())?;
+            }
+            return Ok(report);
+        }
         // Surface parse error via Session (errors are merged there from report)
         Err(e) => {
             let forbid_verbose = input_is_stdin || e != ParserError::ParsePanicError;
@@ -130,11 +267,25 @@ fn format_project<T: FormatHandler>(
         }
     };
 
-    let mut context = FormatContext::new(&krate, report, parse_session, config, handler);
+    // Warn, once for each crate, about widths which leave too little room to format it.
+    if let Some(warning) = config.width_warning() {
+        report.append(
+            main_file.clone(),
+            vec![FormattingError::from_span(
+                krate.spans.inner_span.shrink_to_lo(),
+                &parse_session,
+                ErrorKind::NarrowWidth(warning),
+            )],
+        );
+    }
+
+    let mut context =
+        FormatContext::new(&krate, report, parse_session, config, handler, cancellation);
     let files = modules::ModResolver::new(
         &context.parse_session,
         directory_ownership.unwrap_or(DirectoryOwnership::UnownedViaBlock),
         !input_is_stdin && !config.skip_children(),
+        context.report.clone(),
     )
     .visit_crate(&krate)?
     .into_iter()
@@ -150,6 +301,9 @@ fn format_project<T: FormatHandler>(
     context.parse_session.set_silent_emitter();
 
     for (path, module) in files {
+        if is_cancelled(&context.cancellation) {
+            return Err(ErrorKind::Cancelled);
+        }
         if input_is_stdin && contains_skip(module.attrs()) {
             return echo_back_stdin(
                 context
@@ -158,8 +312,20 @@ fn format_project<T: FormatHandler>(
                     .entire_snippet(),
             );
         }
+        if !context.handler.mark_formatted(&path) {
+            should_emit_verbose(input_is_stdin, config, || {
+                println!(
+                    "Skipping {}: already formatted as a module of another file",
+                    path
+                )
+            });
+            continue;
+        }
         should_emit_verbose(input_is_stdin, config, || println!("Formatting {}", path));
-        context.format_file(path, &module, is_macro_def)?;
+        context.handler.begin_file(&path);
+        let result = context.format_file(path.clone(), &module, is_macro_def);
+        context.handler.end_file(&path, result.as_ref().map(|_| ()));
+        result?;
     }
     timer = timer.done_formatting();
 
@@ -182,6 +348,7 @@ struct FormatContext<'a, T: FormatHandler> {
     parse_session: ParseSess,
     config: &'a Config,
     handler: &'a mut T,
+    cancellation: Option<CancellationToken>,
 }
 
 impl<'a, T: FormatHandler + 'a> FormatContext<'a, T> {
@@ -197,18 +364,103 @@ fn format_file(
         is_macro_def: bool,
     ) -> Result<(), ErrorKind> {
         let snippet_provider = self.parse_session.snippet_provider(module.span);
+
+        // Apply the `rustfmt::config` attributes of the modules enclosing the file, of the
+        // file and of its inline modules. The invalid attributes of the enclosing modules are
+        // reported with their own files.
+        let mut enclosing_config = None;
+        for attrs in module.enclosing_attrs() {
+            let config = enclosing_config.as_ref().unwrap_or(self.config);
+            if let Some(config) = module_config(config, attrs, &mut vec![]) {
+                enclosing_config = Some(config);
+            }
+        }
+        let enclosing_config = enclosing_config.as_ref().unwrap_or(self.config);
+        let mut bad_attrs = vec![];
+        let file_config = module_config(enclosing_config, module.attrs(), &mut bad_attrs);
+        let config = file_config.as_ref().unwrap_or(enclosing_config);
+        let mut module_configs = ModuleConfigs::new();
+        inline_module_configs(config, &module.items, &mut module_configs, &mut bad_attrs);
+        if !bad_attrs.is_empty() {
+            let errors = bad_attrs
+                .into_iter()
+                .map(|span| {
+                    FormattingError::from_span(span, &self.parse_session, ErrorKind::BadAttr)
+                })
+                .collect();
+            self.report.append(path.clone(), errors);
+        }
+
         let mut visitor = FmtVisitor::from_parse_sess(
             &self.parse_session,
-            self.config,
+            config,
             &snippet_provider,
             self.report.clone(),
         );
+        visitor.module_configs = Some(&module_configs);
+        visitor.buffer = std::mem::take(&mut self.handler.reused().buffer);
+        visitor
+            .buffer
+            .reserve(snippet_provider.entire_snippet().len() * 2);
         visitor.skip_context.update_with_attrs(&self.krate.attrs);
         visitor.is_macro_def = is_macro_def;
+        visitor.deadline = file_deadline(config, self.handler.clock());
+        visitor.cancellation = self.cancellation.clone();
         visitor.last_pos = snippet_provider.start_pos();
         visitor.skip_empty_lines(snippet_provider.end_pos());
         visitor.format_separate_mod(module, snippet_provider.end_pos());
 
+        // The output of a cancelled file is incomplete.
+        if is_cancelled(&self.cancellation) {
+            return Err(ErrorKind::Cancelled);
+        }
+
+        // Keep the original text of a file whose AST refers to code outside of it, as the
+        // output lacks that code.
+        if snippet_provider.has_invalid_spans() {
+            self.report.append(
+                path.clone(),
+                vec![FormattingError::from_span(
+                    module.span,
+                    &self.parse_session,
+                    ErrorKind::InvalidSpan,
+                )],
+            );
+            return self.handler.handle_formatted_file(
+                &self.parse_session,
+                path,
+                snippet_provider.entire_snippet().to_owned(),
+                &mut self.report,
+            );
+        }
+
+        // Keep the original text of a file which took too long to format.
+        if visitor.get_context().deadline_exceeded() {
+            self.report.append(
+                path.clone(),
+                vec![FormattingError::from_span(
+                    module.span,
+                    &self.parse_session,
+                    ErrorKind::FileTimeout(config.file_timeout()),
+                )],
+            );
+            return self.handler.handle_formatted_file(
+                &self.parse_session,
+                path,
+                snippet_provider.entire_snippet().to_owned(),
+                &mut self.report,
+            );
+        }
+
+        let toggle_errors: Vec<_> =
+            toggles::check_toggle_comments(&snippet_provider, &module.items)
+                .into_iter()
+                .map(|(span, kind)| FormattingError::from_span(span, &self.parse_session, kind))
+                .collect();
+        if !toggle_errors.is_empty() {
+            self.report.append(path.clone(), toggle_errors);
+        }
+
         debug_assert_eq!(
             visitor.line_number,
             count_newlines(&visitor.buffer),
@@ -224,16 +476,41 @@ fn format_file(
             &mut visitor.buffer,
             &path,
             &visitor.skipped_range.borrow(),
-            self.config,
+            config,
             &self.report,
         );
 
         apply_newline_style(
-            self.config.newline_style(),
+            config.newline_style(),
             &mut visitor.buffer,
             snippet_provider.entire_snippet(),
         );
 
+        // Keep the original text of a file which would lose a comment, see `--verify`.
+        if config.verify() {
+            if let Some(pos) = find_lost_comment(
+                snippet_provider.entire_snippet(),
+                &visitor.buffer,
+                config.format_code_in_doc_comments(),
+            ) {
+                let lo = snippet_provider.start_pos() + BytePos(pos as u32);
+                self.report.append(
+                    path.clone(),
+                    vec![FormattingError::from_span(
+                        mk_sp_lo_plus_one(lo),
+                        &self.parse_session,
+                        ErrorKind::LostComment,
+                    )],
+                );
+                return self.handler.handle_formatted_file(
+                    &self.parse_session,
+                    path,
+                    snippet_provider.entire_snippet().to_owned(),
+                    &mut self.report,
+                );
+            }
+        }
+
         if visitor.macro_rewrite_failure {
             self.report.add_macro_format_failure();
         }
@@ -243,12 +520,69 @@ fn format_file(
         self.handler.handle_formatted_file(
             &self.parse_session,
             path,
-            visitor.buffer.to_owned(),
+            std::mem::take(&mut visitor.buffer),
             &mut self.report,
         )
     }
 }
 
+/// Returns the point in time at which formatting a file started now, as told by `clock`, must
+/// be given up.
+fn file_deadline(config: &Config, clock: fn() -> Instant) -> Option<Deadline> {
+    if config.file_timeout() == 0 || cfg!(target_arch = "wasm32") {
+        return None;
+    }
+    Some(Deadline {
+        at: clock() + Duration::from_secs(config.file_timeout() as u64),
+        clock,
+    })
+}
+
+/// The allocations which the files of a session reuse, rather than allocating them for
+/// each file. They are dropped when the session exceeds the `memory_budget` option.
+#[derive(Default)]
+pub(crate) struct ReusedAllocations {
+    /// The session globals of rustc, i.e. the interners of symbols and spans and the hygiene
+    /// data, of the files of an edition. They grow with each file, as the symbols of a file
+    /// stay interned.
+    pub(crate) session_globals: Option<(Edition, Lrc<SessionGlobals>)>,
+    /// The ignored files of an `ignore` option. Files may be formatted with different
+    /// configs, see `Session::override_config`, so the set is only reused for the same list.
+    pub(crate) ignore_path_set: Option<(IgnoreList, Lrc<IgnorePathSet>)>,
+    /// A buffer for the formatted text of a file.
+    pub(crate) buffer: String,
+}
+
+impl ReusedAllocations {
+    fn session_globals(&mut self, edition: Edition) -> Lrc<SessionGlobals> {
+        match self.session_globals {
+            Some((reused_edition, ref session_globals)) if reused_edition == edition => {
+                Lrc::clone(session_globals)
+            }
+            _ => {
+                let session_globals = Lrc::new(SessionGlobals::new(edition.into()));
+                self.session_globals = Some((edition, Lrc::clone(&session_globals)));
+                session_globals
+            }
+        }
+    }
+
+    fn parse_session(&mut self, config: &Config) -> Result<ParseSess, ErrorKind> {
+        let ignore_list = config.ignore();
+        if let Some((ref reused_list, ref ignore_path_set)) = self.ignore_path_set {
+            if *reused_list == ignore_list {
+                return Ok(ParseSess::with_ignore_path_set(
+                    config,
+                    Lrc::clone(ignore_path_set),
+                ));
+            }
+        }
+        let parse_session = ParseSess::new(config)?;
+        self.ignore_path_set = Some((ignore_list, parse_session.ignore_path_set()));
+        Ok(parse_session)
+    }
+}
+
 // Handle the results of formatting.
 trait FormatHandler {
     fn handle_formatted_file(
@@ -258,6 +592,19 @@ fn handle_formatted_file(
         result: String,
         report: &mut FormatReport,
     ) -> Result<(), ErrorKind>;
+
+    /// Records that `path` is being formatted. Returns `false` if it has already been
+    /// formatted, e.g. as a module of another crate root.
+    fn mark_formatted(&mut self, path: &FileName) -> bool;
+
+    fn reused(&mut self) -> &mut ReusedAllocations;
+
+    /// The clock against which the `file_timeout` option is checked.
+    fn clock(&self) -> fn() -> Instant;
+
+    /// Called before and after `path` is formatted.
+    fn begin_file(&mut self, path: &FileName);
+    fn end_file(&mut self, path: &FileName, result: Result<(), &ErrorKind>);
 }
 
 impl<'b, T: Write + 'b> FormatHandler for Session<'b, T> {
@@ -286,11 +633,77 @@ fn handle_formatted_file(
                 }
                 _ => {}
             }
+            // The result has been written, so its buffer can be reused for the next file.
+            if result.capacity() > self.reused.buffer.capacity() {
+                let mut buffer = result;
+                buffer.clear();
+                self.reused.buffer = buffer;
+            }
+        } else {
+            // Without an output, the formatted files are kept in the session instead. They
+            // are not kept otherwise, as they would pile up over a run across a workspace.
+            self.source_file.push((path, result));
         }
-
-        self.source_file.push((path, result));
         Ok(())
     }
+
+    fn mark_formatted(&mut self, path: &FileName) -> bool {
+        match path {
+            FileName::Real(path) => {
+                let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+                self.formatted_files.insert(path)
+            }
+            FileName::Stdin => true,
+        }
+    }
+
+    fn reused(&mut self) -> &mut ReusedAllocations {
+        &mut self.reused
+    }
+
+    fn clock(&self) -> fn() -> Instant {
+        self.clock
+    }
+
+    fn begin_file(&mut self, path: &FileName) {
+        if let Some(ref mut progress) = self.progress {
+            progress.begin_file(path);
+        }
+    }
+
+    fn end_file(&mut self, path: &FileName, result: Result<(), &ErrorKind>) {
+        if let Some(ref mut progress) = self.progress {
+            progress.end_file(path, result);
+        }
+        if exceeds_memory_budget(&self.config) {
+            self.reused = ReusedAllocations::default();
+        }
+    }
+}
+
+/// Returns `true` if the resident memory of the process is larger than the `memory_budget`
+/// option allows. The resident memory is only known on Linux, elsewhere there is no budget.
+fn exceeds_memory_budget(config: &Config) -> bool {
+    let budget = config.memory_budget();
+    if budget == 0 {
+        return false;
+    }
+    resident_memory_kib().map_or(false, |resident| resident > budget as u64 * 1024)
+}
+
+/// Returns the resident memory of the process in KiB, if it is known.
+fn resident_memory_kib() -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    line["VmRSS:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
 }
 
 pub(crate) struct FormattingError {
@@ -343,7 +756,14 @@ pub(crate) fn format_len(&self) -> (usize, usize) {
             ErrorKind::TrailingWhitespace
             | ErrorKind::DeprecatedAttr
             | ErrorKind::BadAttr
-            | ErrorKind::LostComment => {
+            | ErrorKind::LostComment
+            | ErrorKind::FileTimeout(_)
+            | ErrorKind::UnusedToggleComment
+            | ErrorKind::UnbalancedUnformattedRegion
+            | ErrorKind::RewriteFailure(_)
+            | ErrorKind::ConflictMarker
+            | ErrorKind::InvalidSpan
+            | ErrorKind::NarrowWidth(_) => {
                 let trailing_ws_start = self
                     .line_buffer
                     .rfind(|c: char| !c.is_whitespace())
@@ -372,6 +792,10 @@ pub(crate) struct ReportedErrors {
     // Code is valid, but it is impossible to format it properly.
     pub(crate) has_formatting_errors: bool,
 
+    // Formatted code violates a limit that rustfmt could not satisfy, e.g., a line
+    // exceeds `max_width` or a comment would be lost.
+    pub(crate) has_internal_errors: bool,
+
     // Code contains macro call that was unable to format.
     pub(crate) has_macro_format_failure: bool,
 
@@ -391,6 +815,7 @@ pub(crate) fn add(&mut self, other: &ReportedErrors) {
         self.has_operational_errors |= other.has_operational_errors;
         self.has_parsing_errors |= other.has_parsing_errors;
         self.has_formatting_errors |= other.has_formatting_errors;
+        self.has_internal_errors |= other.has_internal_errors;
         self.has_macro_format_failure |= other.has_macro_format_failure;
         self.has_check_errors |= other.has_check_errors;
         self.has_diff |= other.has_diff;
@@ -492,6 +917,7 @@ struct FormatLines<'a> {
     errors: Vec<FormattingError>,
     line_buffer: String,
     current_line_contains_string_literal: bool,
+    current_line_contains_code: bool,
     format_line: bool,
     config: &'a Config,
 }
@@ -512,6 +938,7 @@ fn new(
             errors: vec![],
             line_buffer: String::with_capacity(config.max_width() * 2),
             current_line_contains_string_literal: false,
+            current_line_contains_code: false,
             format_line: config.file_lines().contains_line(name, 1),
             config,
         }
@@ -553,6 +980,7 @@ fn new_line(&mut self, kind: FullCodeCharKind) {
             if self.line_len > self.config.max_width()
                 && !self.is_skipped_line()
                 && self.should_report_error(kind, &error_kind)
+                && !self.is_exempt_comment_line()
             {
                 let is_string = self.current_line_contains_string_literal;
                 self.push_err(error_kind, kind.is_comment(), is_string);
@@ -569,6 +997,7 @@ fn new_line(&mut self, kind: FullCodeCharKind) {
         self.last_was_space = false;
         self.line_buffer.clear();
         self.current_line_contains_string_literal = false;
+        self.current_line_contains_code = false;
     }
 
     fn char(&mut self, c: char, kind: FullCodeCharKind) {
@@ -583,6 +1012,9 @@ fn char(&mut self, c: char, kind: FullCodeCharKind) {
         if kind.is_string() {
             self.current_line_contains_string_literal = true;
         }
+        if !kind.is_comment() && !c.is_whitespace() {
+            self.current_line_contains_code = true;
+        }
     }
 
     fn push_err(&mut self, kind: ErrorKind, is_comment: bool, is_string: bool) {
@@ -614,6 +1046,17 @@ fn should_report_error(&self, char_kind: FullCodeCharKind, error_kind: &ErrorKin
         }
     }
 
+    /// Returns `true` if the current line only consists of comments and may exceed
+    /// `max_width`, see `comment_overflow`.
+    fn is_exempt_comment_line(&self) -> bool {
+        !self.current_line_contains_code
+            && match self.config.comment_overflow() {
+                CommentOverflow::Error => false,
+                CommentOverflow::AllowUrls => has_url(&self.line_buffer),
+                CommentOverflow::Allow => true,
+            }
+    }
+
     /// Returns `true` if the line with the given line number was skipped by `#[rustfmt::skip]`.
     fn is_skipped_line(&self) -> bool {
         self.skipped_range
@@ -1,7 +1,32 @@
+use rustc_ast::ast;
+
+use crate::config::GeneratedMarkers;
+
 /// Returns `true` if the given span is a part of generated files.
-pub(super) fn is_generated_file(original_snippet: &str) -> bool {
+pub(super) fn is_generated_file(original_snippet: &str, markers: &GeneratedMarkers) -> bool {
     original_snippet
         .lines()
         .take(5) // looking for marker only in the beginning of the file
-        .any(|line| line.contains("@generated"))
+        .any(|line| markers.comments().any(|marker| line.contains(marker)))
+}
+
+/// Returns `true` if any of the given attributes marks its item as generated.
+pub(crate) fn contains_generated_attr(
+    attrs: &[ast::Attribute],
+    markers: &GeneratedMarkers,
+) -> bool {
+    attrs.iter().any(|attr| match attr.kind {
+        ast::AttrKind::Normal(ref normal) => {
+            let path = normal
+                .item
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.as_str().to_owned())
+                .collect::<Vec<_>>()
+                .join("::");
+            markers.attributes().any(|marker| marker == path)
+        }
+        ast::AttrKind::DocComment(..) => false,
+    })
 }
@@ -0,0 +1,288 @@
+//! Checks that the `// rustfmt: off` and `// rustfmt: on` comments of a file are placed
+//! where they take effect.
+//!
+//! A region of unformatted code starts with a `// rustfmt: off` comment in front of a
+//! statement of a block, an item of a module, an arm of a match or an element of an array,
+//! and ends with the next `// rustfmt: on` comment between the nodes of the same list.
+//! Toggle comments anywhere else (e.g. within other expressions or between struct fields)
+//! are ignored, which is reported so that they are not mistaken for working ones. So is a
+//! region whose delimiters are unbalanced, as splicing it back in would break the code.
+
+use rustc_ast::{ast, ptr, visit};
+use rustc_span::{BytePos, Pos, Span};
+
+use crate::comment::{
+    comment_starts, find_rustfmt_toggle, has_balanced_delimiters, rustfmt_toggle,
+};
+use crate::lists::ListItem;
+use crate::spanned::Spanned;
+use crate::utils::mk_sp;
+use crate::visitor::SnippetProvider;
+use crate::ErrorKind;
+
+struct Toggle {
+    span: Span,
+    is_off: bool,
+    used: bool,
+}
+
+/// A region of code which is left as is because of `// rustfmt: off` and `// rustfmt: on`
+/// comments, see `suppressed_region`.
+pub(crate) struct SuppressedRegion {
+    /// The span from the `// rustfmt: off` comment to the end of the region.
+    pub(crate) span: Span,
+    /// The number of nodes within the region.
+    pub(crate) len: usize,
+    /// Whether splicing the original text of the region back in keeps the delimiters of
+    /// the surrounding code balanced. An unbalanced region is formatted as usual.
+    pub(crate) is_balanced: bool,
+}
+
+/// If a `// rustfmt: off` comment is between `lo` and the first of `nodes`, returns the
+/// region from that comment to the first `// rustfmt: on` comment between two of `nodes`,
+/// or to the end of the last of them.
+pub(crate) fn suppressed_region<T: Spanned>(
+    snippet_provider: &SnippetProvider,
+    lo: BytePos,
+    nodes: &[T],
+) -> Option<SuppressedRegion> {
+    let first = nodes.first()?.span();
+    let gap = snippet_provider.span_to_snippet(mk_sp(lo, first.lo()))?;
+    let lo = lo + BytePos::from_usize(find_rustfmt_toggle(gap, "off")?);
+    let (span, len) = nodes
+        .windows(2)
+        .enumerate()
+        .find_map(|(i, pair)| {
+            let gap_lo = pair[0].span().hi();
+            let gap = snippet_provider.span_to_snippet(mk_sp(gap_lo, pair[1].span().lo()))?;
+            let on = find_rustfmt_toggle(gap, "on")?;
+            let end = gap[on..].find('\n').map_or(gap.len(), |len| on + len);
+            Some((mk_sp(lo, gap_lo + BytePos::from_usize(end)), i + 1))
+        })
+        .unwrap_or_else(|| (mk_sp(lo, nodes[nodes.len() - 1].span().hi()), nodes.len()));
+    let is_balanced = snippet_provider
+        .span_to_snippet(span)
+        .map_or(false, has_balanced_delimiters);
+    Some(SuppressedRegion {
+        span,
+        len,
+        is_balanced,
+    })
+}
+
+/// Replaces the list items of `nodes` within each region between `// rustfmt: off` and
+/// `// rustfmt: on` comments, e.g. the rows of a table, by one item holding the original
+/// text of the region. `span` covers `nodes` and the gaps between them. With `keep_comma`,
+/// the comma after the last node of a region is kept in the item, for lists whose items
+/// carry their separators, like match arms. Returns whether any items were replaced.
+pub(crate) fn keep_suppressed_items<T: Spanned>(
+    snippet_provider: &SnippetProvider,
+    span: Span,
+    nodes: &[T],
+    items: &mut Vec<ListItem>,
+    keep_comma: bool,
+) -> bool {
+    let has_toggles = snippet_provider
+        .span_to_snippet(span)
+        .map_or(false, |snippet| snippet.contains("rustfmt:"));
+    if !has_toggles || items.len() != nodes.len() {
+        return false;
+    }
+
+    let mut regions = vec![];
+    let mut gap_lo = span.lo();
+    let mut i = 0;
+    while i < nodes.len() {
+        match suppressed_region(snippet_provider, gap_lo, &nodes[i..]) {
+            Some(region) if region.is_balanced => {
+                regions.push((i, region.len));
+                i += region.len;
+            }
+            _ => i += 1,
+        }
+        gap_lo = nodes[i - 1].span().hi();
+    }
+
+    let mut replaced = false;
+    for (i, len) in regions.into_iter().rev() {
+        let last = i + len - 1;
+        let hi = nodes[last].span().hi();
+        let mut text = match snippet_provider.span_to_snippet(mk_sp(nodes[i].span().lo(), hi)) {
+            Some(text) => text.to_owned(),
+            None => continue,
+        };
+        if keep_comma {
+            let next_lo = nodes
+                .get(last + 1)
+                .map_or(span.hi(), |node| node.span().lo());
+            let is_comma_next = snippet_provider
+                .span_to_snippet(mk_sp(hi, next_lo))
+                .map_or(false, |gap| gap.trim_start().starts_with(','));
+            if is_comma_next {
+                text.push(',');
+            }
+        }
+        let mut item = items[i].clone();
+        item.item = Some(text);
+        item.post_comment = items[last].post_comment.clone();
+        items.splice(i..=last, std::iter::once(item));
+        replaced = true;
+    }
+    replaced
+}
+
+/// Returns the toggle comments of the file of `snippet_provider` which have no effect, and
+/// the regions between toggle comments which are formatted as usual because their
+/// delimiters are unbalanced, given the top-level `items` of the file.
+pub(crate) fn check_toggle_comments(
+    snippet_provider: &SnippetProvider,
+    items: &[ptr::P<ast::Item>],
+) -> Vec<(Span, ErrorKind)> {
+    let text = snippet_provider.entire_snippet();
+    if !text.contains("rustfmt:") {
+        return vec![];
+    }
+    let start_pos = snippet_provider.start_pos();
+    let toggles = comment_starts(text)
+        .filter_map(|i| {
+            let is_off = rustfmt_toggle(&text[i..])? == "off";
+            let len = text[i..].find('\n').unwrap_or(text.len() - i);
+            let lo = start_pos + BytePos::from_usize(i);
+            Some(Toggle {
+                span: mk_sp(lo, lo + BytePos::from_usize(len)),
+                is_off,
+                used: false,
+            })
+        })
+        .collect();
+
+    let mut checker = ToggleChecker {
+        snippet_provider,
+        toggles,
+        unbalanced_regions: vec![],
+        unformatted_spans: vec![],
+    };
+    checker.check_list(start_pos, items, snippet_provider.end_pos());
+    for item in items {
+        visit::Visitor::visit_item(&mut checker, item);
+    }
+
+    let ToggleChecker {
+        toggles,
+        unbalanced_regions,
+        unformatted_spans,
+        ..
+    } = checker;
+    // The contents of macros and of suppressed regions are not formatted here, if at all.
+    let is_formatted = |span: &Span| !unformatted_spans.iter().any(|s| s.contains(*span));
+    let unused_toggles = toggles
+        .into_iter()
+        .filter(|toggle| !toggle.used)
+        .map(|toggle| (toggle.span, ErrorKind::UnusedToggleComment));
+    let unbalanced_regions = unbalanced_regions
+        .into_iter()
+        .map(|span| (span, ErrorKind::UnbalancedUnformattedRegion));
+    let mut errors: Vec<_> = unused_toggles
+        .chain(unbalanced_regions)
+        .filter(|(span, _)| is_formatted(span))
+        .collect();
+    errors.sort_by_key(|(span, _)| span.lo());
+    errors
+}
+
+struct ToggleChecker<'a> {
+    snippet_provider: &'a SnippetProvider,
+    /// The toggle comments of the file, in order.
+    toggles: Vec<Toggle>,
+    unbalanced_regions: Vec<Span>,
+    /// The spans of macros and of balanced suppressed regions.
+    unformatted_spans: Vec<Span>,
+}
+
+impl<'a> ToggleChecker<'a> {
+    /// Marks the toggle comments of the regions found by `suppressed_region` between
+    /// `nodes`, which are the statements of a block or the items of a module spanning from
+    /// `lo` to `hi`, as used.
+    fn check_list<T: Spanned>(&mut self, lo: BytePos, nodes: &[T], hi: BytePos) {
+        let mut gap_lo = lo;
+        let mut i = 0;
+        while i < nodes.len() {
+            let region = match suppressed_region(self.snippet_provider, gap_lo, &nodes[i..]) {
+                Some(region) => region,
+                None => {
+                    gap_lo = nodes[i].span().hi();
+                    i += 1;
+                    continue;
+                }
+            };
+            self.mark_used(region.span, |_| true);
+            if region.is_balanced {
+                self.unformatted_spans.push(region.span);
+            } else {
+                self.unbalanced_regions.push(region.span);
+            }
+            i += region.len;
+            gap_lo = nodes[i - 1].span().hi();
+            // A region which extends to the end of the list may still be closed explicitly.
+            if i == nodes.len() {
+                let mut closed = false;
+                self.mark_used(mk_sp(gap_lo, hi), |toggle| {
+                    let closes = !toggle.is_off && !closed;
+                    closed |= closes;
+                    closes
+                });
+            }
+        }
+    }
+
+    /// Marks the toggle comments within `span` for which `pred` returns `true` as used.
+    fn mark_used(&mut self, span: Span, mut pred: impl FnMut(&Toggle) -> bool) {
+        let first = self.toggles.partition_point(|t| t.span.lo() < span.lo());
+        let last = self.toggles.partition_point(|t| t.span.lo() < span.hi());
+        for toggle in &mut self.toggles[first..last] {
+            if pred(toggle) {
+                toggle.used = true;
+            }
+        }
+    }
+}
+
+impl<'a, 'ast> visit::Visitor<'ast> for ToggleChecker<'a> {
+    fn visit_item(&mut self, item: &'ast ast::Item) {
+        match item.kind {
+            ast::ItemKind::Mod(_, ast::ModKind::Loaded(ref items, ast::Inline::Yes, spans)) => {
+                self.check_list(spans.inner_span.lo(), items, spans.inner_span.hi());
+            }
+            // The items of a module in another file are checked along with that file.
+            ast::ItemKind::Mod(..) => return,
+            ast::ItemKind::MacroDef(..) => {
+                self.unformatted_spans.push(item.span);
+                return;
+            }
+            _ => (),
+        }
+        visit::walk_item(self, item);
+    }
+
+    fn visit_block(&mut self, block: &'ast ast::Block) {
+        self.check_list(block.span.lo(), &block.stmts, block.span.hi());
+        visit::walk_block(self, block);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast ast::Expr) {
+        match expr.kind {
+            ast::ExprKind::Match(ref scrutinee, ref arms) => {
+                self.check_list(scrutinee.span.hi(), arms, expr.span.hi());
+            }
+            ast::ExprKind::Array(ref exprs) => {
+                self.check_list(expr.span.lo(), exprs, expr.span.hi());
+            }
+            _ => (),
+        }
+        visit::walk_expr(self, expr);
+    }
+
+    fn visit_mac_call(&mut self, mac: &'ast ast::MacCall) {
+        self.unformatted_spans.push(mac.span());
+    }
+}
@@ -11,7 +11,12 @@ pub(crate) fn from_ignore_list(ignore_list: &IgnoreList) -> Result<Self, ignore:
         let mut ignore_builder = gitignore::GitignoreBuilder::new(ignore_list.rustfmt_toml_path());
 
         for ignore_path in ignore_list {
-            ignore_builder.add_line(None, ignore_path.to_str().unwrap())?;
+            let ignore_path = ignore_path.to_str().unwrap();
+            // Gitignore patterns only understand `/` as a separator (`\` is an escape),
+            // but Windows users naturally write `\` in their paths.
+            #[cfg(windows)]
+            let ignore_path = &ignore_path.replace('\\', "/");
+            ignore_builder.add_line(None, ignore_path)?;
         }
 
         Ok(IgnorePathSet {
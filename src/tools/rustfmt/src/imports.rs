@@ -17,7 +17,7 @@
 use crate::config::ImportGranularity;
 use crate::config::{Edition, IndentStyle, Version};
 use crate::lists::{
-    definitive_tactic, itemize_list, write_list, ListFormatting, ListItem, Separator,
+    definitive_tactic, itemize_list, write_list, ItemWrap, ListFormatting, ListItem, Separator,
 };
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
@@ -36,15 +36,19 @@ impl<'a> FmtVisitor<'a> {
     pub(crate) fn format_import(&mut self, item: &ast::Item, tree: &ast::UseTree) {
         let span = item.span();
         let shape = self.shape();
-        let rw = UseTree::from_ast(
-            &self.get_context(),
-            tree,
-            None,
-            Some(item.vis.clone()),
-            Some(item.span.lo()),
-            Some(item.attrs.clone()),
-        )
-        .rewrite_top_level(&self.get_context(), shape);
+        let rw = if self.config.format_imports() {
+            UseTree::from_ast(
+                &self.get_context(),
+                tree,
+                None,
+                Some(item.vis.clone()),
+                Some(item.span.lo()),
+                Some(item.attrs.clone()),
+            )
+            .rewrite_top_level(&self.get_context(), shape)
+        } else {
+            None
+        };
         match rw {
             Some(ref s) if s.is_empty() => {
                 // Format up to last newline
@@ -80,10 +84,11 @@ pub(crate) fn format_import(&mut self, item: &ast::Item, tree: &ast::UseTree) {
 // of these (and to maintain idempotence) we must apply the same normalisations
 // to the data structures for sorting.
 //
-// We sort `self` and `super` before other imports, then identifier imports,
-// then glob imports, then lists of imports. We do not take aliases into account
-// when ordering unless the imports are identical except for the alias (rare in
-// practice).
+// We sort `self`, `super` and `crate` before other imports, then identifier
+// imports (snake_case, then CamelCase, then UPPER_SNAKE_CASE), then glob imports,
+// then lists of imports, e.g. `use foo::{self, baz, Bar, BAZ, *};`. We do not take
+// aliases into account when ordering unless the imports are identical except for
+// the alias (rare in practice).
 
 // FIXME(#2531): we should unify the comparison code here with the formatting
 // code elsewhere since we are essentially string-ifying twice. Furthermore, by
@@ -153,6 +158,24 @@ fn remove_alias(&self) -> UseSegment {
         }
     }
 
+    // Clone a version of self named after the name it is imported as, i.e. `foo as bar`
+    // becomes `bar as foo`. Aliases within nested lists are swapped as well.
+    fn swap_alias(&self) -> UseSegment {
+        let kind = match self.kind {
+            UseSegmentKind::Ident(ref s, Some(ref alias)) => {
+                UseSegmentKind::Ident(alias.clone(), Some(s.clone()))
+            }
+            UseSegmentKind::List(ref list) => {
+                UseSegmentKind::List(list.iter().map(UseTree::alias_sort_key).collect())
+            }
+            _ => return self.clone(),
+        };
+        UseSegment {
+            kind,
+            version: self.version,
+        }
+    }
+
     // Check if self == other with their aliases removed.
     fn equal_except_alias(&self, other: &Self) -> bool {
         match (&self.kind, &other.kind) {
@@ -210,6 +233,13 @@ fn contains_comment(&self) -> bool {
     }
 }
 
+/// Sorts `use_trees`, including their nested lists, by the names they import, so that
+/// `use foo as bar;` is sorted as `bar`.
+pub(crate) fn sort_use_trees_by_alias(use_trees: &mut [UseTree]) {
+    use_trees.iter_mut().for_each(UseTree::sort_lists_by_alias);
+    use_trees.sort_by_cached_key(UseTree::alias_sort_key);
+}
+
 pub(crate) fn normalize_use_trees_with_granularity(
     use_trees: Vec<UseTree>,
     import_granularity: ImportGranularity,
@@ -261,6 +291,18 @@ fn flatten_use_trees(
         .collect()
 }
 
+// The attributes without the doc comments preceding all other attributes, or `None` if
+// there are no attributes left. Doc comments in between other attributes are kept, since
+// the gap they leave would be taken for a comment when rewriting the attributes.
+fn without_leading_doc_comments(attrs: &ast::AttrVec) -> Option<ast::AttrVec> {
+    let attrs: ast::AttrVec = attrs
+        .iter()
+        .skip_while(|attr| attr.doc_str().is_some())
+        .cloned()
+        .collect();
+    if attrs.is_empty() { None } else { Some(attrs) }
+}
+
 impl fmt::Debug for UseTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Display::fmt(self, f)
@@ -372,6 +414,27 @@ pub(crate) fn rewrite_top_level(
         }
     }
 
+    // The tree used in place of self when sorting by the names which are imported
+    // rather than by the original names. It keeps the span of self, so unlike the
+    // trees built while merging imports, it does not need a reconstructed span.
+    fn alias_sort_key(&self) -> UseTree {
+        UseTree::from_path(
+            self.path.iter().map(UseSegment::swap_alias).collect(),
+            self.span,
+        )
+    }
+
+    // Sort the nested lists of self by the names they import.
+    fn sort_lists_by_alias(&mut self) {
+        if let Some(UseSegment {
+            kind: UseSegmentKind::List(ref mut list),
+            ..
+        }) = self.path.last_mut()
+        {
+            sort_use_trees_by_alias(list);
+        }
+    }
+
     // FIXME: Use correct span?
     // The given span is essentially incorrect, since we are reconstructing
     // use-statements. This should not be a problem, though, since we have
@@ -469,7 +532,10 @@ fn from_ast(
                     |tree| tree.span.lo(),
                     |tree| tree.span.hi(),
                     |_| Some("".to_owned()), // We only need comments for now.
-                    context.snippet_provider.span_after(a.span, "{"),
+                    context
+                        .snippet_provider
+                        .span_after(a.span, "{")
+                        .unwrap_or_else(|| a.prefix.span.hi()),
                     a.span.hi(),
                     false,
                 );
@@ -686,16 +752,21 @@ fn flatten(self, import_granularity: ImportGranularity) -> Vec<UseTree> {
                     for flattend in &mut nested_use_tree.clone().flatten(import_granularity) {
                         let mut new_path = prefix.to_vec();
                         new_path.append(&mut flattend.path);
+                        // only retain attributes for `ImportGranularity::Item`, doc comments
+                        // are only kept on the first of the split imports
+                        let attrs = match import_granularity {
+                            ImportGranularity::Item if result.is_empty() => self.attrs.clone(),
+                            ImportGranularity::Item => {
+                                self.attrs.as_ref().and_then(without_leading_doc_comments)
+                            }
+                            _ => None,
+                        };
                         result.push(UseTree {
                             path: new_path,
                             span: self.span,
                             list_item: None,
                             visibility: self.visibility.clone(),
-                            // only retain attributes for `ImportGranularity::Item`
-                            attrs: match import_granularity {
-                                ImportGranularity::Item => self.attrs.clone(),
-                                _ => None,
-                            },
+                            attrs,
                         });
                     }
                 }
@@ -866,6 +937,37 @@ fn hash<H: Hasher>(&self, state: &mut H) {
     }
 }
 
+/// The naming convention of an imported identifier, in the order in which identifiers
+/// are sorted: modules and functions, then types and traits, then constants and statics.
+///
+/// Leading underscores and path separators are ignored, so that e.g. `_Foo` is sorted
+/// along with `Foo` rather than in between lowercase and uppercase names.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum IdentCase {
+    /// `snake_case`, or no letter at all.
+    Snake,
+    /// `CamelCase`.
+    Camel,
+    /// `UPPER_SNAKE_CASE`.
+    UpperSnake,
+}
+
+impl IdentCase {
+    fn of(ident: &str) -> IdentCase {
+        let ident = ident.trim_start_matches(|c| c == '_' || c == ':');
+        if !ident.starts_with(char::is_uppercase) {
+            IdentCase::Snake
+        } else if ident
+            .chars()
+            .all(|c| c.is_uppercase() || c == '_' || c.is_numeric())
+        {
+            IdentCase::UpperSnake
+        } else {
+            IdentCase::Camel
+        }
+    }
+}
+
 impl PartialOrd for UseSegment {
     fn partial_cmp(&self, other: &UseSegment) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -905,6 +1007,17 @@ fn is_upper_snake_case(s: &str) -> bool {
                 } else {
                     (pia.as_str(), pib.as_str())
                 };
+                if self.version == Version::Two {
+                    // Break ties on the untrimmed names so that the order doesn't depend
+                    // on the order of the input.
+                    let ident_ord = IdentCase::of(ia)
+                        .cmp(&IdentCase::of(ib))
+                        .then_with(|| ia.cmp(ib))
+                        .then_with(|| pia.cmp(pib));
+                    if ident_ord != Ordering::Equal {
+                        return ident_ord;
+                    }
+                }
                 // snake_case < CamelCase < UPPER_SNAKE_CASE
                 if ia.starts_with(char::is_uppercase) && ib.starts_with(char::is_lowercase) {
                     return Ordering::Greater;
@@ -1003,6 +1116,15 @@ fn rewrite_nested_use_tree(
         })
     });
 
+    // In a list with nested lists, the paths are each on a line of their own.
+    if has_nested_list {
+        for list_item in &mut list_items {
+            if list_item.inner_as_ref().contains("::") {
+                list_item.wrap = ItemWrap::OwnLine;
+            }
+        }
+    }
+
     let remaining_width = if has_nested_list {
         0
     } else {
@@ -1011,9 +1133,14 @@ fn rewrite_nested_use_tree(
 
     let tactic = definitive_tactic(
         &list_items,
-        context.config.imports_layout(),
+        context
+            .config
+            .imports_layout()
+            .to_list_tactic()
+            .unwrap_or(ListTactic::Mixed),
         Separator::Comma,
         remaining_width,
+        context.config,
     );
 
     let ends_with_newline = context.config.imports_indent() == IndentStyle::Block
@@ -1027,8 +1154,7 @@ fn rewrite_nested_use_tree(
         .tactic(tactic)
         .trailing_separator(trailing_separator)
         .ends_with_newline(ends_with_newline)
-        .preserve_newline(true)
-        .nested(has_nested_list);
+        .preserve_newline(true);
 
     let list_str = write_list(&list_items, &fmt)?;
 
@@ -2,6 +2,7 @@
 
 use std::borrow::Cow;
 use std::cmp::{max, min, Ordering};
+use std::ops::Deref;
 
 use regex::Regex;
 use rustc_ast::visit;
@@ -20,15 +21,19 @@
     is_empty_block, is_simple_block_stmt, rewrite_assign_rhs, rewrite_assign_rhs_with,
     rewrite_assign_rhs_with_comments, RhsAssignKind, RhsTactics,
 };
-use crate::lists::{definitive_tactic, itemize_list, write_list, ListFormatting, Separator};
+use crate::lists::{
+    definitive_tactic, itemize_list, sort_list_items, write_list, ItemWrap, ListFormatting,
+    Separator,
+};
 use crate::macros::{rewrite_macro, MacroPosition};
 use crate::overflow;
+use crate::patterns::rewrite_pattern_after_keyword;
 use crate::rewrite::{Rewrite, RewriteContext};
-use crate::shape::{Indent, Shape};
+use crate::shape::{block_width, Indent, Shape};
 use crate::source_map::{LineRangeUtils, SpanUtils};
 use crate::spanned::Spanned;
 use crate::stmt::Stmt;
-use crate::types::opaque_ty;
+use crate::types::{opaque_ty, rewrite_angle_bracketed};
 use crate::utils::*;
 use crate::vertical::rewrite_with_alignment;
 use crate::visitor::FmtVisitor;
@@ -59,7 +64,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         }
 
         let attrs_str = self.attrs.rewrite(context, shape)?;
-        let mut result = if attrs_str.is_empty() {
+        let result = if attrs_str.is_empty() {
             "let ".to_owned()
         } else {
             combine_strs_with_missing_comments(
@@ -79,8 +84,54 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         let pat_shape = shape.offset_left(4)?;
         // 1 = ;
         let pat_shape = pat_shape.sub_width(1)?;
-        let pat_str = self.pat.rewrite(context, pat_shape)?;
-        result.push_str(&pat_str);
+        let pat_str = rewrite_pattern_after_keyword(context, &self.pat, pat_shape)?;
+
+        let one_line_pat = self.rewrite_after_pat(context, shape, result.clone(), &pat_str);
+        let rhs_on_next_line =
+            |(rewrite, lhs_len): &(String, usize)| rewrite[*lhs_len..].starts_with('\n');
+        // A struct pattern which pushes the type or the initializer off its line is broken
+        // vertically instead, e.g. `let Foo {\n    a,\n    b,\n} = expr;`.
+        let try_vertical_pat = match one_line_pat {
+            Some(ref one_line_pat) => {
+                rhs_on_next_line(one_line_pat) && context.config.version() == Version::Two
+            }
+            None => true,
+        };
+        if try_vertical_pat && !pat_str.contains('\n') {
+            if let ast::PatKind::Struct(..) = self.pat.kind {
+                // Leaving no room for the fields after `path {` breaks them vertically.
+                // 2 = ` {`
+                let vertical_pat = pat_str.find(" {").and_then(|path_width| {
+                    let vertical_shape = Shape {
+                        width: min(path_width + 2, pat_shape.width),
+                        ..pat_shape
+                    };
+                    let pat_str = self.pat.rewrite(context, vertical_shape)?;
+                    self.rewrite_after_pat(context, shape, result.clone(), &pat_str)
+                });
+                if let Some(vertical_pat) = vertical_pat {
+                    if one_line_pat.is_none() || !rhs_on_next_line(&vertical_pat) {
+                        return Some(vertical_pat.0);
+                    }
+                }
+            }
+        }
+        one_line_pat.map(|(rewrite, _)| rewrite)
+    }
+}
+
+impl ast::Local {
+    /// Rewrites the type and the initializer of the `let` statement after `pat_str`, the
+    /// rewritten pattern appended to `result`. Also returns the length of the statement
+    /// up to the initializer.
+    fn rewrite_after_pat(
+        &self,
+        context: &RewriteContext<'_>,
+        shape: Shape,
+        mut result: String,
+        pat_str: &str,
+    ) -> Option<(String, usize)> {
+        result.push_str(pat_str);
 
         // String that is placed within the assignment pattern and expression.
         let infix = {
@@ -111,6 +162,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         };
 
         result.push_str(&infix);
+        let lhs_len = result.len();
 
         if let Some((init, _els)) = self.kind.init_else_opt() {
             // 1 = trailing semicolon;
@@ -127,7 +179,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         }
 
         result.push(';');
-        Some(result)
+        Some((result, lhs_len))
     }
 }
 
@@ -393,10 +445,84 @@ pub(crate) fn single_line_fn(
     }
 
     pub(crate) fn visit_static(&mut self, static_parts: &StaticParts<'_>) {
-        let rewrite = rewrite_static(&self.get_context(), static_parts, self.block_indent);
+        let lhs_width = self
+            .aligned_const_widths
+            .get(&static_parts.span.lo())
+            .copied();
+        let rewrite = rewrite_static(
+            &self.get_context(),
+            static_parts,
+            self.block_indent,
+            lhs_width,
+        );
         self.push_rewrite(static_parts.span, rewrite);
     }
 
+    /// Records the widths to which the declarations of const items are padded, so that
+    /// the `=` of consecutive const items line up with `const_align_threshold`. `items`
+    /// are the const items (or associated consts) along with the other items between
+    /// them. A blank line or another item ends a group of aligned consts.
+    pub(crate) fn align_consts<I, T>(&mut self, items: &[I])
+    where
+        I: Deref<Target = T>,
+        T: ConstItem,
+    {
+        let align_threshold = self.config.const_align_threshold();
+        if align_threshold == 0 {
+            return;
+        }
+
+        let mut groups: Vec<Vec<(BytePos, usize)>> = vec![];
+        let mut prev_hi = None;
+        {
+            let context = self.get_context();
+            for item in items.iter().map(|item| &**item) {
+                let span = item.span();
+                let static_parts = match item.const_parts() {
+                    Some(parts) if parts.prefix == "const" && parts.expr_opt.is_some() => parts,
+                    _ => {
+                        prev_hi = None;
+                        continue;
+                    }
+                };
+                // The first and last lines between two items are the ends of their lines.
+                let follows_const = prev_hi.map_or(false, |hi| {
+                    let mut lines = context.snippet(mk_sp(hi, span.lo())).split('\n');
+                    lines.next();
+                    lines.next_back();
+                    !lines.any(is_empty_line)
+                });
+                if !follows_const {
+                    groups.push(vec![]);
+                }
+                prev_hi = Some(span.hi());
+
+                let lhs = rewrite_static_lhs(&context, &static_parts, self.block_indent);
+                if let Some(lhs) = lhs.filter(|lhs| !lhs.contains('\n')) {
+                    if let Some(group) = groups.last_mut() {
+                        group.push((static_parts.span.lo(), lhs.len()));
+                    }
+                }
+            }
+        }
+
+        // Consts with longer declarations than the threshold are left as they are.
+        for group in groups {
+            let pad_to = group
+                .iter()
+                .map(|&(_, width)| width)
+                .filter(|&width| width <= align_threshold)
+                .max();
+            if let Some(pad_to) = pad_to {
+                for (lo, width) in group {
+                    if width <= align_threshold {
+                        self.aligned_const_widths.insert(lo, pad_to);
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn visit_struct(&mut self, struct_parts: &StructParts<'_>) {
         let is_tuple = match struct_parts.def {
             ast::VariantData::Tuple(..) => true,
@@ -414,6 +540,7 @@ pub(crate) fn visit_enum(
         enum_def: &ast::EnumDef,
         generics: &ast::Generics,
         span: Span,
+        sort_variants: bool,
     ) {
         let enum_header =
             format_header(&self.get_context(), "enum ", ident, vis, self.block_indent);
@@ -441,7 +568,7 @@ pub(crate) fn visit_enum(
 
         self.last_pos = body_start;
 
-        match self.format_variant_list(enum_def, body_start, span.hi()) {
+        match self.format_variant_list(enum_def, body_start, span.hi(), sort_variants) {
             Some(ref s) if enum_def.variants.is_empty() => self.push_str(s),
             rw => {
                 self.push_rewrite(mk_sp(body_start, span.hi()), rw);
@@ -456,6 +583,7 @@ fn format_variant_list(
         enum_def: &ast::EnumDef,
         body_lo: BytePos,
         body_hi: BytePos,
+        sort_variants: bool,
     ) -> Option<String> {
         if enum_def.variants.is_empty() {
             let mut buffer = String::with_capacity(128);
@@ -521,6 +649,10 @@ fn format_variant_list(
         if has_multiline_variant && has_single_line_variant {
             items = itemize_list_with(0);
         }
+        if sort_variants {
+            let keys = enum_def.variants.iter().map(|v| v.ident.name.to_string());
+            items = sort_list_items(items, keys.collect());
+        }
 
         let shape = self.shape().sub_width(2)?;
         let fmt = ListFormatting::new(shape, self.config)
@@ -586,6 +718,7 @@ fn format_variant(
     }
 
     fn visit_impl_items(&mut self, items: &[ptr::P<ast::AssocItem>]) {
+        self.align_consts(items);
         if self.get_context().config.reorder_impl_items() {
             type TyOpt = Option<ptr::P<ast::Ty>>;
             use crate::ast::AssocItemKind::*;
@@ -690,7 +823,7 @@ pub(crate) fn format_impl(
     }
 
     let missing_span = mk_sp(self_ty.span.hi(), item.span.hi());
-    let where_span_end = context.snippet_provider.opt_span_before(missing_span, "{");
+    let where_span_end = context.snippet_provider.span_before(missing_span, "{");
     let where_clause_str = rewrite_where_clause(
         context,
         &generics.where_clause.predicates,
@@ -840,10 +973,12 @@ fn format_impl_ref_and_type(
     result.push_str(&generics_str);
     result.push_str(format_constness_right(constness));
 
-    let polarity_str = match polarity {
-        ast::ImplPolarity::Negative(_) => "!",
-        ast::ImplPolarity::Positive => "",
+    let polarity_next_lo = match *trait_ref {
+        Some(ref trait_ref) => trait_ref.path.span.lo(),
+        None => self_ty.span.lo(),
     };
+    let polarity = rewrite_polarity(context, polarity, polarity_next_lo, shape)?;
+    let polarity_str = polarity.as_str();
 
     let polarity_overhead;
     let trait_ref_overhead;
@@ -910,6 +1045,29 @@ fn format_impl_ref_and_type(
     Some(result)
 }
 
+/// Returns the `!` of a negative impl, followed by the comments between it and `next_lo`, the
+/// start of the trait or of the self type.
+fn rewrite_polarity(
+    context: &RewriteContext<'_>,
+    polarity: ast::ImplPolarity,
+    next_lo: BytePos,
+    shape: Shape,
+) -> Option<String> {
+    let bang_span = match polarity {
+        ast::ImplPolarity::Negative(span) => span,
+        ast::ImplPolarity::Positive => return Some(String::new()),
+    };
+    let comment = rewrite_missing_comment(mk_sp(bang_span.hi(), next_lo), shape, context)?;
+    if comment.is_empty() {
+        Some(String::from("!"))
+    } else if !comment.starts_with("//") && !comment.contains('\n') {
+        Some(format!("! {} ", comment))
+    } else {
+        // The trait would have to be on the next line.
+        None
+    }
+}
+
 fn rewrite_trait_ref(
     context: &RewriteContext<'_>,
     trait_ref: &ast::TraitRef,
@@ -944,6 +1102,7 @@ pub(crate) struct StructParts<'a> {
     def: &'a ast::VariantData,
     generics: Option<&'a ast::Generics>,
     span: Span,
+    sort_fields: bool,
 }
 
 impl<'a> StructParts<'a> {
@@ -959,6 +1118,7 @@ fn from_variant(variant: &'a ast::Variant) -> Self {
             def: &variant.data,
             generics: None,
             span: variant.span,
+            sort_fields: contains_sort(&variant.attrs),
         }
     }
 
@@ -975,6 +1135,7 @@ pub(crate) fn from_item(item: &'a ast::Item) -> Self {
             def,
             generics: Some(generics),
             span: item.span,
+            sort_fields: contains_sort(&item.attrs),
         }
     }
 }
@@ -1018,7 +1179,7 @@ pub(crate) fn format_trait(
         );
         result.push_str(&header);
 
-        let body_lo = context.snippet_provider.span_after(item.span, "{");
+        let body_lo = context.snippet_provider.span_after(item.span, "{")?;
 
         let shape = Shape::indented(offset, context.config).offset_left(result.len())?;
         let generics_str =
@@ -1029,7 +1190,7 @@ pub(crate) fn format_trait(
         if !bounds.is_empty() {
             let ident_hi = context
                 .snippet_provider
-                .span_after(item.span, item.ident.as_str());
+                .span_after(item.span, item.ident.as_str())?;
             let bound_hi = bounds.last().unwrap().span().hi();
             let snippet = context.snippet(mk_sp(ident_hi, bound_hi));
             if contains_comment(snippet) {
@@ -1038,7 +1199,7 @@ pub(crate) fn format_trait(
 
             result = rewrite_assign_rhs_with(
                 context,
-                result + ":",
+                result + type_annotation_separator(context.config).trim_end(),
                 bounds,
                 shape,
                 &RhsAssignKind::Bounds,
@@ -1143,6 +1304,7 @@ pub(crate) fn format_trait(
             visitor.block_indent = offset.block_only().block_indent(context.config);
             visitor.last_pos = block_span.lo() + BytePos(open_pos as u32);
 
+            visitor.align_consts(items);
             for item in items {
                 visitor.visit_trait_item(item);
             }
@@ -1241,7 +1403,7 @@ fn format_unit_struct(
 ) -> Option<String> {
     let header_str = format_header(context, p.prefix, p.ident, p.vis, offset);
     let generics_str = if let Some(generics) = p.generics {
-        let hi = context.snippet_provider.span_before(p.span, ";");
+        let hi = context.snippet_provider.span_before(p.span, ";")?;
         format_generics(
             context,
             generics,
@@ -1275,9 +1437,9 @@ pub(crate) fn format_struct_struct(
     let body_lo = if let Some(generics) = struct_parts.generics {
         // Adjust the span to start at the end of the generic arguments before searching for the '{'
         let span = span.with_lo(generics.span.hi());
-        context.snippet_provider.span_after(span, "{")
+        context.snippet_provider.span_after(span, "{")?
     } else {
-        context.snippet_provider.span_after(span, "{")
+        context.snippet_provider.span_after(span, "{")?
     };
 
     let generics_str = match struct_parts.generics {
@@ -1338,6 +1500,7 @@ pub(crate) fn format_struct_struct(
         Shape::indented(offset.block_indent(context.config), context.config).sub_width(1)?,
         mk_sp(body_lo, span.hi()),
         one_line_budget,
+        struct_parts.sort_fields,
     )?;
 
     if !items_str.contains('\n')
@@ -1421,20 +1584,20 @@ fn format_tuple_struct(
         let lo = get_bytepos_after_visibility(struct_parts.vis, span);
         context
             .snippet_provider
-            .span_after(mk_sp(lo, span.hi()), "(")
+            .span_after(mk_sp(lo, span.hi()), "(")?
     } else {
         fields[0].span.lo()
     };
     let body_hi = if fields.is_empty() {
         context
             .snippet_provider
-            .span_after(mk_sp(body_lo, span.hi()), ")")
+            .span_after(mk_sp(body_lo, span.hi()), ")")?
     } else {
         // This is a dirty hack to work around a missing `)` from the span of the last field.
         let last_arg_span = fields[fields.len() - 1].span;
         context
             .snippet_provider
-            .opt_span_after(mk_sp(last_arg_span.hi(), span.hi()), ")")
+            .span_after(mk_sp(last_arg_span.hi(), span.hi()), ")")
             .unwrap_or_else(|| last_arg_span.hi())
     };
 
@@ -1466,7 +1629,7 @@ fn format_tuple_struct(
     if fields.is_empty() {
         let body_hi = context
             .snippet_provider
-            .span_before(mk_sp(body_lo, span.hi()), ")");
+            .span_before(mk_sp(body_lo, span.hi()), ")")?;
         let inner_span = mk_sp(body_lo, body_hi);
         format_empty_struct_or_tuple(context, inner_span, offset, &mut result, "(", ")");
     } else {
@@ -1484,6 +1647,7 @@ fn format_tuple_struct(
             mk_sp(lo, span.hi()),
             context.config.fn_call_width(),
             None,
+            ListLayout::Auto,
         )?;
     }
 
@@ -1621,9 +1785,12 @@ fn rewrite_ty<R: Rewrite>(
 
     if let Some(bounds) = generic_bounds_opt {
         if !bounds.is_empty() {
-            // 2 = `: `
-            let shape = Shape::indented(indent, context.config).offset_left(result.len() + 2)?;
-            let type_bounds = bounds.rewrite(context, shape).map(|s| format!(": {}", s))?;
+            let colon = type_annotation_separator(context.config);
+            let shape =
+                Shape::indented(indent, context.config).offset_left(result.len() + colon.len())?;
+            let type_bounds = bounds
+                .rewrite(context, shape)
+                .map(|s| format!("{}{}", colon, s))?;
             result.push_str(&type_bounds);
         }
     }
@@ -1659,7 +1826,7 @@ fn rewrite_ty<R: Rewrite>(
 
         let comment_span = context
             .snippet_provider
-            .opt_span_before(span, "=")
+            .span_before(span, "=")
             .map(|op_lo| mk_sp(where_clauses.0.1.hi(), op_lo));
 
         let lhs = match comment_span {
@@ -1787,6 +1954,29 @@ pub(crate) fn rewrite_struct_field(
     combine_strs_with_missing_comments(context, &attrs_str, field_str, missing_span, shape, false)
 }
 
+/// An item which may be a const item, see `FmtVisitor::align_consts`.
+pub(crate) trait ConstItem: Spanned {
+    fn const_parts(&self) -> Option<StaticParts<'_>>;
+}
+
+impl ConstItem for ast::Item {
+    fn const_parts(&self) -> Option<StaticParts<'_>> {
+        match self.kind {
+            ast::ItemKind::Const(..) => Some(StaticParts::from_item(self)),
+            _ => None,
+        }
+    }
+}
+
+impl ConstItem for ast::AssocItem {
+    fn const_parts(&self) -> Option<StaticParts<'_>> {
+        match self.kind {
+            ast::AssocItemKind::Const(..) => Some(StaticParts::from_impl_item(self)),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) struct StaticParts<'a> {
     prefix: &'a str,
     vis: &'a ast::Visibility,
@@ -1858,7 +2048,9 @@ pub(crate) fn from_impl_item(ii: &'a ast::AssocItem) -> Self {
     }
 }
 
-fn rewrite_static(
+/// Rewrites the declaration of a static or const item up to the ` =`, e.g.,
+/// `pub const FOO: u8`.
+fn rewrite_static_lhs(
     context: &RewriteContext<'_>,
     static_parts: &StaticParts<'_>,
     offset: Indent,
@@ -1873,49 +2065,95 @@ fn rewrite_static(
         rewrite_ident(context, static_parts.ident),
         colon,
     );
+    let shape = Shape::indented(offset.block_only(), context.config);
+    let nested_indent = offset.block_indent(context.config);
+    let nested_shape = Shape::indented(nested_indent, context.config);
+    let rewrite_nested_ty = || static_parts.ty.rewrite(context, nested_shape);
     // 2 = " =".len()
-    let ty_shape =
-        Shape::indented(offset.block_only(), context.config).offset_left(prefix.len() + 2)?;
-    let ty_str = match static_parts.ty.rewrite(context, ty_shape) {
-        Some(ty_str) => ty_str,
-        None => {
+    let ty_str = match shape.offset_left(prefix.len() + 2) {
+        Some(ty_shape) => static_parts.ty.rewrite(context, ty_shape),
+        // With `Version::Two`, the type goes on the next line if only the declaration up to
+        // the colon fits on the first line.
+        None if context.config.version() == Version::Two
+            && prefix.trim_end().len() <= shape.width =>
+        {
+            None
+        }
+        None => return None,
+    };
+    let nested_ty_str = match ty_str {
+        // With `Version::Two`, a type which would be broken over several lines after the
+        // colon goes on the next line instead, if it fits on a single line there.
+        Some(ref ty_str) if context.config.version() == Version::Two && ty_str.contains('\n') => {
+            rewrite_nested_ty().filter(|nested| !nested.contains('\n'))
+        }
+        Some(_) => None,
+        None => Some(rewrite_nested_ty()?),
+    };
+    match nested_ty_str {
+        Some(nested_ty_str) => {
             if prefix.ends_with(' ') {
                 prefix.pop();
             }
-            let nested_indent = offset.block_indent(context.config);
-            let nested_shape = Shape::indented(nested_indent, context.config);
-            let ty_str = static_parts.ty.rewrite(context, nested_shape)?;
-            format!(
-                "{}{}",
+            Some(format!(
+                "{}{}{}",
+                prefix,
                 nested_indent.to_string_with_newline(context.config),
-                ty_str
-            )
+                nested_ty_str
+            ))
         }
-    };
+        None => Some(format!("{}{}", prefix, ty_str?)),
+    }
+}
+
+/// Rewrites a static or const item. With `lhs_width`, a single-line declaration is padded
+/// to that width, to align its `=` with those of the surrounding consts.
+fn rewrite_static(
+    context: &RewriteContext<'_>,
+    static_parts: &StaticParts<'_>,
+    offset: Indent,
+    lhs_width: Option<usize>,
+) -> Option<String> {
+    let lhs = rewrite_static_lhs(context, static_parts, offset)?;
 
     if let Some(expr) = static_parts.expr_opt {
-        let comments_lo = context.snippet_provider.span_after(static_parts.span, "=");
         let expr_lo = expr.span.lo();
+        // The type may contain an `=` itself, e.g. `dyn Iterator<Item = u8>`.
+        let comments_lo = context
+            .snippet_provider
+            .span_after(mk_sp(static_parts.ty.span.hi(), expr_lo), "=")?;
         let comments_span = mk_sp(comments_lo, expr_lo);
 
-        let lhs = format!("{}{} =", prefix, ty_str);
-
+        let padding = match lhs_width {
+            Some(width) if !lhs.contains('\n') => width.saturating_sub(lhs.len()),
+            _ => 0,
+        };
         // 1 = ;
         let remaining_width = context.budget(offset.block_indent + 1);
-        rewrite_assign_rhs_with_comments(
-            context,
-            &lhs,
-            &**expr,
-            Shape::legacy(remaining_width, offset.block_only()),
-            &RhsAssignKind::Expr(&expr.kind, expr.span),
-            RhsTactics::Default,
-            comments_span,
-            true,
-        )
-        .and_then(|res| recover_comment_removed(res, static_parts.span, context))
-        .map(|s| if s.ends_with(';') { s } else { s + ";" })
+        let rewrite_with_padding = |padding: usize| {
+            rewrite_assign_rhs_with_comments(
+                context,
+                &format!("{}{} =", lhs, " ".repeat(padding)),
+                &**expr,
+                Shape::legacy(remaining_width, offset.block_only()),
+                &RhsAssignKind::Expr(&expr.kind, expr.span),
+                RhsTactics::Default,
+                comments_span,
+                true,
+            )
+        };
+        let padded = rewrite_with_padding(padding);
+        // A const whose initializer no longer fits on its line once padded is not aligned.
+        let rewrite = match padded {
+            Some(ref padded) if padding > 0 && padded.contains('\n') => rewrite_with_padding(0),
+            None if padding > 0 => rewrite_with_padding(0),
+            rewrite => rewrite,
+        };
+        rewrite
+            .and_then(|res| recover_comment_removed(res, static_parts.span, context))
+            .map(|s| if s.ends_with(';') { s } else { s + ";" })
     } else {
-        Some(format!("{}{};", prefix, ty_str))
+        Some(format!("{};", lhs))
     }
 }
 
@@ -1979,18 +2217,16 @@ fn get_missing_param_comments(
 ) -> (String, String) {
     let missing_comment_span = mk_sp(pat_span.hi(), ty_span.lo());
 
-    let span_before_colon = {
-        let missing_comment_span_hi = context
-            .snippet_provider
-            .span_before(missing_comment_span, ":");
-        mk_sp(pat_span.hi(), missing_comment_span_hi)
-    };
-    let span_after_colon = {
-        let missing_comment_span_lo = context
-            .snippet_provider
-            .span_after(missing_comment_span, ":");
-        mk_sp(missing_comment_span_lo, ty_span.lo())
+    let colon_lo = match context
+        .snippet_provider
+        .span_before(missing_comment_span, ":")
+    {
+        Some(colon_lo) => colon_lo,
+        None => return (String::new(), String::new()),
     };
+    let span_before_colon = mk_sp(pat_span.hi(), colon_lo);
+    // 1 = ":"
+    let span_after_colon = mk_sp(colon_lo + BytePos(1), ty_span.lo());
 
     let comment_before_colon = rewrite_missing_comment(span_before_colon, shape, context)
         .filter(|comment| !comment.is_empty())
@@ -2023,6 +2259,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
             rewrite_explicit_self(
                 context,
                 explicit_self,
+                &self.pat,
                 &param_attrs_result,
                 span,
                 shape,
@@ -2091,6 +2328,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
 fn rewrite_explicit_self(
     context: &RewriteContext<'_>,
     explicit_self: &ast::ExplicitSelf,
+    pat: &ast::Pat,
     param_attrs: &str,
     span: Span,
     shape: Shape,
@@ -2124,7 +2362,9 @@ fn rewrite_explicit_self(
                 )?),
             }
         }
-        ast::SelfKind::Explicit(ref ty, mutability) => {
+        // `self` and `mut self` are binding patterns, rewritten like those of other params.
+        ast::SelfKind::Explicit(ref ty, _) => {
+            let pat_str = pat.rewrite(context, Shape::legacy(shape.width, shape.indent))?;
             let type_str = ty.rewrite(
                 context,
                 Shape::legacy(context.config.max_width(), Indent::empty()),
@@ -2133,16 +2373,16 @@ fn rewrite_explicit_self(
             Some(combine_strs_with_missing_comments(
                 context,
                 param_attrs,
-                &format!("{}self: {}", format_mutability(mutability), type_str),
+                &format!("{}: {}", pat_str, type_str),
                 span,
                 shape,
                 !has_multiple_attr_lines,
             )?)
         }
-        ast::SelfKind::Value(mutability) => Some(combine_strs_with_missing_comments(
+        ast::SelfKind::Value(_) => Some(combine_strs_with_missing_comments(
             context,
             param_attrs,
-            &format!("{}self", format_mutability(mutability)),
+            &pat.rewrite(context, Shape::legacy(shape.width, shape.indent))?,
             span,
             shape,
             !has_multiple_attr_lines,
@@ -2194,6 +2434,12 @@ fn rewrite_fn_base(
     span: Span,
     fn_brace_style: FnBraceStyle,
 ) -> Option<(String, bool, bool)> {
+    if !context.config.format_signatures() {
+        let result = context.snippet(span).trim_end().to_owned();
+        let ends_with_comment = last_line_contains_single_line_comment(&result);
+        return Some((result, ends_with_comment, ends_with_comment));
+    }
+
     let mut force_new_line_for_brace = false;
 
     let where_clause = &fn_sig.generics.where_clause;
@@ -2269,15 +2515,15 @@ fn rewrite_fn_base(
     let params_end = if fd.inputs.is_empty() {
         context
             .snippet_provider
-            .span_after(mk_sp(fn_sig.generics.span.hi(), span.hi()), ")")
+            .span_after(mk_sp(fn_sig.generics.span.hi(), span.hi()), ")")?
     } else {
         let last_span = mk_sp(fd.inputs[fd.inputs.len() - 1].span().hi(), span.hi());
-        context.snippet_provider.span_after(last_span, ")")
+        context.snippet_provider.span_after(last_span, ")")?
     };
     let params_span = mk_sp(
         context
             .snippet_provider
-            .span_after(mk_sp(fn_sig.generics.span.hi(), span.hi()), "("),
+            .span_after(mk_sp(fn_sig.generics.span.hi(), span.hi()), "(")?,
         params_end,
     );
     let param_str = rewrite_params(
@@ -2389,6 +2635,16 @@ fn rewrite_fn_base(
                         ret_shape.indent = ret_shape.indent + 4;
                         ret_shape
                     };
+                } else if fd
+                    .output
+                    .rewrite(context, ret_shape)
+                    .map_or(false, |ret_str| ret_str.contains('\n'))
+                {
+                    // A return type which is broken over lines, e.g. the bounds of an
+                    // `impl Trait`, is block indented, with the brace on the next line.
+                    force_new_line_for_brace = true;
+                    ret_shape =
+                        Shape::indented(indent.block_indent(context.config), context.config);
                 }
 
                 result.push_str(&ret_shape.indent.to_string_with_newline(context.config));
@@ -2580,7 +2836,7 @@ fn rewrite_params(
             .trim();
         return Some(comment.to_owned());
     }
-    let param_items: Vec<_> = itemize_list(
+    let mut param_items: Vec<_> = itemize_list(
         context.snippet_provider,
         params.iter(),
         ")",
@@ -2597,6 +2853,17 @@ fn rewrite_params(
         false,
     )
     .collect();
+    if context.config.version() == Version::Two {
+        // A compressed list keeps the `...` of a variadic function next to the parameter
+        // before it, and puts the other parameters after `self` if they fit on one line.
+        for (item, param) in param_items.iter_mut().zip(params) {
+            if param.is_self() {
+                item.wrap = ItemWrap::BreakAfter;
+            } else if let ast::TyKind::CVarArgs = param.ty.kind {
+                item.wrap = ItemWrap::NoBreak;
+            }
+        }
+    }
 
     let tactic = definitive_tactic(
         &param_items,
@@ -2606,6 +2873,7 @@ fn rewrite_params(
             .to_list_tactic(param_items.len()),
         Separator::Comma,
         one_line_budget,
+        context.config,
     );
     let budget = match tactic {
         DefinitiveListTactic::Horizontal => one_line_budget,
@@ -2721,7 +2989,7 @@ fn rewrite_generics(
     }
 
     let params = generics.params.iter();
-    overflow::rewrite_with_angle_brackets(context, ident, params, shape, generics.span)
+    rewrite_angle_bracketed(context, ident, params, shape, generics.span)
 }
 
 fn generics_shape_from_config(config: &Config, shape: Shape, offset: usize) -> Option<Shape> {
@@ -2925,7 +3193,7 @@ fn rewrite_where_clause(
     // FIXME: if indent_style != Visual, then the budgets below might
     // be out by a char or two.
 
-    let budget = context.config.max_width() - offset.width();
+    let budget = block_width(offset, context.config);
     let span_start = predicates[0].span().lo();
     // If we don't have the start of the next span, then use the end of the
     // predicates, but that means we miss comments.
@@ -2946,7 +3214,13 @@ fn rewrite_where_clause(
     );
     let item_vec = items.collect::<Vec<_>>();
     // FIXME: we don't need to collect here
-    let tactic = definitive_tactic(&item_vec, ListTactic::Vertical, Separator::Comma, budget);
+    let tactic = definitive_tactic(
+        &item_vec,
+        ListTactic::Vertical,
+        Separator::Comma,
+        budget,
+        context.config,
+    );
 
     let mut comma_tactic = context.config.trailing_comma();
     // Kind of a hack because we don't usually have trailing commas in where-clauses.
@@ -3030,7 +3304,7 @@ fn format_header(
     let after_vis = vis.span.hi();
     if let Some(before_item_name) = context
         .snippet_provider
-        .opt_span_before(mk_sp(vis.span.lo(), ident.span.hi()), item_name.trim())
+        .span_before(mk_sp(vis.span.lo(), ident.span.hi()), item_name.trim())
     {
         let missing_span = mk_sp(after_vis, before_item_name);
         if let Some(result_with_comment) = combine_strs_with_missing_comments(
@@ -3112,7 +3386,7 @@ fn format_generics(
                     if brace_pos == BracePos::None {
                         span.hi()
                     } else {
-                        context.snippet_provider.span_before(span, "{")
+                        context.snippet_provider.span_before(span, "{")?
                     },
                 ),
                 shape,
@@ -0,0 +1,127 @@
+//! A cost model to choose between alternative layouts of the same code, used with
+//! `optimize_layout`.
+//!
+//! Formatting normally takes the first layout which fits within the width limits. Such a
+//! layout is not always a nice one, e.g. when an argument list is broken within its last
+//! argument and leaves a staircase of closing delimiters behind. The cost of a layout adds
+//! up penalties for what makes it harder to read, so that alternatives can be compared.
+
+use crate::config::Config;
+use crate::utils::unicode_str_width;
+
+/// The cost of each line break.
+const LINE_BREAK_COST: usize = 10;
+/// The cost of each column beyond `max_width`.
+const OVERFLOW_COST: usize = 100;
+/// The cost of each line with nothing but closing delimiters and punctuation, e.g. `))?;`.
+const DANGLING_LINE_COST: usize = 5;
+/// The cost of each parenthesis or bracket beyond the first one which is left open at the
+/// end of a line, e.g. `foo(a, bar(` breaks two lists at once.
+const NESTED_BREAK_COST: usize = 25;
+/// The cost of each level of indentation of a line deeper than the shallowest line after
+/// the first one.
+const NESTING_COST: usize = 1;
+
+/// Alternative layouts are only tried for code whose source is at most this long, as each
+/// alternative takes as long to format as the code itself.
+pub(crate) const OPTIMIZE_LAYOUT_MAX_LEN: usize = 500;
+
+/// Returns the cost of `layout`, whose first line starts at column `offset`. Lower costs
+/// are better.
+pub(crate) fn layout_cost(layout: &str, offset: usize, config: &Config) -> usize {
+    let tab_spaces = config.tab_spaces().max(1);
+    let indent_width = |line: &str| -> usize {
+        line.chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { tab_spaces } else { 1 })
+            .sum()
+    };
+    let base_indent = layout
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(indent_width)
+        .min()
+        .unwrap_or(0);
+
+    let mut cost = 0;
+    for (i, line) in layout.lines().enumerate() {
+        let content = line.trim_start();
+        let indent = indent_width(line);
+        let start = if i == 0 { offset + indent } else { indent };
+        let width = start + unicode_str_width(content);
+        cost += OVERFLOW_COST * width.saturating_sub(config.max_width());
+        cost += NESTED_BREAK_COST * open_delims(content).saturating_sub(1);
+        if i > 0 {
+            cost += LINE_BREAK_COST;
+            cost += NESTING_COST * (indent.saturating_sub(base_indent) / tab_spaces);
+            if !content.is_empty() && content.chars().all(|c| ")]}>,;?.".contains(c)) {
+                cost += DANGLING_LINE_COST;
+            }
+        }
+    }
+    cost
+}
+
+/// Returns the number of parentheses and brackets opened on `line` which are still open at
+/// its end. Braces are not counted, as a block or closure body on its own lines reads fine.
+fn open_delims(line: &str) -> usize {
+    let mut open = 0usize;
+    let mut in_str = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_str => {
+                chars.next();
+            }
+            '"' => in_str = !in_str,
+            _ if in_str => (),
+            '(' | '[' => open += 1,
+            ')' | ']' => open = open.saturating_sub(1),
+            '/' if chars.as_str().starts_with('/') => break,
+            _ => (),
+        }
+    }
+    open
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cost(layout: &str) -> usize {
+        layout_cost(layout, 4, &Config::default())
+    }
+
+    #[test]
+    fn single_line_is_free() {
+        assert_eq!(cost("foo(a, b)"), 0);
+    }
+
+    #[test]
+    fn overflow_is_expensive() {
+        let long = format!("foo({})", "a".repeat(100));
+        let broken = format!("foo(\n    {},\n)", "a".repeat(80));
+        assert!(cost(&long) > cost(&broken));
+    }
+
+    #[test]
+    fn nested_break_is_worse_than_vertical_list() {
+        let nested = "foo(aaaa, bbbb, cccc(\n    dddd,\n))";
+        let vertical = "foo(\n    aaaa,\n    bbbb,\n    cccc(dddd),\n)";
+        assert!(cost(vertical) < cost(nested));
+    }
+
+    #[test]
+    fn closure_body_is_not_a_nested_break() {
+        let overflowed = "foo(a, |x| {\n    x + 1\n})";
+        let vertical = "foo(\n    a,\n    |x| {\n        x + 1\n    },\n)";
+        assert!(cost(overflowed) < cost(vertical));
+    }
+
+    #[test]
+    fn delimiters_in_strings_and_comments_are_ignored() {
+        assert_eq!(open_delims(r#"foo("((", "\"(") // (("#), 0);
+        assert_eq!(open_delims("foo(bar("), 2);
+    }
+}
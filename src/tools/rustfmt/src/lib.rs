@@ -25,13 +25,14 @@
 extern crate rustc_span;
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::io::{self, Write};
 use std::mem;
 use std::panic;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Instant;
 
 use rustc_ast::ast;
 use rustc_span::symbol;
@@ -39,46 +40,61 @@
 
 use crate::comment::LineClasses;
 use crate::emitter::Emitter;
-use crate::formatting::{FormatErrorMap, FormattingError, ReportedErrors, SourceFile};
+use crate::formatting::{
+    FormatErrorMap, FormattingError, ReportedErrors, ReusedAllocations, SourceFile,
+};
 use crate::modules::ModuleResolutionError;
 use crate::parse::parser::DirectoryOwnership;
 use crate::shape::Indent;
 use crate::utils::indent_next_line;
 
+pub use crate::cancellation::CancellationToken;
 pub use crate::config::{
-    load_config, CliOptions, Color, Config, Edition, EmitMode, FileLines, FileName, NewlineStyle,
-    Range, Verbosity,
+    canonicalize_path, explain_config, load_config, migrate_config, CliOptions, Color, Config,
+    ConfigSource, Edition, EmitMode, FileLines, FileName, NewlineStyle, Range, Verbosity,
 };
 
+pub use crate::cursor::map_cursor_offset;
+pub use crate::format_fragment::{format_expr, format_match_arm};
+pub use crate::format_item::{format_item_at, FormattedItem};
 pub use crate::format_report_formatter::{FormatReportFormatter, FormatReportFormatterBuilder};
+pub use crate::progress::FormatProgress;
 
-pub use crate::rustfmt_diff::{ModifiedChunk, ModifiedLines};
+pub use crate::rustfmt_diff::{diff_hunks, DiffHunk, HunkLine, ModifiedChunk, ModifiedLines};
+pub use crate::source_items::{innermost_item, source_items, SourceItem};
 
 #[macro_use]
 mod utils;
 
 mod attr;
+mod cancellation;
 mod chains;
 mod closures;
 mod comment;
 pub(crate) mod config;
 mod coverage;
+mod cursor;
 mod emitter;
 mod expr;
+mod format_fragment;
+mod format_item;
 mod format_report_formatter;
 pub(crate) mod formatting;
 mod ignore_path;
 mod imports;
 mod items;
+mod layout_cost;
 mod lists;
 mod macros;
 mod matches;
 mod missed_spans;
+mod module_config;
 pub(crate) mod modules;
 mod overflow;
 mod pairs;
 mod parse;
 mod patterns;
+mod progress;
 mod release_channel;
 mod reorder;
 mod rewrite;
@@ -86,6 +102,7 @@
 mod shape;
 mod skip;
 pub(crate) mod source_file;
+mod source_items;
 pub(crate) mod source_map;
 mod spanned;
 mod stmt;
@@ -134,6 +151,42 @@ pub enum ErrorKind {
     /// Invalid glob pattern in `ignore` configuration option.
     #[error("Invalid glob pattern found in ignore list: {0}")]
     InvalidGlobPattern(ignore::Error),
+    /// Formatting the file took longer than the `file_timeout` option allows.
+    #[error(
+        "formatting took longer than {0} seconds (see `file_timeout` option), \
+         the file was left unformatted"
+    )]
+    FileTimeout(usize),
+    /// Formatting was cancelled through a `CancellationToken`.
+    #[error("formatting was cancelled")]
+    Cancelled,
+    /// A `// rustfmt: off` or `// rustfmt: on` comment which is not placed between
+    /// statements or items, or an `on` comment without a preceding `off` comment.
+    #[error(
+        "ignored `rustfmt: off`/`rustfmt: on` comment; it must be placed between \
+         statements or items, and `on` must follow `off` in the same block or module"
+    )]
+    UnusedToggleComment,
+    /// The delimiters of a region between `// rustfmt: off` and `// rustfmt: on` comments,
+    /// or of an item with a skip attribute, are unbalanced, so the original text of the
+    /// region cannot be spliced back in.
+    #[error(
+        "the delimiters of this unformatted region are unbalanced; it must span whole \
+         statements or items"
+    )]
+    UnbalancedUnformattedRegion,
+    /// Code was left unformatted because the given construct could not be formatted.
+    #[error("{0} could not be formatted, the code around it was left as is")]
+    RewriteFailure(&'static str),
+    /// The file contains git conflict markers.
+    #[error("the file contains git conflict markers, it was left unformatted")]
+    ConflictMarker,
+    /// The AST of the file refers to code outside of the file, e.g. with a dummy span.
+    #[error("found a span outside of the file, the file was left unformatted")]
+    InvalidSpan,
+    /// `max_width` and `tab_spaces` leave too little room to format most code.
+    #[error("{0}")]
+    NarrowWidth(String),
 }
 
 impl ErrorKind {
@@ -213,23 +266,30 @@ fn track_errors(&self, new_errors: &[FormattingError]) {
         if !new_errors.is_empty() {
             errs.has_formatting_errors = true;
         }
-        if errs.has_operational_errors && errs.has_check_errors && errs.has_unformatted_code_errors
-        {
+        if errs.has_internal_errors && errs.has_check_errors && errs.has_unformatted_code_errors {
             return;
         }
         for err in new_errors {
             match err.kind {
-                ErrorKind::LineOverflow(..) => {
-                    errs.has_operational_errors = true;
+                ErrorKind::LineOverflow(..)
+                | ErrorKind::InvalidSpan
+                | ErrorKind::ConflictMarker
+                | ErrorKind::FileTimeout(_) => {
+                    errs.has_internal_errors = true;
                 }
                 ErrorKind::TrailingWhitespace => {
-                    errs.has_operational_errors = true;
+                    errs.has_internal_errors = true;
                     errs.has_unformatted_code_errors = true;
                 }
-                ErrorKind::LostComment => {
+                ErrorKind::LostComment | ErrorKind::RewriteFailure(_) => {
+                    errs.has_internal_errors = true;
                     errs.has_unformatted_code_errors = true;
                 }
-                ErrorKind::DeprecatedAttr | ErrorKind::BadAttr | ErrorKind::VersionMismatch => {
+                ErrorKind::DeprecatedAttr
+                | ErrorKind::BadAttr
+                | ErrorKind::VersionMismatch
+                | ErrorKind::UnusedToggleComment
+                | ErrorKind::UnbalancedUnformattedRegion => {
                     errs.has_check_errors = true;
                 }
                 _ => {}
@@ -331,6 +391,14 @@ fn format_snippet(snippet: &str, config: &Config, is_macro_def: bool) -> Option<
     .ok()?
 }
 
+/// Returns `true` if formatting `source` with `config` would leave it unchanged, e.g. for
+/// tools which only need to know whether a file needs formatting rather than how.
+///
+/// Returns `false` if `source` cannot be parsed or formatted.
+pub fn is_formatted(source: &str, config: &Config) -> bool {
+    format_snippet(source, config, false).map_or(false, |formatted| formatted.snippet == source)
+}
+
 /// Format the given code block. Mainly targeted for code block in comment.
 /// The code block may be incomplete (i.e., parser may be unable to parse it).
 /// To avoid panic in parser, we wrap the code block with a dummy function.
@@ -429,6 +497,14 @@ pub struct Session<'b, T: Write> {
     pub(crate) errors: ReportedErrors,
     source_file: SourceFile,
     emitter: Box<dyn Emitter + 'b>,
+    // The files formatted so far, so that a module which several crate roots of the session
+    // share is formatted (and reported) only once.
+    formatted_files: HashSet<PathBuf>,
+    reused: ReusedAllocations,
+    progress: Option<Box<dyn FormatProgress + 'b>>,
+    cancellation: Option<CancellationToken>,
+    // The clock against which the `file_timeout` option is checked, which tests replace.
+    pub(crate) clock: fn() -> Instant,
 }
 
 impl<'b, T: Write + 'b> Session<'b, T> {
@@ -445,15 +521,38 @@ pub fn new(config: Config, mut out: Option<&'b mut T>) -> Session<'b, T> {
             emitter,
             errors: ReportedErrors::default(),
             source_file: SourceFile::new(),
+            formatted_files: HashSet::new(),
+            reused: ReusedAllocations::default(),
+            progress: None,
+            cancellation: None,
+            clock: Instant::now,
         }
     }
 
+    /// Notifies `progress` before and after each file formatted by this session.
+    pub fn set_progress<P: FormatProgress + 'b>(&mut self, progress: P) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Stops formatting once `token` is cancelled, see [`CancellationToken`].
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
     /// The main entry point for Rustfmt. Formats the given input according to the
     /// given config. `out` is only necessary if required by the configuration.
     pub fn format(&mut self, input: Input) -> Result<FormatReport, ErrorKind> {
         self.format_input_inner(input, false)
     }
 
+    /// Returns `true` if formatting the file `path` would skip it because of the `ignore`,
+    /// `max_file_size` or `format_generated_files` options, where `text` is the text of the
+    /// file. Those options do not apply to `Input::Text`, so this is for text formatted on
+    /// behalf of a file, e.g. the staged version of a file in git.
+    pub fn skips_file(&self, path: &Path, text: &str) -> bool {
+        formatting::skips_file(&self.config, path, text)
+    }
+
     pub fn override_config<F, U>(&mut self, mut config: Config, f: F) -> U
     where
         F: FnOnce(&mut Session<'b, T>) -> U,
@@ -480,6 +579,10 @@ pub fn has_formatting_errors(&self) -> bool {
         self.errors.has_formatting_errors
     }
 
+    pub fn has_internal_errors(&self) -> bool {
+        self.errors.has_internal_errors
+    }
+
     pub fn has_check_errors(&self) -> bool {
         self.errors.has_check_errors
     }
@@ -496,6 +599,7 @@ pub fn has_no_errors(&self) -> bool {
         !(self.has_operational_errors()
             || self.has_parsing_errors()
             || self.has_formatting_errors()
+            || self.has_internal_errors()
             || self.has_check_errors()
             || self.has_diff()
             || self.has_unformatted_code_errors()
@@ -505,8 +609,12 @@ pub fn has_no_errors(&self) -> bool {
 
 pub(crate) fn create_emitter<'a>(config: &Config) -> Box<dyn Emitter + 'a> {
     match config.emit_mode() {
+        EmitMode::Files if !config.out_dir().is_empty() => Box::new(emitter::OutDirEmitter::new(
+            PathBuf::from(config.out_dir()),
+            config.print_misformatted_file_names(),
+        )),
         EmitMode::Files if config.make_backup() => {
-            Box::new(emitter::FilesWithBackupEmitter::default())
+            Box::new(emitter::FilesWithBackupEmitter::new(config.backup_count()))
         }
         EmitMode::Files => Box::new(emitter::FilesEmitter::new(
             config.print_misformatted_file_names(),
@@ -518,6 +626,10 @@ pub(crate) fn create_emitter<'a>(config: &Config) -> Box<dyn Emitter + 'a> {
         EmitMode::ModifiedLines => Box::new(emitter::ModifiedLinesEmitter::default()),
         EmitMode::Checkstyle => Box::new(emitter::CheckstyleEmitter::default()),
         EmitMode::Diff => Box::new(emitter::DiffEmitter::new(config.clone())),
+        EmitMode::Srcmap => Box::new(emitter::SrcmapEmitter::default()),
+        EmitMode::ItemDiff => Box::new(emitter::ItemDiffEmitter::new(config.edition())),
+        EmitMode::RustcJson => Box::new(emitter::RustcJsonEmitter::default()),
+        EmitMode::Interactive => Box::new(emitter::InteractiveEmitter::new()),
     }
 }
 
@@ -575,6 +687,16 @@ fn test_no_panic_on_format_snippet_and_format_code_block() {
         assert!(format_code_block(snippet, &Config::default(), false).is_none());
     }
 
+    #[test]
+    fn test_is_formatted() {
+        let mut config = Config::default();
+        config.set().newline_style(NewlineStyle::Unix);
+        assert!(is_formatted("fn main() {}\n", &config));
+        assert!(!is_formatted("fn main(){}\n", &config));
+        assert!(!is_formatted("fn main() {}", &config));
+        assert!(!is_formatted("fn main() {", &config));
+    }
+
     fn test_format_inner<F>(formatter: F, input: &str, expected: &str) -> bool
     where
         F: Fn(&str, &Config, bool) -> Option<FormattedSnippet>,
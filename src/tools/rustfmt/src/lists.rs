@@ -5,7 +5,7 @@
 
 use rustc_span::BytePos;
 
-use crate::comment::{find_comment_end, rewrite_comment, FindUncommented};
+use crate::comment::{comment_starts, find_comment_end, rewrite_comment, FindUncommented};
 use crate::config::lists::*;
 use crate::config::{Config, IndentStyle};
 use crate::rewrite::RewriteContext;
@@ -27,10 +27,10 @@ pub(crate) struct ListFormatting<'a> {
     ends_with_newline: bool,
     // Remove newlines between list elements for expressions.
     preserve_newline: bool,
-    // Nested import lists get some special handling for the "Mixed" list type
-    nested: bool,
     // Whether comments should be visually aligned.
     align_comments: bool,
+    // Whether the lines of the list after the first are block indented from it.
+    hanging_indent: bool,
     config: &'a Config,
 }
 
@@ -44,8 +44,8 @@ pub(crate) fn new(shape: Shape, config: &'a Config) -> Self {
             shape,
             ends_with_newline: true,
             preserve_newline: false,
-            nested: false,
             align_comments: true,
+            hanging_indent: false,
             config,
         }
     }
@@ -80,13 +80,13 @@ pub(crate) fn preserve_newline(mut self, preserve_newline: bool) -> Self {
         self
     }
 
-    pub(crate) fn nested(mut self, nested: bool) -> Self {
-        self.nested = nested;
+    pub(crate) fn align_comments(mut self, align_comments: bool) -> Self {
+        self.align_comments = align_comments;
         self
     }
 
-    pub(crate) fn align_comments(mut self, align_comments: bool) -> Self {
-        self.align_comments = align_comments;
+    pub(crate) fn hanging_indent(mut self, hanging_indent: bool) -> Self {
+        self.hanging_indent = hanging_indent;
         self
     }
 
@@ -118,6 +118,22 @@ pub(crate) enum ListItemCommentStyle {
     None,
 }
 
+/// Where a `Mixed` list may break the line around an item. Whatever the policy, an item starts
+/// a new line if the previous item has a post-comment.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub(crate) enum ItemWrap {
+    /// The item starts a new line if it does not fit on the current one.
+    Normal,
+    /// The item is on a line of its own, e.g. a nested import list `a::{b, c}`.
+    OwnLine,
+    /// The item is a no-break atom: it stays on the line of the previous item, which starts
+    /// a new line along with it if the two do not fit, e.g. the `...` of a variadic function.
+    NoBreak,
+    /// The line preferably breaks after the item: the next item starts a new line unless
+    /// the rest of the list fits on the current one, e.g. after the `self` parameter.
+    BreakAfter,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct ListItem {
     // None for comments mean that they are not present.
@@ -129,6 +145,7 @@ pub(crate) struct ListItem {
     pub(crate) post_comment: Option<String>,
     // Whether there is extra whitespace before this item.
     pub(crate) new_lines: bool,
+    pub(crate) wrap: ItemWrap,
 }
 
 impl ListItem {
@@ -139,6 +156,7 @@ pub(crate) fn empty() -> ListItem {
             item: None,
             post_comment: None,
             new_lines: false,
+            wrap: ItemWrap::Normal,
         }
     }
 
@@ -188,6 +206,7 @@ pub(crate) fn from_str<S: Into<String>>(s: S) -> ListItem {
             item: Some(s.into()),
             post_comment: None,
             new_lines: false,
+            wrap: ItemWrap::Normal,
         }
     }
 
@@ -224,6 +243,7 @@ pub(crate) fn definitive_tactic<I, T>(
     tactic: ListTactic,
     sep: Separator,
     width: usize,
+    config: &Config,
 ) -> DefinitiveListTactic
 where
     I: IntoIterator<Item = T> + Clone,
@@ -250,7 +270,9 @@ pub(crate) fn definitive_tactic<I, T>(
         DefinitiveListTactic::Horizontal
     } else {
         match tactic {
-            ListTactic::Mixed => DefinitiveListTactic::Mixed,
+            // Packing items onto lines reflows the whole list when an item is added or
+            // removed, one item per line only changes the line of that item.
+            ListTactic::Mixed if !config.prefer_stable_diffs() => DefinitiveListTactic::Mixed,
             _ => DefinitiveListTactic::Vertical,
         }
     }
@@ -262,33 +284,80 @@ pub(crate) fn write_list<I, T>(items: I, formatting: &ListFormatting<'_>) -> Opt
     I: IntoIterator<Item = T> + Clone,
     T: AsRef<ListItem>,
 {
-    let tactic = formatting.tactic;
-    let sep_len = formatting.separator.len();
-
-    // Now that we know how we will layout, we can decide for sure if there
-    // will be a trailing separator.
-    let mut trailing_separator = formatting.needs_trailing_separator();
-    let mut result = String::with_capacity(128);
+    let mut writer = ListWriter::new(formatting);
     let cloned_items = items.clone();
     let mut iter = items.into_iter().enumerate().peekable();
-    let mut item_max_width: Option<usize> = None;
-    let sep_place =
-        SeparatorPlace::from_tactic(formatting.separator_place, tactic, formatting.separator);
-    let mut prev_item_had_post_comment = false;
-    let mut prev_item_is_nested_import = false;
-
-    let mut line_len = 0;
-    let indent_str = &formatting.shape.indent.to_string(formatting.config);
     while let Some((i, item)) = iter.next() {
-        let item = item.as_ref();
+        let last = iter.peek().is_none();
+        writer.write_item(&cloned_items, i, item.as_ref(), last)?;
+    }
+    Some(writer.result)
+}
+
+/// The state of `write_list` between the items of a list: the layout of an item depends on
+/// the line it starts on and on the previous items.
+struct ListWriter<'b, 'a> {
+    formatting: &'b ListFormatting<'a>,
+    sep_place: SeparatorPlace,
+    indent_str: String,
+    // The indent of the lines after the first, see `hanging_indent`.
+    wrap_indent_str: String,
+    result: String,
+    // Now that we know how we will layout, we can decide for sure if there
+    // will be a trailing separator.
+    trailing_separator: bool,
+    // The width of the current line, for the `Mixed` tactic.
+    line_len: usize,
+    // Whether a `Mixed` list has not wrapped yet.
+    on_first_line: bool,
+    // The width to align the post-comments of the current group of items at.
+    item_max_width: Option<usize>,
+    prev_item_had_post_comment: bool,
+    prev_item_wrap: ItemWrap,
+}
+
+impl<'b, 'a> ListWriter<'b, 'a> {
+    fn new(formatting: &'b ListFormatting<'a>) -> Self {
+        ListWriter {
+            formatting,
+            sep_place: SeparatorPlace::from_tactic(
+                formatting.separator_place,
+                formatting.tactic,
+                formatting.separator,
+            ),
+            indent_str: formatting.shape.indent.to_string(formatting.config),
+            wrap_indent_str: if formatting.hanging_indent {
+                formatting.shape.indent.block_indent(formatting.config)
+            } else {
+                formatting.shape.indent
+            }
+            .to_string(formatting.config),
+            result: String::with_capacity(128),
+            trailing_separator: formatting.needs_trailing_separator(),
+            line_len: 0,
+            on_first_line: true,
+            item_max_width: None,
+            prev_item_had_post_comment: false,
+            prev_item_wrap: ItemWrap::Normal,
+        }
+    }
+
+    /// Writes `item`, the `i`-th item of `items`, along with the separators and line breaks
+    /// around it.
+    fn write_item<I, T>(&mut self, items: &I, i: usize, item: &ListItem, last: bool) -> Option<()>
+    where
+        I: IntoIterator<Item = T> + Clone,
+        T: AsRef<ListItem>,
+    {
+        let formatting = self.formatting;
         let inner_item = item.item.as_ref()?;
         let first = i == 0;
-        let last = iter.peek().is_none();
-        let mut separate = match sep_place {
-            SeparatorPlace::Front => !first,
-            SeparatorPlace::Back => !last || trailing_separator,
+        let mut separate = self.is_separated(first, last);
+        let item_sep_len = if separate {
+            formatting.separator.len()
+        } else {
+            0
         };
-        let item_sep_len = if separate { sep_len } else { 0 };
 
         // Item string may be multi-line. Its length (used for block comment alignment)
         // should be only the length of the last line.
@@ -298,225 +367,370 @@ pub(crate) fn write_list<I, T>(items: I, formatting: &ListFormatting<'_>) -> Opt
             inner_item.as_ref()
         };
         let mut item_last_line_width = item_last_line.len() + item_sep_len;
-        if item_last_line.starts_with(&**indent_str) {
-            item_last_line_width -= indent_str.len();
+        if item_last_line.starts_with(&*self.indent_str) {
+            item_last_line_width -= self.indent_str.len();
         }
 
         if !item.is_substantial() {
-            continue;
+            return Some(());
         }
 
-        match tactic {
+        self.write_item_start(items, i, item, last, item_sep_len, &mut separate);
+        if let Some(ref comment) = item.pre_comment {
+            self.write_pre_comment(comment, item, item_sep_len)?;
+        }
+
+        if separate && self.sep_place.is_front() && !first {
+            self.result.push_str(formatting.separator.trim());
+            self.result.push(' ');
+        }
+        self.result.push_str(inner_item);
+
+        // Post-comments
+        if formatting.tactic == DefinitiveListTactic::Horizontal && item.post_comment.is_some() {
+            let comment = item.post_comment.as_ref().unwrap();
+            let formatted_comment = rewrite_comment(
+                comment,
+                true,
+                Shape::legacy(formatting.shape.width, Indent::empty()),
+                formatting.config,
+            )?;
+
+            self.result.push(' ');
+            self.result.push_str(&formatted_comment);
+        }
+
+        if separate && self.sep_place.is_back() {
+            self.result.push_str(formatting.separator);
+        }
+
+        match item.post_comment {
+            Some(ref comment) if formatting.tactic != DefinitiveListTactic::Horizontal => {
+                self.write_post_comment(
+                    items,
+                    i,
+                    comment,
+                    inner_item,
+                    item_last_line_width,
+                    separate,
+                    last,
+                )?;
+            }
+            _ => self.item_max_width = None,
+        }
+
+        if formatting.preserve_newline
+            && !last
+            && formatting.tactic == DefinitiveListTactic::Vertical
+            && item.new_lines
+        {
+            self.item_max_width = None;
+            self.result.push('\n');
+        }
+
+        self.prev_item_had_post_comment = item.post_comment.is_some();
+        self.prev_item_wrap = item.wrap;
+        Some(())
+    }
+
+    fn is_separated(&self, first: bool, last: bool) -> bool {
+        match self.sep_place {
+            SeparatorPlace::Front => !first,
+            SeparatorPlace::Back => !last || self.trailing_separator,
+        }
+    }
+
+    /// The width the items of `items` from the `i`-th on take on one line, with their
+    /// separators and the spaces between them. If `atoms_only`, only the `i`-th item and the
+    /// no-break atoms right after it are counted.
+    fn run_width<I, T>(&self, items: &I, i: usize, atoms_only: bool) -> usize
+    where
+        I: IntoIterator<Item = T> + Clone,
+        T: AsRef<ListItem>,
+    {
+        let mut iter = items.clone().into_iter().skip(i).peekable();
+        let mut width = 0;
+        let mut j = i;
+        while let Some(item) = iter.next() {
+            let item = item.as_ref();
+            if j > i {
+                if atoms_only && item.wrap != ItemWrap::NoBreak {
+                    break;
+                }
+                // 1 = space between the separator and the item.
+                width += 1;
+            }
+            let last = iter.peek().is_none();
+            width += total_item_width(item);
+            if self.is_separated(j == 0, last) {
+                width += self.formatting.separator.len();
+            }
+            j += 1;
+        }
+        width
+    }
+
+    /// The width available to the current line of a `Mixed` list.
+    fn line_width(&self) -> usize {
+        let shape = self.formatting.shape;
+        if self.on_first_line || !self.formatting.hanging_indent {
+            shape.width
+        } else {
+            shape
+                .width
+                .saturating_sub(self.formatting.config.tab_spaces())
+        }
+    }
+
+    /// Writes what comes between the previous item and `item`, the `i`-th item of `items`: a
+    /// space or a line break, depending on the tactic and on how `item` wraps.
+    fn write_item_start<I, T>(
+        &mut self,
+        items: &I,
+        i: usize,
+        item: &ListItem,
+        last: bool,
+        item_sep_len: usize,
+        separate: &mut bool,
+    ) where
+        I: IntoIterator<Item = T> + Clone,
+        T: AsRef<ListItem>,
+    {
+        let formatting = self.formatting;
+        let inner_item = item.inner_as_ref();
+        let first = i == 0;
+        match formatting.tactic {
             DefinitiveListTactic::Horizontal if !first => {
-                result.push(' ');
+                self.result.push(' ');
             }
             DefinitiveListTactic::SpecialMacro(num_args_before) => {
                 if i == 0 {
                     // Nothing
                 } else if i < num_args_before {
-                    result.push(' ');
+                    self.result.push(' ');
                 } else if i <= num_args_before + 1 {
-                    result.push('\n');
-                    result.push_str(indent_str);
+                    self.result.push('\n');
+                    self.result.push_str(&self.indent_str);
                 } else {
-                    result.push(' ');
+                    self.result.push(' ');
                 }
             }
             DefinitiveListTactic::Vertical
-                if !first && !inner_item.is_empty() && !result.is_empty() =>
+                if !first && !inner_item.is_empty() && !self.result.is_empty() =>
             {
-                result.push('\n');
-                result.push_str(indent_str);
+                self.result.push('\n');
+                self.result.push_str(&self.wrap_indent_str);
             }
             DefinitiveListTactic::Mixed => {
                 let total_width = total_item_width(item) + item_sep_len;
 
                 // 1 is space between separator and item.
-                if (line_len > 0 && line_len + 1 + total_width > formatting.shape.width)
-                    || prev_item_had_post_comment
-                    || (formatting.nested
-                        && (prev_item_is_nested_import || (!first && inner_item.contains("::"))))
-                {
-                    result.push('\n');
-                    result.push_str(indent_str);
-                    line_len = 0;
+                let overflows = |width: usize| {
+                    self.line_len > 0 && self.line_len + 1 + width > self.line_width()
+                };
+                let wrap = if item.wrap == ItemWrap::NoBreak {
+                    self.prev_item_had_post_comment
+                } else {
+                    // The no-break atoms after the item go on the same line as it.
+                    overflows(self.run_width(items, i, true))
+                        || self.prev_item_had_post_comment
+                        || self.prev_item_wrap == ItemWrap::OwnLine
+                        || (!first && item.wrap == ItemWrap::OwnLine)
+                        || (self.prev_item_wrap == ItemWrap::BreakAfter
+                            && overflows(self.run_width(items, i, false)))
+                };
+                if wrap {
+                    self.result.push('\n');
+                    self.result.push_str(&self.wrap_indent_str);
+                    self.line_len = 0;
+                    self.on_first_line = false;
                     if formatting.ends_with_newline {
-                        trailing_separator = true;
+                        self.trailing_separator = true;
                     }
-                } else if line_len > 0 {
-                    result.push(' ');
-                    line_len += 1;
+                } else if self.line_len > 0 {
+                    self.result.push(' ');
+                    self.line_len += 1;
                 }
 
                 if last && formatting.ends_with_newline {
-                    separate = formatting.trailing_separator != SeparatorTactic::Never;
+                    *separate = formatting.trailing_separator != SeparatorTactic::Never;
                 }
 
-                line_len += total_width;
+                self.line_len += total_width;
             }
             _ => {}
         }
+    }
 
-        // Pre-comments
-        if let Some(ref comment) = item.pre_comment {
-            // Block style in non-vertical mode.
-            let block_mode = tactic == DefinitiveListTactic::Horizontal;
-            // Width restriction is only relevant in vertical mode.
-            let comment =
-                rewrite_comment(comment, block_mode, formatting.shape, formatting.config)?;
-            result.push_str(&comment);
-
-            if !inner_item.is_empty() {
-                use DefinitiveListTactic::*;
-                if matches!(tactic, Vertical | Mixed | SpecialMacro(_)) {
-                    // We cannot keep pre-comments on the same line if the comment is normalized.
-                    let keep_comment = if formatting.config.normalize_comments()
-                        || item.pre_comment_style == ListItemCommentStyle::DifferentLine
-                    {
-                        false
-                    } else {
-                        // We will try to keep the comment on the same line with the item here.
-                        // 1 = ` `
-                        let total_width = total_item_width(item) + item_sep_len + 1;
-                        total_width <= formatting.shape.width
-                    };
-                    if keep_comment {
-                        result.push(' ');
-                    } else {
-                        result.push('\n');
-                        result.push_str(indent_str);
-                        // This is the width of the item (without comments).
-                        line_len = item.item.as_ref().map_or(0, |s| unicode_str_width(s));
-                    }
+    fn write_pre_comment(
+        &mut self,
+        comment: &str,
+        item: &ListItem,
+        item_sep_len: usize,
+    ) -> Option<()> {
+        let formatting = self.formatting;
+        let tactic = formatting.tactic;
+        // Block style in non-vertical mode.
+        let block_mode = tactic == DefinitiveListTactic::Horizontal;
+        // Width restriction is only relevant in vertical mode.
+        let comment = rewrite_comment(comment, block_mode, formatting.shape, formatting.config)?;
+        self.result.push_str(&comment);
+
+        if !item.inner_as_ref().is_empty() {
+            use DefinitiveListTactic::*;
+            if matches!(tactic, Vertical | Mixed | SpecialMacro(_)) {
+                // We cannot keep pre-comments on the same line if the comment is normalized.
+                let keep_comment = if formatting.config.normalize_comments()
+                    || item.pre_comment_style == ListItemCommentStyle::DifferentLine
+                {
+                    false
+                } else {
+                    // We will try to keep the comment on the same line with the item here.
+                    // 1 = ` `
+                    let total_width = total_item_width(item) + item_sep_len + 1;
+                    total_width <= formatting.shape.width
+                };
+                if keep_comment {
+                    self.result.push(' ');
                 } else {
-                    result.push(' ')
+                    self.result.push('\n');
+                    self.result.push_str(&self.wrap_indent_str);
+                    self.on_first_line = false;
+                    // This is the width of the item (without comments).
+                    self.line_len = item.item.as_ref().map_or(0, |s| unicode_str_width(s));
                 }
+            } else {
+                self.result.push(' ')
             }
-            item_max_width = None;
         }
+        self.item_max_width = None;
+        Some(())
+    }
 
-        if separate && sep_place.is_front() && !first {
-            result.push_str(formatting.separator.trim());
-            result.push(' ');
-        }
-        result.push_str(inner_item);
+    /// Writes the post-comment of the `i`-th item of a list which is not horizontal, aligned
+    /// with the post-comments of the next items unless it does not fit.
+    #[allow(clippy::too_many_arguments)]
+    fn write_post_comment<I, T>(
+        &mut self,
+        items: &I,
+        i: usize,
+        comment: &str,
+        inner_item: &str,
+        item_last_line_width: usize,
+        separate: bool,
+        last: bool,
+    ) -> Option<()>
+    where
+        I: IntoIterator<Item = T> + Clone,
+        T: AsRef<ListItem>,
+    {
+        let formatting = self.formatting;
+        let overhead = last_line_width(&self.result) + first_line_width(comment.trim());
+
+        let rewrite_post_comment = |item_max_width: &mut Option<usize>| {
+            if item_max_width.is_none() && !last && !inner_item.contains('\n') {
+                *item_max_width = Some(max_width_of_item_with_post_comment(
+                    items,
+                    i,
+                    overhead,
+                    formatting.config.max_width(),
+                ));
+            }
+            let overhead = if starts_with_newline(comment) {
+                0
+            } else if let Some(max_width) = *item_max_width {
+                max_width + 2
+            } else {
+                // 1 = space between item and comment.
+                item_last_line_width + 1
+            };
+            let width = formatting.shape.width.checked_sub(overhead).unwrap_or(1);
+            let offset = formatting.shape.indent + overhead;
+            let comment_shape = Shape::legacy(width, offset);
+
+            let block_style = if !formatting.ends_with_newline && last {
+                true
+            } else if starts_with_newline(comment) {
+                false
+            } else {
+                comment.trim().contains('\n') || comment.trim().len() > width
+            };
 
-        // Post-comments
-        if tactic == DefinitiveListTactic::Horizontal && item.post_comment.is_some() {
-            let comment = item.post_comment.as_ref().unwrap();
-            let formatted_comment = rewrite_comment(
-                comment,
-                true,
-                Shape::legacy(formatting.shape.width, Indent::empty()),
+            rewrite_comment(
+                comment.trim_start(),
+                block_style,
+                comment_shape,
                 formatting.config,
-            )?;
-
-            result.push(' ');
-            result.push_str(&formatted_comment);
-        }
-
-        if separate && sep_place.is_back() {
-            result.push_str(formatting.separator);
-        }
-
-        if tactic != DefinitiveListTactic::Horizontal && item.post_comment.is_some() {
-            let comment = item.post_comment.as_ref().unwrap();
-            let overhead = last_line_width(&result) + first_line_width(comment.trim());
-
-            let rewrite_post_comment = |item_max_width: &mut Option<usize>| {
-                if item_max_width.is_none() && !last && !inner_item.contains('\n') {
-                    *item_max_width = Some(max_width_of_item_with_post_comment(
-                        &cloned_items,
-                        i,
-                        overhead,
-                        formatting.config.max_width(),
-                    ));
-                }
-                let overhead = if starts_with_newline(comment) {
-                    0
-                } else if let Some(max_width) = *item_max_width {
-                    max_width + 2
-                } else {
-                    // 1 = space between item and comment.
-                    item_last_line_width + 1
-                };
-                let width = formatting.shape.width.checked_sub(overhead).unwrap_or(1);
-                let offset = formatting.shape.indent + overhead;
-                let comment_shape = Shape::legacy(width, offset);
-
-                let block_style = if !formatting.ends_with_newline && last {
-                    true
-                } else if starts_with_newline(comment) {
-                    false
-                } else {
-                    comment.trim().contains('\n') || comment.trim().len() > width
-                };
-
-                rewrite_comment(
-                    comment.trim_start(),
-                    block_style,
-                    comment_shape,
-                    formatting.config,
-                )
-            };
+            )
+        };
 
-            let mut formatted_comment = rewrite_post_comment(&mut item_max_width)?;
-
-            if !starts_with_newline(comment) {
-                if formatting.align_comments {
-                    let mut comment_alignment =
-                        post_comment_alignment(item_max_width, inner_item.len());
-                    if first_line_width(&formatted_comment)
-                        + last_line_width(&result)
-                        + comment_alignment
-                        + 1
-                        > formatting.config.max_width()
-                    {
-                        item_max_width = None;
-                        formatted_comment = rewrite_post_comment(&mut item_max_width)?;
-                        comment_alignment =
-                            post_comment_alignment(item_max_width, inner_item.len());
-                    }
-                    for _ in 0..=comment_alignment {
-                        result.push(' ');
-                    }
-                }
-                // An additional space for the missing trailing separator (or
-                // if we skipped alignment above).
-                if !formatting.align_comments
-                    || (last
-                        && item_max_width.is_some()
-                        && !separate
-                        && !formatting.separator.is_empty())
+        let mut formatted_comment = rewrite_post_comment(&mut self.item_max_width)?;
+
+        if !starts_with_newline(comment) {
+            if formatting.align_comments {
+                let mut comment_alignment =
+                    post_comment_alignment(self.item_max_width, inner_item.len());
+                if first_line_width(&formatted_comment)
+                    + last_line_width(&self.result)
+                    + comment_alignment
+                    + 1
+                    > formatting.config.max_width()
                 {
-                    result.push(' ');
+                    self.item_max_width = None;
+                    formatted_comment = rewrite_post_comment(&mut self.item_max_width)?;
+                    comment_alignment =
+                        post_comment_alignment(self.item_max_width, inner_item.len());
+                }
+                for _ in 0..=comment_alignment {
+                    self.result.push(' ');
                 }
-            } else {
-                result.push('\n');
-                result.push_str(indent_str);
             }
-            if formatted_comment.contains('\n') {
-                item_max_width = None;
+            // An additional space for the missing trailing separator (or
+            // if we skipped alignment above).
+            if !formatting.align_comments
+                || (last
+                    && self.item_max_width.is_some()
+                    && !separate
+                    && !formatting.separator.is_empty())
+            {
+                self.result.push(' ');
             }
-            result.push_str(&formatted_comment);
         } else {
-            item_max_width = None;
+            self.result.push('\n');
+            self.result.push_str(&self.indent_str);
         }
-
-        if formatting.preserve_newline
-            && !last
-            && tactic == DefinitiveListTactic::Vertical
-            && item.new_lines
-        {
-            item_max_width = None;
-            result.push('\n');
+        if formatted_comment.contains('\n') {
+            self.item_max_width = None;
         }
+        self.result.push_str(&formatted_comment);
+        Some(())
+    }
+}
 
-        prev_item_had_post_comment = item.post_comment.is_some();
-        prev_item_is_nested_import = inner_item.contains("::");
+/// Sorts `items` by `keys` within each group of items separated by blank lines. The
+/// comments of an item stay attached to it.
+pub(crate) fn sort_list_items<K: Ord>(items: Vec<ListItem>, keys: Vec<K>) -> Vec<ListItem> {
+    fn push_group<K: Ord>(result: &mut Vec<ListItem>, group: &mut Vec<(K, ListItem)>) {
+        let new_lines = group.last().map_or(false, |(_, item)| item.new_lines);
+        group.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let len = group.len();
+        result.extend(group.drain(..).enumerate().map(|(i, (_, mut item))| {
+            item.new_lines = new_lines && i + 1 == len;
+            item
+        }));
     }
 
-    Some(result)
+    let mut result = Vec::with_capacity(items.len());
+    let mut group = vec![];
+    for (item, key) in items.into_iter().zip(keys) {
+        let ends_group = item.new_lines;
+        group.push((key, item));
+        if ends_group {
+            push_group(&mut result, &mut group);
+        }
+    }
+    push_group(&mut result, &mut group);
+    result
 }
 
 fn max_width_of_item_with_post_comment<I, T>(
@@ -668,15 +882,10 @@ pub(crate) fn get_comment_end(
             .unwrap_or_else(|| post_snippet.len());
     }
 
-    let mut block_open_index = post_snippet.find("/*");
-    // check if it really is a block comment (and not `//*` or a nested comment)
-    if let Some(i) = block_open_index {
-        match post_snippet.find('/') {
-            Some(j) if j < i => block_open_index = None,
-            _ if post_snippet[..i].ends_with('/') => block_open_index = None,
-            _ => (),
-        }
-    }
+    // The start of the first comment, if it is a block comment.
+    let block_open_index = comment_starts(post_snippet)
+        .next()
+        .filter(|&i| post_snippet[i..].starts_with("/*"));
     let newline_index = post_snippet.find('\n');
     if let Some(separator_index) = post_snippet.find_uncommented(separator) {
         match (block_open_index, newline_index) {
@@ -782,6 +991,7 @@ fn next(&mut self) -> Option<Self::Item> {
                 },
                 post_comment,
                 new_lines,
+                wrap: ItemWrap::Normal,
             }
         })
     }
@@ -883,19 +1093,30 @@ pub(crate) fn struct_lit_shape(
     }
 }
 
-// Compute the tactic for the internals of a struct-lit-like thing.
+/// Returns the tactic to format the fields of a struct literal or pattern with, where
+/// `layout` is the layout set for them in the config.
 pub(crate) fn struct_lit_tactic(
     h_shape: Option<Shape>,
     context: &RewriteContext<'_>,
     items: &[ListItem],
+    layout: ListLayout,
 ) -> DefinitiveListTactic {
     if let Some(h_shape) = h_shape {
-        let prelim_tactic = match (context.config.indent_style(), items.len()) {
-            (IndentStyle::Visual, 1) => ListTactic::HorizontalVertical,
-            _ if context.config.struct_lit_single_line() => ListTactic::HorizontalVertical,
-            _ => ListTactic::Vertical,
+        let prelim_tactic = match layout.to_list_tactic() {
+            Some(tactic) => tactic,
+            None => match (context.config.indent_style(), items.len()) {
+                (IndentStyle::Visual, 1) => ListTactic::HorizontalVertical,
+                _ if context.config.struct_lit_single_line() => ListTactic::HorizontalVertical,
+                _ => ListTactic::Vertical,
+            },
         };
-        definitive_tactic(items, prelim_tactic, Separator::Comma, h_shape.width)
+        definitive_tactic(
+            items,
+            prelim_tactic,
+            Separator::Comma,
+            h_shape.width,
+            context.config,
+        )
     } else {
         DefinitiveListTactic::Vertical
     }
@@ -936,8 +1157,8 @@ pub(crate) fn struct_lit_formatting<'a>(
         shape,
         ends_with_newline,
         preserve_newline: true,
-        nested: false,
         align_comments: true,
+        hanging_indent: false,
         config: context.config,
     }
 }
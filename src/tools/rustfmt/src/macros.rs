@@ -12,6 +12,9 @@
 use std::collections::HashMap;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 
+use lazy_static::lazy_static;
+use regex::Regex;
+
 use rustc_ast::token::{BinOpToken, Delimiter, Token, TokenKind};
 use rustc_ast::tokenstream::{Cursor, TokenStream, TokenTree};
 use rustc_ast::{ast, ptr};
@@ -25,18 +28,20 @@
     contains_comment, CharClasses, FindUncommented, FullCodeCharKind, LineClasses,
 };
 use crate::config::lists::*;
+use crate::config::Version;
 use crate::expr::{rewrite_array, rewrite_assign_rhs, RhsAssignKind};
 use crate::lists::{itemize_list, write_list, ListFormatting};
 use crate::overflow;
 use crate::parse::macros::lazy_static::parse_lazy_static;
-use crate::parse::macros::{parse_expr, parse_macro_args, ParsedMacroArgs};
+use crate::parse::macros::{parse_expr, parse_macro_args, parse_macro_stmts, ParsedMacroArgs};
 use crate::rewrite::{Rewrite, RewriteContext};
-use crate::shape::{Indent, Shape};
+use crate::shape::{block_width, Indent, Shape};
 use crate::source_map::SpanUtils;
 use crate::spanned::Spanned;
 use crate::utils::{
     format_visibility, indent_next_line, is_empty_line, mk_sp, remove_trailing_white_spaces,
-    rewrite_ident, trim_left_preserve_layout, wrap_str, NodeIdExt,
+    rewrite_ident, rewrite_simple_path, shift_lines_preserve_layout, trim_left_preserve_layout,
+    wrap_str, NodeIdExt,
 };
 use crate::visitor::FmtVisitor;
 
@@ -46,6 +51,9 @@
 pub(crate) enum MacroPosition {
     Item,
     Statement,
+    /// A brace-delimited macro call statement without a trailing semicolon. Its delimiter
+    /// must be kept, since it is what allows more statements to follow it.
+    BraceStatement,
     Expression,
     Pat,
 }
@@ -96,14 +104,11 @@ fn rewrite_macro_name(
     path: &ast::Path,
     extra_ident: Option<symbol::Ident>,
 ) -> String {
-    let name = if path.segments.len() == 1 {
-        // Avoid using pretty-printer in the common case.
-        format!("{}!", rewrite_ident(context, path.segments[0].ident))
-    } else {
-        format!("{}!", pprust::path_to_string(path))
-    };
+    let name = format!("{}!", rewrite_simple_path(context, path));
     match extra_ident {
-        Some(ident) if ident.name != kw::Empty => format!("{} {}", name, ident),
+        Some(ident) if ident.name != kw::Empty => {
+            format!("{} {}", name, rewrite_ident(context, ident))
+        }
         _ => name,
     }
 }
@@ -140,8 +145,21 @@ fn return_macro_parse_failure_fallback(
         context.parse_sess.line_of_byte_pos(span.hi()),
     ));
 
-    // Return the snippet unmodified if the macro is not block-like
-    let mut snippet = context.snippet(span).to_owned();
+    // Return the snippet unmodified if the macro is not block-like. With version Two, an
+    // item or statement macro is re-indented as a whole instead, so that its continuation
+    // lines keep their position relative to the first line.
+    let orig_indent = match position {
+        MacroPosition::Item | MacroPosition::Statement | MacroPosition::BraceStatement => {
+            context.snippet_provider.line_indent_before(span.lo())
+        }
+        _ => None,
+    };
+    let mut snippet = match orig_indent {
+        Some(orig_indent) if context.config.version() == Version::Two => {
+            shift_lines_preserve_layout(context.snippet(span), orig_indent, indent, context.config)
+        }
+        _ => context.snippet(span).to_owned(),
+    };
     if position == MacroPosition::Item {
         snippet.push(';');
     }
@@ -202,11 +220,12 @@ fn rewrite_macro_inner(
     let macro_name = rewrite_macro_name(context, &mac.path, extra_ident);
     let is_forced_bracket = FORCED_BRACKET_MACROS.contains(&&macro_name[..]);
 
-    let style = if is_forced_bracket && !is_nested_macro {
-        Delimiter::Bracket
-    } else {
-        original_style
-    };
+    let style =
+        if is_forced_bracket && !is_nested_macro && position != MacroPosition::BraceStatement {
+            Delimiter::Bracket
+        } else {
+            original_style
+        };
 
     let ts = mac.args.inner_tokens();
     let has_comment = contains_comment(context.snippet(mac.span()));
@@ -231,6 +250,12 @@ fn rewrite_macro_inner(
         }
     }
 
+    if style == Delimiter::Brace && context.config.format_brace_macros() {
+        if let success @ Some(..) = format_brace_macro_body(context, mac, &macro_name, shape, &ts) {
+            return success;
+        }
+    }
+
     let ParsedMacroArgs {
         args: arg_vec,
         vec_with_semi,
@@ -279,6 +304,7 @@ fn rewrite_macro_inner(
                     } else {
                         Some(SeparatorTactic::Never)
                     },
+                    ListLayout::Auto,
                 )
                 .map(|rw| match position {
                     MacroPosition::Item => format!("{};", rw),
@@ -337,6 +363,40 @@ fn rewrite_macro_inner(
     }
 }
 
+/// Formats the body of a brace-delimited macro call like a block, if it parses as a
+/// sequence of statements, e.g. `foo! { let x = 1; bar(x) }`.
+fn format_brace_macro_body(
+    context: &RewriteContext<'_>,
+    mac: &ast::MacCall,
+    macro_name: &str,
+    shape: Shape,
+    ts: &TokenStream,
+) -> Option<String> {
+    let span = match *mac.args {
+        ast::MacArgs::Delimited(dspan, ..) => dspan.entire(),
+        _ => return None,
+    };
+    let stmts = parse_macro_stmts(context, ts.clone())?;
+    // The statements are formatted from their spans, so only format them if all of them
+    // point into the body of this macro call. Otherwise we could lose or duplicate code.
+    let is_real_span = |s: Span| !s.is_dummy() && !s.from_expansion() && span.contains(s);
+    if stmts.is_empty() || !stmts.iter().all(|stmt| is_real_span(stmt.span())) {
+        return None;
+    }
+
+    let block = ast::Block {
+        stmts,
+        id: ast::DUMMY_NODE_ID,
+        rules: ast::BlockCheckMode::Default,
+        span,
+        tokens: None,
+        could_be_bare_literal: false,
+    };
+    // 1 = space between the macro name and the block
+    let block_str = block.rewrite(context, shape.offset_left(macro_name.len() + 1)?)?;
+    Some(format!("{} {}", macro_name, block_str))
+}
+
 fn handle_vec_semi(
     context: &RewriteContext<'_>,
     shape: Shape,
@@ -434,7 +494,7 @@ pub(crate) fn rewrite_macro_def(
             }
             None => None,
         },
-        context.snippet_provider.span_after(span, "{"),
+        context.snippet_provider.span_after(span, "{")?,
         span.hi(),
         false,
     )
@@ -521,6 +581,31 @@ fn replace_names(input: &str) -> Option<(String, HashMap<String, String>)> {
     Some((result, substs))
 }
 
+/// Returns `body`, a macro body whose metavariables were renamed by `replace_names`, with
+/// braces around the metavariables which `matcher` declares as `block`s, or `None` if there
+/// are none.
+fn brace_block_metavariables(
+    matcher: &str,
+    body: &str,
+    substs: &HashMap<String, String>,
+) -> Option<String> {
+    lazy_static! {
+        static ref BLOCK_METAVARIABLE: Regex = Regex::new(r"\$(\w+)\s*:\s*block\b").unwrap();
+    }
+    let mut braced = body.to_owned();
+    for captures in BLOCK_METAVARIABLE.captures_iter(matcher) {
+        let name = match substs.get(&format!("${}", &captures[1])) {
+            Some(name) => name,
+            None => continue,
+        };
+        let name_re = Regex::new(&format!(r"\b{}\b", regex::escape(name))).ok()?;
+        braced = name_re
+            .replace_all(&braced, format!("{{ {} }}", name).as_str())
+            .into_owned();
+    }
+    (braced != body).then(|| braced)
+}
+
 #[derive(Debug, Clone)]
 enum MacroArgKind {
     /// e.g., `$x: expr`.
@@ -1239,6 +1324,11 @@ fn rewrite(
         let old_body = context.snippet(self.body).trim();
         let (body_str, substs) = replace_names(old_body)?;
         let has_block_body = old_body.starts_with('{');
+        let mut body_strs = vec![body_str];
+        if context.config.brace_macro_if_bodies() {
+            let matcher = context.snippet(mk_sp(self.span.lo(), self.whole_body.lo()));
+            body_strs.extend(brace_block_metavariables(matcher, &body_strs[0], &substs));
+        }
 
         let mut config = context.config.clone();
         config.set().hide_parse_errors(true);
@@ -1250,21 +1340,20 @@ fn rewrite(
         } else {
             shape.indent.block_indent(&config)
         };
-        let new_width = config.max_width() - body_indent.width();
+        let new_width = block_width(body_indent, &config);
         config.set().max_width(new_width);
 
-        // First try to format as items, then as statements.
-        let new_body_snippet = match crate::format_snippet(&body_str, &config, true) {
-            Some(new_body) => new_body,
-            None => {
+        // First try to format as items, then as statements. With `brace_macro_if_bodies`, a
+        // body which doesn't parse, e.g. because of `if $cond $body`, is then tried with
+        // braces around its `block` metavariables.
+        let new_body_snippet = body_strs.iter().find_map(|body_str| {
+            config.set().max_width(new_width);
+            crate::format_snippet(body_str, &config, true).or_else(|| {
                 let new_width = new_width + config.tab_spaces();
                 config.set().max_width(new_width);
-                match crate::format_code_block(&body_str, &config, true) {
-                    Some(new_body) => new_body,
-                    None => return None,
-                }
-            }
-        };
+                crate::format_code_block(body_str, &config, true)
+            })
+        })?;
         let new_body = wrap_str(
             new_body_snippet.snippet.to_string(),
             config.max_width(),
@@ -1391,7 +1480,7 @@ fn rewrite_macro_with_items(
 
     let mut visitor = FmtVisitor::from_context(context);
     visitor.block_indent = shape.indent.block_indent(context.config);
-    visitor.last_pos = context.snippet_provider.span_after(span, opener.trim());
+    visitor.last_pos = context.snippet_provider.span_after(span, opener.trim())?;
     for item in items {
         let item = match item {
             MacroArg::Item(item) => item,
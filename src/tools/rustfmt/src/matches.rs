@@ -5,16 +5,17 @@
 use rustc_ast::{ast, ptr};
 use rustc_span::{BytePos, Span};
 
-use crate::comment::{combine_strs_with_missing_comments, rewrite_comment};
+use crate::comment::{combine_strs_with_missing_comments, contains_comment, rewrite_comment};
 use crate::config::lists::*;
 use crate::config::{Config, ControlBraceStyle, IndentStyle, MatchArmLeadingPipe, Version};
 use crate::expr::{
     format_expr, is_empty_block, is_simple_block, is_unsafe_block, prefer_next_line, rewrite_cond,
     ExprType, RhsTactics,
 };
+use crate::formatting::toggles::keep_suppressed_items;
 use crate::lists::{itemize_list, write_list, ListFormatting};
 use crate::rewrite::{Rewrite, RewriteContext};
-use crate::shape::Shape;
+use crate::shape::{Indent, Shape};
 use crate::source_map::SpanUtils;
 use crate::spanned::Spanned;
 use crate::utils::{
@@ -116,7 +117,7 @@ pub(crate) fn rewrite_match(
         };
         context
             .snippet_provider
-            .span_after(mk_sp(cond.span.hi(), hi), "{")
+            .span_after(mk_sp(cond.span.hi(), hi), "{")?
     } else {
         inner_attrs[inner_attrs.len() - 1].span.hi()
     };
@@ -131,6 +132,14 @@ pub(crate) fn rewrite_match(
         }
     } else {
         let span_after_cond = mk_sp(cond.span.hi(), span.hi());
+        if inner_attrs.is_empty() && block_sep == " " {
+            let single_line = rewrite_single_line_match(context, &cond_str, arms, span_after_cond);
+            if let Some(single_line) = single_line {
+                if unicode_str_width(&single_line) <= shape.width {
+                    return Some(single_line);
+                }
+            }
+        }
         Some(format!(
             "match {}{}{{\n{}{}{}\n{}}}",
             cond_str,
@@ -143,6 +152,41 @@ pub(crate) fn rewrite_match(
     }
 }
 
+/// Rewrites a match with at most `single_line_match_max_arms` arms on a single line, e.g.
+/// `match x { A => 1, B => 2 }`. Returns `None` if an arm does not fit on a single line.
+fn rewrite_single_line_match(
+    context: &RewriteContext<'_>,
+    cond_str: &str,
+    arms: &[ast::Arm],
+    span_after_cond: Span,
+) -> Option<String> {
+    if arms.len() > context.config.single_line_match_max_arms()
+        || cond_str.contains('\n')
+        || contains_comment(context.snippet(span_after_cond))
+        || context.config.match_arm_leading_pipes() == MatchArmLeadingPipe::Always
+    {
+        return None;
+    }
+
+    let shape = Shape::legacy(usize::max_value(), Indent::empty());
+    let mut arm_strs = Vec::with_capacity(arms.len());
+    for arm in arms {
+        if !arm.attrs.is_empty() || context.snippet(arm.pat.span).starts_with('|') {
+            return None;
+        }
+        let pat_str = arm.pat.rewrite(context, shape)?;
+        let guard_str = rewrite_guard(context, &arm.guard, shape, pat_str.len(), false)?;
+        let (_, body) = flatten_arm_body(context, &arm.body, None);
+        let body_str = body.rewrite(context, shape)?;
+        let arm_str = format!("{}{} => {}", pat_str, guard_str, body_str);
+        if arm_str.contains('\n') {
+            return None;
+        }
+        arm_strs.push(arm_str);
+    }
+    Some(format!("match {} {{ {} }}", cond_str, arm_strs.join(", ")))
+}
+
 fn arm_comma(config: &Config, body: &ast::Expr, is_last: bool) -> &'static str {
     if is_last && config.trailing_comma() == SeparatorTactic::Never {
         ""
@@ -205,7 +249,16 @@ fn rewrite_match_arms(
         span.hi(),
         false,
     );
-    let arms_vec: Vec<_> = items.collect();
+    let mut arms_vec: Vec<_> = items.collect();
+    // The arms between `// rustfmt: off` and `// rustfmt: on` comments are kept as they are,
+    // along with their commas.
+    keep_suppressed_items(
+        context.snippet_provider,
+        mk_sp(open_brace_pos, span.hi()),
+        arms,
+        &mut arms_vec,
+        true,
+    );
     // We will add/remove commas inside `arm.rewrite()`, and hence no separator here.
     let fmt = ListFormatting::new(arm_shape, context.config)
         .separator("")
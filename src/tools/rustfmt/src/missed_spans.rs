@@ -1,11 +1,14 @@
 use rustc_span::{BytePos, Pos, Span};
 
-use crate::comment::{is_last_comment_block, rewrite_comment, CodeCharKind, CommentCodeSlices};
+use crate::comment::{
+    is_last_comment_block, rewrite_comment, rewrite_comment_from_indent, CodeCharKind,
+    CommentCodeSlices,
+};
 use crate::config::file_lines::FileLines;
 use crate::config::FileName;
 use crate::config::Version;
 use crate::coverage::transform_missing_snippet;
-use crate::shape::{Indent, Shape};
+use crate::shape::{block_width, Indent, Shape};
 use crate::source_map::LineRangeUtils;
 use crate::utils::{count_lf_crlf, count_newlines, last_line_width, mk_sp};
 use crate::visitor::FmtVisitor;
@@ -186,6 +189,9 @@ fn write_snippet_inner<F>(
                 );
                 (lf_count, crlf_count, within_file_lines_range)
             };
+        // Whether the last slice was a block comment which ends its line. Unlike a line
+        // comment, it is written without the line break which follows it.
+        let mut after_block_comment = false;
         for (kind, offset, subslice) in CommentCodeSlices::new(snippet) {
             debug!("{:?}: {:?}", kind, subslice);
 
@@ -194,21 +200,38 @@ fn write_snippet_inner<F>(
             let newline_count = lf_count + crlf_count;
             if CodeCharKind::Comment == kind && within_file_lines_range {
                 // 1: comment.
+                let orig_indent = self
+                    .snippet_provider
+                    .line_indent_before(span.lo() + BytePos::from_usize(offset));
                 self.process_comment(
                     &mut status,
                     snippet,
                     &big_snippet[..(offset + big_diff)],
                     offset,
                     subslice,
+                    orig_indent,
                 );
+                after_block_comment = is_last_comment_block(subslice);
             } else if subslice.trim().is_empty() && newline_count > 0 && within_file_lines_range {
                 // 2: blank lines.
-                self.push_vertical_spaces(newline_count);
+                if after_block_comment {
+                    // The first line break ends the comment's line, so only the ones after it
+                    // are blank lines. Otherwise `blank_lines_lower_bound` would separate the
+                    // comment from the code it is directly attached to.
+                    self.push_str("\n");
+                    if newline_count > 1 {
+                        self.push_vertical_spaces(newline_count - 1);
+                    }
+                } else {
+                    self.push_vertical_spaces(newline_count);
+                }
                 status.cur_line += newline_count;
                 status.line_start = offset + lf_count + crlf_count * 2;
+                after_block_comment = false;
             } else {
                 // 3: code which we failed to format or which is not within file-lines range.
                 self.process_missing_code(&mut status, snippet, subslice, offset, file_name);
+                after_block_comment = false;
             }
         }
 
@@ -230,6 +253,7 @@ fn process_comment(
         big_snippet: &str,
         offset: usize,
         subslice: &str,
+        orig_indent: Option<&str>,
     ) {
         let last_char = big_snippet
             .chars()
@@ -260,7 +284,7 @@ fn process_comment(
 
         let comment_width = ::std::cmp::min(
             self.config.comment_width(),
-            self.config.max_width() - self.block_indent.width(),
+            block_width(self.block_indent, self.config),
         );
         let comment_shape = Shape::legacy(comment_width, comment_indent);
 
@@ -287,8 +311,17 @@ fn process_comment(
                 }
             }
         } else {
-            let comment_str = rewrite_comment(subslice, false, comment_shape, self.config)
-                .unwrap_or_else(|| String::from(subslice));
+            let comment_str = match orig_indent {
+                Some(orig_indent) => rewrite_comment_from_indent(
+                    subslice,
+                    false,
+                    comment_shape,
+                    self.config,
+                    orig_indent,
+                ),
+                None => rewrite_comment(subslice, false, comment_shape, self.config),
+            }
+            .unwrap_or_else(|| String::from(subslice));
             self.push_str(&comment_str);
         }
 
@@ -0,0 +1,243 @@
+//! Overrides of the configuration for the code of a module with an inner
+//! `#![rustfmt::config(name = value, ..)]` attribute, e.g. for generated bindings or tables
+//! which read better with a larger `max_width` than the rest of the crate.
+//!
+//! An item with an `#[rustfmt::optimize]` attribute is formatted with `optimize_layout`,
+//! for the few functions whose first layout which fits reads badly, without paying for
+//! the slower layout search in the rest of the crate.
+
+use std::collections::HashMap;
+
+use rustc_ast::ast;
+use rustc_span::{BytePos, Span};
+
+use crate::config::Config;
+
+/// The options about the formatting of code, which can be set for a module. The other
+/// options are about the operation of rustfmt, e.g. `edition` or `file_timeout`, or must be
+/// the same for a whole file, e.g. `newline_style`, and so cannot be set for a module. A new
+/// formatting option must be added here to be set for a module.
+const FORMATTING_OPTIONS: &[&str] = &[
+    "max_width",
+    "hard_tabs",
+    "tab_spaces",
+    "indent_style",
+    "use_small_heuristics",
+    "fn_call_width",
+    "attr_fn_like_width",
+    "struct_lit_width",
+    "struct_variant_width",
+    "array_width",
+    "chain_width",
+    "single_line_if_else_max_width",
+    "wrap_comments",
+    "format_code_in_doc_comments",
+    "doc_comment_code_block_width",
+    "comment_width",
+    "normalize_comments",
+    "normalize_doc_attributes",
+    "format_strings",
+    "format_macro_matchers",
+    "format_macro_bodies",
+    "format_brace_macros",
+    "brace_macro_if_bodies",
+    "hex_literal_case",
+    "format_imports",
+    "format_patterns",
+    "format_signatures",
+    "empty_item_single_line",
+    "struct_lit_single_line",
+    "struct_lit_width_ignores_base",
+    "fn_single_line",
+    "where_single_line",
+    "imports_indent",
+    "imports_layout",
+    "imports_granularity",
+    "group_imports",
+    "reorder_imports",
+    "reorder_modules",
+    "test_modules_last",
+    "reorder_impl_items",
+    "reorder_by_alias",
+    "type_punctuation_density",
+    "keyword_prefix_spacing",
+    "space_before_colon",
+    "space_after_colon",
+    "spaces_around_ranges",
+    "spaces_around_struct_braces",
+    "space_between_closing_angle_brackets",
+    "binop_separator",
+    "binop_args_indent",
+    "remove_nested_parens",
+    "remove_redundant_parens",
+    "combine_control_expr",
+    "short_array_element_width_threshold",
+    "overflow_delimited_expr",
+    "prefer_stable_diffs",
+    "optimize_layout",
+    "call_args_layout",
+    "struct_lit_layout",
+    "pattern_layout",
+    "struct_field_align_threshold",
+    "const_align_threshold",
+    "enum_discrim_align_threshold",
+    "match_arm_blocks",
+    "single_line_match_max_arms",
+    "match_arm_leading_pipes",
+    "force_multiline_blocks",
+    "force_brace_closure_bodies_in_chains",
+    "fn_args_layout",
+    "brace_style",
+    "control_brace_style",
+    "trailing_semicolon",
+    "trailing_comma",
+    "match_block_trailing_comma",
+    "blank_lines_upper_bound",
+    "blank_lines_lower_bound",
+    "inline_attribute_width",
+    "merge_derives",
+    "use_try_shorthand",
+    "use_field_init_shorthand",
+    "force_explicit_abi",
+    "condense_wildcard_suffixes",
+];
+
+/// The configurations of the inline modules and items of a file which override the
+/// configuration, keyed by the start of the spans of the modules and items.
+pub(crate) type ModuleConfigs = HashMap<BytePos, Config>;
+
+/// Returns `true` if the path segments are those of `#![rustfmt::config(..)]`.
+pub(crate) fn is_config_attr(segments: &[ast::PathSegment]) -> bool {
+    segments.len() == 2
+        && segments[0].ident.name.as_str() == "rustfmt"
+        && segments[1].ident.name.as_str() == "config"
+}
+
+/// Returns `true` if the path segments are those of `#[rustfmt::optimize]`.
+pub(crate) fn is_optimize_attr(segments: &[ast::PathSegment]) -> bool {
+    segments.len() == 2
+        && segments[0].ident.name.as_str() == "rustfmt"
+        && segments[1].ident.name.as_str() == "optimize"
+}
+
+/// Returns the configuration of an item with the attributes `attrs`, within code which is
+/// formatted with `config`, if the item has an `#[rustfmt::optimize]` attribute and
+/// `optimize_layout` is not set already. `optimize_layout` is unstable, so without unstable
+/// features the attribute is ignored, and its span is added to `errors`.
+fn optimized_config(
+    config: &Config,
+    attrs: &[ast::Attribute],
+    errors: &mut Vec<Span>,
+) -> Option<Config> {
+    let optimize_attr = attrs.iter().find(|attr| {
+        attr.style == ast::AttrStyle::Outer
+            && matches!(attr.kind, ast::AttrKind::Normal(ref normal)
+                if is_optimize_attr(&normal.item.path.segments))
+    })?;
+    if config.optimize_layout() {
+        return None;
+    }
+    if !unstable_features_available(config) {
+        errors.push(optimize_attr.span);
+        return None;
+    }
+    let mut config = config.clone();
+    config.set().optimize_layout(true);
+    Some(config)
+}
+
+/// Returns the configuration of a module with the attributes `attrs`, within code which is
+/// formatted with `config`, or `None` if the attributes do not override any option. The
+/// spans of invalid overrides, which are ignored, are added to `errors`.
+pub(crate) fn module_config(
+    config: &Config,
+    attrs: &[ast::Attribute],
+    errors: &mut Vec<Span>,
+) -> Option<Config> {
+    let mut module_config: Option<Config> = None;
+    let config_attrs = attrs.iter().filter(|attr| {
+        attr.style == ast::AttrStyle::Inner
+            && matches!(attr.kind, ast::AttrKind::Normal(ref normal)
+                if is_config_attr(&normal.item.path.segments))
+    });
+    for attr in config_attrs {
+        let items = match attr.meta_item_list() {
+            Some(items) => items,
+            None => {
+                errors.push(attr.span);
+                continue;
+            }
+        };
+        for item in items {
+            match option_override(config, &item) {
+                Some((name, value)) => module_config
+                    .get_or_insert_with(|| config.clone())
+                    .override_value(&name, &value),
+                None => errors.push(item.span()),
+            }
+        }
+    }
+    module_config
+}
+
+/// Returns the name and value of the option set by `item`, e.g. `max_width = 150`, if it
+/// is an option which can be set for a module and the value is valid. Unstable options can
+/// only be set when unstable features are available, as in config files.
+fn option_override(config: &Config, item: &ast::NestedMetaItem) -> Option<(String, String)> {
+    let name = item.ident()?.name.to_string();
+    let value = match item.meta_item()?.name_value_literal()?.kind {
+        ast::LitKind::Str(value, _) => value.to_string(),
+        ast::LitKind::Int(value, _) => value.to_string(),
+        ast::LitKind::Bool(value) => value.to_string(),
+        _ => return None,
+    };
+    if !FORMATTING_OPTIONS.contains(&&*name)
+        || !Config::is_valid_key_val(&name, &value)
+        || !Config::is_stable_option(&name) && !unstable_features_available(config)
+    {
+        return None;
+    }
+    Some((name, value))
+}
+
+/// Returns `true` if code formatted with `config` may use unstable options.
+fn unstable_features_available(config: &Config) -> bool {
+    config.unstable_features() || crate::is_nightly_channel!()
+}
+
+/// Returns the configurations of the inline modules and the optimized items among `items`
+/// and their inline modules, where `items` are formatted with `config`. The optimized
+/// items include the associated items of impls and traits.
+pub(crate) fn inline_module_configs(
+    config: &Config,
+    items: &[rustc_ast::ptr::P<ast::Item>],
+    configs: &mut ModuleConfigs,
+    errors: &mut Vec<Span>,
+) {
+    for item in items {
+        let item_config = optimized_config(config, &item.attrs, errors);
+        let config = item_config.as_ref().unwrap_or(config);
+        let assoc_items = match item.kind {
+            ast::ItemKind::Mod(_, ast::ModKind::Loaded(ref items, ast::Inline::Yes, _)) => {
+                let module_config = module_config(config, &item.attrs, errors).or(item_config);
+                let config = module_config.as_ref().unwrap_or(config);
+                inline_module_configs(config, items, configs, errors);
+                if let Some(module_config) = module_config {
+                    configs.insert(item.span.lo(), module_config);
+                }
+                continue;
+            }
+            ast::ItemKind::Impl(ref impl_) => &impl_.items[..],
+            ast::ItemKind::Trait(ref trait_) => &trait_.items[..],
+            _ => &[],
+        };
+        for assoc_item in assoc_items {
+            if let Some(assoc_config) = optimized_config(config, &assoc_item.attrs, errors) {
+                configs.insert(assoc_item.span.lo(), assoc_config);
+            }
+        }
+        if let Some(item_config) = item_config {
+            configs.insert(item.span.lo(), item_config);
+        }
+    }
+}
@@ -16,6 +16,7 @@
 };
 use crate::parse::session::ParseSess;
 use crate::utils::{contains_skip, mk_sp};
+use crate::{ErrorKind, FormatReport, FormattingError};
 
 mod visitor;
 
@@ -28,6 +29,9 @@ pub(crate) struct Module<'a> {
     pub(crate) items: Cow<'a, Vec<rustc_ast::ptr::P<ast::Item>>>,
     inner_attr: ast::AttrVec,
     pub(crate) span: Span,
+    // The inner attributes of the modules enclosing an out-of-line module, from the crate
+    // root down.
+    enclosing_attrs: Vec<ast::AttrVec>,
 }
 
 impl<'a> Module<'a> {
@@ -37,22 +41,24 @@ pub(crate) fn new(
         mod_items: Cow<'a, Vec<rustc_ast::ptr::P<ast::Item>>>,
         mod_attrs: Cow<'a, ast::AttrVec>,
     ) -> Self {
-        let inner_attr = mod_attrs
-            .iter()
-            .filter(|attr| attr.style == ast::AttrStyle::Inner)
-            .cloned()
-            .collect();
         Module {
             items: mod_items,
-            inner_attr,
+            inner_attr: inner_attrs(&mod_attrs),
             span: mod_span,
             ast_mod_kind,
+            enclosing_attrs: vec![],
         }
     }
 
     pub(crate) fn attrs(&self) -> &[ast::Attribute] {
         &self.inner_attr
     }
+
+    /// Returns the inner attributes of the modules which enclose this module, if it is the
+    /// module of a file other than the crate root, from the crate root down.
+    pub(crate) fn enclosing_attrs(&self) -> impl Iterator<Item = &[ast::Attribute]> {
+        self.enclosing_attrs.iter().map(|attrs| &**attrs)
+    }
 }
 
 /// Maps each module to the corresponding file.
@@ -61,6 +67,10 @@ pub(crate) struct ModResolver<'ast, 'sess> {
     directory: Directory,
     file_map: FileModMap<'ast>,
     recursive: bool,
+    // The inner attributes of the modules enclosing the module being visited.
+    enclosing_attrs: Vec<ast::AttrVec>,
+    // Where files which are skipped because of merge conflicts are reported.
+    report: FormatReport,
 }
 
 /// Represents errors while trying to resolve modules.
@@ -104,6 +114,7 @@ pub(crate) fn new(
         parse_sess: &'sess ParseSess,
         directory_ownership: DirectoryOwnership,
         recursive: bool,
+        report: FormatReport,
     ) -> Self {
         ModResolver {
             directory: Directory {
@@ -113,9 +124,23 @@ pub(crate) fn new(
             file_map: BTreeMap::new(),
             parse_sess,
             recursive,
+            enclosing_attrs: vec![],
+            report,
         }
     }
 
+    /// Reports that the file of `span`, the first line of a conflict, is skipped.
+    fn report_conflict_marker(&self, span: Span) {
+        self.report.append(
+            self.parse_sess.span_to_filename(span),
+            vec![FormattingError::from_span(
+                span,
+                self.parse_sess,
+                ErrorKind::ConflictMarker,
+            )],
+        );
+    }
+
     /// Creates a map that maps a file name to the module in AST.
     pub(crate) fn visit_crate(
         mut self,
@@ -129,6 +154,7 @@ pub(crate) fn visit_crate(
 
         // Skip visiting sub modules when the input is from stdin.
         if self.recursive {
+            self.enclosing_attrs.push(inner_attrs(&krate.attrs));
             self.visit_mod_from_ast(&krate.items)?;
         }
 
@@ -259,13 +285,15 @@ fn insert_sub_mod(
         sub_mod_kind: SubModKind<'c, 'ast>,
     ) -> Result<(), ModuleResolutionError> {
         match sub_mod_kind {
-            SubModKind::External(mod_path, _, sub_mod) => {
+            SubModKind::External(mod_path, _, mut sub_mod) => {
+                sub_mod.enclosing_attrs = self.enclosing_attrs.clone();
                 self.file_map
                     .entry(FileName::Real(mod_path))
                     .or_insert(sub_mod);
             }
             SubModKind::MultiExternal(mods) => {
-                for (mod_path, _, sub_mod) in mods {
+                for (mod_path, _, mut sub_mod) in mods {
+                    sub_mod.enclosing_attrs = self.enclosing_attrs.clone();
                     self.file_map
                         .entry(FileName::Real(mod_path))
                         .or_insert(sub_mod);
@@ -314,6 +342,13 @@ fn visit_sub_mod_after_directory_update(
         if let Some(directory) = directory {
             self.directory = directory;
         }
+        self.enclosing_attrs.push(sub_mod.inner_attr.clone());
+        let result = self.visit_sub_mod_items(sub_mod);
+        self.enclosing_attrs.pop();
+        result
+    }
+
+    fn visit_sub_mod_items(&mut self, sub_mod: Module<'ast>) -> Result<(), ModuleResolutionError> {
         match (sub_mod.ast_mod_kind, sub_mod.items) {
             (Some(Cow::Borrowed(ast::ModKind::Loaded(items, _, _))), _) => {
                 self.visit_mod_from_ast(items)
@@ -352,6 +387,10 @@ fn find_external_module(
                         Cow::Owned(attrs),
                     ),
                 ))),
+                Err(ParserError::ConflictMarker(span)) => {
+                    self.report_conflict_marker(span);
+                    Ok(None)
+                }
                 Err(ParserError::ParseError) => Err(ModuleResolutionError {
                     module: mod_name.to_string(),
                     kind: ModuleResolutionErrorKind::ParseError { file: path },
@@ -419,6 +458,14 @@ fn find_external_module(
                         }
                         Ok(Some(SubModKind::MultiExternal(mods_outside_ast)))
                     }
+                    Err(ParserError::ConflictMarker(span)) => {
+                        self.report_conflict_marker(span);
+                        if outside_mods_empty {
+                            Ok(None)
+                        } else {
+                            Ok(Some(SubModKind::MultiExternal(mods_outside_ast)))
+                        }
+                    }
                     Err(ParserError::ParseError) => Err(ModuleResolutionError {
                         module: mod_name.to_string(),
                         kind: ModuleResolutionErrorKind::ParseError { file: file_path },
@@ -529,6 +576,10 @@ fn find_mods_outside_of_ast(
                 match Parser::parse_file_as_module(self.parse_sess, &actual_path, sub_mod.span) {
                     Ok((ref attrs, _, _)) if contains_skip(attrs) => continue,
                     Ok(m) => m,
+                    Err(ParserError::ConflictMarker(span)) => {
+                        self.report_conflict_marker(span);
+                        continue;
+                    }
                     Err(..) => continue,
                 };
 
@@ -562,6 +613,14 @@ fn find_path_value(attrs: &[ast::Attribute]) -> Option<Symbol> {
     attrs.iter().flat_map(path_value).next()
 }
 
+fn inner_attrs(attrs: &[ast::Attribute]) -> ast::AttrVec {
+    attrs
+        .iter()
+        .filter(|attr| attr.style == ast::AttrStyle::Inner)
+        .cloned()
+        .collect()
+}
+
 fn is_cfg_if(item: &ast::Item) -> bool {
     match item.kind {
         ast::ItemKind::MacCall(ref mac) => {
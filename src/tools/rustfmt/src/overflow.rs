@@ -3,17 +3,19 @@
 use std::cmp::min;
 
 use itertools::Itertools;
-use rustc_ast::token::Delimiter;
+use rustc_ast::token::{Delimiter, LitKind};
 use rustc_ast::{ast, ptr};
 use rustc_span::Span;
 
 use crate::closures;
 use crate::config::lists::*;
-use crate::config::Version;
+use crate::config::{IndentStyle, Version};
 use crate::expr::{
     can_be_overflowed_expr, is_every_expr_simple, is_method_call, is_nested_call, is_simple_expr,
     rewrite_cond,
 };
+use crate::formatting::toggles::keep_suppressed_items;
+use crate::layout_cost::{layout_cost, OPTIMIZE_LAYOUT_MAX_LEN};
 use crate::lists::{
     definitive_tactic, itemize_list, write_list, ListFormatting, ListItem, Separator,
 };
@@ -65,7 +67,7 @@
     ("fail", 0),
 ];
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub(crate) enum OverflowableItem<'a> {
     Expr(&'a ast::Expr),
     GenericParam(&'a ast::GenericParam),
@@ -80,6 +82,12 @@ pub(crate) enum OverflowableItem<'a> {
 
 impl<'a> Rewrite for OverflowableItem<'a> {
     fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
+        if context.config.binop_args_indent() == IndentStyle::Block && self.is_binary() {
+            let old_value = context.block_indent_binop.replace(true);
+            let result = self.map(|item| item.rewrite(context, shape));
+            context.block_indent_binop.replace(old_value);
+            return result;
+        }
         self.map(|item| item.rewrite(context, shape))
     }
 }
@@ -149,6 +157,25 @@ pub(crate) fn is_nested_call(&self) -> bool {
         }
     }
 
+    /// Returns `true` if the item is a binary expression, possibly behind a prefix, a suffix
+    /// or parentheses.
+    fn is_binary(&self) -> bool {
+        self.to_expr().map_or(false, is_binary_expr)
+    }
+
+    /// Returns `true` if the item is a string literal spanning several lines which is kept
+    /// as it is written.
+    fn is_multi_line_str_lit(&self, context: &RewriteContext<'_>, rewrite: &str) -> bool {
+        match self.to_expr().map(|expr| &expr.kind) {
+            Some(ast::ExprKind::Lit(token_lit)) => {
+                matches!(token_lit.kind, LitKind::Str | LitKind::StrRaw(_))
+                    && rewrite.contains('\n')
+                    && rewrite == context.snippet(self.span())
+            }
+            _ => false,
+        }
+    }
+
     pub(crate) fn to_expr(&self) -> Option<&'a ast::Expr> {
         match self {
             OverflowableItem::Expr(expr) => Some(expr),
@@ -252,6 +279,7 @@ pub(crate) fn rewrite_with_parens<'a, T: 'a + IntoOverflowableItem<'a>>(
     span: Span,
     item_max_width: usize,
     force_separator_tactic: Option<SeparatorTactic>,
+    layout: ListLayout,
 ) -> Option<String> {
     Context::new(
         context,
@@ -264,6 +292,7 @@ pub(crate) fn rewrite_with_parens<'a, T: 'a + IntoOverflowableItem<'a>>(
         item_max_width,
         force_separator_tactic,
         None,
+        layout,
     )
     .rewrite(shape)
 }
@@ -286,6 +315,7 @@ pub(crate) fn rewrite_with_angle_brackets<'a, T: 'a + IntoOverflowableItem<'a>>(
         context.config.max_width(),
         None,
         None,
+        ListLayout::Auto,
     )
     .rewrite(shape)
 }
@@ -315,10 +345,12 @@ pub(crate) fn rewrite_with_square_brackets<'a, T: 'a + IntoOverflowableItem<'a>>
         context.config.array_width(),
         force_separator_tactic,
         Some(("[", "]")),
+        ListLayout::Auto,
     )
     .rewrite(shape)
 }
 
+#[derive(Clone)]
 struct Context<'a> {
     context: &'a RewriteContext<'a>,
     items: Vec<OverflowableItem<'a>>,
@@ -332,6 +364,7 @@ struct Context<'a> {
     one_line_width: usize,
     force_separator_tactic: Option<SeparatorTactic>,
     custom_delims: Option<(&'a str, &'a str)>,
+    layout: ListLayout,
 }
 
 impl<'a> Context<'a> {
@@ -346,6 +379,7 @@ fn new<T: 'a + IntoOverflowableItem<'a>>(
         item_max_width: usize,
         force_separator_tactic: Option<SeparatorTactic>,
         custom_delims: Option<(&'a str, &'a str)>,
+        layout: ListLayout,
     ) -> Context<'a> {
         let used_width = extra_offset(ident, shape);
         // 1 = `()`
@@ -370,6 +404,7 @@ fn new<T: 'a + IntoOverflowableItem<'a>>(
             one_line_width,
             force_separator_tactic,
             custom_delims,
+            layout,
         }
     }
 
@@ -377,12 +412,12 @@ fn last_item(&self) -> Option<&OverflowableItem<'_>> {
         self.items.last()
     }
 
-    fn items_span(&self) -> Span {
+    fn items_span(&self) -> Option<Span> {
         let span_lo = self
             .context
             .snippet_provider
-            .span_after(self.span, self.prefix);
-        mk_sp(span_lo, self.span.hi())
+            .span_after(self.span, self.prefix)?;
+        Some(mk_sp(span_lo, self.span.hi()))
     }
 
     fn rewrite_last_item_with_overflow(
@@ -445,15 +480,42 @@ fn default_tactic(&self, list_items: &[ListItem]) -> DefinitiveListTactic {
             ListTactic::LimitedHorizontalVertical(self.item_max_width),
             Separator::Comma,
             self.one_line_width,
+            self.context.config,
         )
     }
 
+    /// Returns `true` if the items fit on one line apart from the lines of multi-line string
+    /// literals, which are kept as they are. The other items must be on a single line.
+    fn fits_around_multi_line_str_lits(&self, list_items: &[ListItem]) -> bool {
+        let mut has_multi_line_str_lit = false;
+        for (item, list_item) in self.items.iter().zip(list_items) {
+            let rewrite = list_item.inner_as_ref();
+            if list_item.has_comment() || item.has_attrs() {
+                return false;
+            }
+            if item.is_multi_line_str_lit(self.context, rewrite) {
+                has_multi_line_str_lit = true;
+            } else if rewrite.contains('\n') {
+                return false;
+            }
+        }
+        let items_str = list_items.iter().map(ListItem::inner_as_ref).join(", ");
+        has_multi_line_str_lit
+            && first_line_width(&items_str) <= self.one_line_width
+            && last_line_width(&items_str) <= self.one_line_width
+    }
+
     fn try_overflow_last_item(&self, list_items: &mut Vec<ListItem>) -> DefinitiveListTactic {
         // 1 = "("
+        // With `binop_args_indent = "Block"`, a binary expression is not combined with the
+        // callee, as its continuation lines would be aligned with its first line rather than
+        // block indented like those of the other arguments.
         let combine_arg_with_callee = self.items.len() == 1
             && self.items[0].is_expr()
             && !self.items[0].has_attrs()
-            && self.ident.len() < self.context.config.tab_spaces();
+            && self.ident.len() < self.context.config.tab_spaces()
+            && !(self.context.config.binop_args_indent() == IndentStyle::Block
+                && self.items[0].is_binary());
         let overflow_last = combine_arg_with_callee || can_be_overflowed(self.context, &self.items);
 
         // Replace the last item with its first line to see if it fits with
@@ -498,6 +560,7 @@ fn try_overflow_last_item(&self, list_items: &mut Vec<ListItem>) -> DefinitiveLi
             ListTactic::LimitedHorizontalVertical(self.item_max_width),
             Separator::Comma,
             self.one_line_width,
+            self.context.config,
         );
 
         // Replace the stub with the full overflowing last argument if the rewrite
@@ -550,7 +613,11 @@ fn try_overflow_last_item(&self, list_items: &mut Vec<ListItem>) -> DefinitiveLi
                     tactic = self.default_tactic(list_items);
 
                     if tactic == DefinitiveListTactic::Vertical {
-                        if let Some((all_simple, num_args_before)) =
+                        if self.context.config.version() == Version::Two
+                            && self.fits_around_multi_line_str_lits(list_items)
+                        {
+                            tactic = DefinitiveListTactic::Horizontal;
+                        } else if let Some((all_simple, num_args_before)) =
                             maybe_get_args_offset(self.ident, &self.items)
                         {
                             let one_line = all_simple
@@ -559,18 +626,21 @@ fn try_overflow_last_item(&self, list_items: &mut Vec<ListItem>) -> DefinitiveLi
                                     ListTactic::HorizontalVertical,
                                     Separator::Comma,
                                     self.nested_shape.width,
+                                    self.context.config,
                                 ) == DefinitiveListTactic::Horizontal
                                 && definitive_tactic(
                                     &list_items[num_args_before + 1..],
                                     ListTactic::HorizontalVertical,
                                     Separator::Comma,
                                     self.nested_shape.width,
+                                    self.context.config,
                                 ) == DefinitiveListTactic::Horizontal;
 
                             if one_line {
                                 tactic = DefinitiveListTactic::SpecialMacro(num_args_before);
                             };
-                        } else if is_every_expr_simple(&self.items)
+                        } else if !self.context.config.prefer_stable_diffs()
+                            && is_every_expr_simple(&self.items)
                             && no_long_items(
                                 list_items,
                                 self.context.config.short_array_element_width_threshold(),
@@ -587,8 +657,38 @@ fn try_overflow_last_item(&self, list_items: &mut Vec<ListItem>) -> DefinitiveLi
         tactic
     }
 
+    /// Returns the tactic for the layout set in the config, which overrides `tactic` unless
+    /// it puts all items on one line.
+    fn apply_layout(
+        &self,
+        list_items: &mut [ListItem],
+        tactic: DefinitiveListTactic,
+    ) -> DefinitiveListTactic {
+        match self.layout.to_list_tactic() {
+            Some(list_tactic)
+                if !self.items.is_empty()
+                    && (list_tactic == ListTactic::Vertical
+                        || tactic != DefinitiveListTactic::Horizontal) =>
+            {
+                // The last item is not overflowed with any of these layouts.
+                list_items[self.items.len() - 1].item = self
+                    .items
+                    .last()
+                    .and_then(|last_item| last_item.rewrite(self.context, self.nested_shape));
+                definitive_tactic(
+                    &*list_items,
+                    list_tactic,
+                    Separator::Comma,
+                    self.one_line_width,
+                    self.context.config,
+                )
+            }
+            _ => tactic,
+        }
+    }
+
     fn rewrite_items(&self) -> Option<(bool, String)> {
-        let span = self.items_span();
+        let span = self.items_span()?;
         let items = itemize_list(
             self.context.snippet_provider,
             self.items.iter(),
@@ -603,10 +703,31 @@ fn rewrite_items(&self) -> Option<(bool, String)> {
         );
         let mut list_items: Vec<_> = items.collect();
 
-        // Try letting the last argument overflow to the next line with block
-        // indentation. If its first line fits on one line with the other arguments,
-        // we format the function arguments horizontally.
-        let tactic = self.try_overflow_last_item(&mut list_items);
+        // The elements of an array between `// rustfmt: off` and `// rustfmt: on` comments,
+        // e.g. the rows of a table, are kept as they are.
+        let is_array = self.custom_delims.is_some()
+            && self.items.iter().all(|item| {
+                matches!(
+                    item,
+                    OverflowableItem::Expr(..) | OverflowableItem::MacroArg(..)
+                )
+            });
+        let tactic = if is_array
+            && keep_suppressed_items(
+                self.context.snippet_provider,
+                span,
+                &self.items,
+                &mut list_items,
+                false,
+            ) {
+            DefinitiveListTactic::Vertical
+        } else {
+            // Try letting the last argument overflow to the next line with block
+            // indentation. If its first line fits on one line with the other arguments,
+            // we format the function arguments horizontally.
+            let tactic = self.try_overflow_last_item(&mut list_items);
+            self.apply_layout(&mut list_items, tactic)
+        };
         let trailing_separator = if let Some(tactic) = self.force_separator_tactic {
             tactic
         } else if !self.context.use_block_indent() {
@@ -682,6 +803,47 @@ fn wrap_items(&self, items_str: &str, shape: Shape, is_extendable: bool) -> Stri
     }
 
     fn rewrite(&self, shape: Shape) -> Option<String> {
+        if !self.can_optimize_layout() {
+            return self.rewrite_first_fit(shape);
+        }
+
+        // Nested lists keep their first layout which fits, so that the number of
+        // alternatives does not grow exponentially with the nesting depth.
+        self.context.optimizing_layout.replace(true);
+        let first_fit = self.rewrite_first_fit(shape);
+        let vertical = Context {
+            one_line_width: 0,
+            ..self.clone()
+        }
+        .rewrite_first_fit(shape);
+        self.context.optimizing_layout.replace(false);
+
+        match (first_fit, vertical) {
+            (Some(first_fit), Some(vertical)) => {
+                let config = self.context.config;
+                let offset = shape.used_width();
+                if layout_cost(&vertical, offset, config) < layout_cost(&first_fit, offset, config)
+                {
+                    Some(vertical)
+                } else {
+                    Some(first_fit)
+                }
+            }
+            (first_fit, vertical) => first_fit.or(vertical),
+        }
+    }
+
+    /// Returns `true` if the vertical layout of the items is to be compared with the first
+    /// layout which fits, see `optimize_layout`.
+    fn can_optimize_layout(&self) -> bool {
+        self.context.config.optimize_layout()
+            && !self.context.optimizing_layout.get()
+            && !self.items.is_empty()
+            && self.one_line_width != 0
+            && self.context.snippet(self.span).len() <= OPTIMIZE_LAYOUT_MAX_LEN
+    }
+
+    fn rewrite_first_fit(&self, shape: Shape) -> Option<String> {
         let (extendable, items_str) = self.rewrite_items()?;
 
         // If we are using visual indent style and failed to format, retry with block indent.
@@ -690,7 +852,7 @@ fn rewrite(&self, shape: Shape) -> Option<String> {
             && !extendable
         {
             self.context.use_block.replace(true);
-            let result = self.rewrite(shape);
+            let result = self.rewrite_first_fit(shape);
             self.context.use_block.replace(false);
             return result;
         }
@@ -699,6 +861,19 @@ fn rewrite(&self, shape: Shape) -> Option<String> {
     }
 }
 
+fn is_binary_expr(expr: &ast::Expr) -> bool {
+    match expr.kind {
+        ast::ExprKind::Binary(..) => true,
+        ast::ExprKind::AddrOf(_, _, ref expr)
+        | ast::ExprKind::Box(ref expr)
+        | ast::ExprKind::Try(ref expr)
+        | ast::ExprKind::Unary(_, ref expr)
+        | ast::ExprKind::Cast(ref expr, _)
+        | ast::ExprKind::Paren(ref expr) => is_binary_expr(expr),
+        _ => false,
+    }
+}
+
 fn need_block_indent(s: &str, shape: Shape) -> bool {
     s.lines().skip(1).any(|s| {
         s.find(|c| !char::is_whitespace(c))
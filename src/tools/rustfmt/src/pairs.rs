@@ -34,10 +34,13 @@ pub(crate) fn rewrite_all_pairs(
     shape: Shape,
     context: &RewriteContext<'_>,
 ) -> Option<String> {
+    // Only the outermost binary expression of an argument is block indented, not the ones
+    // nested in its operands.
+    let block_indent = context.block_indent_binop.replace(false);
     expr.flatten(context, shape).and_then(|list| {
         // First we try formatting on one line.
         rewrite_pairs_one_line(&list, shape, context)
-            .or_else(|| rewrite_pairs_multiline(&list, shape, context))
+            .or_else(|| rewrite_pairs_multiline(&list, shape, context, block_indent))
     })
 }
 
@@ -93,11 +96,12 @@ fn rewrite_pairs_multiline<T: Rewrite>(
     list: &PairList<'_, '_, T>,
     shape: Shape,
     context: &RewriteContext<'_>,
+    block_indent: bool,
 ) -> Option<String> {
     let rhs_offset = shape.rhs_overhead(context.config);
     let nested_shape = (match context.config.indent_style() {
-        IndentStyle::Visual => shape.visual_indent(0),
-        IndentStyle::Block => shape.block_indent(context.config.tab_spaces()),
+        IndentStyle::Visual if !block_indent => shape.visual_indent(0),
+        IndentStyle::Visual | IndentStyle::Block => shape.block_indent(context.config.tab_spaces()),
     })
     .with_max_width(context.config)
     .sub_width(rhs_offset)?;
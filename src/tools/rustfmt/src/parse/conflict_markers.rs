@@ -0,0 +1,58 @@
+//! Detection of the conflict markers which git leaves in files with merge conflicts, so that
+//! such files are skipped and reported as errors rather than failing to parse, or being
+//! formatted with only one side of the conflict.
+
+/// Returns the line number of the first conflict in `src`, i.e. a `<<<<<<<` line followed by
+/// a `=======` line and a `>>>>>>>` line, each at the start of the line.
+pub(crate) fn find_conflict_marker(src: &str) -> Option<usize> {
+    let mut start = None;
+    let mut separated = false;
+    for (i, line) in src.lines().enumerate() {
+        if is_marker(line, '<') {
+            start = Some(i + 1);
+            separated = false;
+        } else if start.is_some() && line.trim_end() == "=======" {
+            separated = true;
+        } else if separated && is_marker(line, '>') {
+            return start;
+        }
+    }
+    None
+}
+
+/// Returns whether `line` is a marker of seven `c`, optionally followed by a label.
+fn is_marker(line: &str, c: char) -> bool {
+    let rest = match line.get(..7) {
+        Some(marker) if marker.chars().all(|m| m == c) => &line[7..],
+        _ => return false,
+    };
+    rest.is_empty() || rest.starts_with(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_conflicts() {
+        let src = "fn main() {\n<<<<<<< HEAD\n    a();\n=======\n    b();\n>>>>>>> branch\n}\n";
+        assert_eq!(find_conflict_marker(src), Some(2));
+
+        let diff3 = "<<<<<<< ours\r\na\r\n||||||| base\r\n=======\r\nb\r\n>>>>>>> theirs\r\n";
+        assert_eq!(find_conflict_marker(diff3), Some(1));
+    }
+
+    #[test]
+    fn ignores_partial_markers() {
+        assert_eq!(find_conflict_marker("// =======\nfn main() {}\n"), None);
+        assert_eq!(
+            find_conflict_marker("<<<<<<<< a\n=======\n>>>>>>> b\n"),
+            None
+        );
+        assert_eq!(find_conflict_marker("<<<<<<< a\n>>>>>>> b\n"), None);
+        assert_eq!(
+            find_conflict_marker("let s = \"\n=======\n>>>>>>> b\n\";\n"),
+            None
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use rustc_ast::token::{Delimiter, TokenKind};
 use rustc_ast::tokenstream::TokenStream;
 use rustc_ast::{ast, ptr};
-use rustc_parse::parser::{ForceCollect, Parser};
+use rustc_parse::parser::{AttemptLocalParseRecovery, ForceCollect, Parser};
 use rustc_parse::{stream_to_parser, MACRO_ARGUMENTS};
 use rustc_session::parse::ParseSess;
 use rustc_span::symbol::{self, kw};
@@ -166,6 +166,31 @@ pub(crate) fn parse_expr(
     parser.parse_expr().ok()
 }
 
+/// Parses the body of a brace-delimited macro call as the statements of a block.
+pub(crate) fn parse_macro_stmts(
+    context: &RewriteContext<'_>,
+    tokens: TokenStream,
+) -> Option<Vec<ast::Stmt>> {
+    let mut parser = build_parser(context, tokens);
+    let mut stmts = vec![];
+    while parser.token.kind != TokenKind::Eof {
+        match parser.parse_full_stmt(AttemptLocalParseRecovery::No) {
+            Ok(Some(stmt)) => stmts.push(stmt),
+            Ok(None) => return None,
+            Err(e) => {
+                e.cancel();
+                parser.sess.span_diagnostic.reset_err_count();
+                return None;
+            }
+        }
+    }
+    if parser.sess.span_diagnostic.has_errors().is_some() {
+        parser.sess.span_diagnostic.reset_err_count();
+        return None;
+    }
+    Some(stmts)
+}
+
 const RUST_KW: [Symbol; 59] = [
     kw::PathRoot,
     kw::DollarCrate,
@@ -1,3 +1,4 @@
+pub(crate) mod conflict_markers;
 pub(crate) mod macros;
 pub(crate) mod parser;
 pub(crate) mod session;
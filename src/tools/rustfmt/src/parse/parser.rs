@@ -5,10 +5,12 @@
 use rustc_ast::{ast, ptr};
 use rustc_errors::Diagnostic;
 use rustc_parse::{new_parser_from_file, parser::Parser as RawParser};
-use rustc_span::{sym, Span};
+use rustc_span::{sym, FileName, Span};
 
 use crate::attr::first_attr_value_str_by_name;
+use crate::parse::conflict_markers::find_conflict_marker;
 use crate::parse::session::ParseSess;
+use crate::utils::mk_sp;
 use crate::Input;
 
 pub(crate) type DirectoryOwnership = rustc_expand::module::DirOwnership;
@@ -47,8 +49,17 @@ pub(crate) fn sess(mut self, sess: &'a ParseSess) -> ParserBuilder<'a> {
     pub(crate) fn build(self) -> Result<Parser<'a>, ParserError> {
         let sess = self.sess.ok_or(ParserError::NoParseSess)?;
         let input = self.input.ok_or(ParserError::NoInput)?;
+        let file_name = match input {
+            Input::File(ref file) => FileName::from(file.clone()),
+            Input::Text(_) => FileName::Custom("stdin".to_owned()),
+        };
 
-        let parser = match Self::parser(sess.inner(), input) {
+        let parser = Self::parser(sess.inner(), input);
+        // The conflict may also be why the file could not be lexed.
+        if let Some(span) = find_loaded_conflict_marker(sess.inner(), &file_name) {
+            return Err(ParserError::ConflictMarker(span));
+        }
+        let parser = match parser {
             Ok(p) => p,
             Err(db) => {
                 if let Some(diagnostics) = db {
@@ -88,6 +99,20 @@ pub(crate) enum ParserError {
     ParserCreationError,
     ParseError,
     ParsePanicError,
+    /// The file contains git conflict markers, the first of them on the line of the span.
+    ConflictMarker(Span),
+}
+
+/// Returns the span of the first line of the first conflict of the file `file_name`, which the
+/// parser has already loaded, see `find_conflict_marker`.
+fn find_loaded_conflict_marker(
+    sess: &rustc_session::parse::ParseSess,
+    file_name: &FileName,
+) -> Option<Span> {
+    let source_file = sess.source_map().get_source_file(file_name)?;
+    let line = find_conflict_marker(source_file.src.as_deref()?)?;
+    let bounds = source_file.line_bounds(line - 1);
+    Some(mk_sp(bounds.start, bounds.end))
 }
 
 impl<'a> Parser<'a> {
@@ -110,28 +135,37 @@ pub(crate) fn parse_file_as_module(
         path: &Path,
         span: Span,
     ) -> Result<(ast::AttrVec, Vec<ptr::P<ast::Item>>, Span), ParserError> {
+        let file_name = FileName::from(path.to_path_buf());
         let result = catch_unwind(AssertUnwindSafe(|| {
             let mut parser = new_parser_from_file(sess.inner(), path, Some(span));
+            if let Some(span) = find_loaded_conflict_marker(sess.inner(), &file_name) {
+                return Err(span);
+            }
             match parser.parse_mod(&TokenKind::Eof) {
-                Ok((a, i, spans)) => Some((a, i, spans.inner_span)),
+                Ok((a, i, spans)) => Ok(Some((a, i, spans.inner_span))),
                 Err(mut e) => {
                     e.emit();
                     if sess.can_reset_errors() {
                         sess.reset_errors();
                     }
-                    None
+                    Ok(None)
                 }
             }
         }));
         match result {
-            Ok(Some(m)) if !sess.has_errors() => Ok(m),
-            Ok(Some(m)) if sess.can_reset_errors() => {
+            Ok(Err(span)) => Err(ParserError::ConflictMarker(span)),
+            Ok(Ok(Some(m))) if !sess.has_errors() => Ok(m),
+            Ok(Ok(Some(m))) if sess.can_reset_errors() => {
                 sess.reset_errors();
                 Ok(m)
             }
             Ok(_) => Err(ParserError::ParseError),
-            Err(..) if path.exists() => Err(ParserError::ParseError),
-            Err(_) => Err(ParserError::ParsePanicError),
+            // The conflict may also be why the file could not be lexed.
+            Err(..) => match find_loaded_conflict_marker(sess.inner(), &file_name) {
+                Some(span) => Err(ParserError::ConflictMarker(span)),
+                None if path.exists() => Err(ParserError::ParseError),
+                None => Err(ParserError::ParsePanicError),
+            },
         }
     }
 
@@ -156,6 +156,15 @@ pub(crate) fn new(config: &Config) -> Result<ParseSess, ErrorKind> {
             Ok(ignore_path_set) => Lrc::new(ignore_path_set),
             Err(e) => return Err(ErrorKind::InvalidGlobPattern(e)),
         };
+        Ok(ParseSess::with_ignore_path_set(config, ignore_path_set))
+    }
+
+    /// Creates a `ParseSess` which ignores the files of `ignore_path_set`, which must have been
+    /// built from the `ignore` option of `config`, e.g. by [`ParseSess::new`].
+    pub(crate) fn with_ignore_path_set(
+        config: &Config,
+        ignore_path_set: Lrc<IgnorePathSet>,
+    ) -> ParseSess {
         let source_map = Lrc::new(SourceMap::new(FilePathMapping::empty()));
         let can_reset_errors = Lrc::new(AtomicBool::new(false));
 
@@ -167,11 +176,15 @@ pub(crate) fn new(config: &Config) -> Result<ParseSess, ErrorKind> {
         );
         let parse_sess = RawParseSess::with_span_handler(handler, source_map);
 
-        Ok(ParseSess {
+        ParseSess {
             parse_sess,
             ignore_path_set,
             can_reset_errors,
-        })
+        }
+    }
+
+    pub(crate) fn ignore_path_set(&self) -> Lrc<IgnorePathSet> {
+        Lrc::clone(&self.ignore_path_set)
     }
 
     /// Determine the submodule path for the given module identifier.
@@ -4,10 +4,13 @@
 use rustc_ast::ptr;
 use rustc_span::{BytePos, Span};
 
-use crate::comment::{combine_strs_with_missing_comments, FindUncommented};
+use crate::comment::{combine_strs_with_missing_comments, contains_comment, FindUncommented};
 use crate::config::lists::*;
 use crate::config::Version;
-use crate::expr::{can_be_overflowed_expr, rewrite_unary_prefix, wrap_struct_field};
+use crate::expr::{
+    can_be_overflowed_expr, rewrite_keyword_prefix, rewrite_unary_prefix, struct_lit_str,
+    wrap_struct_field,
+};
 use crate::lists::{
     definitive_tactic, itemize_list, shape_for_tactic, struct_lit_formatting, struct_lit_shape,
     struct_lit_tactic, write_list, ListFormatting, ListItem, Separator,
@@ -19,8 +22,10 @@
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
 use crate::spanned::Spanned;
-use crate::types::{rewrite_path, PathContext};
-use crate::utils::{format_mutability, mk_sp, mk_sp_lo_plus_one, rewrite_ident};
+use crate::types::{rewrite_path, rewrite_path_with_broken_qself, PathContext};
+use crate::utils::{
+    format_mutability, keyword_prefix, mk_sp, mk_sp_lo_plus_one, rewrite_ident, spaced_range_delim,
+};
 
 /// Returns `true` if the given pattern is "short".
 /// A short pattern is defined by the following grammar:
@@ -69,38 +74,68 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
     }
 }
 
+/// Rewrites `pat` where it follows a keyword, e.g. in `if let`. With `Version::Two`, the lines
+/// an or-pattern wraps onto are then block indented from the line it starts on.
+pub(crate) fn rewrite_pattern_after_keyword(
+    context: &RewriteContext<'_>,
+    pat: &ast::Pat,
+    shape: Shape,
+) -> Option<String> {
+    match pat.kind {
+        PatKind::Or(ref pats)
+            if context.config.version() == Version::Two && context.config.format_patterns() =>
+        {
+            rewrite_or_pattern(context, pats, shape, true)
+        }
+        _ => pat.rewrite(context, shape),
+    }
+}
+
+fn rewrite_or_pattern(
+    context: &RewriteContext<'_>,
+    pats: &[ptr::P<ast::Pat>],
+    shape: Shape,
+    hanging_indent: bool,
+) -> Option<String> {
+    let pat_strs = pats
+        .iter()
+        .map(|p| p.rewrite(context, shape))
+        .collect::<Option<Vec<_>>>()?;
+
+    let use_mixed_layout = !context.config.prefer_stable_diffs()
+        && pats
+            .iter()
+            .zip(pat_strs.iter())
+            .all(|(pat, pat_str)| is_short_pattern(pat, pat_str));
+    let items: Vec<_> = pat_strs.into_iter().map(ListItem::from_str).collect();
+    let tactic = if use_mixed_layout {
+        DefinitiveListTactic::Mixed
+    } else {
+        definitive_tactic(
+            &items,
+            ListTactic::HorizontalVertical,
+            Separator::VerticalBar,
+            shape.width,
+            context.config,
+        )
+    };
+    let fmt = ListFormatting::new(shape, context.config)
+        .tactic(tactic)
+        .separator(" |")
+        .separator_place(context.config.binop_separator())
+        .ends_with_newline(false)
+        .hanging_indent(hanging_indent);
+    write_list(&items, &fmt)
+}
+
 impl Rewrite for Pat {
     fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
+        if !context.config.format_patterns() {
+            return Some(context.snippet(self.span).to_owned());
+        }
         match self.kind {
-            PatKind::Or(ref pats) => {
-                let pat_strs = pats
-                    .iter()
-                    .map(|p| p.rewrite(context, shape))
-                    .collect::<Option<Vec<_>>>()?;
-
-                let use_mixed_layout = pats
-                    .iter()
-                    .zip(pat_strs.iter())
-                    .all(|(pat, pat_str)| is_short_pattern(pat, pat_str));
-                let items: Vec<_> = pat_strs.into_iter().map(ListItem::from_str).collect();
-                let tactic = if use_mixed_layout {
-                    DefinitiveListTactic::Mixed
-                } else {
-                    definitive_tactic(
-                        &items,
-                        ListTactic::HorizontalVertical,
-                        Separator::VerticalBar,
-                        shape.width,
-                    )
-                };
-                let fmt = ListFormatting::new(shape, context.config)
-                    .tactic(tactic)
-                    .separator(" |")
-                    .separator_place(context.config.binop_separator())
-                    .ends_with_newline(false);
-                write_list(&items, &fmt)
-            }
-            PatKind::Box(ref pat) => rewrite_unary_prefix(context, "box ", &**pat, shape),
+            PatKind::Or(ref pats) => rewrite_or_pattern(context, pats, shape, false),
+            PatKind::Box(ref pat) => rewrite_keyword_prefix(context, &["box"], &**pat, shape),
             PatKind::Ident(BindingAnnotation(by_ref, mutability), ident, ref sub_pat) => {
                 let prefix = match by_ref {
                     ByRef::Yes => "ref",
@@ -110,11 +145,17 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 let id_str = rewrite_ident(context, ident);
                 let sub_pat = match *sub_pat {
                     Some(ref p) => {
-                        // 2 - `@ `.
-                        let width = shape
-                            .width
-                            .checked_sub(prefix.len() + mut_infix.len() + id_str.len() + 2)?;
-                        let lo = context.snippet_provider.span_after(self.span, "@");
+                        let width = if context.config.version() == Version::One {
+                            // 2 - `@ `.
+                            shape
+                                .width
+                                .checked_sub(prefix.len() + mut_infix.len() + id_str.len() + 2)?
+                        } else {
+                            // The sub-pattern follows `ref mut ident @ `; 3 = ` @ `.
+                            let lead_width = keyword_prefix(&[prefix, mut_infix]).len();
+                            shape.width.checked_sub(lead_width + id_str.len() + 3)?
+                        };
+                        let lo = context.snippet_provider.span_after(self.span, "@")?;
                         combine_strs_with_missing_comments(
                             context,
                             "@",
@@ -129,10 +170,10 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
 
                 // combine prefix and mut
                 let (first_lo, first) = if !prefix.is_empty() && !mut_infix.is_empty() {
-                    let hi = context.snippet_provider.span_before(self.span, "mut");
-                    let lo = context.snippet_provider.span_after(self.span, "ref");
+                    let hi = context.snippet_provider.span_before(self.span, "mut")?;
+                    let lo = context.snippet_provider.span_after(self.span, "ref")?;
                     (
-                        context.snippet_provider.span_after(self.span, "mut"),
+                        context.snippet_provider.span_after(self.span, "mut")?,
                         combine_strs_with_missing_comments(
                             context,
                             prefix,
@@ -144,12 +185,12 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                     )
                 } else if !prefix.is_empty() {
                     (
-                        context.snippet_provider.span_after(self.span, "ref"),
+                        context.snippet_provider.span_after(self.span, "ref")?,
                         prefix.to_owned(),
                     )
                 } else if !mut_infix.is_empty() {
                     (
-                        context.snippet_provider.span_after(self.span, "mut"),
+                        context.snippet_provider.span_after(self.span, "mut")?,
                         mut_infix.to_owned(),
                     )
                 } else {
@@ -157,7 +198,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 };
 
                 let next = if !sub_pat.is_empty() {
-                    let hi = context.snippet_provider.span_before(self.span, "@");
+                    let hi = context.snippet_provider.span_before(self.span, "@")?;
                     combine_strs_with_missing_comments(
                         context,
                         id_str,
@@ -187,7 +228,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 }
             }
             PatKind::Rest => {
-                if 1 <= shape.width {
+                if 2 <= shape.width {
                     Some("..".to_owned())
                 } else {
                     None
@@ -199,19 +240,8 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                     RangeEnd::Included(RangeSyntax::DotDotEq) => "..=",
                     RangeEnd::Excluded => "..",
                 };
-                let infix = if context.config.spaces_around_ranges() {
-                    let lhs_spacing = match lhs {
-                        None => "",
-                        Some(_) => " ",
-                    };
-                    let rhs_spacing = match rhs {
-                        None => "",
-                        Some(_) => " ",
-                    };
-                    format!("{}{}{}", lhs_spacing, infix, rhs_spacing)
-                } else {
-                    infix.to_owned()
-                };
+                let infix = spaced_range_delim(context.config, infix, lhs.is_some(), rhs.is_some())
+                    .unwrap_or_else(|| infix.to_owned());
                 rewrite_pair(
                     &RangeOperand(lhs),
                     &RangeOperand(rhs),
@@ -222,19 +252,31 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 )
             }
             PatKind::Ref(ref pat, mutability) => {
-                let prefix = format!("&{}", format_mutability(mutability));
+                let prefix = format!("&{}", keyword_prefix(&[format_mutability(mutability)]));
                 rewrite_unary_prefix(context, &prefix, &**pat, shape)
             }
             PatKind::Tuple(ref items) => rewrite_tuple_pat(items, None, self.span, context, shape),
             PatKind::Path(ref q_self, ref path) => {
-                rewrite_path(context, PathContext::Expr, q_self, path, shape)
+                rewrite_path(context, PathContext::Expr, q_self, path, shape).or_else(|| {
+                    // A qualified self which does not fit on the line of the pattern is put on
+                    // a line of its own.
+                    let qself = q_self
+                        .as_ref()
+                        .filter(|_| context.config.version() == Version::Two)?;
+                    rewrite_path_with_broken_qself(context, PathContext::Expr, qself, path, shape)
+                })
             }
             PatKind::TupleStruct(ref q_self, ref path, ref pat_vec) => {
                 let path_str = rewrite_path(context, PathContext::Expr, q_self, path, shape)?;
                 rewrite_tuple_pat(pat_vec, Some(path_str), self.span, context, shape)
             }
             PatKind::Lit(ref expr) => expr.rewrite(context, shape),
-            PatKind::Slice(ref slice_pat) if context.config.version() == Version::One => {
+            // Comments between the elements (e.g. around a `..` rest pattern) are only kept
+            // by the list formatting below.
+            PatKind::Slice(ref slice_pat)
+                if context.config.version() == Version::One
+                    && !contains_comment(context.snippet(self.span)) =>
+            {
                 let rw: Vec<String> = slice_pat
                     .iter()
                     .map(|p| {
@@ -280,10 +322,26 @@ fn rewrite_struct_pat(
 ) -> Option<String> {
     // 2 =  ` {`
     let path_shape = shape.sub_width(2)?;
-    let path_str = rewrite_path(context, PathContext::Expr, qself, path, path_shape)?;
+    let (path_str, brace_on_next_line) =
+        match rewrite_path(context, PathContext::Expr, qself, path, path_shape) {
+            Some(path_str) => (path_str, false),
+            // The path alone (nearly) fills the line, so the brace goes on the next line.
+            None => (
+                rewrite_path(context, PathContext::Expr, qself, path, shape)?,
+                true,
+            ),
+        };
+    let brace_sep = if brace_on_next_line {
+        shape.indent.to_string_with_newline(context.config)
+    } else {
+        " ".into()
+    };
 
     if fields.is_empty() && !ellipsis {
-        return Some(format!("{} {{}}", path_str));
+        if brace_on_next_line {
+            return Some(format!("{}{}{{}}", path_str, brace_sep));
+        }
+        return Some(struct_lit_str(context, &path_str, ""));
     }
 
     let (ellipsis_str, terminator) = if ellipsis { (", ..", "..") } else { ("", "}") };
@@ -291,6 +349,14 @@ fn rewrite_struct_pat(
     // 3 = ` { `, 2 = ` }`.
     let (h_shape, v_shape) =
         struct_lit_shape(shape, context, path_str.len() + 3, ellipsis_str.len() + 2)?;
+    let h_shape = h_shape.filter(|_| !brace_on_next_line);
+    // A field on a line of its own is followed by a comma, which its pattern, however deeply
+    // nested, must leave room for.
+    let field_shape = if context.config.version() == Version::Two {
+        v_shape.sub_width(1)?
+    } else {
+        v_shape
+    };
 
     let items = itemize_list(
         context.snippet_provider,
@@ -305,14 +371,14 @@ fn rewrite_struct_pat(
             }
         },
         |f| f.span.hi(),
-        |f| f.rewrite(context, v_shape),
-        context.snippet_provider.span_after(span, "{"),
+        |f| f.rewrite(context, field_shape),
+        context.snippet_provider.span_after(span, "{")?,
         span.hi(),
         false,
     );
     let item_vec = items.collect::<Vec<_>>();
 
-    let tactic = struct_lit_tactic(h_shape, context, &item_vec);
+    let tactic = struct_lit_tactic(h_shape, context, &item_vec, context.config.pattern_layout());
     let nested_shape = shape_for_tactic(tactic, h_shape, v_shape);
     let fmt = struct_lit_formatting(nested_shape, tactic, context, false);
 
@@ -344,7 +410,11 @@ fn rewrite_struct_pat(
 
     // ast::Pat doesn't have attrs so use &[]
     let fields_str = wrap_struct_field(context, &[], &fields_str, shape, v_shape, one_line_width)?;
-    Some(format!("{} {{{}}}", path_str, fields_str))
+    if brace_on_next_line {
+        Some(format!("{}{}{{{}}}", path_str, brace_sep, fields_str))
+    } else {
+        Some(struct_lit_str(context, &path_str, &fields_str))
+    }
 }
 
 impl Rewrite for PatField {
@@ -361,8 +431,11 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
             self.attrs.rewrite(context, shape)?
         };
 
-        let pat_str = self.pat.rewrite(context, shape)?;
         if self.is_shorthand {
+            let pat_str = match rewrite_shorthand_binding(context, self) {
+                Some(pat_str) => pat_str,
+                None => self.pat.rewrite(context, shape)?,
+            };
             combine_strs_with_missing_comments(
                 context,
                 &attrs_str,
@@ -374,16 +447,35 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         } else {
             let nested_shape = shape.block_indent(context.config.tab_spaces());
             let id_str = rewrite_ident(context, self.ident);
-            let one_line_width = id_str.len() + 2 + pat_str.len();
-            let pat_and_id_str = if one_line_width <= shape.width {
-                format!("{}: {}", id_str, pat_str)
+            let one_line_pat = if context.config.version() == Version::One {
+                let pat_str = self.pat.rewrite(context, shape)?;
+                let one_line_width = id_str.len() + 2 + pat_str.len();
+                Some(pat_str).filter(|_| one_line_width <= shape.width)
             } else {
-                format!(
-                    "{}:\n{}{}",
-                    id_str,
-                    nested_shape.indent.to_string(context.config),
-                    self.pat.rewrite(context, nested_shape)?
-                )
+                // The pattern follows `ident: `, so it only gets what is left of the line.
+                // 2 = `: `
+                shape
+                    .offset_left(id_str.len() + 2)
+                    .and_then(|pat_shape| self.pat.rewrite(context, pat_shape))
+                    .filter(|pat_str| !pat_str.contains('\n'))
+            };
+            let pat_and_id_str = match one_line_pat {
+                Some(pat_str) => format!("{}: {}", id_str, pat_str),
+                None => {
+                    // With `version = Two`, the pattern on the next line is left as much room as
+                    // the field had.
+                    let next_line_shape = if context.config.version() == Version::Two {
+                        nested_shape.sub_width(context.config.tab_spaces())?
+                    } else {
+                        nested_shape
+                    };
+                    format!(
+                        "{}:\n{}{}",
+                        id_str,
+                        nested_shape.indent.to_string(context.config),
+                        self.pat.rewrite(context, next_line_shape)?
+                    )
+                }
             };
             combine_strs_with_missing_comments(
                 context,
@@ -397,6 +489,33 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
     }
 }
 
+/// Rewrites the shorthand field pattern `field` if it binds the field with binding modes,
+/// e.g. `box ref mut x` in `Foo { box ref mut x }`, keeping the shorthand form.
+fn rewrite_shorthand_binding(context: &RewriteContext<'_>, field: &PatField) -> Option<String> {
+    let (box_str, binding) = match field.pat.kind {
+        PatKind::Box(ref pat) => ("box", pat),
+        _ => ("", &field.pat),
+    };
+    let (by_ref, mutability) = match binding.kind {
+        PatKind::Ident(BindingAnnotation(by_ref, mutability), _, None) => (by_ref, mutability),
+        _ => return None,
+    };
+    // Comments between the keywords cannot be placed anywhere else.
+    let snippet = context.snippet(field.pat.span);
+    if contains_comment(snippet) {
+        return Some(snippet.to_owned());
+    }
+    let ref_str = match by_ref {
+        ByRef::Yes => "ref",
+        ByRef::No => "",
+    };
+    Some(format!(
+        "{}{}",
+        keyword_prefix(&[box_str, ref_str, format_mutability(mutability)]),
+        rewrite_ident(context, field.ident)
+    ))
+}
+
 #[derive(Debug)]
 pub(crate) enum TuplePatField<'a> {
     Pat(&'a ptr::P<ast::Pat>),
@@ -495,6 +614,7 @@ fn rewrite_tuple_pat(
         } else {
             None
         },
+        context.config.pattern_layout(),
     )
 }
 
@@ -505,6 +625,10 @@ fn count_wildcard_suffix_len(
     shape: Shape,
 ) -> usize {
     let mut suffix_len = 0;
+    let list_lo = match context.snippet_provider.span_after(span, "(") {
+        Some(list_lo) => list_lo,
+        None => return suffix_len,
+    };
 
     let items: Vec<_> = itemize_list(
         context.snippet_provider,
@@ -514,7 +638,7 @@ fn count_wildcard_suffix_len(
         |item| item.span().lo(),
         |item| item.span().hi(),
         |item| item.rewrite(context, shape),
-        context.snippet_provider.span_after(span, "("),
+        list_lo,
         span.hi() - BytePos(1),
         false,
     )
@@ -525,6 +649,11 @@ fn count_wildcard_suffix_len(
         .rev()
         .take_while(|i| matches!(i.item, Some(ref internal_string) if internal_string == "_"))
     {
+        // The comment after a wildcard would be dropped along with the wildcard.
+        if item.post_comment.is_some() {
+            break;
+        }
+
         suffix_len += 1;
 
         if item.has_comment() {
@@ -0,0 +1,28 @@
+//! Notifications about the progress of a [`Session`](crate::Session), for embedders which
+//! show progress bars or logs while a large crate or workspace is formatted.
+
+use crate::{ErrorKind, FileName};
+
+/// Receives a notification before and after each file of a session is formatted.
+///
+/// The files of a crate are only known once its root has been parsed, so the number of
+/// files of a run is not known in advance.
+pub trait FormatProgress {
+    /// Called right before `path` is formatted.
+    fn begin_file(&mut self, _path: &FileName) {}
+
+    /// Called right after `path` has been formatted, with the error which stopped the
+    /// formatting of the file, if any. Errors reported for parts of the file, e.g. lines
+    /// exceeding the maximum width, are part of the `FormatReport` of the session instead.
+    fn end_file(&mut self, _path: &FileName, _result: Result<(), &ErrorKind>) {}
+}
+
+impl<P: FormatProgress + ?Sized> FormatProgress for &mut P {
+    fn begin_file(&mut self, path: &FileName) {
+        (**self).begin_file(path)
+    }
+
+    fn end_file(&mut self, path: &FileName, result: Result<(), &ErrorKind>) {
+        (**self).end_file(path, result)
+    }
+}
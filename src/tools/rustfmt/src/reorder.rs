@@ -11,8 +11,12 @@
 use rustc_ast::ast;
 use rustc_span::{symbol::sym, Span};
 
+use crate::cancellation::is_cancelled;
+use crate::comment::find_rustfmt_toggle;
 use crate::config::{Config, GroupImportsTactic};
-use crate::imports::{normalize_use_trees_with_granularity, UseSegmentKind, UseTree};
+use crate::imports::{
+    normalize_use_trees_with_granularity, sort_use_trees_by_alias, UseSegmentKind, UseTree,
+};
 use crate::items::{is_mod_decl, rewrite_extern_crate, rewrite_mod};
 use crate::lists::{itemize_list, write_list, ListFormatting, ListItem};
 use crate::rewrite::RewriteContext;
@@ -23,11 +27,29 @@
 use crate::visitor::FmtVisitor;
 
 /// Choose the ordering between the given two items.
-fn compare_items(a: &ast::Item, b: &ast::Item) -> Ordering {
+fn compare_items(a: &ast::Item, b: &ast::Item, config: &Config) -> Ordering {
     match (&a.kind, &b.kind) {
+        (&ast::ItemKind::Mod(..), &ast::ItemKind::Mod(..)) if config.test_modules_last() => {
+            is_cfg_test(a)
+                .cmp(&is_cfg_test(b))
+                .then_with(|| a.ident.as_str().cmp(b.ident.as_str()))
+        }
         (&ast::ItemKind::Mod(..), &ast::ItemKind::Mod(..)) => {
             a.ident.as_str().cmp(b.ident.as_str())
         }
+        (&ast::ItemKind::ExternCrate(ref a_name), &ast::ItemKind::ExternCrate(ref b_name))
+            if config.reorder_by_alias() =>
+        {
+            // `extern crate foo as bar;`
+            //                      ^^^ Comparing this, then the original name.
+            let result = a.ident.as_str().cmp(b.ident.as_str());
+            if result != Ordering::Equal {
+                return result;
+            }
+            let a_orig_name = a_name.unwrap_or(a.ident.name);
+            let b_orig_name = b_name.unwrap_or(b.ident.name);
+            a_orig_name.as_str().cmp(b_orig_name.as_str())
+        }
         (&ast::ItemKind::ExternCrate(ref a_name), &ast::ItemKind::ExternCrate(ref b_name)) => {
             // `extern crate foo as bar;`
             //               ^^^ Comparing this.
@@ -119,7 +141,11 @@ fn rewrite_reorderable_or_regroupable_items(
                 GroupImportsTactic::StdExternalCrate => group_imports(normalized_items),
             };
 
-            if context.config.reorder_imports() {
+            if context.config.reorder_imports() && context.config.reorder_by_alias() {
+                regrouped_items
+                    .iter_mut()
+                    .for_each(|items| sort_use_trees_by_alias(items))
+            } else if context.config.reorder_imports() {
                 regrouped_items.iter_mut().for_each(|items| items.sort())
             }
 
@@ -158,7 +184,7 @@ fn rewrite_reorderable_or_regroupable_items(
             );
 
             let mut item_pair_vec: Vec<_> = list_items.zip(reorderable_items.iter()).collect();
-            item_pair_vec.sort_by(|a, b| compare_items(a.1, b.1));
+            item_pair_vec.sort_by(|a, b| compare_items(a.1, b.1, context.config));
             let item_vec: Vec<_> = item_pair_vec.into_iter().map(|pair| pair.0).collect();
 
             wrap_reorderable_items(context, &item_vec, shape)
@@ -170,6 +196,16 @@ fn contains_macro_use_attr(item: &ast::Item) -> bool {
     crate::attr::contains_name(&item.attrs, sym::macro_use)
 }
 
+/// Returns `true` if `item` has a `#[cfg(test)]` attribute.
+fn is_cfg_test(item: &ast::Item) -> bool {
+    item.attrs.iter().any(|attr| {
+        attr.has_name(sym::cfg)
+            && attr
+                .meta_item_list()
+                .map_or(false, |list| list.len() == 1 && list[0].has_name(sym::test))
+    })
+}
+
 /// Divides imports into three groups, corresponding to standard, external
 /// and local imports. Sorts each subgroup.
 fn group_imports(uts: Vec<UseTree>) -> Vec<Vec<UseTree>> {
@@ -230,7 +266,7 @@ fn is_reorderable(self, config: &Config) -> bool {
         match self {
             ReorderableItemKind::ExternCrate => config.reorder_imports(),
             ReorderableItemKind::Mod => config.reorder_modules(),
-            ReorderableItemKind::Use => config.reorder_imports(),
+            ReorderableItemKind::Use => config.reorder_imports() && config.format_imports(),
             ReorderableItemKind::Other => false,
         }
     }
@@ -240,7 +276,9 @@ fn is_regroupable(self, config: &Config) -> bool {
             ReorderableItemKind::ExternCrate
             | ReorderableItemKind::Mod
             | ReorderableItemKind::Other => false,
-            ReorderableItemKind::Use => config.group_imports() != GroupImportsTactic::Preserve,
+            ReorderableItemKind::Use => {
+                config.format_imports() && config.group_imports() != GroupImportsTactic::Preserve
+            }
         }
     }
 
@@ -264,10 +302,15 @@ fn walk_reorderable_or_regroupable_items(
         in_group: bool,
     ) -> usize {
         let mut last = self.parse_sess.lookup_line_range(items[0].span());
+        let mut prev_hi = items[0].span().lo();
         let item_length = items
             .iter()
             .take_while(|ppi| {
-                item_kind.is_same_item_kind(&***ppi)
+                // Stop before a `// rustfmt: off` region, it is pushed as is.
+                let gap = self.snippet(mk_sp(prev_hi, ppi.span().lo()));
+                prev_hi = ppi.span().hi();
+                find_rustfmt_toggle(gap, "off").is_none()
+                    && item_kind.is_same_item_kind(&***ppi)
                     && (!in_group || {
                         let current = self.parse_sess.lookup_line_range(ppi.span());
                         let in_same_group = current.lo < last.hi + 2;
@@ -305,7 +348,17 @@ fn walk_reorderable_or_regroupable_items(
     /// Visits and format the given items. Items are reordered If they are
     /// consecutive and reorderable.
     pub(crate) fn visit_items_with_reordering(&mut self, mut items: &[&ast::Item]) {
+        self.align_consts(items);
         while !items.is_empty() {
+            // The rest of the file is not needed anymore.
+            if is_cancelled(&self.cancellation) {
+                return;
+            }
+            if let Some((span, len)) = self.suppressed_region(items) {
+                self.push_suppressed_region(span);
+                items = &items[len..];
+                continue;
+            }
             // If the next item is a `use`, `extern crate` or `mod`, then extract it and any
             // subsequent items that have the same item kind to be reordered within
             // `walk_reorderable_items`. Otherwise, just format the next item for output.
@@ -2,6 +2,7 @@
 
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::time::Instant;
 
 use rustc_ast::ptr;
 use rustc_span::Span;
@@ -36,6 +37,8 @@ pub(crate) struct RewriteContext<'a> {
     pub(crate) is_if_else_block: Cell<bool>,
     // When rewriting chain, veto going multi line except the last element
     pub(crate) force_one_line_chain: Cell<bool>,
+    // When rewriting the arguments of a method call in a chain.
+    pub(crate) is_chain_call_arg: Cell<bool>,
     pub(crate) snippet_provider: &'a SnippetProvider,
     // Used for `format_snippet`
     pub(crate) macro_rewrite_failure: Cell<bool>,
@@ -43,6 +46,81 @@ pub(crate) struct RewriteContext<'a> {
     pub(crate) report: FormatReport,
     pub(crate) skip_context: SkipContext,
     pub(crate) skipped_range: Rc<RefCell<Vec<(usize, usize)>>>,
+    // When formatting the current file must be given up, see `file_timeout`.
+    pub(crate) deadline: Option<Deadline>,
+    pub(crate) rewrite_failures: Rc<RewriteFailures>,
+    // When trying alternative layouts with `optimize_layout`, nested lists keep the first
+    // layout which fits.
+    pub(crate) optimizing_layout: Cell<bool>,
+    // When rewriting a binary expression passed as an argument with
+    // `binop_args_indent = "Block"`, block indent its continuation lines.
+    pub(crate) block_indent_binop: Cell<bool>,
+}
+
+/// The point in time at which formatting the current file must be given up, see
+/// `file_timeout`, and the clock which tells the time.
+#[derive(Clone, Copy)]
+pub(crate) struct Deadline {
+    pub(crate) at: Instant,
+    pub(crate) clock: fn() -> Instant,
+}
+
+impl Deadline {
+    pub(crate) fn is_exceeded(&self) -> bool {
+        (self.clock)() >= self.at
+    }
+}
+
+/// A construct which could not be rewritten.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RewriteFailure {
+    pub(crate) span: Span,
+    /// What the construct is, e.g. `string literal`.
+    pub(crate) construct: &'static str,
+    /// The number of failures recorded before and including this one.
+    count: usize,
+}
+
+/// Keeps track of the innermost construct which failed to be rewritten, to report why
+/// code was left unformatted.
+///
+/// Rewrites are often attempted several times with different layouts, so inner
+/// constructs also fail while the enclosing construct eventually succeeds. When a
+/// construct fails, the failure of an inner construct is only kept if it happened during
+/// the rewrite of that construct, i.e. since its `mark`.
+#[derive(Debug, Default)]
+pub(crate) struct RewriteFailures {
+    count: Cell<usize>,
+    innermost: Cell<Option<RewriteFailure>>,
+}
+
+impl RewriteFailures {
+    /// Returns a mark to pass to `record` if the rewrite, which starts now, fails.
+    pub(crate) fn mark(&self) -> usize {
+        self.count.get()
+    }
+
+    /// Records that the rewrite of `construct` at `span`, which started at `mark`, failed.
+    pub(crate) fn record(&self, mark: usize, span: Span, construct: &'static str) {
+        let count = self.count.get() + 1;
+        self.count.set(count);
+        let is_inner =
+            |failure: RewriteFailure| failure.count > mark && span.contains(failure.span);
+        if !self.innermost.get().map_or(false, is_inner) {
+            self.innermost.set(Some(RewriteFailure {
+                span,
+                construct,
+                count,
+            }));
+        }
+    }
+
+    /// Returns the innermost failure within `span`, and forgets about any failure.
+    pub(crate) fn take_within(&self, span: Span) -> Option<RewriteFailure> {
+        self.innermost
+            .take()
+            .filter(|failure| span.contains(failure.span))
+    }
 }
 
 pub(crate) struct InsideMacroGuard {
@@ -64,7 +142,7 @@ fn drop(&mut self) {
 
 impl<'a> RewriteContext<'a> {
     pub(crate) fn snippet(&self, span: Span) -> &str {
-        self.snippet_provider.span_to_snippet(span).unwrap()
+        self.snippet_provider.snippet(span)
     }
 
     /// Returns `true` if we should use block indent style for rewriting function call.
@@ -95,4 +173,10 @@ pub(crate) fn leave_macro(&self) {
     pub(crate) fn is_if_else_block(&self) -> bool {
         self.is_if_else_block.get()
     }
+
+    /// Returns `true` if the time budget for formatting the current file is spent.
+    pub(crate) fn deadline_exceeded(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| deadline.is_exceeded())
+    }
 }
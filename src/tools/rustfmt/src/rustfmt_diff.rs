@@ -139,6 +139,69 @@ fn from_str(s: &str) -> Result<ModifiedLines, ()> {
     }
 }
 
+/// A hunk of a unified diff between an original and a formatted text: a run of changed lines
+/// along with the unchanged lines around it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DiffHunk {
+    /// The first line of the hunk in the original text, starting at 1.
+    pub original_start: u32,
+    /// The number of lines of the hunk in the original text, i.e. its context and removed
+    /// lines.
+    pub original_len: u32,
+    /// The first line of the hunk in the formatted text, starting at 1.
+    pub formatted_start: u32,
+    /// The number of lines of the hunk in the formatted text, i.e. its context and added
+    /// lines.
+    pub formatted_len: u32,
+    /// The lines of the hunk, in order.
+    pub lines: Vec<HunkLine>,
+}
+
+/// A line of a `DiffHunk`, without its line terminator.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum HunkLine {
+    /// A line of both texts.
+    Context(String),
+    /// A line of the original text which formatting removes.
+    Removed(String),
+    /// A line of the formatted text which formatting adds.
+    Added(String),
+}
+
+impl From<Mismatch> for DiffHunk {
+    fn from(mismatch: Mismatch) -> DiffHunk {
+        let lines: Vec<_> = mismatch
+            .lines
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Context(line) => HunkLine::Context(line),
+                DiffLine::Resulting(line) => HunkLine::Removed(line),
+                DiffLine::Expected(line) => HunkLine::Added(line),
+            })
+            .collect();
+        let count = |is_counted: fn(&HunkLine) -> bool| {
+            lines.iter().filter(|line| is_counted(line)).count() as u32
+        };
+        DiffHunk {
+            original_start: mismatch.line_number_orig,
+            original_len: count(|line| !matches!(line, HunkLine::Added(_))),
+            formatted_start: mismatch.line_number,
+            formatted_len: count(|line| !matches!(line, HunkLine::Removed(_))),
+            lines,
+        }
+    }
+}
+
+/// Returns the hunks of the unified diff from `original` to `formatted`, with up to
+/// `context_size` unchanged lines before and after the changed lines of each hunk. This is
+/// the diff rustfmt prints with `--check`, e.g. with `context_size = 3`.
+pub fn diff_hunks(original: &str, formatted: &str, context_size: usize) -> Vec<DiffHunk> {
+    make_diff(original, formatted, context_size)
+        .into_iter()
+        .map(DiffHunk::from)
+        .collect()
+}
+
 // This struct handles writing output to stdout and abstracts away the logic
 // of printing in color, if it's possible in the executing environment.
 pub(crate) struct OutputWriter {
@@ -283,7 +346,7 @@ pub(crate) fn print_diff<F>(diff: Vec<Mismatch>, get_section_title: F, config: &
 #[cfg(test)]
 mod test {
     use super::DiffLine::*;
-    use super::{make_diff, Mismatch};
+    use super::{diff_hunks, make_diff, DiffHunk, HunkLine, Mismatch};
     use super::{ModifiedChunk, ModifiedLines};
 
     #[test]
@@ -367,6 +430,39 @@ fn diff_trailing_newline() {
         );
     }
 
+    #[test]
+    fn diff_hunks_line_ranges() {
+        let src = "one\ntwo\nthree\nfour\nfive\nsix\n";
+        let dest = "one\ntwo\n3\nthree\nfour\nsix\n";
+        assert_eq!(
+            diff_hunks(src, dest, 1),
+            vec![
+                DiffHunk {
+                    original_start: 2,
+                    original_len: 2,
+                    formatted_start: 2,
+                    formatted_len: 3,
+                    lines: vec![
+                        HunkLine::Context("two".to_owned()),
+                        HunkLine::Added("3".to_owned()),
+                        HunkLine::Context("three".to_owned()),
+                    ],
+                },
+                DiffHunk {
+                    original_start: 4,
+                    original_len: 3,
+                    formatted_start: 5,
+                    formatted_len: 2,
+                    lines: vec![
+                        HunkLine::Context("four".to_owned()),
+                        HunkLine::Removed("five".to_owned()),
+                        HunkLine::Context("six".to_owned()),
+                    ],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn modified_lines_from_str() {
         use std::str::FromStr;
@@ -2,11 +2,13 @@
 use std::cmp::min;
 use std::ops::{Add, Sub};
 
-use crate::Config;
+use crate::config::{Config, Version};
 
+/// The arithmetic on indents saturates, so that deeply nested code becomes too wide to
+/// format instead of wrapping around or panicking.
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Indent {
-    // Width of the block indent, in characters. Must be a multiple of
+    // Width of the block indent, in characters. Should be a multiple of
     // Config::tab_spaces.
     pub(crate) block_indent: usize,
     // Alignment in characters.
@@ -18,6 +20,21 @@ pub(crate) struct Indent {
 const INDENT_BUFFER: &str =
     "\n                                                                                ";
 
+/// With `Version::Two`, code is given at least this many columns (or half of `max_width`,
+/// if that is less) however deeply it is indented. Otherwise, with a small `max_width`,
+/// nested code has no room left and is left unformatted along with everything around it.
+const MIN_BLOCK_WIDTH: usize = 20;
+
+/// Returns the width left for code indented by `indent`.
+pub(crate) fn block_width(indent: Indent, config: &Config) -> usize {
+    let width = config.max_width().saturating_sub(indent.width());
+    if config.version() == Version::Two {
+        width.max(MIN_BLOCK_WIDTH.min(config.max_width() / 2))
+    } else {
+        width
+    }
+}
+
 impl Indent {
     pub(crate) fn new(block_indent: usize, alignment: usize) -> Indent {
         Indent {
@@ -28,9 +45,10 @@ pub(crate) fn new(block_indent: usize, alignment: usize) -> Indent {
 
     pub(crate) fn from_width(config: &Config, width: usize) -> Indent {
         if config.hard_tabs() {
-            let tab_num = width / config.tab_spaces();
-            let alignment = width % config.tab_spaces();
-            Indent::new(config.tab_spaces() * tab_num, alignment)
+            let tab_spaces = config.tab_spaces().max(1);
+            let tab_num = width / tab_spaces;
+            let alignment = width % tab_spaces;
+            Indent::new(tab_spaces * tab_num, alignment)
         } else {
             Indent::new(width, 0)
         }
@@ -48,7 +66,7 @@ pub(crate) fn block_only(&self) -> Indent {
     }
 
     pub(crate) fn block_indent(mut self, config: &Config) -> Indent {
-        self.block_indent += config.tab_spaces();
+        self.block_indent = self.block_indent.saturating_add(config.tab_spaces());
         self
     }
 
@@ -62,7 +80,7 @@ pub(crate) fn block_unindent(mut self, config: &Config) -> Indent {
     }
 
     pub(crate) fn width(&self) -> usize {
-        self.block_indent + self.alignment
+        self.block_indent.saturating_add(self.alignment)
     }
 
     pub(crate) fn to_string(&self, config: &Config) -> Cow<'static, str> {
@@ -75,11 +93,23 @@ pub(crate) fn to_string_with_newline(&self, config: &Config) -> Cow<'static, str
 
     fn to_string_inner(&self, config: &Config, offset: usize) -> Cow<'static, str> {
         let (num_tabs, num_spaces) = if config.hard_tabs() {
-            (self.block_indent / config.tab_spaces(), self.alignment)
+            let tab_spaces = config.tab_spaces().max(1);
+            // With `Version::Two`, a block indent which is not a whole number of tabs (e.g.
+            // from `Shape::block_indent`) is completed with spaces instead of being cut
+            // down to the previous tab stop, which misaligned the following lines.
+            let rest = if config.version() == Version::Two {
+                self.block_indent % tab_spaces
+            } else {
+                0
+            };
+            (
+                self.block_indent / tab_spaces,
+                rest.saturating_add(self.alignment),
+            )
         } else {
             (0, self.width())
         };
-        let num_chars = num_tabs + num_spaces;
+        let num_chars = num_tabs.saturating_add(num_spaces);
         if num_tabs == 0 && num_chars + offset <= INDENT_BUFFER_LEN {
             Cow::from(&INDENT_BUFFER[offset..=num_chars])
         } else {
@@ -103,8 +133,8 @@ impl Add for Indent {
 
     fn add(self, rhs: Indent) -> Indent {
         Indent {
-            block_indent: self.block_indent + rhs.block_indent,
-            alignment: self.alignment + rhs.alignment,
+            block_indent: self.block_indent.saturating_add(rhs.block_indent),
+            alignment: self.alignment.saturating_add(rhs.alignment),
         }
     }
 }
@@ -114,8 +144,8 @@ impl Sub for Indent {
 
     fn sub(self, rhs: Indent) -> Indent {
         Indent::new(
-            self.block_indent - rhs.block_indent,
-            self.alignment - rhs.alignment,
+            self.block_indent.saturating_sub(rhs.block_indent),
+            self.alignment.saturating_sub(rhs.alignment),
         )
     }
 }
@@ -124,7 +154,7 @@ impl Add<usize> for Indent {
     type Output = Indent;
 
     fn add(self, rhs: usize) -> Indent {
-        Indent::new(self.block_indent, self.alignment + rhs)
+        Indent::new(self.block_indent, self.alignment.saturating_add(rhs))
     }
 }
 
@@ -132,7 +162,7 @@ impl Sub<usize> for Indent {
     type Output = Indent;
 
     fn sub(self, rhs: usize) -> Indent {
-        Indent::new(self.block_indent, self.alignment - rhs)
+        Indent::new(self.block_indent, self.alignment.saturating_sub(rhs))
     }
 }
 
@@ -175,7 +205,7 @@ pub(crate) fn legacy(width: usize, indent: Indent) -> Shape {
 
     pub(crate) fn indented(indent: Indent, config: &Config) -> Shape {
         Shape {
-            width: config.max_width().saturating_sub(indent.width()),
+            width: block_width(indent, config),
             indent,
             offset: indent.alignment,
         }
@@ -183,7 +213,7 @@ pub(crate) fn indented(indent: Indent, config: &Config) -> Shape {
 
     pub(crate) fn with_max_width(&self, config: &Config) -> Shape {
         Shape {
-            width: config.max_width().saturating_sub(self.indent.width()),
+            width: block_width(self.indent, config),
             ..*self
         }
     }
@@ -370,4 +400,76 @@ fn shape_block_indent_with_alignment() {
         assert_eq!(28, shape.indent.alignment);
         assert_eq!(28, shape.offset);
     }
+
+    #[test]
+    fn indent_sub_saturates() {
+        let indent = Indent::new(4, 2) - Indent::new(8, 4);
+        assert_eq!(0, indent.width());
+
+        let indent = Indent::new(4, 2) - 3;
+        assert_eq!(4, indent.block_indent);
+        assert_eq!(0, indent.alignment);
+
+        let indent = Indent::new(usize::MAX, 0) + Indent::new(4, 0);
+        assert_eq!(usize::MAX, indent.block_indent);
+        assert_eq!(usize::MAX, (indent + 4).width());
+    }
+
+    #[test]
+    fn deeply_nested_indent_to_string() {
+        let mut config = Config::default();
+        let mut indent = Indent::empty();
+        for _ in 0..12 {
+            indent = indent.block_indent(&config);
+        }
+        // Beyond the length of `INDENT_BUFFER`.
+        let mut indent = indent + 60;
+        assert_eq!(108, indent.width());
+        assert_eq!(" ".repeat(108), indent.to_string(&config));
+        assert_eq!(
+            format!("\n{}", " ".repeat(108)),
+            indent.to_string_with_newline(&config)
+        );
+
+        config.set().hard_tabs(true);
+        assert_eq!(
+            format!("{}{}", "\t".repeat(12), " ".repeat(60)),
+            indent.to_string(&config)
+        );
+        for _ in 0..12 {
+            indent = indent.block_unindent(&config);
+        }
+        assert_eq!(60, indent.width());
+        assert_eq!(0, indent.block_unindent(&config).block_indent);
+    }
+
+    #[test]
+    fn hard_tabs_block_indent_with_spaces() {
+        let mut config = Config::default();
+        config.set().hard_tabs(true);
+        // E.g. from `Shape::block_indent` with the width of a prefix.
+        let indent = Indent::new(42, 3);
+
+        // 10 tabs, the 2 columns left of the block indent are cut off
+        assert_eq!(format!("{}   ", "\t".repeat(10)), indent.to_string(&config));
+
+        config.set().version(Version::Two);
+        assert_eq!(
+            format!("{}     ", "\t".repeat(10)),
+            indent.to_string(&config)
+        );
+    }
+
+    #[test]
+    fn block_width_floor() {
+        let mut config = Config::default();
+        config.set().max_width(30);
+        assert_eq!(2, block_width(Indent::new(28, 0), &config));
+        assert_eq!(0, block_width(Indent::new(40, 0), &config));
+
+        config.set().version(Version::Two);
+        assert_eq!(18, block_width(Indent::new(12, 0), &config));
+        assert_eq!(15, block_width(Indent::new(28, 0), &config));
+        assert_eq!(15, block_width(Indent::new(40, 0), &config));
+    }
 }
@@ -0,0 +1,308 @@
+//! The items of a source file and the lines they span, for reporting changes by item
+//! rather than by line.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use rustc_ast::ast;
+use rustc_ast::visit::{self, AssocCtxt, Visitor};
+use rustc_span::Span;
+
+use crate::config::{Config, Edition};
+use crate::parse::parser::Parser;
+use crate::parse::session::ParseSess;
+use crate::spanned::Spanned;
+use crate::Input;
+
+/// An item (including associated and foreign items) of a source file, as returned by
+/// [`source_items`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceItem {
+    /// The 1-based line the item starts on, including its attributes and doc comments.
+    pub first_line: usize,
+    /// The 1-based line the item ends on.
+    pub last_line: usize,
+    /// The description of the item, e.g. `fn foo::bar`, `fn <Foo as Display>::fmt` or
+    /// `impl Display for Foo`.
+    pub description: String,
+}
+
+/// Returns the items of `source`, ordered by their first lines, so that an item comes
+/// after all items containing it. Items generated by macros are not included.
+///
+/// Returns `None` if `source` cannot be parsed.
+pub fn source_items(source: &str, edition: Edition) -> Option<Vec<SourceItem>> {
+    catch_unwind(AssertUnwindSafe(|| {
+        rustc_span::create_session_if_not_set_then(edition.into(), |_| {
+            let mut config = Config::default();
+            config.set().edition(edition);
+            config.set().hide_parse_errors(true);
+            let sess = ParseSess::new(&config).ok()?;
+            let krate = Parser::parse_crate(Input::Text(source.to_owned()), &sess).ok()?;
+
+            let mut collector = ItemCollector {
+                sess: &sess,
+                source,
+                line_starts: line_starts(source),
+                paths: vec![],
+                items: vec![],
+            };
+            visit::walk_crate(&mut collector, &krate);
+            Some(collector.items)
+        })
+    }))
+    .ok()?
+}
+
+/// Returns the innermost item of `items`, as returned by [`source_items`], which contains
+/// the 1-based `line`.
+pub fn innermost_item(items: &[SourceItem], line: usize) -> Option<&SourceItem> {
+    items
+        .iter()
+        .rev()
+        .find(|item| item.first_line <= line && line <= item.last_line)
+}
+
+/// Returns the byte positions at which the lines of `source` start.
+fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+struct ItemCollector<'a> {
+    sess: &'a ParseSess,
+    source: &'a str,
+    line_starts: Vec<usize>,
+    /// The prefixes of the paths of the items nested in the items being visited, e.g.
+    /// `foo` in `mod foo` or `<Foo as Display>` in `impl Display for Foo`.
+    paths: Vec<String>,
+    items: Vec<SourceItem>,
+}
+
+impl<'a> ItemCollector<'a> {
+    fn byte_pos(&self, pos: rustc_span::BytePos) -> usize {
+        let source_map = self.sess.inner().source_map();
+        source_map.lookup_byte_offset(pos).pos.to_usize()
+    }
+
+    fn snippet(&self, span: Span) -> String {
+        let lo = self.byte_pos(span.lo());
+        let hi = self.byte_pos(span.hi());
+        collapse(&self.source[lo..hi])
+    }
+
+    fn line(&self, byte_pos: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= byte_pos)
+    }
+
+    /// Returns the prefix of the paths of the items in the item being visited.
+    fn path(&self) -> String {
+        self.paths.last().cloned().unwrap_or_default()
+    }
+
+    /// Returns the path of an item named `name` in the item being visited.
+    fn qualified(&self, name: &str) -> String {
+        match self.paths.last() {
+            Some(path) if !path.is_empty() => format!("{}::{}", path, name),
+            _ => name.to_owned(),
+        }
+    }
+
+    /// Records the item spanning `span` and visits its children with `path` as the prefix
+    /// of their paths. Without a description, only the children are recorded.
+    fn record(
+        &mut self,
+        span: Span,
+        description: Option<String>,
+        path: Option<String>,
+        walk: impl FnOnce(&mut Self),
+    ) {
+        if let Some(description) = description {
+            let lo = self.byte_pos(span.lo());
+            let hi = self.byte_pos(span.hi()).max(lo + 1);
+            self.items.push(SourceItem {
+                first_line: self.line(lo),
+                last_line: self.line(hi - 1),
+                description,
+            });
+        }
+        let path = path.unwrap_or_else(|| self.path());
+        self.paths.push(path);
+        walk(self);
+        self.paths.pop();
+    }
+
+    /// Returns the description of a named item of `kind`, and the prefix of the paths of
+    /// its children.
+    fn named(&self, kind: &str, ident: rustc_span::symbol::Ident) -> (String, String) {
+        let path = self.qualified(&ident.to_string());
+        (format!("{} {}", kind, path), path)
+    }
+
+    fn describe_item(&self, item: &ast::Item) -> Option<(String, String)> {
+        let kind = match item.kind {
+            ast::ItemKind::Fn(..) => "fn",
+            ast::ItemKind::Struct(..) => "struct",
+            ast::ItemKind::Enum(..) => "enum",
+            ast::ItemKind::Union(..) => "union",
+            ast::ItemKind::Trait(..) | ast::ItemKind::TraitAlias(..) => "trait",
+            ast::ItemKind::Mod(..) => "mod",
+            ast::ItemKind::TyAlias(..) => "type",
+            ast::ItemKind::Static(..) => "static",
+            ast::ItemKind::Const(..) => "const",
+            ast::ItemKind::MacroDef(..) => "macro",
+            ast::ItemKind::Impl(ref impl_) => {
+                let generics = if impl_.generics.params.is_empty() {
+                    String::new()
+                } else {
+                    self.snippet(impl_.generics.span)
+                };
+                let self_ty = self.snippet(impl_.self_ty.span);
+                return Some(match impl_.of_trait {
+                    Some(ref trait_ref) => {
+                        let negative = match impl_.polarity {
+                            ast::ImplPolarity::Negative(_) => "!",
+                            ast::ImplPolarity::Positive => "",
+                        };
+                        let trait_path = self.snippet(trait_ref.path.span);
+                        (
+                            format!(
+                                "impl{} {}{} for {}",
+                                generics, negative, trait_path, self_ty
+                            ),
+                            format!("<{} as {}{}>", self_ty, negative, trait_path),
+                        )
+                    }
+                    None => (format!("impl{} {}", generics, self_ty), self_ty),
+                });
+            }
+            ast::ItemKind::Use(ref use_tree) => {
+                let description = format!("use {}", self.snippet(use_tree.span));
+                return Some((description, self.path()));
+            }
+            ast::ItemKind::ExternCrate(orig_name) => {
+                let description = match orig_name {
+                    Some(orig_name) => format!("extern crate {} as {}", orig_name, item.ident),
+                    None => format!("extern crate {}", item.ident),
+                };
+                return Some((description, self.path()));
+            }
+            ast::ItemKind::ForeignMod(..)
+            | ast::ItemKind::GlobalAsm(..)
+            | ast::ItemKind::MacCall(..) => return None,
+        };
+        Some(self.named(kind, item.ident))
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for ItemCollector<'a> {
+    fn visit_item(&mut self, item: &'ast ast::Item) {
+        let (description, path) = match self.describe_item(item) {
+            Some((description, path)) => (Some(description), Some(path)),
+            None => (None, None),
+        };
+        self.record(item.span(), description, path, |this| {
+            visit::walk_item(this, item)
+        });
+    }
+
+    fn visit_assoc_item(&mut self, item: &'ast ast::AssocItem, ctxt: AssocCtxt) {
+        let kind = match item.kind {
+            ast::AssocItemKind::Const(..) => Some("const"),
+            ast::AssocItemKind::Fn(..) => Some("fn"),
+            ast::AssocItemKind::Type(..) => Some("type"),
+            ast::AssocItemKind::MacCall(..) => None,
+        };
+        let (description, path) = match kind {
+            Some(kind) => {
+                let (description, path) = self.named(kind, item.ident);
+                (Some(description), Some(path))
+            }
+            None => (None, None),
+        };
+        self.record(item.span(), description, path, |this| {
+            visit::walk_assoc_item(this, item, ctxt)
+        });
+    }
+
+    fn visit_foreign_item(&mut self, item: &'ast ast::ForeignItem) {
+        let kind = match item.kind {
+            ast::ForeignItemKind::Static(..) => Some("static"),
+            ast::ForeignItemKind::Fn(..) => Some("fn"),
+            ast::ForeignItemKind::TyAlias(..) => Some("type"),
+            ast::ForeignItemKind::MacCall(..) => None,
+        };
+        let (description, path) = match kind {
+            Some(kind) => {
+                let (description, path) = self.named(kind, item.ident);
+                (Some(description), Some(path))
+            }
+            None => (None, None),
+        };
+        self.record(item.span(), description, path, |this| {
+            visit::walk_foreign_item(this, item)
+        });
+    }
+}
+
+/// Collapses runs of whitespace in `s` into single spaces.
+fn collapse(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn items(source: &str) -> Vec<(usize, usize, String)> {
+        source_items(source, Edition::Edition2018)
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.first_line, item.last_line, item.description))
+            .collect()
+    }
+
+    #[test]
+    fn finds_nested_items() {
+        let source = "mod foo {\n    fn bar() {}\n}\n\n\
+                      /// Docs.\n#[derive(Debug)]\nstruct A(u8);\n\n\
+                      impl<T> Display for Foo<T> {\n    fn fmt(&self) {}\n}\n\
+                      use a::{b, c};\n";
+        assert_eq!(
+            items(source),
+            vec![
+                (1, 3, "mod foo".to_owned()),
+                (2, 2, "fn foo::bar".to_owned()),
+                (5, 7, "struct A".to_owned()),
+                (9, 11, "impl<T> Display for Foo<T>".to_owned()),
+                (10, 10, "fn <Foo<T> as Display>::fmt".to_owned()),
+                (12, 12, "use a::{b, c}".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn delimiters_in_literals_and_comments() {
+        let source = "fn a() {\n    let s = r\"C:\\\";\n    let c = '}';\n    // }\n}\n\
+                      const B: [u8; 2] = [1, 2];\n";
+        assert_eq!(
+            items(source),
+            vec![(1, 5, "fn a".to_owned()), (6, 6, "const B".to_owned())]
+        );
+    }
+
+    #[test]
+    fn innermost_item_at_line() {
+        let items = source_items("mod a {\n    fn b() {}\n}\n", Edition::Edition2018).unwrap();
+        assert_eq!(
+            innermost_item(&items, 2).map(|item| item.description.as_str()),
+            Some("fn a::b")
+        );
+        assert_eq!(
+            innermost_item(&items, 3).map(|item| item.description.as_str()),
+            Some("mod a")
+        );
+        assert_eq!(innermost_item(&items, 4), None);
+        assert_eq!(source_items("fn a(", Edition::Edition2018), None);
+    }
+}
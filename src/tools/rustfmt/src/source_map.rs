@@ -8,12 +8,14 @@
 use crate::visitor::SnippetProvider;
 
 pub(crate) trait SpanUtils {
-    fn span_after(&self, original: Span, needle: &str) -> BytePos;
+    /// Returns the position right after the first occurrence of `needle` in `original`
+    /// which isn't within a comment or a literal, or `None` if there is none.
+    fn span_after(&self, original: Span, needle: &str) -> Option<BytePos>;
     fn span_after_last(&self, original: Span, needle: &str) -> BytePos;
-    fn span_before(&self, original: Span, needle: &str) -> BytePos;
+    /// Returns the position of the first occurrence of `needle` in `original` which isn't
+    /// within a comment or a literal, or `None` if there is none.
+    fn span_before(&self, original: Span, needle: &str) -> Option<BytePos>;
     fn span_before_last(&self, original: Span, needle: &str) -> BytePos;
-    fn opt_span_after(&self, original: Span, needle: &str) -> Option<BytePos>;
-    fn opt_span_before(&self, original: Span, needle: &str) -> Option<BytePos>;
 }
 
 pub(crate) trait LineRangeUtils {
@@ -26,18 +28,13 @@ pub(crate) trait LineRangeUtils {
 }
 
 impl SpanUtils for SnippetProvider {
-    fn span_after(&self, original: Span, needle: &str) -> BytePos {
-        self.opt_span_after(original, needle).unwrap_or_else(|| {
-            panic!(
-                "bad span: `{}`: `{}`",
-                needle,
-                self.span_to_snippet(original).unwrap()
-            )
-        })
+    fn span_after(&self, original: Span, needle: &str) -> Option<BytePos> {
+        self.span_before(original, needle)
+            .map(|bytepos| bytepos + BytePos(needle.len() as u32))
     }
 
     fn span_after_last(&self, original: Span, needle: &str) -> BytePos {
-        let snippet = self.span_to_snippet(original).unwrap();
+        let snippet = self.snippet(original);
         let mut offset = 0;
 
         while let Some(additional_offset) = snippet[offset..].find_uncommented(needle) {
@@ -47,36 +44,21 @@ fn span_after_last(&self, original: Span, needle: &str) -> BytePos {
         original.lo() + BytePos(offset as u32)
     }
 
-    fn span_before(&self, original: Span, needle: &str) -> BytePos {
-        self.opt_span_before(original, needle).unwrap_or_else(|| {
-            panic!(
-                "bad span: `{}`: `{}`",
-                needle,
-                self.span_to_snippet(original).unwrap()
-            )
-        })
+    fn span_before(&self, original: Span, needle: &str) -> Option<BytePos> {
+        let snippet = self.span_to_snippet(original)?;
+        let offset = snippet.find_in_code(needle)?;
+
+        Some(original.lo() + BytePos(offset as u32))
     }
 
     fn span_before_last(&self, original: Span, needle: &str) -> BytePos {
-        let snippet = self.span_to_snippet(original).unwrap();
+        let snippet = self.snippet(original);
         let mut offset = 0;
 
         while let Some(additional_offset) = snippet[offset..].find_uncommented(needle) {
             offset += additional_offset + needle.len();
         }
 
-        original.lo() + BytePos(offset as u32 - 1)
-    }
-
-    fn opt_span_after(&self, original: Span, needle: &str) -> Option<BytePos> {
-        self.opt_span_before(original, needle)
-            .map(|bytepos| bytepos + BytePos(needle.len() as u32))
-    }
-
-    fn opt_span_before(&self, original: Span, needle: &str) -> Option<BytePos> {
-        let snippet = self.span_to_snippet(original)?;
-        let offset = snippet.find_uncommented(needle)?;
-
-        Some(original.lo() + BytePos(offset as u32))
+        original.lo() + BytePos((offset as u32).saturating_sub(1))
     }
 }
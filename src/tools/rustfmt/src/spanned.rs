@@ -17,6 +17,12 @@ fn span(&self) -> Span {
     }
 }
 
+impl<T: Spanned> Spanned for &T {
+    fn span(&self) -> Span {
+        (**self).span()
+    }
+}
+
 impl<T> Spanned for source_map::Spanned<T> {
     fn span(&self) -> Span {
         self.span
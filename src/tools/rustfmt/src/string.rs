@@ -160,20 +160,22 @@ fn detect_url(s: &[&str], index: usize) -> Option<usize> {
         Some(pos) => pos + 1,
         None => 0,
     };
+    let end = match s[index..].iter().position(|g| is_whitespace(g)) {
+        Some(pos) => index + pos,
+        None => s.len(),
+    };
     // 8 = minimum length for a string to contain a URL
-    if s.len() < start + 8 {
+    if end < start + 8 {
         return None;
     }
-    let split = s[start..].concat();
-    if split.contains("https://")
-        || split.contains("http://")
-        || split.contains("ftp://")
-        || split.contains("file://")
+    // Only the word at `index` matters, a URL further on can be moved to the next line.
+    let word = s[start..end].concat();
+    if word.contains("https://")
+        || word.contains("http://")
+        || word.contains("ftp://")
+        || word.contains("file://")
     {
-        match s[index..].iter().position(|g| is_whitespace(g)) {
-            Some(pos) => Some(index + pos - 1),
-            None => Some(s.len() - 1),
-        }
+        Some(end - 1)
     } else {
         None
     }
@@ -721,5 +723,9 @@ fn detect_urls() {
         let string = "aaa file://example.org";
         let graphemes = UnicodeSegmentation::graphemes(&*string, false).collect::<Vec<&str>>();
         assert_eq!(detect_url(&graphemes, 8), Some(21));
+
+        let string = "aaa something https://example.org";
+        let graphemes = UnicodeSegmentation::graphemes(&*string, false).collect::<Vec<&str>>();
+        assert_eq!(detect_url(&graphemes, 6), None);
     }
 }
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
@@ -8,12 +9,18 @@
 use std::process::{Command, Stdio};
 use std::str::Chars;
 use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::config::{Color, Config, EmitMode, FileName, NewlineStyle};
+use rustc_data_structures::sync::Lrc;
+
+use crate::config::{Color, CommentOverflow, Config, EmitMode, FileName, NewlineStyle};
 use crate::formatting::{ReportedErrors, SourceFile};
 use crate::rustfmt_diff::{make_diff, print_diff, DiffLine, Mismatch, ModifiedChunk, OutputWriter};
 use crate::source_file;
-use crate::{is_nightly_channel, FormatReport, FormatReportFormatterBuilder, Input, Session};
+use crate::{
+    is_nightly_channel, CancellationToken, FormatProgress, FormatReport,
+    FormatReportFormatterBuilder, Input, Session,
+};
 
 use rustfmt_config_proc_macro::nightly_only_test;
 
@@ -452,6 +459,80 @@ fn format_files_find_new_files_via_cfg_if() {
     });
 }
 
+#[test]
+fn progress_is_reported_for_each_file() {
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl FormatProgress for Events {
+        fn begin_file(&mut self, path: &FileName) {
+            self.0.push(format!("begin {}", path));
+        }
+
+        fn end_file(&mut self, path: &FileName, result: Result<(), &crate::ErrorKind>) {
+            self.0.push(format!("end {} {}", path, result.is_ok()));
+        }
+    }
+
+    init_log();
+    let dir = Path::new("tests/mod-resolver/shared-modules");
+    let mut events = Events::default();
+    {
+        let mut session = Session::<io::Stdout>::new(Config::default(), None);
+        session.set_progress(&mut events);
+        for root in &["lib.rs", "main.rs"] {
+            session.format(Input::File(dir.join(root))).unwrap();
+        }
+    }
+    let path = |file: &str| dir.join(file).display().to_string();
+    assert_eq!(
+        events.0,
+        vec![
+            format!("begin {}", path("lib.rs")),
+            format!("end {} true", path("lib.rs")),
+            format!("begin {}", path("shared.rs")),
+            format!("end {} true", path("shared.rs")),
+            format!("begin {}", path("main.rs")),
+            format!("end {} true", path("main.rs")),
+        ]
+    );
+}
+
+#[test]
+fn cancelled_session_stops_formatting() {
+    struct CancelOnBegin(CancellationToken, Vec<String>);
+
+    impl FormatProgress for CancelOnBegin {
+        fn begin_file(&mut self, _path: &FileName) {
+            self.0.cancel();
+        }
+
+        fn end_file(&mut self, path: &FileName, result: Result<(), &crate::ErrorKind>) {
+            self.1.push(format!("{} {:?}", path, result));
+        }
+    }
+
+    init_log();
+    let token = CancellationToken::new();
+    let mut progress = CancelOnBegin(token.clone(), vec![]);
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut buf));
+        session.set_progress(&mut progress);
+        session.set_cancellation_token(token.clone());
+        let input = Input::Text("fn main () {}\nfn foo () {}".to_owned());
+        assert!(matches!(
+            session.format(input),
+            Err(crate::ErrorKind::Cancelled)
+        ));
+    }
+    assert!(token.is_cancelled());
+    assert_eq!(progress.1, vec!["<stdin> Err(Cancelled)"]);
+    assert!(!String::from_utf8(buf).unwrap().contains("fn"));
+}
+
 #[test]
 fn stdin_formatting_smoke_test() {
     init_log();
@@ -466,9 +547,79 @@ fn stdin_formatting_smoke_test() {
     }
 
     #[cfg(not(windows))]
-    assert_eq!(buf, "<stdin>:\n\nfn main() {}\n".as_bytes());
+    assert_eq!(buf, "==== <stdin> ====\nfn main() {}\n".as_bytes());
     #[cfg(windows)]
-    assert_eq!(buf, "<stdin>:\n\nfn main() {}\r\n".as_bytes());
+    assert_eq!(buf, "==== <stdin> ====\nfn main() {}\r\n".as_bytes());
+}
+
+#[test]
+fn written_files_are_not_kept_in_the_session() {
+    init_log();
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    let mut buf: Vec<u8> = vec![];
+    let mut session = Session::new(config, Some(&mut buf));
+    session
+        .format(Input::File(PathBuf::from("tests/source/impls.rs")))
+        .unwrap();
+    assert!(session.source_file.is_empty());
+}
+
+#[test]
+fn allocations_are_reused_across_files() {
+    init_log();
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    let mut buf: Vec<u8> = vec![];
+    let mut session = Session::new(config, Some(&mut buf));
+    session
+        .format(Input::File(PathBuf::from("tests/source/impls.rs")))
+        .unwrap();
+    let (_, ignore_path_set) = session.reused.ignore_path_set.clone().unwrap();
+    let (_, session_globals) = session.reused.session_globals.clone().unwrap();
+    let capacity = session.reused.buffer.capacity();
+    assert!(capacity > 0);
+    session
+        .format(Input::File(PathBuf::from("tests/source/fn-simple.rs")))
+        .unwrap();
+    assert!(session.reused.buffer.capacity() >= capacity);
+    let (_, reused_session_globals) = session.reused.session_globals.clone().unwrap();
+    assert!(Lrc::ptr_eq(&session_globals, &reused_session_globals));
+    let (_, reused_ignore_path_set) = session.reused.ignore_path_set.clone().unwrap();
+    assert!(Lrc::ptr_eq(&ignore_path_set, &reused_ignore_path_set));
+
+    // A file formatted with another `ignore` option does not reuse the ignored files of the
+    // previous ones.
+    let mut config =
+        Config::from_toml(r#"ignore = ["tests/source/impls.rs"]"#, Path::new("")).unwrap();
+    config.set().emit_mode(EmitMode::Stdout);
+    let ignore = config.ignore();
+    session.override_config(config, |session| {
+        session
+            .format(Input::File(PathBuf::from("tests/source/impls.rs")))
+            .unwrap()
+    });
+    let (ignore_list, reused_ignore_path_set) = session.reused.ignore_path_set.clone().unwrap();
+    assert_eq!(ignore_list, ignore);
+    assert!(!Lrc::ptr_eq(&ignore_path_set, &reused_ignore_path_set));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn reused_allocations_are_dropped_above_memory_budget() {
+    init_log();
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    // The test process takes more than 1 MiB.
+    config.set().memory_budget(1);
+    let mut buf: Vec<u8> = vec![];
+    let mut session = Session::new(config, Some(&mut buf));
+    session
+        .format(Input::File(PathBuf::from("tests/source/impls.rs")))
+        .unwrap();
+    assert!(session.reused.session_globals.is_none());
+    assert!(session.reused.ignore_path_set.is_none());
+    assert_eq!(session.reused.buffer.capacity(), 0);
 }
 
 #[test]
@@ -574,10 +725,26 @@ fn stdin_generated_files_issue_5172() {
     // N.B. this should be changed once `format_generated_files` is supported with stdin
     assert_eq!(
         String::from_utf8(buf).unwrap(),
-        "<stdin>:\n\n//@generated\nfn main() {}\n",
+        "==== <stdin> ====\n//@generated\nfn main() {}\n",
     );
 }
 
+#[test]
+fn skips_files_larger_than_max_file_size() {
+    init_log();
+    let input = Input::File(PathBuf::from("tests/source/impls.rs"));
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().max_file_size(10);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut buf));
+        session.format(input).unwrap();
+        assert!(session.has_no_errors());
+    }
+    assert!(buf.is_empty());
+}
+
 #[test]
 fn stdin_handles_mod_inner_ignore_attr() {
     // see https://github.com/rust-lang/rustfmt/issues/5368
@@ -627,6 +794,190 @@ fn format_lines_errors_are_reported_with_tabs() {
     assert!(session.has_formatting_errors());
 }
 
+#[test]
+fn narrow_widths_are_reported() {
+    init_log();
+    let has_warnings = |max_width, tab_spaces| {
+        let mut config = Config::default();
+        config.set().max_width(max_width);
+        config.set().tab_spaces(tab_spaces);
+        let mut session = Session::<io::Stdout>::new(config, None);
+        let report = session
+            .format(Input::Text("fn main() {}\n".to_owned()))
+            .unwrap();
+        report.has_warnings()
+    };
+    assert!(has_warnings(30, 2));
+    assert!(has_warnings(60, 8));
+    assert!(!has_warnings(40, 4));
+    assert!(!has_warnings(100, 4));
+}
+
+#[test]
+fn rewrite_failures_are_reported() {
+    init_log();
+    let input = format!(
+        "fn main() {{\n    let x = foo(a, \"{}\");\n}}\n",
+        "a".repeat(100)
+    );
+    let has_formatting_errors = |error_on_unformatted| {
+        let mut config = Config::default();
+        config.set().error_on_unformatted(error_on_unformatted);
+        let mut session = Session::<io::Stdout>::new(config, None);
+        session.format(Input::Text(input.clone())).unwrap();
+        session.has_formatting_errors()
+    };
+    assert!(has_formatting_errors(true));
+    assert!(!has_formatting_errors(false));
+}
+
+#[test]
+fn comment_overflow_exempts_comment_lines() {
+    init_log();
+    let url_comment = format!("// See https://example.com/{}\n", "a".repeat(100));
+    let plain_comment = format!("// {}\n", "a ".repeat(60));
+    let code = format!("const A: &str = \"{}\"; // b\n", "a".repeat(100));
+    let has_errors = |input: &str, comment_overflow| {
+        let mut config = Config::default();
+        config.set().error_on_line_overflow(true);
+        config.set().error_on_unformatted(true);
+        config.set().comment_overflow(comment_overflow);
+        let mut session = Session::<io::Stdout>::new(config, None);
+        session.format(Input::Text(input.to_owned())).unwrap();
+        session.has_formatting_errors()
+    };
+    assert!(has_errors(&url_comment, CommentOverflow::Error));
+    assert!(!has_errors(&url_comment, CommentOverflow::AllowUrls));
+    assert!(has_errors(&plain_comment, CommentOverflow::AllowUrls));
+    assert!(!has_errors(&plain_comment, CommentOverflow::Allow));
+    assert!(has_errors(&code, CommentOverflow::Allow));
+}
+
+#[test]
+fn unused_toggle_comments_are_reported() {
+    init_log();
+    let has_check_errors = |input: &str| {
+        let mut session = Session::<io::Stdout>::new(Config::default(), None);
+        session.format(Input::Text(input.to_owned())).unwrap();
+        session.has_check_errors()
+    };
+    assert!(!has_check_errors(
+        "fn main() {\n    // rustfmt: off\n    let a=1;\n    // rustfmt: on\n}\n"
+    ));
+    assert!(!has_check_errors(
+        "fn main() {\n    match a {\n        // rustfmt: off\n        0   => 1,\n        \
+         // rustfmt: on\n        _ => 0,\n    }\n}\n"
+    ));
+    assert!(!has_check_errors(
+        "fn main() {\n    let a = [\n        // rustfmt: off\n        (0,  1),\n    ];\n}\n"
+    ));
+    assert!(has_check_errors(
+        "fn main() {\n    let a = (1, // rustfmt: off\n        2);\n}\n"
+    ));
+    assert!(has_check_errors(
+        "fn main() {\n    // rustfmt: on\n    let a = 1;\n}\n"
+    ));
+}
+
+#[test]
+fn file_timeout_is_an_internal_error() {
+    init_log();
+    // A clock which moves on by an hour whenever it is read, so that the deadline has passed
+    // as soon as it is checked, however fast the file is formatted.
+    fn clock() -> Instant {
+        thread_local!(static NOW: Cell<Instant> = Cell::new(Instant::now()));
+        NOW.with(|now| {
+            let time = now.get();
+            now.set(time + Duration::from_secs(3600));
+            time
+        })
+    }
+    let has_internal_errors = |file_timeout| {
+        let mut config = Config::default();
+        config.set().file_timeout(file_timeout);
+        let mut session = Session::<io::Stdout>::new(config, None);
+        session.clock = clock;
+        session
+            .format(Input::Text("fn main() {\n    let a=1;\n}\n".to_owned()))
+            .unwrap();
+        session.has_internal_errors()
+    };
+    assert!(has_internal_errors(1));
+    assert!(!has_internal_errors(0));
+}
+
+#[test]
+fn file_config_attribute_is_applied() {
+    init_log();
+    let input = "#![rustfmt::config(max_width = 40)]\n\
+                 fn main() {\n    foo(aaaaaaaaaa, bbbbbbbbbb, cccccccccc);\n}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().newline_style(NewlineStyle::Unix);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut buf));
+        session.format(Input::Text(input.to_owned())).unwrap();
+        assert!(session.has_no_errors());
+    }
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "==== <stdin> ====\n#![rustfmt::config(max_width = 40)]\n\
+         fn main() {\n    foo(\n        aaaaaaaaaa,\n        bbbbbbbbbb,\n\
+         \x20       cccccccccc,\n    );\n}\n"
+    );
+}
+
+#[test]
+fn config_attributes_apply_to_out_of_line_submodules() {
+    init_log();
+    let dir = Path::new("tests/mod-resolver/module-config");
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().newline_style(NewlineStyle::Unix);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut buf));
+        session.format(Input::File(dir.join("lib.rs"))).unwrap();
+        assert!(session.has_no_errors());
+    }
+    let output = String::from_utf8(buf).unwrap();
+    let formatted = |file: &str| {
+        let header = format!("==== {} ====\n", dir.join(file).display());
+        let start = output.find(&header).unwrap() + header.len();
+        output[start..].split("====").next().unwrap().to_owned()
+    };
+    assert_eq!(
+        formatted("c.rs"),
+        "fn main() {\n    foo(\n        aaaaaaaaaa,\n        bbbbbbbbbb,\n\
+         \x20       cccccccccc,\n    );\n}\n"
+    );
+    assert_eq!(
+        formatted("a/b.rs"),
+        "fn main() {\n  foo(\n    aaaaaaaaaa,\n    bbbbbbbbbb,\n    cccccccccc,\n  );\n}\n"
+    );
+}
+
+#[test]
+fn invalid_config_attributes_are_reported() {
+    init_log();
+    let has_check_errors = |input: &str| {
+        let mut session = Session::<io::Stdout>::new(Config::default(), None);
+        session.format(Input::Text(input.to_owned())).unwrap();
+        session.has_check_errors()
+    };
+    assert!(!has_check_errors(
+        "mod a {\n    #![rustfmt::config(max_width = 80, hard_tabs = false)]\n}\n"
+    ));
+    assert!(has_check_errors(
+        "mod a {\n    #![rustfmt::config(max_width = \"wide\")]\n}\n"
+    ));
+    assert!(has_check_errors(
+        "mod a {\n    #![rustfmt::config(edition = \"2018\")]\n}\n"
+    ));
+    assert!(has_check_errors("#![rustfmt::config]\n"));
+}
+
 // For each file, run rustfmt and collect the output.
 // Returns the number of files checked and the number of failures.
 fn check_files(files: Vec<PathBuf>, opt_config: &Option<PathBuf>) -> (Vec<FormatReport>, u32, u32) {
@@ -648,7 +999,7 @@ fn check_files(files: Vec<PathBuf>, opt_config: &Option<PathBuf>) -> (Vec<Format
         debug!("Testing '{}'...", file_name.display());
 
         match idempotent_check(&file_name, opt_config) {
-            Ok(ref report) if report.has_warnings() => {
+            Ok(ref report) if has_unexpected_warnings(report) => {
                 print!("{}", FormatReportFormatterBuilder::new(report).build());
                 fails += 1;
             }
@@ -731,13 +1082,25 @@ enum IdempotentCheckError {
     Parse,
 }
 
+/// Returns `true` if `report` has warnings other than those about the narrow widths which some
+/// tests use on purpose.
+fn has_unexpected_warnings(report: &FormatReport) -> bool {
+    report
+        .internal
+        .borrow()
+        .0
+        .values()
+        .flatten()
+        .any(|error| !matches!(error.kind, crate::ErrorKind::NarrowWidth(_)))
+}
+
 fn idempotent_check(
     filename: &PathBuf,
     opt_config: &Option<PathBuf>,
 ) -> Result<FormatReport, IdempotentCheckError> {
     let sig_comments = read_significant_comments(filename);
     let config = if let Some(ref config_file_path) = opt_config {
-        Config::from_toml_path(config_file_path).expect("`rustfmt.toml` not found")
+        Config::from_toml_path(config_file_path, None).expect("`rustfmt.toml` not found")
     } else {
         read_config(filename)
     };
@@ -80,3 +80,24 @@ fn fallback_and_try_to_resolve_external_submod_relative_to_current_dir_path() {
         ],
     )
 }
+
+#[test]
+fn shared_modules_are_formatted_once() {
+    let mut session = Session::<io::Stdout>::new(Default::default(), None);
+    for root in &["lib.rs", "main.rs"] {
+        let path = PathBuf::from("tests/mod-resolver/shared-modules").join(root);
+        session
+            .format(Input::File(path))
+            .expect("Should not have had any execution errors");
+    }
+    let shared = FileName::Real(PathBuf::from("tests/mod-resolver/shared-modules/shared.rs"));
+    let formatted_files: Vec<_> = session.source_file.iter().map(|(name, _)| name).collect();
+    assert_eq!(formatted_files.len(), 3);
+    assert_eq!(
+        formatted_files
+            .iter()
+            .filter(|&&name| *name == shared)
+            .count(),
+        1
+    );
+}
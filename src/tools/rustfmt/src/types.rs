@@ -7,13 +7,13 @@
 
 use crate::comment::{combine_strs_with_missing_comments, contains_comment};
 use crate::config::lists::*;
-use crate::config::{IndentStyle, TypeDensity, Version};
+use crate::config::{Config, Density, IndentStyle, TypeDensity, Version};
 use crate::expr::{
     format_expr, rewrite_assign_rhs, rewrite_call, rewrite_tuple, rewrite_unary_prefix, ExprType,
     RhsAssignKind,
 };
 use crate::lists::{
-    definitive_tactic, itemize_list, write_list, ListFormatting, ListItem, Separator,
+    definitive_tactic, itemize_list, write_list, ItemWrap, ListFormatting, ListItem, Separator,
 };
 use crate::macros::{rewrite_macro, MacroPosition};
 use crate::overflow;
@@ -24,7 +24,8 @@
 use crate::spanned::Spanned;
 use crate::utils::{
     colon_spaces, extra_offset, first_line_width, format_extern, format_mutability,
-    last_line_extendable, last_line_width, mk_sp, rewrite_ident,
+    last_line_extendable, last_line_width, mk_sp, rewrite_ident, unicode_str_width, wrap_str,
+    wrap_token,
 };
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -42,57 +43,139 @@ pub(crate) fn rewrite_path(
     path: &ast::Path,
     shape: Shape,
 ) -> Option<String> {
-    let skip_count = qself.as_ref().map_or(0, |x| x.position);
-
-    let mut result = if path.is_global() && qself.is_none() && path_context != PathContext::Import {
+    let result = if path.is_global() && qself.is_none() && path_context != PathContext::Import {
         "::".to_owned()
     } else {
         String::new()
     };
 
-    let mut span_lo = path.span.lo();
-
     if let Some(qself) = qself {
-        result.push('<');
-
-        let fmt_ty = qself.ty.rewrite(context, shape)?;
-        result.push_str(&fmt_ty);
-
-        if skip_count > 0 {
-            result.push_str(" as ");
-            if path.is_global() && path_context != PathContext::Import {
-                result.push_str("::");
-            }
-
-            // 3 = ">::".len()
-            let shape = shape.sub_width(3)?;
-
-            result = rewrite_path_segments(
-                PathContext::Type,
-                result,
-                path.segments.iter().take(skip_count),
-                span_lo,
-                path.span.hi(),
-                context,
-                shape,
-            )?;
-        }
-
-        result.push_str(">::");
-        span_lo = qself.ty.span.hi() + BytePos(1);
+        let mut qself_str = rewrite_qself(context, path_context, qself, path, result + "<", shape)?;
+        qself_str.push_str(closing_angle_bracket(&qself_str, context.config));
+        qself_str.push_str("::");
+        return rewrite_qself_path_rest(context, path_context, qself, path, qself_str, shape);
     }
 
     rewrite_path_segments(
         path_context,
         result,
-        path.segments.iter().skip(skip_count),
-        span_lo,
+        path.segments.iter(),
+        path.span.lo(),
+        path.span.hi(),
+        context,
+        shape,
+    )
+}
+
+/// Rewrites a path, the qualified self of which is put on a line of its own, i.e.
+/// `<\n    Foo as Bar\n>::CONST`. It is for the callers which have no other layout to try when
+/// `rewrite_path` fails.
+pub(crate) fn rewrite_path_with_broken_qself(
+    context: &RewriteContext<'_>,
+    path_context: PathContext,
+    qself: &ast::QSelf,
+    path: &ast::Path,
+    shape: Shape,
+) -> Option<String> {
+    let nested_shape = shape
+        .block()
+        .block_indent(context.config.tab_spaces())
+        .with_max_width(context.config);
+    let qself_str = rewrite_qself(
+        context,
+        path_context,
+        qself,
+        path,
+        String::new(),
+        nested_shape,
+    )?;
+    let qself_str = format!(
+        "<{}{}{}>::",
+        nested_shape.indent.to_string_with_newline(context.config),
+        qself_str,
+        shape.indent.to_string_with_newline(context.config),
+    );
+    rewrite_qself_path_rest(context, path_context, qself, path, qself_str, shape)
+}
+
+/// Rewrites the segments of a path after its qualified self, appended to `qself_str`.
+fn rewrite_qself_path_rest(
+    context: &RewriteContext<'_>,
+    path_context: PathContext,
+    qself: &ast::QSelf,
+    path: &ast::Path,
+    qself_str: String,
+    shape: Shape,
+) -> Option<String> {
+    rewrite_path_segments(
+        path_context,
+        qself_str,
+        path.segments.iter().skip(qself.position),
+        qself.ty.span.hi() + BytePos(1),
         path.span.hi(),
         context,
         shape,
     )
 }
 
+/// Rewrites the qualified self of a path, i.e. `Foo as Bar` in `<Foo as Bar>::CONST`,
+/// appended to `buffer`.
+fn rewrite_qself(
+    context: &RewriteContext<'_>,
+    path_context: PathContext,
+    qself: &ast::QSelf,
+    path: &ast::Path,
+    mut buffer: String,
+    shape: Shape,
+) -> Option<String> {
+    let fmt_ty = qself.ty.rewrite(context, shape)?;
+    buffer.push_str(&fmt_ty);
+
+    if qself.position > 0 {
+        buffer.push_str(" as ");
+        if path.is_global() && path_context != PathContext::Import {
+            buffer.push_str("::");
+        }
+
+        // 3 = ">::".len()
+        let shape = shape.sub_width(3)?;
+
+        buffer = rewrite_path_segments(
+            PathContext::Type,
+            buffer,
+            path.segments.iter().take(qself.position),
+            path.span.lo(),
+            path.span.hi(),
+            context,
+            shape,
+        )?;
+    }
+    Some(buffer)
+}
+
+/// Rewrites `path` if it does not fit within `shape`. A path without generic arguments
+/// cannot be broken over lines, so it is treated like a single token, see `wrap_token`.
+pub(crate) fn rewrite_overlong_path(
+    context: &RewriteContext<'_>,
+    path_context: PathContext,
+    qself: &Option<ptr::P<ast::QSelf>>,
+    path: &ast::Path,
+    shape: Shape,
+) -> Option<String> {
+    if qself.is_some() || path.segments.iter().any(|segment| segment.args.is_some()) {
+        return None;
+    }
+    let width = unicode_str_width(context.snippet(path.span));
+    let path_str = rewrite_path(
+        context,
+        path_context,
+        qself,
+        path,
+        Shape::legacy(width, shape.indent),
+    )?;
+    wrap_token(path_str, context.config, shape)
+}
+
 fn rewrite_path_segments<'a, I>(
     path_context: PathContext,
     mut buffer: String,
@@ -191,7 +274,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         }
 
         let infix = match (&self.kind, context.config.type_punctuation_density()) {
-            (Bound { .. }, _) => ": ",
+            (Bound { .. }, _) => type_bound_colon(context),
             (Equality { .. }, TypeDensity::Wide) => " = ",
             (Equality { .. }, TypeDensity::Compressed) => "=",
         };
@@ -268,7 +351,7 @@ fn rewrite_segment(
                 // Update position of last bracket.
                 *span_lo = context
                     .snippet_provider
-                    .span_after(mk_sp(*span_lo, span_hi), "<");
+                    .span_after(mk_sp(*span_lo, span_hi), "<")?;
             }
             _ => (),
         }
@@ -298,9 +381,26 @@ fn format_function_type<'a, I>(
         IndentStyle::Block => shape.offset_left(4)?,
         IndentStyle::Visual => shape.block_left(4)?,
     };
+    // With block indent, the arguments can be put vertically so that the return type
+    // follows the closing paren at the start of a line, where there is more room, e.g.,
+    // for a long `Fn(...) -> ...` bound in a where clause.
+    let mut force_vertical = false;
     let output = match *output {
         FnRetTy::Ty(ref ty) => {
-            let type_str = ty.rewrite(context, ty_shape)?;
+            let type_str = match ty.rewrite(context, ty_shape) {
+                Some(type_str) => type_str,
+                None if context.use_block_indent() && inputs.len() > 0 => {
+                    force_vertical = true;
+                    // 5 = ") -> "
+                    let closing_paren_shape = Shape {
+                        width: shape.width + shape.offset,
+                        offset: 0,
+                        ..shape.block()
+                    };
+                    ty.rewrite(context, closing_paren_shape.offset_left(5)?)?
+                }
+                None => return None,
+            };
             format!(" -> {}", type_str)
         }
         FnRetTy::Default(..) => String::new(),
@@ -320,10 +420,10 @@ fn format_function_type<'a, I>(
     };
 
     let is_inputs_empty = inputs.len() == 0;
-    let list_lo = context.snippet_provider.span_after(span, "(");
+    let list_lo = context.snippet_provider.span_after(span, "(")?;
     let (list_str, tactic) = if is_inputs_empty {
-        let tactic = get_tactics(&[], &output, shape);
-        let list_hi = context.snippet_provider.span_before(span, ")");
+        let tactic = get_tactics(&[], &output, shape, context.config);
+        let list_hi = context.snippet_provider.span_before(span, ")")?;
         let comment = context
             .snippet_provider
             .span_to_snippet(mk_sp(list_lo, list_hi))?
@@ -353,8 +453,18 @@ fn format_function_type<'a, I>(
             false,
         );
 
-        let item_vec: Vec<_> = items.collect();
-        let tactic = get_tactics(&item_vec, &output, shape);
+        let mut item_vec: Vec<_> = items.collect();
+        if variadic && context.config.version() == Version::Two {
+            // The `...` stays next to the parameter before it.
+            if let Some(item) = item_vec.last_mut() {
+                item.wrap = ItemWrap::NoBreak;
+            }
+        }
+        let tactic = if force_vertical {
+            DefinitiveListTactic::Vertical
+        } else {
+            get_tactics(&item_vec, &output, shape, context.config)
+        };
         let trailing_separator = if !context.use_block_indent() || variadic {
             SeparatorTactic::Never
         } else {
@@ -382,7 +492,16 @@ fn format_function_type<'a, I>(
             shape.block().indent.to_string_with_newline(context.config),
         )
     };
-    if output.is_empty() || last_line_width(&args) + first_line_width(&output) <= shape.width {
+    let last_line_budget = if args.contains('\n') && context.config.version() == Version::Two {
+        // The closing paren starts a line at the block indent.
+        shape.width + shape.offset
+    } else {
+        shape.width
+    };
+    if output.is_empty()
+        || force_vertical
+        || last_line_width(&args) + first_line_width(&output) <= last_line_budget
+    {
         Some(format!("{}{}", args, output))
     } else {
         Some(format!(
@@ -400,16 +519,28 @@ fn type_bound_colon(context: &RewriteContext<'_>) -> &'static str {
 
 // If the return type is multi-lined, then force to use multiple lines for
 // arguments as well.
-fn get_tactics(item_vec: &[ListItem], output: &str, shape: Shape) -> DefinitiveListTactic {
+fn get_tactics(
+    item_vec: &[ListItem],
+    output: &str,
+    shape: Shape,
+    config: &Config,
+) -> DefinitiveListTactic {
     if output.contains('\n') {
         DefinitiveListTactic::Vertical
     } else {
+        // With `Version::Two`, the parameters of function types are packed like those of
+        // functions.
+        let tactic = match config.fn_args_layout() {
+            Density::Compressed if config.version() == Version::Two => ListTactic::Mixed,
+            _ => ListTactic::HorizontalVertical,
+        };
         definitive_tactic(
             item_vec,
-            ListTactic::HorizontalVertical,
+            tactic,
             Separator::Comma,
             // 2 is for the case of ',\n'
             shape.width.saturating_sub(2 + output.len()),
+            config,
         )
     }
 }
@@ -465,6 +596,38 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
     }
 }
 
+/// Returns the closing angle bracket to append to `preceding`. With
+/// `space_between_closing_angle_brackets`, consecutive closing angle brackets are
+/// separated by a space, since very old compilers lex `>>` as a shift operator.
+pub(crate) fn closing_angle_bracket(preceding: &str, config: &Config) -> &'static str {
+    if config.space_between_closing_angle_brackets() && preceding.ends_with('>') {
+        " >"
+    } else {
+        ">"
+    }
+}
+
+/// Rewrites `items` as a list of generic parameters or arguments in angle brackets,
+/// prefixed by `ident`.
+pub(crate) fn rewrite_angle_bracketed<'a, T: 'a + overflow::IntoOverflowableItem<'a>>(
+    context: &'a RewriteContext<'_>,
+    ident: &'a str,
+    items: impl Iterator<Item = &'a T>,
+    shape: Shape,
+    span: Span,
+) -> Option<String> {
+    let result = overflow::rewrite_with_angle_brackets(context, ident, items, shape, span)?;
+    if !context.config.space_between_closing_angle_brackets() {
+        return Some(result);
+    }
+    let mut result = match result.strip_suffix('>') {
+        Some(list) => list.to_owned(),
+        None => return Some(result),
+    };
+    result.push_str(closing_angle_bracket(&result, context.config));
+    wrap_str(result, context.config.max_width(), shape)
+}
+
 fn rewrite_generic_args(
     gen_args: &ast::GenericArgs,
     context: &RewriteContext<'_>,
@@ -486,7 +649,7 @@ fn rewrite_generic_args(
                 })
                 .collect::<Vec<_>>();
 
-            overflow::rewrite_with_angle_brackets(context, "", args.iter(), shape, span)
+            rewrite_angle_bracketed(context, "", args.iter(), shape, span)
         }
         ast::GenericArgs::Parenthesized(ref data) => format_function_type(
             data.inputs.iter().map(|x| &**x),
@@ -596,7 +759,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
         {
             result.push_str("const ");
             result.push_str(rewrite_ident(context, self.ident));
-            result.push_str(": ");
+            result.push_str(colon_spaces(context.config));
             result.push_str(&ty.rewrite(context, shape)?);
             if let Some(default) = default {
                 let eq_str = match context.config.type_punctuation_density() {
@@ -693,7 +856,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 let mut_len = mut_str.len();
                 let mut result = String::with_capacity(128);
                 result.push('&');
-                let ref_hi = context.snippet_provider.span_after(self.span(), "&");
+                let ref_hi = context.snippet_provider.span_after(self.span(), "&")?;
                 let mut cmnt_lo = ref_hi;
 
                 if let Some(ref lifetime) = *lifetime {
@@ -720,7 +883,7 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 }
 
                 if ast::Mutability::Mut == mt.mutbl {
-                    let mut_hi = context.snippet_provider.span_after(self.span(), "mut");
+                    let mut_hi = context.snippet_provider.span_after(self.span(), "mut")?;
                     let before_mut_span = mk_sp(cmnt_lo, mut_hi - BytePos::from_usize(3));
                     if contains_comment(context.snippet(before_mut_span)) {
                         result = combine_strs_with_missing_comments(
@@ -800,7 +963,9 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 rewrite_tuple(context, items.iter(), self.span, shape, items.len() == 1)
             }
             ast::TyKind::Path(ref q_self, ref path) => {
-                rewrite_path(context, PathContext::Type, q_self, path, shape)
+                rewrite_path(context, PathContext::Type, q_self, path, shape).or_else(|| {
+                    rewrite_overlong_path(context, PathContext::Type, q_self, path, shape)
+                })
             }
             ast::TyKind::Array(ref ty, ref repeats) => rewrite_pair(
                 &**ty,
@@ -831,7 +996,8 @@ fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>
                 let rw = if context.config.version() == Version::One {
                     it.rewrite(context, shape)
                 } else {
-                    join_bounds(context, shape, it, false)
+                    // 5 is length of 'impl '. Broken bounds are block indented.
+                    join_bounds(context, shape.offset_left(5)?, it, true)
                 };
                 rw.map(|it_str| {
                     let space = if it_str.is_empty() { "" } else { " " };
@@ -947,7 +1113,7 @@ fn join_bounds_inner(
             let trailing_span = if i < items.len() - 1 {
                 let hi = context
                     .snippet_provider
-                    .span_before(mk_sp(items[i + 1].span().lo(), item.span().hi()), "+");
+                    .span_before(mk_sp(items[i + 1].span().lo(), item.span().hi()), "+")?;
 
                 Some(mk_sp(item.span().hi(), hi))
             } else {
@@ -956,7 +1122,7 @@ fn join_bounds_inner(
             let (leading_span, has_leading_comment) = if i > 0 {
                 let lo = context
                     .snippet_provider
-                    .span_after(mk_sp(items[i - 1].span().hi(), item.span().lo()), "+");
+                    .span_after(mk_sp(items[i - 1].span().hi(), item.span().lo()), "+")?;
 
                 let span = mk_sp(lo, item.span().lo());
 
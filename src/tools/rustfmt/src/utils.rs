@@ -9,8 +9,10 @@
 use rustc_span::{sym, symbol, BytePos, LocalExpnId, Span, Symbol, SyntaxContext};
 use unicode_width::UnicodeWidthStr;
 
-use crate::comment::{filter_normal_code, CharClasses, FullCodeCharKind, LineClasses};
-use crate::config::{Config, Version};
+use crate::comment::{
+    comment_starts, filter_normal_code, CharClasses, FullCodeCharKind, LineClasses,
+};
+use crate::config::{Config, TypeDensity, Version};
 use crate::rewrite::RewriteContext;
 use crate::shape::{Indent, Shape};
 
@@ -24,10 +26,36 @@ pub(crate) fn skip_annotation() -> Symbol {
     Symbol::intern("rustfmt::skip")
 }
 
+#[inline]
+pub(crate) fn sort_annotation() -> Symbol {
+    Symbol::intern("rustfmt::sort")
+}
+
+/// Returns `ident` as written in the source, with the `r#` prefix of a raw identifier.
+/// Formatting an `Ident` or its `Symbol` instead drops the prefix, unless the identifier
+/// is a keyword of the edition of the code.
 pub(crate) fn rewrite_ident<'a>(context: &'a RewriteContext<'_>, ident: symbol::Ident) -> &'a str {
     context.snippet(ident.span)
 }
 
+/// Rewrites a path without generic arguments, e.g. the path of a macro or of a
+/// `pub(in path)` visibility, keeping the raw identifiers of its segments.
+pub(crate) fn rewrite_simple_path(context: &RewriteContext<'_>, path: &Path) -> String {
+    let mut segments = path.segments.iter();
+    // The leading `::` of a global path is an empty segment, which must be
+    // rewritten as a prefix rather than be joined with the other segments.
+    let prefix = if path.is_global() {
+        segments.next();
+        "::"
+    } else {
+        ""
+    };
+    let segments: Vec<_> = segments
+        .map(|segment| rewrite_ident(context, segment.ident))
+        .collect();
+    format!("{}{}", prefix, segments.join("::"))
+}
+
 // Computes the length of a string's last line, minus offset.
 pub(crate) fn extra_offset(text: &str, shape: Shape) -> usize {
     match text.rfind('\n') {
@@ -58,15 +86,8 @@ pub(crate) fn format_visibility(
         VisibilityKind::Public => Cow::from("pub "),
         VisibilityKind::Inherited => Cow::from(""),
         VisibilityKind::Restricted { ref path, .. } => {
-            let Path { ref segments, .. } = **path;
-            let mut segments_iter = segments.iter().map(|seg| rewrite_ident(context, seg.ident));
-            if path.is_global() {
-                segments_iter
-                    .next()
-                    .expect("Non-global path in pub(restricted)?");
-            }
             let is_keyword = |s: &str| s == "crate" || s == "self" || s == "super";
-            let path = segments_iter.collect::<Vec<_>>().join("::");
+            let path = rewrite_simple_path(context, path);
             let in_str = if is_keyword(&path) { "" } else { "in " };
 
             Cow::from(format!("pub({}{}) ", in_str, path))
@@ -130,6 +151,31 @@ pub(crate) fn format_mutability(mutability: ast::Mutability) -> &'static str {
     }
 }
 
+/// Returns the prefix made of `keywords` (e.g. `box`, `ref mut`, `raw const`), each
+/// followed by a single space. Empty keywords are skipped. See `spaced_prefix` for the space
+/// after the last keyword.
+pub(crate) fn keyword_prefix(keywords: &[&str]) -> String {
+    keywords
+        .iter()
+        .map(|keyword| keyword.trim())
+        .filter(|keyword| !keyword.is_empty())
+        .fold(String::new(), |mut prefix, keyword| {
+            prefix.push_str(keyword);
+            prefix.push(' ');
+            prefix
+        })
+}
+
+/// Returns `prefix`, e.g. `box ` or `&mut `, as it is written before `operand`. With
+/// `keyword_prefix_spacing = "Compressed"`, the space after the last keyword is dropped before
+/// an operand which starts with a delimiter, e.g. `box(a, b)`.
+pub(crate) fn spaced_prefix<'a>(config: &Config, prefix: &'a str, operand: &str) -> &'a str {
+    match config.keyword_prefix_spacing() {
+        TypeDensity::Compressed if operand.starts_with(&['(', '[', '{'][..]) => prefix.trim_end(),
+        _ => prefix,
+    }
+}
+
 #[inline]
 pub(crate) fn format_extern(
     ext: ast::Extern,
@@ -184,14 +230,17 @@ pub(crate) fn is_single_line(s: &str) -> bool {
     !s.chars().any(|c| c == '\n')
 }
 
-#[inline]
 pub(crate) fn first_line_contains_single_line_comment(s: &str) -> bool {
-    s.lines().next().map_or(false, |l| l.contains("//"))
+    let first_line = s.lines().next().unwrap_or("");
+    comment_starts(first_line).any(|i| first_line[i..].starts_with("//"))
 }
 
-#[inline]
 pub(crate) fn last_line_contains_single_line_comment(s: &str) -> bool {
-    s.lines().last().map_or(false, |l| l.contains("//"))
+    // The whole text is scanned, since the last line may be within a block comment or
+    // string literal.
+    let s = s.strip_suffix('\n').unwrap_or(s);
+    let last_line_start = s.rfind('\n').map_or(0, |i| i + 1);
+    comment_starts(s).any(|i| i >= last_line_start && s[i..].starts_with("//"))
 }
 
 #[inline]
@@ -274,6 +323,25 @@ pub(crate) fn contains_skip(attrs: &[Attribute]) -> bool {
         .any(|a| a.meta().map_or(false, |a| is_skip(&a)))
 }
 
+/// Returns `true` if the attributes contain `#[rustfmt::sort]`, which requests the fields
+/// or variants of the annotated item to be sorted alphabetically.
+#[inline]
+pub(crate) fn contains_sort(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|a| {
+        a.meta().map_or(false, |a| {
+            matches!(a.kind, MetaItemKind::Word)
+                && pprust::path_to_string(&a.path) == sort_annotation().as_str()
+        })
+    })
+}
+
+/// Returns `true` if the path segments are those of `#[rustfmt::sort]`.
+pub(crate) fn is_sort_attr(segments: &[ast::PathSegment]) -> bool {
+    segments.len() == 2
+        && segments[0].ident.name.as_str() == "rustfmt"
+        && segments[1].ident.name.as_str() == "sort"
+}
+
 #[inline]
 pub(crate) fn semicolon_for_expr(context: &RewriteContext<'_>, expr: &ast::Expr) -> bool {
     // Never try to insert semicolons on expressions when we're inside
@@ -391,6 +459,23 @@ pub(crate) fn wrap_str(s: String, max_width: usize, shape: Shape) -> Option<Stri
     }
 }
 
+/// Like `wrap_str`, for `token`, a single token (or a path of them) which cannot be broken
+/// over lines. With `Version::Two`, a token which does not fit even on a line of its own is
+/// returned anyway, overflowing `max_width`, if `shape` starts a line. Elsewhere on a line it
+/// is rejected, so that the caller retries with the token on a line of its own, e.g. the
+/// right-hand side of `let y = 0x…;` goes on the line after the `=`.
+pub(crate) fn wrap_token(token: String, config: &Config, shape: Shape) -> Option<String> {
+    let own_line_width = config.max_width().saturating_sub(shape.indent.width());
+    if config.version() == Version::Two
+        && shape.offset == shape.indent.alignment
+        && is_single_line(&token)
+        && unicode_str_width(&token) > own_line_width
+    {
+        return Some(token);
+    }
+    wrap_str(token, config.max_width(), shape)
+}
+
 fn is_valid_str(snippet: &str, max_width: usize, shape: Shape) -> bool {
     if !snippet.is_empty() {
         // First line must fits with `shape.width`.
@@ -430,6 +515,28 @@ pub(crate) fn colon_spaces(config: &Config) -> &'static str {
     }
 }
 
+/// Returns the range operator `delim` surrounded by the spaces required by
+/// `spaces_around_ranges`, or `None` if the option is not set. A space is only added on
+/// the sides which have an operand.
+#[inline]
+pub(crate) fn spaced_range_delim(
+    config: &Config,
+    delim: &str,
+    has_lhs: bool,
+    has_rhs: bool,
+) -> Option<String> {
+    if !config.spaces_around_ranges() {
+        return None;
+    }
+    let space_if = |b: bool| if b { " " } else { "" };
+    Some(format!(
+        "{}{}{}",
+        space_if(has_lhs),
+        delim,
+        space_if(has_rhs)
+    ))
+}
+
 #[inline]
 pub(crate) fn left_most_sub_expr(e: &ast::Expr) -> &ast::Expr {
     match e.kind {
@@ -637,6 +744,37 @@ pub(crate) fn trim_left_preserve_layout(
     )
 }
 
+/// Shifts every line but the first of `orig` from `orig_indent`, the original indentation
+/// of the first line, to `indent`, so that the layout of the lines relative to the first
+/// one is preserved. Lines within multi-line string literals are left untouched.
+pub(crate) fn shift_lines_preserve_layout(
+    orig: &str,
+    orig_indent: &str,
+    indent: Indent,
+    config: &Config,
+) -> String {
+    let orig_indent_width = get_prefix_space_width(config, orig_indent);
+    let mut lines = LineClasses::new(orig);
+    let (first_kind, mut result) = match lines.next() {
+        Some(first_line) => first_line,
+        None => return String::new(),
+    };
+    let mut can_indent = indent_next_line(first_kind, &result, config);
+    for (kind, line) in lines {
+        result.push('\n');
+        if can_indent && !is_empty_line(&line) {
+            let new_indent_width = (get_prefix_space_width(config, &line) + indent.width())
+                .saturating_sub(orig_indent_width);
+            result.push_str(&Indent::from_width(config, new_indent_width).to_string(config));
+            result.push_str(line.trim_start());
+        } else {
+            result.push_str(&line);
+        }
+        can_indent = indent_next_line(kind, &line, config);
+    }
+    result
+}
+
 /// Based on the given line, determine if the next line can be indented or not.
 /// This allows to preserve the indentation of multi-line literals when
 /// re-inserted a code block that has been formatted separately from the rest
@@ -696,6 +834,38 @@ fn test_remove_trailing_white_spaces() {
         assert_eq!(remove_trailing_white_spaces(s), s);
     }
 
+    #[test]
+    fn test_keyword_prefix() {
+        assert_eq!(keyword_prefix(&["box"]), "box ");
+        assert_eq!(keyword_prefix(&["ref", "mut "]), "ref mut ");
+        assert_eq!(keyword_prefix(&["", "mut"]), "mut ");
+        assert_eq!(keyword_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_spaced_prefix() {
+        let mut config = Config::default();
+        assert_eq!(spaced_prefix(&config, "box ", "(a, b)"), "box ");
+        config.set().keyword_prefix_spacing(TypeDensity::Compressed);
+        assert_eq!(spaced_prefix(&config, "box ", "(a, b)"), "box");
+        assert_eq!(spaced_prefix(&config, "&mut ", "[a]"), "&mut");
+        assert_eq!(spaced_prefix(&config, "&mut ", "a"), "&mut ");
+        assert_eq!(spaced_prefix(&config, "!", "(a)"), "!");
+    }
+
+    #[test]
+    fn test_contains_single_line_comment() {
+        assert!(first_line_contains_single_line_comment("a // b\nc"));
+        assert!(!first_line_contains_single_line_comment("a\nb // c"));
+        assert!(!first_line_contains_single_line_comment(
+            "\"http://a\" /* // */"
+        ));
+        assert!(last_line_contains_single_line_comment("a\nb // c\n"));
+        assert!(!last_line_contains_single_line_comment("a // b\nc"));
+        assert!(!last_line_contains_single_line_comment("/* a\n// b */"));
+        assert!(!last_line_contains_single_line_comment("\"a\n// b\""));
+    }
+
     #[test]
     fn test_trim_left_preserve_layout() {
         let s = "aaa\n\tbbb\n    ccc";
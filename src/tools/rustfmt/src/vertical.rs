@@ -11,7 +11,8 @@
 use crate::expr::rewrite_field;
 use crate::items::{rewrite_struct_field, rewrite_struct_field_prefix};
 use crate::lists::{
-    definitive_tactic, itemize_list, write_list, ListFormatting, ListItem, Separator,
+    definitive_tactic, itemize_list, sort_list_items, write_list, ListFormatting, ListItem,
+    Separator,
 };
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::{Indent, Shape};
@@ -24,6 +25,8 @@
 pub(crate) trait AlignedItem {
     fn skip(&self) -> bool;
     fn get_span(&self) -> Span;
+    /// The key by which the item is ordered when sorting is requested.
+    fn sort_key(&self) -> Option<String>;
     fn rewrite_prefix(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String>;
     fn rewrite_aligned_item(
         &self,
@@ -42,6 +45,10 @@ fn get_span(&self) -> Span {
         self.span()
     }
 
+    fn sort_key(&self) -> Option<String> {
+        self.ident.map(|ident| ident.name.to_string())
+    }
+
     fn rewrite_prefix(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
         let attrs_str = self.attrs.rewrite(context, shape)?;
         let missing_span = if self.attrs.is_empty() {
@@ -81,6 +88,10 @@ fn get_span(&self) -> Span {
         self.span()
     }
 
+    fn sort_key(&self) -> Option<String> {
+        Some(self.ident.name.to_string())
+    }
+
     fn rewrite_prefix(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
         let attrs_str = self.attrs.rewrite(context, shape)?;
         let name = rewrite_ident(context, self.ident);
@@ -115,6 +126,7 @@ pub(crate) fn rewrite_with_alignment<T: AlignedItem>(
     shape: Shape,
     span: Span,
     one_line_width: usize,
+    sort: bool,
 ) -> Option<String> {
     let (spaces, group_index) = if context.config.struct_field_align_threshold() > 0 {
         group_aligned_items(context, fields)
@@ -131,7 +143,7 @@ pub(crate) fn rewrite_with_alignment<T: AlignedItem>(
         let rest_lo = rest[0].get_span().lo();
         let missing_span = mk_sp(init_hi, rest_lo);
         let missing_span = mk_sp(
-            context.snippet_provider.span_after(missing_span, ","),
+            context.snippet_provider.span_after(missing_span, ",")?,
             missing_span.hi(),
         );
 
@@ -171,12 +183,14 @@ pub(crate) fn rewrite_with_alignment<T: AlignedItem>(
         shape.indent,
         one_line_width,
         force_separator,
+        sort,
     )?;
     if rest.is_empty() {
         Some(result + spaces)
     } else {
         let rest_span = mk_sp(init_last_pos, span.hi());
-        let rest_str = rewrite_with_alignment(rest, context, shape, rest_span, one_line_width)?;
+        let rest_str =
+            rewrite_with_alignment(rest, context, shape, rest_span, one_line_width, sort)?;
         Some(format!(
             "{}{}\n{}{}",
             result,
@@ -212,6 +226,7 @@ fn rewrite_aligned_items_inner<T: AlignedItem>(
     offset: Indent,
     one_line_width: usize,
     force_trailing_separator: bool,
+    sort: bool,
 ) -> Option<String> {
     // 1 = ","
     let item_shape = Shape::indented(offset, context.config).sub_width(1)?;
@@ -241,6 +256,7 @@ fn rewrite_aligned_items_inner<T: AlignedItem>(
         ListTactic::HorizontalVertical,
         Separator::Comma,
         one_line_width,
+        context.config,
     );
 
     if tactic == DefinitiveListTactic::Horizontal {
@@ -263,6 +279,11 @@ fn rewrite_aligned_items_inner<T: AlignedItem>(
         context.config.trailing_comma()
     };
 
+    if sort {
+        let keys = fields.iter().map(AlignedItem::sort_key).collect();
+        items = sort_list_items(items, keys);
+    }
+
     let fmt = ListFormatting::new(item_shape, context.config)
         .tactic(tactic)
         .trailing_separator(separator_tactic)
@@ -1,4 +1,5 @@
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use rustc_ast::{ast, token::Delimiter, visit};
@@ -6,26 +7,35 @@
 use rustc_span::{symbol, BytePos, Pos, Span};
 
 use crate::attr::*;
-use crate::comment::{contains_comment, rewrite_comment, CodeCharKind, CommentCodeSlices};
+use crate::cancellation::CancellationToken;
+use crate::comment::{
+    contains_comment, has_balanced_delimiters, rewrite_comment, rewrite_comment_from_indent,
+    CodeCharKind, CommentCodeSlices,
+};
 use crate::config::Version;
 use crate::config::{BraceStyle, Config};
 use crate::coverage::transform_missing_snippet;
+use crate::formatting::generated::contains_generated_attr;
+use crate::formatting::toggles;
 use crate::items::{
-    format_impl, format_trait, format_trait_alias, is_mod_decl, is_use_item, rewrite_extern_crate,
-    rewrite_type_alias, FnBraceStyle, FnSig, ItemVisitorKind, StaticParts, StructParts,
+    format_impl, format_trait, format_trait_alias, is_extern_crate, is_mod_decl, is_use_item,
+    rewrite_extern_crate, rewrite_type_alias, FnBraceStyle, FnSig, ItemVisitorKind, StaticParts,
+    StructParts,
 };
 use crate::macros::{macro_style, rewrite_macro, rewrite_macro_def, MacroPosition};
+use crate::module_config::{is_config_attr, is_optimize_attr, ModuleConfigs};
 use crate::modules::Module;
 use crate::parse::session::ParseSess;
-use crate::rewrite::{Rewrite, RewriteContext};
+use crate::rewrite::{Deadline, Rewrite, RewriteContext, RewriteFailure, RewriteFailures};
 use crate::shape::{Indent, Shape};
 use crate::skip::{is_skip_attr, SkipContext};
 use crate::source_map::{LineRangeUtils, SpanUtils};
 use crate::spanned::Spanned;
 use crate::stmt::Stmt;
 use crate::utils::{
-    self, contains_skip, count_newlines, depr_skip_annotation, format_unsafety, inner_attributes,
-    last_line_width, mk_sp, ptr_vec_to_ref_vec, rewrite_ident, starts_with_newline, stmt_expr,
+    self, contains_skip, contains_sort, count_newlines, depr_skip_annotation, format_unsafety,
+    inner_attributes, last_line_width, mk_sp, ptr_vec_to_ref_vec, rewrite_ident,
+    starts_with_newline, stmt_expr,
 };
 use crate::{ErrorKind, FormatReport, FormattingError};
 
@@ -37,13 +47,31 @@ pub(crate) struct SnippetProvider {
     start_pos: usize,
     /// An end position of the file that this snippet lives.
     end_pos: usize,
+    /// The spans outside of the file which were asked for, e.g. dummy spans of nodes
+    /// generated by the parser or spans from other files.
+    invalid_spans: RefCell<Vec<Span>>,
 }
 
 impl SnippetProvider {
+    /// Returns the text of `span`, or `None` if `span` is not within the file.
     pub(crate) fn span_to_snippet(&self, span: Span) -> Option<&str> {
         let start_index = span.lo().to_usize().checked_sub(self.start_pos)?;
         let end_index = span.hi().to_usize().checked_sub(self.start_pos)?;
-        Some(&self.big_snippet[start_index..end_index])
+        self.big_snippet.get(start_index..end_index)
+    }
+
+    /// Returns the text of `span`, or an empty string if `span` is not within the file, in
+    /// which case `span` is recorded so that the file can be left unformatted.
+    pub(crate) fn snippet(&self, span: Span) -> &str {
+        self.span_to_snippet(span).unwrap_or_else(|| {
+            self.invalid_spans.borrow_mut().push(span);
+            ""
+        })
+    }
+
+    /// Returns whether a span outside of the file was asked for with `snippet`.
+    pub(crate) fn has_invalid_spans(&self) -> bool {
+        !self.invalid_spans.borrow().is_empty()
     }
 
     pub(crate) fn new(start_pos: BytePos, end_pos: BytePos, big_snippet: Lrc<String>) -> Self {
@@ -53,6 +81,20 @@ pub(crate) fn new(start_pos: BytePos, end_pos: BytePos, big_snippet: Lrc<String>
             big_snippet,
             start_pos,
             end_pos,
+            invalid_spans: RefCell::new(vec![]),
+        }
+    }
+
+    /// Returns the whitespace preceding `pos` on its line, or `None` if anything other
+    /// than whitespace precedes `pos` on its line.
+    pub(crate) fn line_indent_before(&self, pos: BytePos) -> Option<&str> {
+        let index = pos.to_usize().checked_sub(self.start_pos)?;
+        let before = self.big_snippet.get(..index)?;
+        let indent = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+        if indent.chars().all(|c| c == ' ' || c == '\t') {
+            Some(indent)
+        } else {
+            None
         }
     }
 
@@ -87,6 +129,15 @@ pub(crate) struct FmtVisitor<'a> {
     pub(crate) report: FormatReport,
     pub(crate) skip_context: SkipContext,
     pub(crate) is_macro_def: bool,
+    pub(crate) deadline: Option<Deadline>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    /// The configurations of the inline modules of the file with a `rustfmt::config`
+    /// attribute.
+    pub(crate) module_configs: Option<&'a ModuleConfigs>,
+    /// The widths to which the declarations of aligned const items are padded, keyed by
+    /// the start of the items' spans.
+    pub(crate) aligned_const_widths: HashMap<BytePos, usize>,
+    pub(crate) rewrite_failures: Rc<RewriteFailures>,
 }
 
 impl<'a> Drop for FmtVisitor<'a> {
@@ -175,7 +226,13 @@ fn visit_stmt(&mut self, stmt: &Stmt<'_>, include_empty_semi: bool) {
                         get_span_without_attrs(stmt.as_ast_node()),
                     );
                 } else {
-                    self.visit_mac(&mac_stmt.mac, None, MacroPosition::Statement);
+                    let position = match mac_stmt.style {
+                        ast::MacStmtStyle::Braces => MacroPosition::BraceStatement,
+                        ast::MacStmtStyle::Semicolon | ast::MacStmtStyle::NoBraces => {
+                            MacroPosition::Statement
+                        }
+                    };
+                    self.visit_mac(&mac_stmt.mac, None, position);
                 }
                 self.format_missing(stmt.span().hi());
             }
@@ -345,7 +402,21 @@ fn close_block(&mut self, span: Span, unindent_comment: bool) {
                             self.push_str(&self.block_indent.to_string_with_newline(config));
                         }
 
-                        let comment_str = rewrite_comment(&sub_slice, false, comment_shape, config);
+                        let orig_indent = self
+                            .snippet_provider
+                            .line_indent_before(span.lo() + BytePos::from_usize(offset));
+                        let comment_str = match orig_indent {
+                            Some(orig_indent) if !comment_on_same_line => {
+                                rewrite_comment_from_indent(
+                                    &sub_slice,
+                                    false,
+                                    comment_shape,
+                                    config,
+                                    orig_indent,
+                                )
+                            }
+                            _ => rewrite_comment(&sub_slice, false, comment_shape, config),
+                        };
                         match comment_str {
                             Some(ref s) => self.push_str(s),
                             None => self.push_str(&sub_slice),
@@ -430,6 +501,12 @@ pub(crate) fn visit_fn(
     pub(crate) fn visit_item(&mut self, item: &ast::Item) {
         skip_out_of_file_lines_range_visitor!(self, item.span);
 
+        // The configuration of an inline module is applied to its items by `format_mod`.
+        let config = self.config;
+        if !matches!(item.kind, ast::ItemKind::Mod(..)) {
+            self.config = self.item_config(item.span).unwrap_or(config);
+        }
+
         // This is where we bail out if there is a skip attribute. This is only
         // complex in the module case. It is complex because the module could be
         // in a separate file and there might be attributes in both files, but
@@ -520,7 +597,14 @@ pub(crate) fn visit_item(&mut self, item: &ast::Item) {
                 }
                 ast::ItemKind::Enum(ref def, ref generics) => {
                     self.format_missing_with_indent(source!(self, item.span).lo());
-                    self.visit_enum(item.ident, &item.vis, def, generics, item.span);
+                    self.visit_enum(
+                        item.ident,
+                        &item.vis,
+                        def,
+                        generics,
+                        item.span,
+                        contains_sort(&item.attrs),
+                    );
                     self.last_pos = source!(self, item.span).hi();
                 }
                 ast::ItemKind::Mod(unsafety, ref mod_kind) => {
@@ -595,6 +679,7 @@ pub(crate) fn visit_item(&mut self, item: &ast::Item) {
             };
         }
         self.skip_context = skip_context_saved;
+        self.config = config;
     }
 
     fn visit_ty_alias_kind(
@@ -628,6 +713,20 @@ fn visit_assoc_item(&mut self, visitor_kind: &ItemVisitorKind<'_>) {
             return;
         }
 
+        let config = self.config;
+        self.config = self.item_config(ai.span).unwrap_or(config);
+        self.visit_assoc_item_kind(ai, visitor_kind, assoc_ctxt);
+        self.config = config;
+    }
+
+    fn visit_assoc_item_kind(
+        &mut self,
+        ai: &ast::AssocItem,
+        visitor_kind: &ItemVisitorKind<'_>,
+        assoc_ctxt: visit::AssocCtxt,
+    ) {
+        use ItemVisitorKind::*;
+
         // TODO(calebcartwright): consider enabling box_patterns feature gate
         match (&ai.kind, visitor_kind) {
             (ast::AssocItemKind::Const(..), AssocTraitItem(_)) => {
@@ -691,16 +790,21 @@ fn visit_mac(&mut self, mac: &ast::MacCall, ident: Option<symbol::Ident>, pos: M
         let (span, rewrite) = match macro_style(mac, &self.get_context()) {
             Delimiter::Bracket | Delimiter::Parenthesis if MacroPosition::Item == pos => {
                 let search_span = mk_sp(mac.span().hi(), self.snippet_provider.end_pos());
-                let hi = self.snippet_provider.span_before(search_span, ";");
-                let target_span = mk_sp(mac.span().lo(), hi + BytePos(1));
-                let rewrite = rewrite.map(|rw| {
-                    if !rw.ends_with(';') {
-                        format!("{};", rw)
-                    } else {
-                        rw
+                match self.snippet_provider.span_before(search_span, ";") {
+                    Some(hi) => {
+                        let target_span = mk_sp(mac.span().lo(), hi + BytePos(1));
+                        let rewrite = rewrite.map(|rw| {
+                            if !rw.ends_with(';') {
+                                format!("{};", rw)
+                            } else {
+                                rw
+                            }
+                        });
+                        (target_span, rewrite)
                     }
-                });
-                (target_span, rewrite)
+                    // Leave the macro as is if its semicolon cannot be found.
+                    None => (mac.span(), None),
+                }
             }
             _ => (mac.span(), rewrite),
         };
@@ -726,9 +830,31 @@ fn push_rewrite_inner(&mut self, span: Span, rewrite: Option<String>) {
 
     pub(crate) fn push_rewrite(&mut self, span: Span, rewrite: Option<String>) {
         self.format_missing_with_indent(source!(self, span).lo());
+        let failure = self.rewrite_failures.take_within(span);
+        if let Some(failure) = failure.filter(|_| rewrite.is_none()) {
+            self.report_rewrite_failure(failure);
+        }
         self.push_rewrite_inner(span, rewrite);
     }
 
+    /// Reports the innermost construct which failed to be rewritten, because of which the
+    /// code around it is left as is.
+    fn report_rewrite_failure(&mut self, failure: RewriteFailure) {
+        // A macro definition whose body is partly left as is is still formatted.
+        if !self.config.error_on_unformatted() || self.is_macro_def {
+            return;
+        }
+        let file_name = self.parse_sess.span_to_filename(failure.span);
+        self.report.append(
+            file_name,
+            vec![FormattingError::from_span(
+                failure.span,
+                self.parse_sess,
+                ErrorKind::RewriteFailure(failure.construct),
+            )],
+        );
+    }
+
     pub(crate) fn push_skipped_with_span(
         &mut self,
         attrs: &[ast::Attribute],
@@ -747,11 +873,45 @@ pub(crate) fn push_skipped_with_span(
         // or it can be on the same line as the last attribute.
         // So here we need to take a minimum between the two.
         let lo = std::cmp::min(attrs_end + 1, first_line);
+        // The item is not formatted either way, but splicing back in text with unbalanced
+        // delimiters would break the surrounding code.
+        let snippet = self.snippet(item_span);
+        if !has_balanced_delimiters(snippet) {
+            let file_name = self.parse_sess.span_to_filename(item_span);
+            self.report.append(
+                file_name,
+                vec![FormattingError::from_span(
+                    item_span,
+                    self.parse_sess,
+                    ErrorKind::UnbalancedUnformattedRegion,
+                )],
+            );
+        }
         self.push_rewrite_inner(item_span, None);
         let hi = self.line_number + 1;
         self.skipped_range.borrow_mut().push((lo, hi));
     }
 
+    /// If a `// rustfmt: off` comment precedes the first of `nodes`, returns the span of the
+    /// region it starts along with the number of `nodes` within it, see
+    /// `toggles::suppressed_region`. A region with unbalanced delimiters is formatted as usual,
+    /// which is reported by `toggles::check_toggle_comments`.
+    pub(crate) fn suppressed_region<T: Spanned>(&self, nodes: &[T]) -> Option<(Span, usize)> {
+        toggles::suppressed_region(self.snippet_provider, self.last_pos, nodes)
+            .filter(|region| region.is_balanced)
+            .map(|region| (region.span, region.len))
+    }
+
+    /// Pushes a region found by `suppressed_region` as is.
+    pub(crate) fn push_suppressed_region(&mut self, span: Span) {
+        self.format_missing_with_indent(span.lo());
+        let lo = self.line_number + 1;
+        self.push_str(self.snippet(span));
+        self.last_pos = span.hi();
+        let hi = self.line_number + 1;
+        self.skipped_range.borrow_mut().push((lo, hi));
+    }
+
     pub(crate) fn from_context(ctx: &'a RewriteContext<'_>) -> FmtVisitor<'a> {
         let mut visitor = FmtVisitor::from_parse_sess(
             ctx.parse_sess,
@@ -760,6 +920,8 @@ pub(crate) fn from_context(ctx: &'a RewriteContext<'_>) -> FmtVisitor<'a> {
             ctx.report.clone(),
         );
         visitor.skip_context.update(ctx.skip_context.clone());
+        visitor.deadline = ctx.deadline;
+        visitor.rewrite_failures = ctx.rewrite_failures.clone();
         visitor.set_parent_context(ctx);
         visitor
     }
@@ -773,7 +935,9 @@ pub(crate) fn from_parse_sess(
         FmtVisitor {
             parent_context: None,
             parse_sess: parse_session,
-            buffer: String::with_capacity(snippet_provider.big_snippet.len() * 2),
+            // Only the visitor of a whole file needs room for the whole file, the visitors of
+            // blocks within it start empty.
+            buffer: String::new(),
             last_pos: BytePos(0),
             block_indent: Indent::empty(),
             config,
@@ -785,6 +949,11 @@ pub(crate) fn from_parse_sess(
             macro_rewrite_failure: false,
             report,
             skip_context: Default::default(),
+            deadline: None,
+            cancellation: None,
+            module_configs: None,
+            aligned_const_widths: HashMap::new(),
+            rewrite_failures: Rc::new(RewriteFailures::default()),
         }
     }
 
@@ -793,7 +962,14 @@ pub(crate) fn opt_snippet(&'b self, span: Span) -> Option<&'a str> {
     }
 
     pub(crate) fn snippet(&'b self, span: Span) -> &'a str {
-        self.opt_snippet(span).unwrap()
+        self.snippet_provider.snippet(span)
+    }
+
+    /// Returns the configuration of the module or item at `span` if it overrides the
+    /// configuration, e.g. with `#[rustfmt::optimize]`.
+    fn item_config(&self, span: Span) -> Option<&'a Config> {
+        self.module_configs
+            .and_then(|configs| configs.get(&span.lo()))
     }
 
     // Returns true if we should skip the following item.
@@ -831,6 +1007,11 @@ pub(crate) fn visit_attrs(&mut self, attrs: &[ast::Attribute], style: ast::AttrS
         if contains_skip(attrs) {
             return true;
         }
+        if !self.config.format_generated_files()
+            && contains_generated_attr(attrs, &self.config.generated_markers())
+        {
+            return true;
+        }
 
         let attrs: Vec<_> = attrs.iter().filter(|a| a.style == style).cloned().collect();
         if attrs.is_empty() {
@@ -849,6 +1030,9 @@ fn is_unknown_rustfmt_attr(&self, segments: &[ast::PathSegment]) -> bool {
             return false;
         }
         !is_skip_attr(segments)
+            && !utils::is_sort_attr(segments)
+            && !is_config_attr(segments)
+            && !is_optimize_attr(segments)
     }
 
     fn walk_mod_items(&mut self, items: &[rustc_ast::ptr::P<ast::Item>]) {
@@ -860,10 +1044,20 @@ fn walk_stmts(&mut self, stmts: &[Stmt<'_>], include_current_empty_semi: bool) {
             return;
         }
 
-        // Extract leading `use ...;`.
+        if let Some((span, len)) = self.suppressed_region(stmts) {
+            self.push_suppressed_region(span);
+            self.walk_stmts(&stmts[len..], false);
+            return;
+        }
+
+        // Extract leading `use ...;` (and `extern crate ...;` with `Version::Two`).
+        let version = self.config.version();
+        let is_import = |item: &ast::Item| {
+            is_use_item(item) || (version == Version::Two && is_extern_crate(item))
+        };
         let items: Vec<_> = stmts
             .iter()
-            .take_while(|stmt| stmt.to_item().map_or(false, is_use_item))
+            .take_while(|stmt| stmt.to_item().map_or(false, is_import))
             .filter_map(|stmt| stmt.to_item())
             .collect();
 
@@ -933,19 +1127,27 @@ fn format_mod(
                 _ => self.push_str(" {"),
             }
             // Hackery to account for the closing }.
-            let mod_lo = self.snippet_provider.span_after(source!(self, s), "{");
+            let mod_lo = self
+                .snippet_provider
+                .span_after(source!(self, s), "{")
+                .unwrap_or_else(|| source!(self, inner_span).lo());
             let body_snippet =
                 self.snippet(mk_sp(mod_lo, source!(self, inner_span).hi() - BytePos(1)));
             let body_snippet = body_snippet.trim();
             if body_snippet.is_empty() {
                 self.push_str("}");
             } else {
+                let config = self.config;
+                if let Some(module_config) = self.module_configs.and_then(|c| c.get(&s.lo())) {
+                    self.config = module_config;
+                }
                 self.last_pos = mod_lo;
                 self.block_indent = self.block_indent.block_indent(self.config);
                 self.visit_attrs(attrs, ast::AttrStyle::Inner);
                 self.walk_mod_items(items);
                 let missing_span = self.next_span(inner_span.hi() - BytePos(1));
                 self.close_block(missing_span, false);
+                self.config = config;
             }
             self.last_pos = source!(self, inner_span).hi();
         } else {
@@ -968,7 +1170,7 @@ pub(crate) fn format_separate_mod(&mut self, m: &Module<'_>, end_pos: BytePos) {
     pub(crate) fn skip_empty_lines(&mut self, end_pos: BytePos) {
         while let Some(pos) = self
             .snippet_provider
-            .opt_span_after(self.next_span(end_pos), "\n")
+            .span_after(self.next_span(end_pos), "\n")
         {
             if let Some(snippet) = self.opt_snippet(self.next_span(pos)) {
                 if snippet.trim().is_empty() {
@@ -999,12 +1201,43 @@ pub(crate) fn get_context(&self) -> RewriteContext<'_> {
             use_block: Cell::new(false),
             is_if_else_block: Cell::new(false),
             force_one_line_chain: Cell::new(false),
+            is_chain_call_arg: Cell::new(false),
             snippet_provider: self.snippet_provider,
             macro_rewrite_failure: Cell::new(false),
             is_macro_def: self.is_macro_def,
             report: self.report.clone(),
             skip_context: self.skip_context.clone(),
             skipped_range: self.skipped_range.clone(),
+            deadline: self.deadline,
+            rewrite_failures: self.rewrite_failures.clone(),
+            optimizing_layout: Cell::new(false),
+            block_indent_binop: Cell::new(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rustc_span::DUMMY_SP;
+
+    #[test]
+    fn spans_outside_of_the_file_are_recorded() {
+        let provider =
+            SnippetProvider::new(BytePos(10), BytePos(17), Lrc::new(String::from("fn é()")));
+        assert_eq!(provider.snippet(mk_sp(BytePos(10), BytePos(12))), "fn");
+        assert!(!provider.has_invalid_spans());
+
+        // A dummy span, a span past the end, a span before the start and a span within `é`.
+        for span in [
+            DUMMY_SP,
+            mk_sp(BytePos(13), BytePos(20)),
+            mk_sp(BytePos(5), BytePos(11)),
+            mk_sp(BytePos(13), BytePos(14)),
+        ] {
+            assert_eq!(provider.span_to_snippet(span), None);
+            assert_eq!(provider.snippet(span), "");
         }
+        assert!(provider.has_invalid_spans());
     }
 }
@@ -0,0 +1,131 @@
+//! A small library crate, formatted with `use_small_heuristics = "Max"`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::io::{self, Read, Write};
+
+/// The errors of a `Store`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    NotFound(String),
+    Conflict { key: String, existing: u64, requested: u64 },
+    Io(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(key) => write!(f, "no value for `{}`", key),
+            Error::Conflict { key, existing, requested } => write!(
+                f,
+                "conflict for `{}`: {} != {}",
+                key, existing, requested
+            ),
+            Error::Io(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e.to_string())
+    }
+}
+
+/// A versioned key-value store.
+#[derive(Default)]
+pub struct Store<V> {
+    values: HashMap<String, (u64, V)>,
+    // The keys in the order of their last update.
+    history: Vec<String>,
+}
+
+impl<V: Clone + Display> Store<V> {
+    pub fn new() -> Self {
+        Store { values: HashMap::new(), history: Vec::new() }
+    }
+
+    pub fn get(&self, key: &str) -> Result<&V, Error> {
+        self.values
+            .get(key)
+            .map(|(_, value)| value)
+            .ok_or_else(|| Error::NotFound(key.to_owned()))
+    }
+
+    /// Sets `key` to `value` if the current version of `key` is `version`.
+    pub fn set(
+        &mut self,
+        key: &str,
+        version: u64,
+        value: V,
+    ) -> Result<u64, Error> {
+        let current = self.values.get(key).map_or(0, |&(version, _)| version);
+        if current != version {
+            return Err(Error::Conflict {
+                key: key.to_owned(),
+                existing: current,
+                requested: version,
+            });
+        }
+        self.values.insert(key.to_owned(), (version + 1, value));
+        self.history.retain(|other| other != key);
+        self.history.push(key.to_owned());
+        Ok(version + 1)
+    }
+
+    pub fn dump<W: Write>(&self, out: &mut W) -> Result<(), Error>
+    where
+        V: Ord,
+    {
+        let sorted: BTreeMap<_, _> =
+            self.values.iter().map(|(key, (_, value))| (key, value)).collect();
+        for (key, value) in sorted {
+            writeln!(out, "{} = {}", key, value)?;
+        }
+        Ok(())
+    }
+
+    pub fn recent(&self, n: usize) -> impl Iterator<Item = (&str, &V)> + '_ {
+        self.history.iter().rev().take(n).filter_map(move |key| {
+            self.values.get(key).map(|(_, value)| (key.as_str(), value))
+        })
+    }
+}
+
+pub fn read_all<R: Read>(mut input: R) -> io::Result<Vec<String>> {
+    let mut text = String::new();
+    input.read_to_string(&mut text)?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+macro_rules! check {
+    ($cond:expr, $($arg:tt)*) => {
+        if !$cond { panic!($($arg)*); }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get() {
+        let mut store = Store::new();
+        let version = store.set("a", 0, 1).unwrap();
+        check!(version == 1, "unexpected version {}", version);
+        assert_eq!(
+            store.set("a", 0, 2),
+            Err(Error::Conflict {
+                key: "a".to_owned(),
+                existing: 1,
+                requested: 0
+            })
+        );
+        assert_eq!(store.get("a"), Ok(&1));
+    }
+}
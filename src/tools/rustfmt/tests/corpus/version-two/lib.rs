@@ -0,0 +1,85 @@
+//! Shapes, formatted with `version = "Two"`.
+
+use std::f64::consts::PI;
+
+pub trait Shape {
+    fn area(&self) -> f64;
+
+    fn perimeter(&self) -> f64;
+
+    /// Returns how close the shape is to a circle, 1 for a circle.
+    fn roundness(&self) -> f64 {
+        4.0 * PI * self.area() / (self.perimeter() * self.perimeter())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rectangle {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Circle(pub f64);
+
+impl Shape for Rectangle {
+    fn area(&self) -> f64 {
+        self.width * self.height
+    }
+
+    fn perimeter(&self) -> f64 {
+        2.0 * (self.width + self.height)
+    }
+}
+
+impl Shape for Circle {
+    fn area(&self) -> f64 {
+        PI * self.0 * self.0
+    }
+
+    fn perimeter(&self) -> f64 {
+        2.0 * PI * self.0
+    }
+}
+
+pub fn largest<'a>(shapes: &'a [Box<dyn Shape>]) -> Option<&'a dyn Shape> {
+    shapes.iter().map(|shape| shape.as_ref()).max_by(|a, b| {
+        a.area()
+            .partial_cmp(&b.area())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })
+}
+
+pub fn describe(shape: &dyn Shape) -> &'static str {
+    let roundness = shape.roundness();
+    if roundness > 0.99 {
+        "round"
+    } else if roundness > 0.7 {
+        "squarish"
+    } else {
+        "elongated"
+    }
+}
+
+pub const UNIT_SQUARE: Rectangle = Rectangle {
+    width: 1.0,
+    height: 1.0,
+};
+
+static NAMES: &[&str] = &[
+    "rectangle",
+    "circle",
+    "triangle",
+    "pentagon",
+    "hexagon",
+    "heptagon",
+    "octagon",
+];
+
+pub fn name(sides: usize) -> Option<&'static str> {
+    match sides {
+        0 => Some(NAMES[1]),
+        3..=8 => NAMES.get(sides - 1).copied(),
+        _ => None,
+    }
+}
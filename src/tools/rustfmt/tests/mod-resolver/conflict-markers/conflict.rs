@@ -0,0 +1,7 @@
+fn conflict() {
+<<<<<<< HEAD
+    ours( 1 );
+=======
+    theirs(2);
+>>>>>>> branch
+}
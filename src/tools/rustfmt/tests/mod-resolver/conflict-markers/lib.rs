@@ -0,0 +1,2 @@
+mod a;
+mod conflict;
@@ -0,0 +1,3 @@
+fn main() {
+    foo(aaaaaaaaaa, bbbbbbbbbb, cccccccccc);
+}
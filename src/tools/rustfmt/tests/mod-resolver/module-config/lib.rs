@@ -0,0 +1,7 @@
+#![rustfmt::config(max_width = 40)]
+
+mod a {
+    #![rustfmt::config(tab_spaces = 2)]
+    mod b;
+}
+mod c;
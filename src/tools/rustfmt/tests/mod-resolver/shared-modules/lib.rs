@@ -0,0 +1,3 @@
+mod shared;
+
+pub fn lib() {}
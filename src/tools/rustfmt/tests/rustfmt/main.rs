@@ -1,12 +1,34 @@
 //! Integration tests for rustfmt.
 
 use std::env;
-use std::fs::remove_file;
+use std::fs::{self, remove_file};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Output};
 
 /// Run the rustfmt executable and return its output.
 fn rustfmt(args: &[&str]) -> (String, String) {
+    let output = run_rustfmt(args);
+    (
+        String::from_utf8(output.stdout).expect("utf-8"),
+        String::from_utf8(output.stderr).expect("utf-8"),
+    )
+}
+
+/// Run the rustfmt executable and return its exit code.
+fn rustfmt_exit_code(args: &[&str]) -> Option<i32> {
+    run_rustfmt(args).status.code()
+}
+
+fn run_rustfmt(args: &[&str]) -> Output {
+    let mut command = rustfmt_command(args);
+    match command.output() {
+        Ok(output) => output,
+        Err(e) => panic!("failed to run `{:?}`: {}", command, e),
+    }
+}
+
+/// Returns the command running the rustfmt executable with `args`.
+fn rustfmt_command(args: &[&str]) -> Command {
     let mut bin_dir = env::current_exe().unwrap();
     bin_dir.pop(); // chop off test exe name
     if bin_dir.ends_with("deps") {
@@ -20,13 +42,9 @@ fn rustfmt(args: &[&str]) -> (String, String) {
     paths.insert(0, bin_dir);
     let new_path = env::join_paths(paths).unwrap();
 
-    match Command::new(&cmd).args(args).env("PATH", new_path).output() {
-        Ok(output) => (
-            String::from_utf8(output.stdout).expect("utf-8"),
-            String::from_utf8(output.stderr).expect("utf-8"),
-        ),
-        Err(e) => panic!("failed to run `{:?} {:?}`: {}", cmd, args, e),
-    }
+    let mut command = Command::new(&cmd);
+    command.args(args).env("PATH", new_path);
+    command
 }
 
 macro_rules! assert_that {
@@ -114,6 +132,14 @@ fn rustfmt_usage_text() {
     assert!(stdout.contains("Format Rust code\n\nusage: rustfmt [options] <file>..."));
 }
 
+#[test]
+fn config_reference() {
+    let args = ["--help=config-reference"];
+    let (stdout, _) = rustfmt(&args);
+    assert!(stdout.starts_with("# Configuration Options\n"));
+    assert!(stdout.contains("\n## `max_width`\n\nMaximum width of each line\n"));
+}
+
 #[test]
 fn mod_resolution_error_multiple_candidate_files() {
     // See also https://github.com/rust-lang/rustfmt/issues/5167
@@ -157,3 +183,245 @@ fn mod_resolution_error_path_attribute_does_not_exist() {
     // The path attribute points to a file that does not exist
     assert!(stderr.contains("does_not_exist.rs does not exist"));
 }
+
+#[test]
+fn skips_files_with_conflict_markers() {
+    let error = "the file contains git conflict markers, it was left unformatted";
+    for file in ["lib.rs", "conflict.rs"] {
+        let path = format!("tests/mod-resolver/conflict-markers/{}", file);
+        let output = run_rustfmt(&["--check", &path]);
+        let stdout = String::from_utf8(output.stdout).expect("utf-8");
+        let stderr = String::from_utf8(output.stderr).expect("utf-8");
+        assert!(stderr.contains(error));
+        assert!(stderr.contains("conflict.rs:2"));
+        assert!(!stdout.contains("conflict.rs"));
+        assert_eq!(output.status.code(), Some(4));
+    }
+}
+
+#[test]
+fn unknown_config_profile() {
+    let args = [
+        "--profile",
+        "release",
+        "--config-path",
+        "tests/config/profile",
+        "tests/source/fn-simple.rs",
+    ];
+    let (_stdout, stderr) = rustfmt(&args);
+    assert!(stderr.contains("the config profile `release` is not defined in"));
+}
+
+#[test]
+fn compare_with_requires_unstable_features() {
+    let args = ["--compare-with", "rustfmt", "tests/target/fn-simple.rs"];
+    let (_stdout, stderr) = rustfmt(&args);
+    assert!(
+        stderr.contains("Unstable option (`--compare-with`) used without `--unstable-features`")
+    );
+}
+
+#[test]
+fn compare_with_same_binary() {
+    let binary = env!("CARGO_BIN_EXE_rustfmt");
+    let args = [
+        "--unstable-features",
+        "--compare-with",
+        binary,
+        "tests/target/fn-simple.rs",
+    ];
+    let output = run_rustfmt(&args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(0), "stderr: {}", stderr);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("1 of 1 files formatted the same by {}\n", binary)
+    );
+}
+
+#[test]
+fn keep_backups_requires_a_count() {
+    let args = ["--keep-backups", "0", "tests/target/fn-simple.rs"];
+    let (_stdout, stderr) = rustfmt(&args);
+    assert!(stderr.contains("Invalid value for `--keep-backups`: `0`"));
+}
+
+#[test]
+fn compare_with_config() {
+    let args = [
+        "--unstable-features",
+        "--compare-with",
+        "tests/config/small_tabs.toml",
+        "tests/target/fn-simple.rs",
+    ];
+    let output = run_rustfmt(&args);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "stdout: {}", stdout);
+    assert!(stdout.contains("tests/target/fn-simple.rs:4: formatting of `fn simple` differs"));
+    assert!(stdout.ends_with("0 of 1 files formatted the same by tests/config/small_tabs.toml\n"));
+}
+
+#[test]
+fn explain_config_lists_sources() {
+    let args = [
+        "--explain-config",
+        "max_width",
+        "--config",
+        "max_width=120",
+        "tests/config/inherit/child",
+    ];
+    let (stdout, _stderr) = rustfmt(&args);
+    assert!(stdout.starts_with("max_width = 120\n    default: 100\n"));
+    assert!(stdout.contains("rustfmt.toml: 80\n"));
+    assert!(stdout.contains("rustfmt.toml: 60\n"));
+    assert!(stdout.ends_with("    command line: 120\n"));
+}
+
+#[test]
+fn files_are_sorted_and_deduplicated() {
+    let args = [
+        "tests/b-missing.rs",
+        "tests/a-missing.rs",
+        "tests/b-missing.rs",
+    ];
+    let (_stdout, stderr) = rustfmt(&args);
+    assert_eq!(stderr.matches("b-missing.rs` does not exist").count(), 1);
+    assert!(stderr.find("a-missing.rs").unwrap() < stderr.find("b-missing.rs").unwrap());
+}
+
+#[test]
+fn out_dir_leaves_input_untouched() {
+    let out_dir = env::temp_dir().join("rustfmt-out-dir");
+    let _ = fs::remove_dir_all(&out_dir);
+    let input = "tests/source/fn-simple.rs";
+    let original = fs::read_to_string(input).unwrap();
+
+    let args = ["--out-dir", out_dir.to_str().unwrap(), input];
+    let (_stdout, _stderr) = rustfmt(&args);
+    let formatted = fs::read_to_string(out_dir.join(input)).unwrap();
+    assert_eq!(fs::read_to_string(input).unwrap(), original);
+    assert_ne!(formatted, original);
+    let _ = fs::remove_dir_all(&out_dir);
+}
+
+#[test]
+fn out_dir_rejects_current_dir() {
+    let input = "tests/source/fn-simple.rs";
+    let original = fs::read_to_string(input).unwrap();
+
+    let args = ["--out-dir", ".", input];
+    let (_stdout, stderr) = rustfmt(&args);
+    assert!(stderr.contains("`--out-dir` must not be the current directory"));
+    assert_eq!(fs::read_to_string(input).unwrap(), original);
+}
+
+#[test]
+fn check_with_item_diff() {
+    let args = [
+        "--unstable-features",
+        "--check",
+        "--emit",
+        "item-diff",
+        "tests/source/fn-simple.rs",
+    ];
+    let (stdout, _stderr) = rustfmt(&args);
+    assert!(stdout.contains("tests/source/fn-simple.rs:3: fn simple\n"));
+    assert!(!stdout.contains("Diff in"));
+}
+
+#[test]
+fn check_with_rustc_json() {
+    let args = [
+        "--unstable-features",
+        "--check",
+        "--emit",
+        "rustc-json",
+        "tests/source/fn-simple.rs",
+    ];
+    let (stdout, _stderr) = rustfmt(&args);
+    let first_line = stdout.lines().next().unwrap();
+    assert!(first_line.starts_with("{\"message\":\"code is not formatted\""));
+    assert!(first_line.contains("\"file_name\":\"tests/source/fn-simple.rs\""));
+    assert!(first_line.contains("\"suggestion_applicability\":\"MachineApplicable\""));
+}
+
+#[test]
+fn interactive_conflicts_with_check() {
+    let args = [
+        "--unstable-features",
+        "--check",
+        "--interactive",
+        "tests/source/fn-simple.rs",
+    ];
+    let (_stdout, stderr) = rustfmt(&args);
+    assert!(stderr.contains("Invalid to use `--interactive` with `--check` or `--emit`"));
+}
+
+#[test]
+fn self_check_corpus() {
+    assert_that!(
+        &["--self-check-corpus"],
+        contains("4 of 4 corpus files reproduced")
+    );
+
+    let corpus = env::temp_dir().join("rustfmt-corpus");
+    let _ = fs::remove_dir_all(&corpus);
+    fs::create_dir_all(&corpus).unwrap();
+    fs::write(corpus.join("rustfmt.toml"), "").unwrap();
+    fs::write(corpus.join("lib.rs"), "fn  main() {}\n").unwrap();
+    let (stdout, _stderr) = rustfmt(&["--self-check-corpus", corpus.to_str().unwrap()]);
+    assert!(stdout.contains("lib.rs:1: formatting of `fn main` changed\n"));
+    assert!(stdout.contains("0 of 1 corpus files reproduced"));
+    let _ = fs::remove_dir_all(&corpus);
+}
+
+#[test]
+fn error_on_unformatted_fails_on_rewrite_failures() {
+    let dir = env::temp_dir().join("rustfmt-rewrite-failure");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("lib.rs");
+    let source = format!(
+        "fn main() {{\n    let x = foo(a, \"{}\");\n}}\n",
+        "a".repeat(100)
+    );
+    fs::write(&file, source).unwrap();
+    let file = file.to_str().unwrap();
+
+    assert_eq!(rustfmt_exit_code(&["--check", file]), Some(0));
+    let args = ["--check", "--config", "error_on_unformatted=true", file];
+    assert_eq!(rustfmt_exit_code(&args), Some(4));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn check_staged_skips_ignored_and_generated_files() {
+    let dir = env::temp_dir().join("rustfmt-check-staged");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git").args(args).current_dir(&dir).status();
+        assert!(status.unwrap().success());
+    };
+    git(&["init", "-q"]);
+    fs::write(
+        dir.join("rustfmt.toml"),
+        "ignore = [\"ignored.rs\"]\nformat_generated_files = false\n",
+    )
+    .unwrap();
+    fs::write(dir.join("checked.rs"), "fn  main() {}\n").unwrap();
+    fs::write(dir.join("ignored.rs"), "fn  main() {}\n").unwrap();
+    fs::write(dir.join("generated.rs"), "// @generated\nfn  main() {}\n").unwrap();
+    git(&["add", "."]);
+
+    let output = rustfmt_command(&["--check", "--staged"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Diff in checked.rs (staged) at line 1:"));
+    assert!(!stdout.contains("ignored.rs"));
+    assert!(!stdout.contains("generated.rs"));
+    assert_eq!(output.status.code(), Some(1));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,7 @@
+// rustfmt-error_on_line_overflow: false
+
+fn main() {
+    xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx   +=   yyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy;
+    x   = "the quick brown fox jumps over the lazy dog, the quick brown fox jumps over the lazy dog";
+    y   = "the quick brown fox jumps over the lazy dog, the quick brown fox jumps over the lazy dogs!";
+}
@@ -0,0 +1,16 @@
+// rustfmt-version: Two
+// Multi-line block comments keep their layout when they are re-indented
+
+fn main() {
+        /* A comment
+             with a nested line
+           and a continuation */
+        let x = 1;
+}
+
+mod m {
+/* Lorem
+     ipsum
+       dolor */
+fn f() {}
+}
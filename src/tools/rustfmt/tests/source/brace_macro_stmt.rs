@@ -0,0 +1,9 @@
+fn main() {
+    vec! { 1, 2 }
+    let x = 1;
+    vec! { 1, 2 };
+    foo! { a, b }
+    foo! { a, b }   ;
+    lazy_static! { static ref X: u8 = 1; }
+    lazy_static! { static ref Y: u8 = 1; };
+}
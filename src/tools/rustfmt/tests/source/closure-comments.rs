@@ -0,0 +1,20 @@
+// Comments between the parameters of a closure and its body.
+
+fn main() {
+    let f = |x|   /* note */   x + 1;
+    let g = |x| // note
+        x + 1;
+    let h = |x| /* note */ {
+        let y = x;
+        y + 1
+    };
+    let i = move |x: u32| -> u32 /* ret */ { x };
+    let j = |x| // note
+    {
+        let y = x;
+        y + 1
+    };
+    let k = || /* first line
+                * second line */ foo();
+    foo.map(|x| /* note */ x + 1);
+}
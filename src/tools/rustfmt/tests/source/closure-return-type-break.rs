@@ -0,0 +1,13 @@
+// rustfmt-version: Two
+// Closures with a return type that does not fit on the line of the parameters.
+
+fn main() {
+    let g = |a: u32| -> Result<HashMap<String, Vec<u32>>, Box<dyn Error + Send + Sync + Unpin + 'static>> {
+        let x = a;
+        foo(x)
+    };
+    let h = move |x: usize| -> Result<Vec<(String, Option<PathBuf>)>, ConfigurationError> {
+        let y = x;
+        bar(y)
+    };
+}
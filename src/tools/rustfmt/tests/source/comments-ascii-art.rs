@@ -0,0 +1,21 @@
+// rustfmt-wrap_comments: true
+// rustfmt-max_width: 50
+// Tables and diagrams keep their layout.
+
+/// The text of this doc comment is longer than the maximum width, so it is wrapped.
+///
+/// | Option          | Default | Description          |
+/// |-----------------|---------|----------------------|
+/// | `wrap_comments` | `false` | Break comments to fit |
+fn table() {}
+
+// The text of this comment is longer than the maximum width, so it is wrapped.
+//
+// +-------------+        +--------------------+
+// | source file | -----> | formatted file     |
+// +-------------+        +--------------------+
+fn diagram() {}
+
+// rustfmt: nowrap
+// This comment is longer than the maximum width, but it is not wrapped.
+fn marker() {}
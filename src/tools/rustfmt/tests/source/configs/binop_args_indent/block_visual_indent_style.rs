@@ -0,0 +1,8 @@
+// rustfmt-binop_args_indent: Block
+// rustfmt-indent_style: Visual
+// Binary expressions as arguments of calls with visual indent style
+
+fn main() {
+    foobar(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa + bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb + cccccccccccccccccccccccccccccccccccccccc);
+    f(g(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa && bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb));
+}
@@ -0,0 +1,12 @@
+// rustfmt-blank_lines_lower_bound: 1
+
+fn foo() {}
+/* comment */
+fn foobar() {}
+/* comment */
+
+
+fn foobar1() {}
+/* a */
+/* b */
+fn foobar2() {}
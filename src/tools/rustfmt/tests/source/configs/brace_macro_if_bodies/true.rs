@@ -0,0 +1,14 @@
+// rustfmt-brace_macro_if_bodies: true
+// Braces around block metavariables
+
+macro_rules! when {
+    ($cond:expr, $body:block) => {
+        if $cond $body
+    };
+}
+
+macro_rules! either {
+    ($cond:expr, $a:block, $b:block) => {
+        if $cond $a else $b
+    };
+}
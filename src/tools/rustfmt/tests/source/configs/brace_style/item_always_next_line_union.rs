@@ -0,0 +1,8 @@
+// rustfmt-brace_style: AlwaysNextLine
+// Union brace style
+
+union Foo {}
+
+union Lorem { ipsum: bool, dolor: u8 }
+
+union Dolor<T> where T: Copy { sit: T }
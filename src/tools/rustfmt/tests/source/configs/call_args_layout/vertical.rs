@@ -0,0 +1,8 @@
+// rustfmt-call_args_layout: Vertical
+// Function and method call arguments are laid out vertically
+
+fn main() {
+    foo(a, b);
+    foo.bar(a, b);
+    foo();
+}
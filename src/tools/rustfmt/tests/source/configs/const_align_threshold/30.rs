@@ -0,0 +1,23 @@
+// rustfmt-const_align_threshold: 30
+
+const READ: u8 = 0b0001;
+const WRITE: u8 = 0b0010;
+// Allows running the file.
+const EXECUTE: u8 = 0b0100;
+#[doc(hidden)]
+const SETUID: u8 = 0o4000;
+const A_VERY_LONG_NAME_FOR_A_CONSTANT: u8 = 0b1000;
+
+pub const ALL: u8 = READ | WRITE | EXECUTE;
+pub const NONE: u8 = 0;
+static NOT_ALIGNED: u8 = 1;
+const X: u8 = 1;
+
+mod registers {
+    const CTRL: u32 = 0x0000;
+    const STATUS: u32 = 0x0004;
+}
+
+// The initializer would not fit on the line once padded.
+const A_LONGER_NAME: u8 = 1;
+const S: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod temp";
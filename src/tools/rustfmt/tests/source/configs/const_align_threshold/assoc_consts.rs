@@ -0,0 +1,17 @@
+// rustfmt-const_align_threshold: 30
+
+impl Permissions {
+    const READ: u8 = 0b0001;
+    pub const WRITE: u8 = 0b0010;
+
+    const EXECUTE: u8 = 0b0100;
+    fn foo() {}
+    const A_VERY_LONG_NAME_FOR_A_CONSTANT: u8 = 0b1000;
+}
+
+trait Registers {
+    const CTRL: u32 = 0x0000;
+    const STATUS_REGISTER: u32 = 0x0004;
+    const NO_DEFAULT: u32;
+    const DATA: u32 = 0x0008;
+}
@@ -0,0 +1,29 @@
+// rustfmt-control_brace_style: ClosingNextLine
+// Comments between the block and `else`
+
+fn main() {
+    if lorem {
+        println!("ipsum!");
+    } // dolor
+    else {
+        println!("sit!");
+    }
+
+    if lorem {
+        println!("ipsum!");
+    } /* dolor */ else if sit {
+        println!("amet!");
+    }
+    // consectetur
+    else {
+        println!("adipiscing!");
+    }
+
+    if lorem {
+        println!("ipsum!");
+    } // dolor
+      // sit
+    else {
+        println!("amet!");
+    }
+}
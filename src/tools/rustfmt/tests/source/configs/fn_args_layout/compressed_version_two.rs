@@ -0,0 +1,13 @@
+// rustfmt-fn_args_layout: Compressed
+// rustfmt-version: Two
+// Function arguments density
+
+trait Lorem {
+    fn lorem(&mut self, ipsum: Ipsum, dolor: Dolor, sit: Sit, amet: Amet, consectetur: Consectetur, elit: Elit);
+
+    fn lorem_ipsum_dolor_sit(&self, ipsum: Ipsum, dolor: Dolor, sit: Sit, amet: Amet, consectetur: Consectetur);
+}
+
+extern "C" {
+    fn lorem(ipsum: *const Ipsum, dolor: Dolor, sit: Sit, amet: Amet, consectetur: Consectetur, adip: A, ...);
+}
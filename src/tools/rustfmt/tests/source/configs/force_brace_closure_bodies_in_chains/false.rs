@@ -0,0 +1,7 @@
+// rustfmt-force_brace_closure_bodies_in_chains: false
+
+fn main() {
+    let values = items.iter().map(|item| match item { Some(value) => value, None => 0 }).collect::<Vec<_>>();
+
+    result(|maybe_value| match maybe_value { None => foo(), Some(value) => bar() });
+}
@@ -0,0 +1,12 @@
+// rustfmt-format_brace_macros: false
+
+fn main() {
+    foo! {
+        let x=1;
+            bar( x )
+    }
+}
+
+my_macro! {
+    fn  helper() -> u32 { 42 }
+}
@@ -0,0 +1,15 @@
+// rustfmt-format_brace_macros: true
+
+fn main() {
+    foo! {
+        let x=1;
+            bar( x )
+    }
+
+    // Not a sequence of statements.
+    foo! { a  =>  b }
+}
+
+my_macro! {
+    fn  helper() -> u32 { 42 }
+}
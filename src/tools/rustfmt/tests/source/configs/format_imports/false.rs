@@ -0,0 +1,8 @@
+// rustfmt-format_imports: false
+// Keep use declarations as written
+
+use std::{io,fmt};
+use   std::collections::{HashMap};
+use a::b;
+
+fn main(){let x=1;}
@@ -0,0 +1,9 @@
+// rustfmt-format_patterns: false
+// Keep patterns as written
+
+fn main(){
+    let Foo{a,b:(x,y),..}=foo;
+    match x {
+        Some( y )|None=>{}
+    }
+}
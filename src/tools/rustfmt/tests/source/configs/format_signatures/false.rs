@@ -0,0 +1,8 @@
+// rustfmt-format_signatures: false
+// Keep function signatures as written
+
+fn foo(a:u32,b : u32)->u32{a+b}
+
+trait Bar {
+    fn bar( &self )->u32;
+}
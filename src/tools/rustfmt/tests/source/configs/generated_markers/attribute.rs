@@ -0,0 +1,8 @@
+// rustfmt-format_generated_files: false
+
+#[automatically_derived]
+impl   Clone for Foo { fn clone(&self)->Self{ *self } }
+
+fn   main()
+{
+}
@@ -0,0 +1,9 @@
+// DO NOT EDIT
+// rustfmt-format_generated_files: false
+// rustfmt-generated_markers: DO NOT EDIT
+
+fn main()
+{
+    println!("hello, world")
+    ;
+}
@@ -0,0 +1,5 @@
+// rustfmt-imports_indent: Block
+// rustfmt-imports_layout: Auto
+
+use comment::{contains_comment, recover_comment_removed, rewrite_comment, FindUncommented};
+use lists::{definitive_tactic, itemize_list, shape_for_tactic, struct_lit_formatting, struct_lit_shape, struct_lit_tactic, write_list, DefinitiveListTactic, ListFormatting, ListItem, ListTactic, SeparatorTactic};
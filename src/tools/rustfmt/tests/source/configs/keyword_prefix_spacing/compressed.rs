@@ -0,0 +1,9 @@
+// rustfmt-keyword_prefix_spacing: Compressed
+// Keyword prefix spacing
+
+fn main() {
+    let lorem = &mut  [ipsum, dolor];
+    let sit = &mut(amet, consectetur);
+    let &mut (a, b) = c;
+    let &mut x = y;
+}
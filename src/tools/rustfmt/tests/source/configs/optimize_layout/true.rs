@@ -0,0 +1,9 @@
+// rustfmt-optimize_layout: true
+// Layout optimization
+
+fn main() {
+    outer_function_name(inner_function_name(first_argument, second_argument, third_argument_values));
+    foo(a, |x| {
+        x + 1
+    });
+}
@@ -0,0 +1,7 @@
+// rustfmt-pattern_layout: Vertical
+// Pattern fields are laid out vertically
+
+fn main() {
+    let Point { x, y } = point;
+    let (a, b) = pair;
+}
@@ -0,0 +1,15 @@
+// rustfmt-prefer_stable_diffs: true
+// Stable diffs
+
+use foo::{aaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbb, cccccccccccccccccc, dddddddddddddddddd, eeeeeeeeeeeeeeeeee, ffffffffffffffffff};
+use bar::{a, b};
+
+fn main() {
+    let v = [1000, 2000, 3000, 4000, 5000, 6000, 7000, 8000, 9000, 10000, 11000, 12000, 13000, 14000, 15000, 16000];
+    let w = [1, 2, 3];
+    match x {
+        1 | 2 => {}
+        AAAAAAAAAAAAAAAAAAAA | BBBBBBBBBBBBBBBBBBBB | CCCCCCCCCCCCCCCCCCCC| DDDDDDDDDDDDDDDDDDDD | EEEEEEEEEEE => {}
+        _ => {}
+    }
+}
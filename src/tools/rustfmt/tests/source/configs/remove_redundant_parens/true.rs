@@ -0,0 +1,14 @@
+// rustfmt-remove_redundant_parens: true
+
+fn main() {
+    if (a && b) {
+        return (c + d);
+    }
+    while (x.is_some()) {}
+    if ((a)) {}
+    if (Foo { x } == bar) {}
+    if (a..) {}
+    if (/* comment */ a) {}
+    if let Some(x) = (foo) {}
+    return (a);
+}
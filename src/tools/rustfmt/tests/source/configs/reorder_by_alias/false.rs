@@ -0,0 +1,11 @@
+// rustfmt-reorder_by_alias: false
+// Sort renamed imports by their alias
+
+extern crate core;
+extern crate alloc as zalloc;
+extern crate  std  as  astd ;
+
+use qux;
+use zed as apple;
+use bar::{Quux, Foo as Zoo, self as bbar};
+use foo::{a as z, b};
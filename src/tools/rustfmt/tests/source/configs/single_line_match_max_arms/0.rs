@@ -0,0 +1,6 @@
+// rustfmt-single_line_match_max_arms: 0
+// Single line match
+
+fn two_arms(x: Ipsum) -> u8 {
+    match x { Ipsum::A => 1, Ipsum::B => 2 }
+}
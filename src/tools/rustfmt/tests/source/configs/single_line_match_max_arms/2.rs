@@ -0,0 +1,42 @@
+// rustfmt-single_line_match_max_arms: 2
+// Single line match
+
+fn two_arms(x: Ipsum) -> u8 {
+    match x {
+        Ipsum::A => 1,
+        Ipsum::B => { 2 }
+    }
+}
+
+fn guard(x: u8) -> bool {
+    match x { n if n > 2 => true, _ => false }
+}
+
+fn three_arms(x: Ipsum) -> u8 {
+    match x { Ipsum::A => 1, Ipsum::B => 2, Ipsum::C => 3 }
+}
+
+fn too_long(x: Ipsum) -> &'static str {
+    match x {
+        Ipsum::A => "Lorem ipsum dolor sit amet, consectetur adipiscing elit",
+        Ipsum::B => "sed do eiusmod",
+    }
+}
+
+fn comment(x: Ipsum) -> u8 {
+    match x {
+        // Lorem ipsum
+        Ipsum::A => 1,
+        Ipsum::B => 2,
+    }
+}
+
+fn multi_line_body(x: Ipsum) -> u8 {
+    match x {
+        Ipsum::A => {
+            foo();
+            1
+        }
+        Ipsum::B => 2,
+    }
+}
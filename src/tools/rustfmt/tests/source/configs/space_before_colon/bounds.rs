@@ -0,0 +1,12 @@
+// rustfmt-space_before_colon: true
+// Space before colon in bounds
+
+struct S<T: Eq = u32, const N: usize = 3>(T);
+
+trait Tr: Eq + Ord {
+    type A: Eq;
+}
+
+fn f<T: Iterator<Item: Eq>, U>(x: T) where U: Ord {}
+
+type F = fn(a: u8) -> u8;
@@ -0,0 +1,12 @@
+// rustfmt-space_between_closing_angle_brackets: false
+// Space between closing angle brackets
+
+struct Foo<T: Into<Vec<Vec<u8>>>> {
+    a: Vec<Vec<Vec<T> > >,
+    b: HashMap<String, Vec<Box<dyn Fn() -> Vec<T>>>>,
+}
+
+fn lorem<T: Iterator<Item = Option<T>>>(t: T) -> <T as Iterator<Item = Vec<u8>>>::Item {
+    let x: Vec<Vec<u8>> = <Vec<Vec<u8>>>::new();
+    let y = x.iter().collect::<Vec<Vec<u8>>>();
+}
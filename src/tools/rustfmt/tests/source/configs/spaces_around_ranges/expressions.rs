@@ -0,0 +1,15 @@
+// rustfmt-spaces_around_ranges: true
+// Spaces around ranges in expressions
+
+fn main() {
+    let x = &a[..n];
+    let y = &a[n..];
+    let z = (0..=n).map(|i| i..i + 1);
+    let s = S { a, ..Default::default() };
+    match x {
+        ..=5 => {}
+        5.. => {}
+        [a, .., b] => {}
+        S { a, .. } => {}
+    }
+}
@@ -0,0 +1,11 @@
+// rustfmt-spaces_around_struct_braces: true
+// Spaces around struct braces
+
+fn main() {
+    let Point {x, y} = point;
+    let origin = Point {  x: 0, y: 0 };
+    let Lorem{..} = lorem;
+    let dolor = Dolor { sit: amet, consectetur: adipiscing, elit: sed, eiusmod: tempor };
+    let Empty {} = empty;
+    let empty = Empty {};
+}
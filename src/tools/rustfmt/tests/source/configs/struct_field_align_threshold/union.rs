@@ -0,0 +1,14 @@
+// rustfmt-struct_field_align_threshold: 20
+
+union Foo {
+    x: u32,
+    yy: u32, // comment
+    zzz: u32,
+}
+
+pub union Bar<T: Copy> where T: Eq {
+    x: u32,
+    yy: T,
+
+    xxxxxxx: u32,
+}
@@ -0,0 +1,7 @@
+// rustfmt-struct_lit_layout: Mixed
+// rustfmt-version: Two
+// The `..` of a struct literal stays next to the last field
+
+fn main() {
+    Lorem { lorem_ipsum_dolor, sit_amet_consecte, adipiscing_elit_s, eiusmod_tempor_in, consectetur_ad, .. } = lorem;
+}
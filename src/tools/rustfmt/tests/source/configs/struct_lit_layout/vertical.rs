@@ -0,0 +1,6 @@
+// rustfmt-struct_lit_layout: Vertical
+// Struct literal fields are laid out vertically
+
+fn main() {
+    let point = Point { x: 1, y: 2 };
+}
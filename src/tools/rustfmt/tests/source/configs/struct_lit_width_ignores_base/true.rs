@@ -0,0 +1,9 @@
+// rustfmt-struct_lit_width_ignores_base: true
+
+fn main() {
+    let lorem = Lorem { foo, ..Default::default() };
+    let lorem = Lorem { ..Default::default() };
+    let lorem = Lorem { foo: 1, bar: 2, ..Default::default() };
+    let configuration = Configuration { alpha: 1, beta: 2, gamma: 3, delta: 4, ..Default::default() };
+    let lorem = Lorem { foo, ..some_function_with_a_long_name(ipsum, dolor, sit, amet, consectetur) };
+}
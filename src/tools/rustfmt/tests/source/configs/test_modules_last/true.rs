@@ -0,0 +1,7 @@
+// rustfmt-test_modules_last: true
+// Test modules last
+
+mod utils;
+#[cfg(test)]
+mod tests;
+mod parser;
@@ -0,0 +1,7 @@
+// rustfmt-verify: true
+// rustfmt-wrap_comments: true
+// rustfmt-comment_width: 40
+// Verify
+
+// Lorem ipsum dolor sit amet, consectetur adipiscing elit.
+fn main() {}
@@ -0,0 +1,13 @@
+// rustfmt-version: Two
+
+fn main() {
+    extern crate  foo;
+    extern crate bar as baz;
+    use  std::io ;
+    use std::{fmt,  collections::HashMap};
+
+    let x = 1;
+
+    use b::c;
+    use a;
+}
@@ -0,0 +1,21 @@
+// Patterns of fn params
+
+fn tuple((a,b): (u32, u32)) {}
+
+fn tuple_struct(Point(x,  y): Point, Wrapper(ref   inner): &Wrapper) {}
+
+fn structure(Point { x, y: ref   y }: Point) {}
+
+fn slice([first, .., last]: [u8; 4]) {}
+
+impl Foo {
+    fn value(mut   self) {}
+
+    fn value_comment(mut /* lorem */ self) {}
+
+    fn explicit(mut  self: Box<Self>) {}
+
+    fn explicit_comment(mut /* ipsum */ self: Box<Self>) {}
+
+    fn reference(&mut self) {}
+}
@@ -0,0 +1,7 @@
+// rustfmt-control_brace_style: ClosingNextLine
+
+fn main() {
+    let x = if a { b } // The usual case.
+    else { c };
+    let y = if a { b } else { c };
+}
@@ -0,0 +1,18 @@
+// Negative impls and the obsolete `impl Trait for .. {}` syntax
+
+impl  !  Send  for  Foo  {  }
+
+unsafe impl Send for .. { }
+
+impl  Trait  for  ..  {
+}
+
+impl<T> ! Sync for Foo<T> where T: Copy { }
+
+impl ! /* not */ Send for Foo { }
+
+impl<T> !Send for Foooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooo<T> {}
+
+impl !Send for Foo {
+    // Empty
+}
@@ -0,0 +1,10 @@
+// rustfmt-version: Two
+// Bounds of `impl Trait` are broken once `impl ` no longer fits.
+
+fn short(a: impl   Clone+Send) -> impl   Iterator<Item=u32>+Send {
+    unimplemented!()
+}
+
+fn bounds(a: u8) -> impl Iterator<Item = u32> + DoubleEndedIterator + ExactSizeIterator + FusedIterator + Clone + Sync {
+    unimplemented!()
+}
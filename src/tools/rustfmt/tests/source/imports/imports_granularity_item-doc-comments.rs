@@ -0,0 +1,16 @@
+// rustfmt-imports_granularity: Item
+
+// Doc comments are only kept on the first import, other attributes on all of them
+/// Collections used below
+#[cfg(feature = "foo")]
+use std::collections::{HashMap, HashSet};
+
+const A: usize = 0;
+
+#[doc(hidden)]
+pub use foo::{a, b};
+
+const B: usize = 0;
+
+/// Only documentation
+use bar::{c, d};
@@ -0,0 +1,10 @@
+// rustfmt-version: Two
+// Ordering of `self`, globs, modules, types and constants within use lists
+
+use foo::{*, Bar, baz, self};
+
+use foo::{_Hidden, Visible, _private, public};
+
+use foo::{CONST, Type, r#type, module, super::sibling};
+
+use foo::{r#bar, bar};
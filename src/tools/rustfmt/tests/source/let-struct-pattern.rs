@@ -0,0 +1,8 @@
+// rustfmt-version: Two
+// Destructuring let with a long struct pattern
+
+fn main() {
+    let some_module::some_submodule::another_module::yet_another_module::SomeStruct { a, b, c } = expr;
+    let some_module::some_submodule::another_module::yet_another_module::SomeStruct { a, b, c }: Foo = expr;
+    let Foo { a, b } = expr;
+}
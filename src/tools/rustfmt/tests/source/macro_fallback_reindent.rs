@@ -0,0 +1,11 @@
+// rustfmt-version: Two
+
+fn main() {
+        foo!(a b
+             c d);
+}
+
+mod bar {
+        foo!(a b
+           c d);
+}
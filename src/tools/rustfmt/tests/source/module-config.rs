@@ -0,0 +1,24 @@
+// rustfmt-max_width: 60
+// Inline modules can override the configuration
+
+fn foo() {
+    let x = some_function(first_argument, second_argument, third);
+}
+
+mod bindings {
+    #![rustfmt::config(max_width = 100, fn_args_layout = "Compressed")]
+
+    fn foo() {
+        let x = some_function(first_argument, second_argument, third);
+    }
+
+    pub fn bar(aaaaaaaaaaaa: u32, bbbbbbbbbbbbbbb: u32, cccccccccccc: u32, dddddddd: u32, eeeeeeeeeee: u32) {}
+
+    mod nested {
+        #![rustfmt::config(max_width = 80)]
+
+        fn foo() {
+            let x = some_function(first_argument, second_argument, third);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+// rustfmt-version: Two
+// Multi-line string literal arguments keep the other arguments on the same line.
+
+fn main() {
+    foo(a, r#"
+hello
+"#, b);
+    let x = query(conn, "
+        SELECT * FROM t
+    ", &params);
+    foo(a, r#"
+hello
+"#);
+    foo(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb, r#"
+hello
+"#, c);
+    foo(a, // comment
+    r#"
+hello
+"#);
+}
@@ -0,0 +1,12 @@
+// rustfmt-max_width: 20
+// rustfmt-version: One
+
+fn f() {
+    if a {
+        if b {
+            if c {
+                foo( x );
+            }
+        }
+    }
+}
@@ -0,0 +1,12 @@
+// rustfmt-max_width: 20
+// rustfmt-version: Two
+
+fn f() {
+    if a {
+        if b {
+            if c {
+                foo( x );
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+// rustfmt-version: Two
+// rustfmt-max_width: 40
+
+fn main() {
+    let x = foo(a_very_long_identifier_that_never_fits_anywhere,b);
+    let y = 0x1234_5678_9abc_def0_1234_5678_9abc_def0_1234;
+}
@@ -23,3 +23,9 @@ fn main() {
 }
 
 fn op(foo: Bar, key : &[u8], upd : Fn(Option<&memcache::Item> , Baz  ) -> Result) -> MapResult {}
+
+fn prefixes() {
+    let x = :: std :: mem :: size_of :: < u8 > ();
+    let y: crate :: foo :: Bar = self :: baz();
+    let z: < T as :: std :: ops :: Add > :: Output = super :: qux();
+}
@@ -0,0 +1,12 @@
+// rustfmt-version: Two
+// Sub-patterns of bindings only get what is left of the line after `ref mut ident @ `, and
+// the patterns of fields leave room for the trailing comma.
+
+fn main() {
+    match x {
+        ref mut x @ Some((aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb)) => 1,
+        ref x @ Some((a, b)) => 2,
+        Foo { field: ref y @ Some((a, b)) } => 3,
+        Foo { field: Some((Ok(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa), b)) } => 4,
+    }
+}
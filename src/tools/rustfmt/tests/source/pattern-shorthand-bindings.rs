@@ -0,0 +1,7 @@
+// Shorthand field patterns with binding modes
+
+fn main() {
+    let Foo { box   x, ref  mut y, box ref mut   z, mut w } = foo;
+    let Foo { a, ref /* lorem */ mut b, box /* ipsum */ c } = foo;
+    let Foo { x: box x, y: ref mut y } = foo;
+}
@@ -0,0 +1,17 @@
+// rustfmt-condense_wildcard_suffixes: true
+// Comments of the arguments of tuple struct patterns
+
+fn main() {
+    match x {
+        Token::Literal(
+            // The kind of the literal
+            kind,
+            symbol, // The symbol
+            /* The suffix */ suffix,
+            span_of_the_literal_in_the_source,
+        ) => 1,
+        Quad(x, _, _ /* lorem */, _) => 2,
+        Quad(x, _, _, _ /* ipsum */) => 3,
+        Quad(x, _, _, _) => 4,
+    }
+}
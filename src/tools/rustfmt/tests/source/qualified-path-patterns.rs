@@ -0,0 +1,9 @@
+// rustfmt-version: Two
+// Qualified paths whose self type does not fit on one line
+
+fn main() {
+    match x {
+        <aaaaaaaaaa::bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb as DDDDDDDDDDDDDDDDDDDDDDDDDDDDDDDDDDDD>::CONST => 1,
+        <Foo as Bar>::CONST => 2,
+    }
+}
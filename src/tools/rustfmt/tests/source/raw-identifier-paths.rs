@@ -0,0 +1,10 @@
+// Raw identifiers are kept in the paths of macros and visibilities.
+
+fn main() {
+    r#foo::r#bar!(  );
+    ::r#foo::r#baz!(a,b);
+    r#qux!  { }
+}
+
+pub(in r#foo::r#bar) fn f() {}
+pub(in ::r#foo) struct S;
@@ -0,0 +1,22 @@
+// rustfmt-unstable: true
+// `#[rustfmt::optimize]` formats an item with `optimize_layout`.
+
+fn greedy() {
+    outer_function_name(inner_function_name(first_argument, second_argument, third_argument_values));
+}
+
+#[rustfmt::optimize]
+fn optimized() {
+    outer_function_name(inner_function_name(first_argument, second_argument, third_argument_values));
+}
+
+impl Foo {
+    #[rustfmt::optimize]
+    fn optimized_method() {
+        outer_function_name(inner_function_name(first_argument, second_argument, third_argument_values));
+    }
+
+    fn greedy_method() {
+        outer_function_name(inner_function_name(first_argument, second_argument, third_argument_values));
+    }
+}
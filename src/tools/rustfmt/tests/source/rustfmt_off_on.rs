@@ -0,0 +1,50 @@
+// Statements, items, match arms and array elements between `// rustfmt: off` and
+// `// rustfmt: on` are left as is.
+
+fn main() {
+    let a=1;
+    // rustfmt: off
+    let identity = [1, 0,
+                    0, 1];
+    let  b  =  2;
+    // rustfmt: on
+    let c=3;
+    // rustfmt: off
+    let   d = [ 1,2 ];
+}
+
+// rustfmt: off
+const TABLE: [(u8, u8); 2] = [(0,  1),
+                              (1,  0)];
+// rustfmt: on
+
+fn   foo ( ) { }
+
+use b::c;
+// rustfmt: off
+use   a::b;
+// rustfmt: on
+use a::a;
+
+fn arms(x: u8) -> u8 {
+    match x {
+        0=>1,
+        // rustfmt: off
+        1   => 10,
+        2   => 20,
+        // rustfmt: on
+        _=>0,
+    }
+}
+
+#[test]
+fn table() {
+    let cases = [
+        (0,0),
+        // rustfmt: off
+        (1,   1),
+        (10,  100),
+        // rustfmt: on
+        (2,4),
+    ];
+}
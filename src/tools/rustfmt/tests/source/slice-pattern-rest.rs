@@ -0,0 +1,11 @@
+// Rest patterns in slice patterns
+
+fn main() {
+    match x {
+        [first,  .., last] => 1,
+        [.., last] => 2,
+        [first, rest @ ..] => 3,
+        [first, /* lorem */ .., last] => 4,
+        [first, .. /* ipsum */] => 5,
+    }
+}
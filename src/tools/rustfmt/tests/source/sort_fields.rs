@@ -0,0 +1,28 @@
+#[rustfmt::sort]
+struct Config {
+    /// The width.
+    width: usize,
+    // Whether to use tabs.
+    #[serde(default)]
+    hard_tabs: bool,
+    edition: Edition, // trailing comment
+
+    zeta: u8,
+    alpha: u8,
+}
+
+#[rustfmt::sort]
+struct Small { b: u8, a: u8 }
+
+struct Unsorted {
+    b: u8,
+    a: u8,
+}
+
+#[rustfmt::sort]
+enum Color {
+    Red,
+    /// Green.
+    Green(u8),
+    Blue { b: u8, a: u8 },
+}
@@ -0,0 +1,10 @@
+// rustfmt-version: Two
+// Break points of static and const items
+
+#[thread_local] #[allow(non_upper_case_globals)] pub static mut counter: Cell<usize> = Cell::new(0);
+
+pub static mut XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX: u8 = 0;
+
+pub(crate) static mut REQUEST_HANDLERS_BY_NAME_FOR_THE_SERVER: Mutex<Option<HashMap<&'static str, Box<dyn Fn(u32) -> u32>>>> = Mutex::new(None);
+
+static ITEMS: &dyn Iterator<Item = u8> =   /* empty */   &EMPTY;
@@ -0,0 +1,8 @@
+// Struct patterns whose path leaves no room for the opening brace
+
+fn main() {
+    match x {
+        aaaaaaaaaaaaaaaaaaaaaaaaaa::bbbbbbbbbbbbbbbbbbbbbbbbbb::Cccccccccccccccccccccccccccccc { x, y } => 1,
+        aaaaaaaaaaaaaaaaaaaaaaaaaa::bbbbbbbbbbbbbbbbbbbbbbbbbb::Cccccccccccccccccccccccccccccc { .. } => 2,
+    }
+}
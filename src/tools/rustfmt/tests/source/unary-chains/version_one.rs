@@ -0,0 +1,13 @@
+// rustfmt-version: One
+
+fn main() {
+    let a = ! ! flag;
+    let b = - - x;
+    let c = & & x;
+    let d = & &mut x;
+    let e = *  * x;
+    let f = - -1;
+    let g = &raw const &x;
+    let h = !-!x;
+    let i = - -(aaaaaaaaaaaaaaaaaaaaaaaaaaaa + bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb + cccccccccccccccccccccccccccccccc);
+}
@@ -6,3 +6,9 @@ pub mod inner_mod {
       pub ( self ) fn inner_mod_visible_fn() {}
     }
 }
+
+pub mod prefixes {
+      pub ( in ::outer_mod ) fn global_visible_fn() {}
+    pub ( in crate :: outer_mod ) fn crate_path_visible_fn() {}
+       pub ( in self :: inner ) fn self_path_visible_fn() {}
+}
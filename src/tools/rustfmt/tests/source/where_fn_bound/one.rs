@@ -0,0 +1,13 @@
+// rustfmt-version: One
+
+fn foo<FunctionTypeParameter>(f: FunctionTypeParameter)
+where
+    FunctionTypeParameter: for<'a, 'b> FnMut(&'a VeryLongArgumentTypeNumberOne, &'b Two) -> some::long::path::to::ResultTypeXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX,
+{
+}
+
+fn bar<FunctionTypeParameter>(f: FunctionTypeParameter)
+where
+    FunctionTypeParameter: for<'a, 'b> FnMut(&'a VeryLongArgumentTypeNumberOne, &'b Two) -> some::long::path::to::ResultTypeXXXXXXXXXXXXXXX,
+{
+}